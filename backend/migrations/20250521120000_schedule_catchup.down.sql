@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE schedule DROP COLUMN last_tick_at;
+ALTER TABLE schedule DROP COLUMN catchup_max_ticks;
+ALTER TABLE schedule DROP COLUMN catchup_policy;
+DROP TYPE SCHEDULE_CATCHUP_POLICY;
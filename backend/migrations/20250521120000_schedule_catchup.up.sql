@@ -0,0 +1,6 @@
+-- Add up migration script here
+CREATE TYPE SCHEDULE_CATCHUP_POLICY AS ENUM ('skip', 'latest', 'all');
+
+ALTER TABLE schedule ADD COLUMN catchup_policy SCHEDULE_CATCHUP_POLICY NOT NULL DEFAULT 'skip';
+ALTER TABLE schedule ADD COLUMN catchup_max_ticks INTEGER;
+ALTER TABLE schedule ADD COLUMN last_tick_at TIMESTAMPTZ;
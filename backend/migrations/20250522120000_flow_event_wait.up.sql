@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE flow_event_wait (
+    job             UUID          NOT NULL PRIMARY KEY,
+    flow            UUID          NOT NULL REFERENCES v2_job_queue (id) ON DELETE CASCADE,
+    workspace_id    VARCHAR(50)   NOT NULL,
+    trigger_kind    TRIGGER_KIND  NOT NULL,
+    trigger_path    VARCHAR(255)  NOT NULL,
+    message_key     VARCHAR(255)  NOT NULL,
+    correlation_key JSONB         NOT NULL,
+    created_at      TIMESTAMPTZ   NOT NULL DEFAULT now()
+);
+
+CREATE INDEX flow_event_wait_trigger_idx ON flow_event_wait (workspace_id, trigger_kind, trigger_path);
+
+GRANT ALL ON flow_event_wait TO windmill_user, windmill_admin;
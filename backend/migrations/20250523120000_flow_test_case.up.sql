@@ -0,0 +1,13 @@
+-- Add up migration script here
+CREATE TABLE flow_test_case (
+    workspace_id VARCHAR(50)  NOT NULL,
+    flow_path    VARCHAR(255) NOT NULL,
+    name         VARCHAR(255) NOT NULL,
+    value        JSONB        NOT NULL,
+    edited_by    VARCHAR(255) NOT NULL,
+    edited_at    TIMESTAMPTZ  NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, flow_path, name),
+    FOREIGN KEY (workspace_id, flow_path) REFERENCES flow (workspace_id, path) ON DELETE CASCADE
+);
+
+GRANT ALL ON flow_test_case TO windmill_user, windmill_admin;
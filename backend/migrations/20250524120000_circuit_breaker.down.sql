@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE circuit_breaker;
+DROP TYPE CIRCUIT_BREAKER_ACTION;
+DROP TYPE CIRCUIT_BREAKER_STATE;
+DROP TYPE CIRCUIT_BREAKER_KIND;
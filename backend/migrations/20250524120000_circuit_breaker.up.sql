@@ -0,0 +1,26 @@
+-- Add up migration script here
+CREATE TYPE CIRCUIT_BREAKER_KIND AS ENUM ('script', 'resource');
+CREATE TYPE CIRCUIT_BREAKER_STATE AS ENUM ('closed', 'open', 'half_open');
+CREATE TYPE CIRCUIT_BREAKER_ACTION AS ENUM ('fail', 'delay');
+
+CREATE TABLE circuit_breaker (
+    workspace_id      VARCHAR(50)            NOT NULL,
+    kind              CIRCUIT_BREAKER_KIND   NOT NULL,
+    path              VARCHAR(255)           NOT NULL,
+    failure_threshold INTEGER                NOT NULL DEFAULT 5,
+    window_s          INTEGER                NOT NULL DEFAULT 60,
+    cooldown_s        INTEGER                NOT NULL DEFAULT 60,
+    on_open           CIRCUIT_BREAKER_ACTION NOT NULL DEFAULT 'fail',
+    state             CIRCUIT_BREAKER_STATE  NOT NULL DEFAULT 'closed',
+    failure_count     INTEGER                NOT NULL DEFAULT 0,
+    window_start      TIMESTAMPTZ,
+    opened_at         TIMESTAMPTZ,
+    probe_job         UUID,
+    probe_started_at  TIMESTAMPTZ,
+    edited_by         VARCHAR(255)           NOT NULL,
+    edited_at         TIMESTAMPTZ            NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, kind, path),
+    FOREIGN KEY (workspace_id) REFERENCES workspace (id) ON DELETE CASCADE
+);
+
+GRANT ALL ON circuit_breaker TO windmill_user, windmill_admin;
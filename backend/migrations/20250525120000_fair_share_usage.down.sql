@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS fair_share;
+DROP TABLE IF EXISTS fair_share_usage;
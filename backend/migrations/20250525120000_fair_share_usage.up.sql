@@ -0,0 +1,21 @@
+-- Add up migration script here
+CREATE TABLE fair_share_usage (
+    workspace_id    VARCHAR(50)  NOT NULL,
+    permissioned_as VARCHAR(55)  NOT NULL,
+    bucket          TIMESTAMPTZ  NOT NULL,
+    duration_ms     BIGINT       NOT NULL,
+    PRIMARY KEY (workspace_id, permissioned_as, bucket),
+    FOREIGN KEY (workspace_id) REFERENCES workspace (id) ON DELETE CASCADE
+);
+
+CREATE INDEX fair_share_usage_bucket_idx ON fair_share_usage (bucket);
+
+CREATE TABLE fair_share (
+    worker_group VARCHAR(255) NOT NULL,
+    key          VARCHAR(255) NOT NULL,
+    share        FLOAT8       NOT NULL,
+    PRIMARY KEY (worker_group, key)
+);
+
+GRANT ALL ON fair_share_usage TO windmill_user, windmill_admin;
+GRANT ALL ON fair_share TO windmill_user, windmill_admin;
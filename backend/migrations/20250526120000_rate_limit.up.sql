@@ -0,0 +1,15 @@
+-- Add up migration script here
+CREATE TABLE rate_limit (
+    workspace_id VARCHAR(50)      NOT NULL,
+    key          VARCHAR(255)     NOT NULL,
+    rate         DOUBLE PRECISION NOT NULL,
+    burst        INTEGER          NOT NULL DEFAULT 1,
+    tat          TIMESTAMPTZ,
+    edited_by    VARCHAR(255)     NOT NULL,
+    edited_at    TIMESTAMPTZ      NOT NULL DEFAULT now(),
+    PRIMARY KEY (workspace_id, key),
+    FOREIGN KEY (workspace_id) REFERENCES workspace (id) ON DELETE CASCADE,
+    CHECK (rate > 0 AND burst > 0)
+);
+
+GRANT ALL ON rate_limit TO windmill_user, windmill_admin;
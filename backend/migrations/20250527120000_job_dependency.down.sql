@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE v2_job_queue DROP COLUMN blocked_by_dependencies;
+DROP TABLE job_dependency;
+DROP TYPE JOB_DEPENDENCY_CONDITION;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TYPE JOB_DEPENDENCY_CONDITION AS ENUM ('success', 'failure', 'any');
+
+CREATE TABLE job_dependency (
+    job_id         UUID                     NOT NULL,
+    upstream_id    UUID                     NOT NULL,
+    workspace_id   VARCHAR(50)              NOT NULL,
+    condition      JOB_DEPENDENCY_CONDITION NOT NULL DEFAULT 'success',
+    -- the rate limit the job reserves a token of once unblocked
+    rate_limit_key VARCHAR(255),
+    PRIMARY KEY (job_id, upstream_id),
+    FOREIGN KEY (job_id) REFERENCES v2_job (id) ON DELETE CASCADE
+);
+
+CREATE INDEX job_dependency_upstream_id_idx ON job_dependency (upstream_id);
+
+ALTER TABLE v2_job_queue ADD COLUMN blocked_by_dependencies BOOLEAN NOT NULL DEFAULT false;
+
+GRANT ALL ON job_dependency TO windmill_user, windmill_admin;
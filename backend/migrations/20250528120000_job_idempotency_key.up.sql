@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE job_idempotency_key (
+    workspace_id    VARCHAR(50)  NOT NULL,
+    key             VARCHAR(255) NOT NULL,
+    runnable_path   VARCHAR(255) NOT NULL,
+    permissioned_as VARCHAR(55)  NOT NULL,
+    job_id          UUID         NOT NULL,
+    expires_at      TIMESTAMPTZ  NOT NULL,
+    PRIMARY KEY (workspace_id, key, runnable_path, permissioned_as),
+    FOREIGN KEY (workspace_id) REFERENCES workspace (id) ON DELETE CASCADE,
+    FOREIGN KEY (job_id) REFERENCES v2_job (id) ON DELETE CASCADE
+);
+
+CREATE INDEX job_idempotency_key_expires_at_idx ON job_idempotency_key (expires_at);
+CREATE INDEX job_idempotency_key_job_id_idx ON job_idempotency_key (job_id);
+
+GRANT ALL ON job_idempotency_key TO windmill_user, windmill_admin;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+CREATE TABLE job_result_stream (
+    job_id       UUID        NOT NULL,
+    seq          INTEGER     NOT NULL,
+    workspace_id VARCHAR(50) NOT NULL,
+    value        JSONB       NOT NULL,
+    PRIMARY KEY (job_id, seq),
+    FOREIGN KEY (job_id) REFERENCES v2_job (id) ON DELETE CASCADE
+);
+
+GRANT ALL ON job_result_stream TO windmill_user, windmill_admin;
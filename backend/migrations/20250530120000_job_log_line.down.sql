@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE job_log_line;
+DROP TYPE JOB_LOG_STREAM;
+DROP TYPE JOB_LOG_LEVEL;
@@ -0,0 +1,22 @@
+-- Add up migration script here
+CREATE TYPE JOB_LOG_LEVEL AS ENUM ('debug', 'info', 'warning', 'error', 'critical');
+CREATE TYPE JOB_LOG_STREAM AS ENUM ('stdout', 'stderr');
+
+CREATE TABLE job_log_line (
+    id           BIGSERIAL      PRIMARY KEY,
+    job_id       UUID           NOT NULL,
+    -- the root flow of the job, or the job itself, to search the logs of all the steps at once
+    root_job_id  UUID           NOT NULL,
+    workspace_id VARCHAR(50)    NOT NULL,
+    step_id      VARCHAR(255),
+    ts           TIMESTAMPTZ    NOT NULL,
+    level        JOB_LOG_LEVEL,
+    stream       JOB_LOG_STREAM NOT NULL,
+    message      TEXT           NOT NULL,
+    FOREIGN KEY (job_id) REFERENCES v2_job (id) ON DELETE CASCADE
+);
+
+CREATE INDEX job_log_line_job_id_ts_idx ON job_log_line (job_id, ts);
+CREATE INDEX job_log_line_root_job_id_ts_idx ON job_log_line (root_job_id, ts);
+
+GRANT ALL ON job_log_line TO windmill_user, windmill_admin;
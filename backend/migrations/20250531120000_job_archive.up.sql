@@ -0,0 +1,68 @@
+-- Add up migration script here
+-- jobs archived to the instance object store, with what is needed to list and filter them
+CREATE TABLE job_archive (
+    id               UUID         PRIMARY KEY,
+    workspace_id     VARCHAR(50)  NOT NULL,
+    created_at       TIMESTAMPTZ  NOT NULL,
+    started_at       TIMESTAMPTZ,
+    completed_at     TIMESTAMPTZ  NOT NULL,
+    created_by       VARCHAR(255) NOT NULL,
+    runnable_path    VARCHAR(255),
+    runnable_id      BIGINT,
+    kind             JOB_KIND     NOT NULL,
+    status           JOB_STATUS   NOT NULL,
+    tag              VARCHAR(255) NOT NULL,
+    parent_job       UUID,
+    is_flow_step     BOOLEAN      NOT NULL,
+    permissioned_as  VARCHAR(55)  NOT NULL,
+    visible_to_owner BOOLEAN      NOT NULL,
+    -- parquet file holding the job in the object store
+    object_path      TEXT         NOT NULL,
+    FOREIGN KEY (workspace_id) REFERENCES workspace (id) ON DELETE CASCADE
+);
+
+CREATE INDEX job_archive_workspace_id_created_at_idx ON job_archive (workspace_id, created_at DESC);
+
+ALTER TABLE job_archive ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY see_folder_extra_perms_user ON job_archive
+    AS PERMISSIVE
+    FOR ALL
+    TO windmill_user
+    USING ((visible_to_owner IS TRUE) AND (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 1) = 'f'::TEXT) AND
+           (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 2) = ANY (
+               REGEXP_SPLIT_TO_ARRAY(CURRENT_SETTING('session.folders_read'::TEXT), ','::TEXT))));
+
+CREATE POLICY see_own_path ON job_archive
+    AS PERMISSIVE
+    FOR ALL
+    TO windmill_user
+    USING ((visible_to_owner IS TRUE) AND (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 1) = 'u'::TEXT) AND
+           (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 2) = CURRENT_SETTING('session.user'::TEXT)));
+
+CREATE POLICY see_member_path ON job_archive
+    AS PERMISSIVE
+    FOR ALL
+    TO windmill_user
+    USING ((visible_to_owner IS TRUE) AND (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 1) = 'g'::TEXT) AND
+           (SPLIT_PART((runnable_path)::TEXT, '/'::TEXT, 2) = ANY
+            (REGEXP_SPLIT_TO_ARRAY(CURRENT_SETTING('session.groups'::TEXT), ','::TEXT))));
+
+CREATE POLICY see_own ON job_archive
+    AS PERMISSIVE
+    FOR ALL
+    TO windmill_user
+    USING ((SPLIT_PART((permissioned_as)::TEXT, '/'::TEXT, 1) = 'u'::TEXT) AND
+           (SPLIT_PART((permissioned_as)::TEXT, '/'::TEXT, 2) = CURRENT_SETTING('session.user'::TEXT)));
+
+CREATE POLICY see_member ON job_archive
+    AS PERMISSIVE
+    FOR ALL
+    TO windmill_user
+    USING ((SPLIT_PART((permissioned_as)::TEXT, '/'::TEXT, 1) = 'g'::TEXT) AND
+           (SPLIT_PART((permissioned_as)::TEXT, '/'::TEXT, 2) = ANY
+            (REGEXP_SPLIT_TO_ARRAY(CURRENT_SETTING('session.groups'::TEXT), ','::TEXT))));
+
+CREATE POLICY admin_policy ON job_archive FOR ALL TO windmill_admin USING (true);
+
+GRANT ALL ON job_archive TO windmill_user, windmill_admin;
@@ -0,0 +1,200 @@
+use anyhow::anyhow;
+
+#[cfg(not(target_arch = "wasm32"))]
+use regex::Regex;
+#[cfg(target_arch = "wasm32")]
+use regex_lite::Regex;
+
+use serde_json::Value;
+use windmill_parser::{Arg, MainArgSignature, Typ};
+
+lazy_static::lazy_static! {
+    static ref RE_MAIN: Regex = Regex::new(r#"(?m)^[\t ]*(?:local[\t ]+)?function[\t ]+main[\t ]*\(([^)]*)\)"#).unwrap();
+    // ---@param name? type description
+    static ref RE_PARAM: Regex = Regex::new(r#"^---[\t ]*@param[\t ]+(\w+)(\??)[\t ]+(\S+)"#).unwrap();
+    // name = name or default
+    static ref RE_DEFAULT: Regex = Regex::new(r#"(?m)^[\t ]*(\w+)[\t ]*=[\t ]*(\w+)[\t ]+or[\t ]+([^\r\n]+?)[\t ]*(?:--[^\r\n]*)?$"#).unwrap();
+}
+
+/// Arguments of `function main(a, b)`, typed by the `---@param` annotations right above it.
+/// Defaults are read from the `a = a or <literal>` idiom in the body of main
+pub fn parse_lua_sig(code: &str) -> anyhow::Result<MainArgSignature> {
+    let main = RE_MAIN
+        .captures(code)
+        .ok_or_else(|| anyhow!("Cannot find main function."))?;
+    let main_start = main.get(0).map(|m| m.start()).unwrap_or_default();
+
+    let annotations = code[..main_start]
+        .lines()
+        .rev()
+        .skip_while(|l| l.trim().is_empty())
+        .take_while(|l| l.trim_start().starts_with("---"))
+        .filter_map(|l| RE_PARAM.captures(l.trim_start()))
+        .map(|cap| {
+            let (typ, optional) = parse_lua_typ(&cap[3]);
+            (cap[1].to_string(), (typ, optional || &cap[2] == "?"))
+        })
+        .collect::<Vec<_>>();
+
+    let body = &code[main_start..];
+    let mut star_args = false;
+    let mut args = vec![];
+    for name in main[1].split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        if name == "..." {
+            star_args = true;
+            continue;
+        }
+        let (typ, optional) = annotations
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, t)| t.clone())
+            .unwrap_or((Typ::Unknown, false));
+        let default = RE_DEFAULT
+            .captures_iter(body)
+            .find(|cap| &cap[1] == name && &cap[2] == name)
+            .and_then(|cap| parse_lua_literal(&cap[3]));
+        args.push(Arg {
+            name: name.to_string(),
+            otyp: None,
+            typ,
+            has_default: optional || default.is_some(),
+            default,
+            oidx: None,
+        });
+    }
+
+    Ok(MainArgSignature {
+        star_args,
+        star_kwargs: false,
+        args,
+        no_main_func: Some(false),
+        has_preprocessor: None,
+    })
+}
+
+/// The type of an annotation and whether it is optional (`type?` or `type|nil`)
+fn parse_lua_typ(typ: &str) -> (Typ, bool) {
+    if let Some(typ) = typ.strip_suffix('?').or_else(|| typ.strip_suffix("|nil")) {
+        return (parse_lua_typ(typ).0, true);
+    }
+    let typ = match typ {
+        "string" => Typ::Str(None),
+        "integer" => Typ::Int,
+        "number" => Typ::Float,
+        "boolean" => Typ::Bool,
+        "any" | "unknown" => Typ::Unknown,
+        t if t == "table" || t.starts_with("table<") => Typ::Object(vec![]),
+        t if t.ends_with("[]") => Typ::List(Box::new(parse_lua_typ(&t[..t.len() - 2]).0)),
+        t if t.starts_with('"') || t.starts_with('\'') => Typ::Str(Some(
+            t.split('|')
+                .map(|x| x.trim_matches(|c| c == '"' || c == '\'').to_string())
+                .collect(),
+        )),
+        // as in python, any other class is the resource type of the same name
+        t if t.chars().all(|c| c.is_alphanumeric() || c == '_') => Typ::Resource(t.to_string()),
+        _ => Typ::Unknown,
+    };
+    (typ, false)
+}
+
+fn parse_lua_literal(literal: &str) -> Option<Value> {
+    match literal {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        l if l.len() >= 2 && l.starts_with('\'') && l.ends_with('\'') => {
+            Some(Value::String(l[1..l.len() - 1].to_string()))
+        }
+        l => serde_json::from_str::<Value>(l)
+            .ok()
+            .filter(|v| v.is_number() || v.is_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_lua_sig() -> anyhow::Result<()> {
+        let code = r#"
+local helper = require_nothing
+
+---Reshapes the input
+---@param name string the name
+---@param count? integer
+---@param tags string[]
+---@param mode "fast"|"slow"
+---@param db postgresql
+---@param extra table
+function main(name, count, tags, mode, db, extra, ...)
+  count = count or 3
+  mode = mode or 'fast'
+  return { name = name, count = count }
+end
+"#;
+        assert_eq!(
+            parse_lua_sig(code)?,
+            MainArgSignature {
+                star_args: true,
+                star_kwargs: false,
+                args: vec![
+                    Arg {
+                        otyp: None,
+                        name: "name".to_string(),
+                        typ: Typ::Str(None),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "count".to_string(),
+                        typ: Typ::Int,
+                        default: Some(json!(3)),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "tags".to_string(),
+                        typ: Typ::List(Box::new(Typ::Str(None))),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "mode".to_string(),
+                        typ: Typ::Str(Some(vec!["fast".to_string(), "slow".to_string()])),
+                        default: Some(json!("fast")),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "db".to_string(),
+                        typ: Typ::Resource("postgresql".to_string()),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "extra".to_string(),
+                        typ: Typ::Object(vec![]),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                ],
+                no_main_func: Some(false),
+                has_preprocessor: None
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::anyhow;
+
+#[cfg(not(target_arch = "wasm32"))]
+use regex::Regex;
+#[cfg(target_arch = "wasm32")]
+use regex_lite::Regex;
+
+use serde_json::Value;
+use windmill_parser::{json_to_typ, Arg, MainArgSignature, Typ};
+
+lazy_static::lazy_static! {
+    static ref RE_MAIN: Regex = Regex::new(r#"(?m)^[\t ]*main[\t ]*(?:<<?-|=)[\t ]*function[\t ]*\("#).unwrap();
+}
+
+/// Arguments of `main <- function(x = 1, y = "a", ...)`, typed from their default value. As in
+/// the `match.arg` idiom, a `c("a", "b")` default is an enum defaulting to its first value
+pub fn parse_r_sig(code: &str) -> anyhow::Result<MainArgSignature> {
+    let main = RE_MAIN
+        .find(code)
+        .ok_or_else(|| anyhow!("Cannot find main function."))?;
+    let params = strip_comments(&code[main.end()..]);
+    let params = &params[..closing_paren(&params)?];
+
+    let mut star_args = false;
+    let mut args = vec![];
+    for param in split_top_level(params) {
+        if param == "..." {
+            star_args = true;
+            continue;
+        }
+        let (name, default) = match param.split_once('=') {
+            Some((name, default)) => (name.trim(), Some(default.trim())),
+            None => (param, None),
+        };
+        let name = name.trim_matches('`');
+        let (typ, default_value) = default.map(parse_r_default).unwrap_or((Typ::Unknown, None));
+        args.push(Arg {
+            name: name.to_string(),
+            otyp: None,
+            typ,
+            has_default: default.is_some(),
+            default: default_value,
+            oidx: None,
+        });
+    }
+
+    Ok(MainArgSignature {
+        star_args,
+        star_kwargs: false,
+        args,
+        no_main_func: Some(false),
+        has_preprocessor: None,
+    })
+}
+
+/// Removes the `#` comments, which may contain unbalanced quotes or parentheses
+fn strip_comments(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let (mut quote, mut comment) = (None, false);
+    for c in code.chars() {
+        match (quote, comment, c) {
+            (_, true, '\n') => comment = false,
+            (_, true, _) => continue,
+            (Some(q), _, c) if c == q => quote = None,
+            (None, _, '"' | '\'') => quote = Some(c),
+            (None, _, '#') => {
+                comment = true;
+                continue;
+            }
+            _ => (),
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Index of the parenthesis closing the parameter list that `params` starts right after
+fn closing_paren(params: &str) -> anyhow::Result<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in params.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')') if depth == 0 => return Ok(i),
+            (None, ')' | ']' | '}') => depth -= 1,
+            _ => (),
+        }
+    }
+    Err(anyhow!("Cannot find the end of the arguments of main."))
+}
+
+/// Splits on the commas that are not nested in parentheses or quotes
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start, mut quote) = (0i32, 0, None);
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(s[start..].trim());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+fn parse_r_default(default: &str) -> (Typ, Option<Value>) {
+    if let Some(values) = default
+        .strip_prefix("c(")
+        .and_then(|d| d.strip_suffix(')'))
+        .map(|d| {
+            split_top_level(d)
+                .into_iter()
+                .map(parse_r_literal)
+                .collect::<Option<Vec<_>>>()
+        })
+    {
+        return match values {
+            Some(values) if !values.is_empty() && values.iter().all(Value::is_string) => {
+                let variants = values
+                    .iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect();
+                (Typ::Str(Some(variants)), values.into_iter().next())
+            }
+            Some(values) => {
+                let typ = values.first().map(json_to_typ).unwrap_or(Typ::Unknown);
+                (Typ::List(Box::new(typ)), Some(Value::Array(values)))
+            }
+            None => (Typ::List(Box::new(Typ::Unknown)), None),
+        };
+    }
+    match default {
+        "list()" => (Typ::Object(vec![]), None),
+        "NULL" | "NA" => (Typ::Unknown, None),
+        d => match parse_r_literal(d) {
+            Some(value) => (json_to_typ(&value), Some(value)),
+            None => (Typ::Unknown, None),
+        },
+    }
+}
+
+fn parse_r_literal(literal: &str) -> Option<Value> {
+    match literal {
+        "TRUE" | "T" => Some(Value::Bool(true)),
+        "FALSE" | "F" => Some(Value::Bool(false)),
+        l if l.len() >= 2 && l.starts_with('\'') && l.ends_with('\'') => {
+            Some(Value::String(l[1..l.len() - 1].to_string()))
+        }
+        // integer literals, as in 10L
+        l if l.ends_with('L') => l[..l.len() - 1].parse::<i64>().ok().map(Value::from),
+        l => serde_json::from_str::<Value>(l)
+            .ok()
+            .filter(|v| v.is_number() || v.is_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_r_sig() -> anyhow::Result<()> {
+        let code = r#"
+library(dplyr)
+
+main <- function(name,
+                 count = 3L, # how many (or none)
+                 ratio = 0.5,
+                 mode = c("fast", "slow"),
+                 ids = c(1, 2),
+                 verbose = FALSE,
+                 extra = NULL,
+                 ...) {
+  mode <- match.arg(mode)
+  data.frame(name = rep(name, count))
+}
+"#;
+        assert_eq!(
+            parse_r_sig(code)?,
+            MainArgSignature {
+                star_args: true,
+                star_kwargs: false,
+                args: vec![
+                    Arg {
+                        otyp: None,
+                        name: "name".to_string(),
+                        typ: Typ::Unknown,
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "count".to_string(),
+                        typ: Typ::Int,
+                        default: Some(json!(3)),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "ratio".to_string(),
+                        typ: Typ::Float,
+                        default: Some(json!(0.5)),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "mode".to_string(),
+                        typ: Typ::Str(Some(vec!["fast".to_string(), "slow".to_string()])),
+                        default: Some(json!("fast")),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "ids".to_string(),
+                        typ: Typ::List(Box::new(Typ::Int)),
+                        default: Some(json!([1, 2])),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "verbose".to_string(),
+                        typ: Typ::Bool,
+                        default: Some(json!(false)),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "extra".to_string(),
+                        typ: Typ::Unknown,
+                        default: None,
+                        has_default: true,
+                        oidx: None
+                    },
+                ],
+                no_main_func: Some(false),
+                has_preprocessor: None
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+use anyhow::anyhow;
+
+#[cfg(not(target_arch = "wasm32"))]
+use regex::Regex;
+#[cfg(target_arch = "wasm32")]
+use regex_lite::Regex;
+
+use serde_json::Value;
+use windmill_parser::{json_to_typ, Arg, MainArgSignature, Typ};
+
+lazy_static::lazy_static! {
+    static ref RE_MAIN: Regex = Regex::new(r#"(?m)^[\t ]*def[\t ]+main\b[\t ]*"#).unwrap();
+    // # @param name [Type, nil] description, or # @param [Type] name
+    static ref RE_PARAM: Regex = Regex::new(r#"^#[\t ]*@param[\t ]+(?:(\w+)[\t ]+\[([^\]]*)\]|\[([^\]]*)\][\t ]+(\w+))"#).unwrap();
+}
+
+/// Parameters of `def main(a, b = 1, c:, d: 'x', *rest, **opts)`, typed by the YARD `@param`
+/// tags right above it. Untagged parameters are typed from their default value
+pub fn parse_ruby_sig(code: &str) -> anyhow::Result<MainArgSignature> {
+    let main = RE_MAIN
+        .find(code)
+        .ok_or_else(|| anyhow!("Cannot find main method."))?;
+
+    let annotations = code[..main.start()]
+        .lines()
+        .rev()
+        .skip_while(|l| l.trim().is_empty())
+        .take_while(|l| l.trim_start().starts_with('#'))
+        .filter_map(|l| RE_PARAM.captures(l.trim_start()))
+        .filter_map(|cap| {
+            let name = cap.get(1).or_else(|| cap.get(4))?.as_str().to_string();
+            let typ = cap.get(2).or_else(|| cap.get(3))?.as_str();
+            Some((name, parse_yard_typ(typ)))
+        })
+        .collect::<Vec<_>>();
+
+    let params = &code[main.end()..];
+    let params = match params.strip_prefix('(') {
+        Some(params) => &params[..closing_paren(params)?],
+        // parentheses are optional around the parameters of a method
+        None => params.lines().next().unwrap_or_default(),
+    };
+
+    let mut star_args = false;
+    let mut star_kwargs = false;
+    let mut args = vec![];
+    for param in split_top_level(params, false) {
+        if param.starts_with("**") {
+            star_kwargs = true;
+            continue;
+        } else if param.starts_with('*') {
+            star_args = true;
+            continue;
+        } else if param.starts_with('&') {
+            continue;
+        }
+
+        let (name, default) = match param.split_once(['=', ':']) {
+            Some((name, default)) => (name.trim(), Some(default.trim())),
+            None => (param, None),
+        };
+        // a required keyword parameter is `name:` without a value
+        let default = default.filter(|d| !d.is_empty());
+        let default_value = default.and_then(parse_ruby_literal);
+
+        let (typ, optional) = annotations
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, t)| t.clone())
+            .unwrap_or_else(|| {
+                let typ = default_value
+                    .as_ref()
+                    .map(json_to_typ)
+                    .unwrap_or(Typ::Unknown);
+                (typ, false)
+            });
+        args.push(Arg {
+            name: name.to_string(),
+            otyp: None,
+            typ,
+            has_default: optional || default.is_some(),
+            default: default_value,
+            oidx: None,
+        });
+    }
+
+    Ok(MainArgSignature {
+        star_args,
+        star_kwargs,
+        args,
+        no_main_func: Some(false),
+        has_preprocessor: None,
+    })
+}
+
+/// Index of the parenthesis closing the parameter list that `params` starts right after
+fn closing_paren(params: &str) -> anyhow::Result<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in params.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')') if depth == 0 => return Ok(i),
+            (None, ')' | ']' | '}') => depth -= 1,
+            _ => (),
+        }
+    }
+    Err(anyhow!("Cannot find the end of the parameters of main."))
+}
+
+/// Splits on the commas that are not nested in brackets or quotes. `<` and `>` only count as
+/// brackets in YARD types, as in `Hash<String, Integer>`
+fn split_top_level(s: &str, angle_brackets: bool) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start, mut quote, mut prev) = (0i32, 0, None, ' ');
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            (None, '<') if angle_brackets => depth += 1,
+            (None, '>') if angle_brackets && prev != '=' => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+        prev = c;
+    }
+    parts.push(s[start..].trim());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+/// The type of a YARD type list and whether it is optional (it contains `nil`)
+fn parse_yard_typ(types: &str) -> (Typ, bool) {
+    let types = split_top_level(types, true);
+    let optional = types.contains(&"nil");
+    let types = types
+        .into_iter()
+        .filter(|t| *t != "nil")
+        .collect::<Vec<_>>();
+
+    let typ = match types[..] {
+        [typ] => parse_yard_single_typ(typ),
+        [] => Typ::Unknown,
+        // literal values, as in [:fast, :slow] or ['fast', 'slow']
+        _ if types
+            .iter()
+            .all(|t| parse_ruby_literal(t).is_some_and(|v| v.is_string())) =>
+        {
+            Typ::Str(Some(
+                types
+                    .iter()
+                    .filter_map(|t| parse_ruby_literal(t))
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect(),
+            ))
+        }
+        [..] if types
+            .iter()
+            .all(|t| matches!(*t, "TrueClass" | "FalseClass")) =>
+        {
+            Typ::Bool
+        }
+        _ => Typ::Unknown,
+    };
+    (typ, optional)
+}
+
+fn parse_yard_single_typ(typ: &str) -> Typ {
+    match typ {
+        "String" | "Symbol" => Typ::Str(None),
+        "Integer" => Typ::Int,
+        "Float" | "Numeric" | "BigDecimal" => Typ::Float,
+        "Boolean" | "TrueClass" | "FalseClass" => Typ::Bool,
+        "Date" | "DateTime" | "Time" => Typ::Datetime,
+        "Array" => Typ::List(Box::new(Typ::Unknown)),
+        "Hash" => Typ::Object(vec![]),
+        "Object" | "BasicObject" => Typ::Unknown,
+        t if t.starts_with("Array<") && t.ends_with('>') => {
+            Typ::List(Box::new(parse_yard_typ(&t[6..t.len() - 1]).0))
+        }
+        t if t.starts_with("Hash<") || t.starts_with("Hash{") => Typ::Object(vec![]),
+        // as in python, any other class is the resource type of the same name
+        t if t.starts_with(|c: char| c.is_ascii_uppercase())
+            && t.chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            Typ::Resource(to_snake_case(t))
+        }
+        _ => Typ::Unknown,
+    }
+}
+
+fn to_snake_case(class_name: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in class_name.char_indices() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+fn parse_ruby_literal(literal: &str) -> Option<Value> {
+    match literal {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        l if l.len() >= 2 && l.starts_with('\'') && l.ends_with('\'') => {
+            Some(Value::String(l[1..l.len() - 1].to_string()))
+        }
+        l if l.len() >= 2
+            && l.starts_with(':')
+            && l[1..].chars().all(|c| c.is_alphanumeric() || c == '_') =>
+        {
+            Some(Value::String(l[1..].to_string()))
+        }
+        // underscores are digit separators, as in 1_000
+        l if l.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+            serde_json::from_str::<Value>(&l.replace('_', ""))
+                .ok()
+                .filter(|v| v.is_number())
+        }
+        // json covers double quoted strings, arrays and hashes with string keys
+        l => serde_json::from_str::<Value>(&l.replace("=>", ":"))
+            .ok()
+            .filter(|v| !v.is_null()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_ruby_sig() -> anyhow::Result<()> {
+        let code = r#"
+require 'json'
+
+# Reshapes the input
+#
+# @param name [String] the name
+# @param count [Integer, nil]
+# @param tags [Array<String>]
+# @param mode [:fast, :slow]
+# @param db [Postgresql]
+def main(name, count = 3, tags = [], mode: :fast, db:, ratio: 0.5, *rest, **opts, &block)
+  { name: name, count: count }
+end
+"#;
+        assert_eq!(
+            parse_ruby_sig(code)?,
+            MainArgSignature {
+                star_args: true,
+                star_kwargs: true,
+                args: vec![
+                    Arg {
+                        otyp: None,
+                        name: "name".to_string(),
+                        typ: Typ::Str(None),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "count".to_string(),
+                        typ: Typ::Int,
+                        default: Some(json!(3)),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "tags".to_string(),
+                        typ: Typ::List(Box::new(Typ::Str(None))),
+                        default: Some(json!([])),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "mode".to_string(),
+                        typ: Typ::Str(Some(vec!["fast".to_string(), "slow".to_string()])),
+                        default: Some(json!("fast")),
+                        has_default: true,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "db".to_string(),
+                        typ: Typ::Resource("postgresql".to_string()),
+                        default: None,
+                        has_default: false,
+                        oidx: None
+                    },
+                    Arg {
+                        otyp: None,
+                        name: "ratio".to_string(),
+                        typ: Typ::Float,
+                        default: Some(json!(0.5)),
+                        has_default: true,
+                        oidx: None
+                    },
+                ],
+                no_main_func: Some(false),
+                has_preprocessor: None
+            }
+        );
+
+        Ok(())
+    }
+}
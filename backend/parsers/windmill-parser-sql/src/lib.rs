@@ -52,7 +52,23 @@ pub fn parse_oracledb_sig(code: &str) -> anyhow::Result<MainArgSignature> {
 }
 
 pub fn parse_pgsql_sig(code: &str) -> anyhow::Result<MainArgSignature> {
-    let parsed = parse_pg_file(&code)?;
+    let parsed = parse_pg_file(&code, parse_pg_typ)?;
+    if let Some(x) = parsed {
+        let args = x;
+        Ok(MainArgSignature {
+            star_args: false,
+            star_kwargs: false,
+            args,
+            no_main_func: None,
+            has_preprocessor: None,
+        })
+    } else {
+        Err(anyhow!("Error parsing sql".to_string()))
+    }
+}
+
+pub fn parse_duckdb_sig(code: &str) -> anyhow::Result<MainArgSignature> {
+    let parsed = parse_pg_file(&code, parse_duckdb_typ)?;
     if let Some(x) = parsed {
         let args = x;
         Ok(MainArgSignature {
@@ -143,11 +159,13 @@ pub fn parse_s3_mode(code: &str) -> anyhow::Result<Option<S3ModeArgs>> {
         Some(x) => x,
         None => return Ok(None),
     };
-    let args_str = cap
-        .get(1)
-        .map(|x| x.as_str().to_string())
-        .unwrap_or_default();
+    let args_str = cap.get(1).map(|x| x.as_str()).unwrap_or_default();
+
+    parse_s3_mode_args(args_str).map(Some)
+}
 
+/// The `prefix=... storage=... format=...` arguments of an s3 mode annotation
+pub fn parse_s3_mode_args(args_str: &str) -> anyhow::Result<S3ModeArgs> {
     let mut prefix = None;
     let mut storage = None;
     let mut format = S3ModeFormat::Json;
@@ -171,7 +189,7 @@ pub fn parse_s3_mode(code: &str) -> anyhow::Result<Option<S3ModeArgs>> {
         }
     }
 
-    Ok(Some(S3ModeArgs { prefix, storage, format }))
+    Ok(S3ModeArgs { prefix, storage, format })
 }
 
 pub fn parse_sql_blocks(code: &str) -> Vec<&str> {
@@ -466,7 +484,8 @@ pub fn parse_pg_statement_arg_indices(code: &str) -> HashSet<i32> {
     arg_indices
 }
 
-fn parse_pg_file(code: &str) -> anyhow::Result<Option<Vec<Arg>>> {
+/// Positional `$n::type` args, shared by the languages using the postgres conventions
+fn parse_pg_file(code: &str, parse_typ: fn(&str) -> Typ) -> anyhow::Result<Option<Vec<Arg>>> {
     let mut args = vec![];
     let mut hm: HashMap<i32, String> = HashMap::new();
     for cap in RE_CODE_PGSQL.captures_iter(code) {
@@ -483,7 +502,7 @@ fn parse_pg_file(code: &str) -> anyhow::Result<Option<Vec<Arg>>> {
         let typ = v.to_lowercase();
         args.push(Arg {
             name: format!("${}", i),
-            typ: parse_pg_typ(typ.as_str()),
+            typ: parse_typ(typ.as_str()),
             default: None,
             otyp: Some(typ),
             has_default: false,
@@ -711,6 +730,25 @@ pub fn parse_pg_typ(typ: &str) -> Typ {
     }
 }
 
+pub fn parse_duckdb_typ(typ: &str) -> Typ {
+    if typ.ends_with("[]") {
+        let base_typ = parse_duckdb_typ(typ.strip_suffix("[]").unwrap());
+        Typ::List(Box::new(base_typ))
+    } else {
+        match typ {
+            "varchar" | "text" | "string" | "char" | "bpchar" | "uuid" => Typ::Str(None),
+            "tinyint" | "smallint" | "integer" | "int" | "bigint" | "hugeint" | "utinyint"
+            | "usmallint" | "uinteger" | "ubigint" | "int1" | "int2" | "int4" | "int8" => Typ::Int,
+            "float" | "real" | "double" | "decimal" | "numeric" | "float4" | "float8" => Typ::Float,
+            "boolean" | "bool" => Typ::Bool,
+            "date" | "time" | "timestamp" | "timestamptz" | "datetime" => Typ::Datetime,
+            "json" => Typ::Object(vec![]),
+            "blob" | "bytea" => Typ::Bytes,
+            _ => Typ::Str(None),
+        }
+    }
+}
+
 pub fn parse_bigquery_typ(typ: &str) -> Typ {
     if typ.ends_with("[]") {
         let base_typ = parse_bigquery_typ(typ.strip_suffix("[]").unwrap());
@@ -1205,4 +1243,51 @@ SELECT * FROM table_name WHERE thing = :name4;
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_duckdb_sig() -> anyhow::Result<()> {
+        let code = r#"
+-- $1 day = 2024-01-01
+-- $2 tags
+SELECT count(*) FROM read_parquet('s3:///events/*.parquet')
+WHERE day = $1::DATE AND tag = ANY($2::VARCHAR[]) AND amount > $3::HUGEINT;
+"#;
+        assert_eq!(
+            parse_duckdb_sig(code)?,
+            MainArgSignature {
+                star_args: false,
+                star_kwargs: false,
+                args: vec![
+                    Arg {
+                        otyp: Some("date".to_string()),
+                        name: "day".to_string(),
+                        typ: Typ::Datetime,
+                        default: Some(json!("2024-01-01")),
+                        has_default: true,
+                        oidx: Some(1),
+                    },
+                    Arg {
+                        otyp: Some("varchar[]".to_string()),
+                        name: "tags".to_string(),
+                        typ: Typ::List(Box::new(Typ::Str(None))),
+                        default: None,
+                        has_default: false,
+                        oidx: Some(2),
+                    },
+                    Arg {
+                        otyp: Some("hugeint".to_string()),
+                        name: "$3".to_string(),
+                        typ: Typ::Int,
+                        default: None,
+                        has_default: false,
+                        oidx: Some(3),
+                    },
+                ],
+                no_main_func: None,
+                has_preprocessor: None
+            }
+        );
+
+        Ok(())
+    }
 }
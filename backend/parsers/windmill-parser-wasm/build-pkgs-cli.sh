@@ -9,11 +9,11 @@ wasm-pack build --release --target deno --out-dir $OUT_DIR --features "ts-parser
 # sed -i 's/"windmill-parser-wasm"/"windmill-parser-wasm-ts"/' $OUT_DIR/package.json
 rm $OUT_DIR/.gitignore
 
-#-# sql languages, graphql and bash/powershell, since they all use regex
+#-# sql languages, graphql, bash/powershell, lua, ruby and r, since they all use regex
 OUT_DIR="../../../cli/wasm/regex"
 mkdir -p $OUT_DIR
 wasm-pack build --release --target deno --out-dir $OUT_DIR \
-	--features "sql-parser,graphql-parser,bash-parser" \
+	--features "sql-parser,graphql-parser,bash-parser,lua-parser,ruby-parser,r-parser" \
 	-Z build-std=panic_abort,std -Z build-std-features=panic_immediate_abort
 # sed -i 's/"windmill-parser-wasm"/"windmill-parser-wasm-regex"/' $OUT_DIR/package.json
 rm $OUT_DIR/.gitignore
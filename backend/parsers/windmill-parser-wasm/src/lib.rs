@@ -114,6 +114,12 @@ pub fn parse_mssql(code: &str) -> String {
     wrap_sig(windmill_parser_sql::parse_mssql_sig(code))
 }
 
+#[cfg(feature = "sql-parser")]
+#[wasm_bindgen]
+pub fn parse_duckdb(code: &str) -> String {
+    wrap_sig(windmill_parser_sql::parse_duckdb_sig(code))
+}
+
 #[cfg(feature = "sql-parser")]
 #[wasm_bindgen]
 pub fn parse_db_resource(code: &str) -> Option<String> {
@@ -162,4 +168,22 @@ pub fn parse_java(code: &str) -> String {
     wrap_sig(windmill_parser_java::parse_java_signature(code))
 }
 
+#[cfg(feature = "lua-parser")]
+#[wasm_bindgen]
+pub fn parse_lua(code: &str) -> String {
+    wrap_sig(windmill_parser_lua::parse_lua_sig(code))
+}
+
+#[cfg(feature = "ruby-parser")]
+#[wasm_bindgen]
+pub fn parse_ruby(code: &str) -> String {
+    wrap_sig(windmill_parser_ruby::parse_ruby_sig(code))
+}
+
+#[cfg(feature = "r-parser")]
+#[wasm_bindgen]
+pub fn parse_r(code: &str) -> String {
+    wrap_sig(windmill_parser_r::parse_r_sig(code))
+}
+
 // for related places search: ADD_NEW_LANG
@@ -46,10 +46,10 @@ use windmill_common::{
         CRITICAL_ALERT_MUTE_UI_SETTING, CRITICAL_ERROR_CHANNELS_SETTING,
         DEFAULT_TAGS_PER_WORKSPACE_SETTING, DEFAULT_TAGS_WORKSPACES_SETTING,
         EXPOSE_DEBUG_METRICS_SETTING, EXPOSE_METRICS_SETTING, EXTRA_PIP_INDEX_URL_SETTING,
-        HUB_BASE_URL_SETTING, INSTANCE_PYTHON_VERSION_SETTING, JOB_DEFAULT_TIMEOUT_SECS_SETTING,
-        JWT_SECRET_SETTING, KEEP_JOB_DIR_SETTING, LICENSE_KEY_SETTING,
-        MONITOR_LOGS_ON_OBJECT_STORE_SETTING, NPM_CONFIG_REGISTRY_SETTING, NUGET_CONFIG_SETTING,
-        OTEL_SETTING, PIP_INDEX_URL_SETTING, REQUEST_SIZE_LIMIT_SETTING,
+        HUB_BASE_URL_SETTING, INSTANCE_PYTHON_VERSION_SETTING, JOB_ARCHIVE_ON_OBJECT_STORE_SETTING,
+        JOB_DEFAULT_TIMEOUT_SECS_SETTING, JWT_SECRET_SETTING, KEEP_JOB_DIR_SETTING,
+        LICENSE_KEY_SETTING, MONITOR_LOGS_ON_OBJECT_STORE_SETTING, NPM_CONFIG_REGISTRY_SETTING,
+        NUGET_CONFIG_SETTING, OTEL_SETTING, PIP_INDEX_URL_SETTING, REQUEST_SIZE_LIMIT_SETTING,
         REQUIRE_PREEXISTING_USER_FOR_OAUTH_SETTING, RETENTION_PERIOD_SECS_SETTING,
         SAML_METADATA_SETTING, SCIM_TOKEN_SETTING, TIMEOUT_WAIT_RESULT_SETTING,
     },
@@ -63,17 +63,20 @@ use windmill_common::{
     utils::{empty_as_none, now_from_db, rd_string, report_critical_error, Mode},
     worker::{
         load_env_vars, load_init_bash_from_env, load_whitelist_env_vars_from_env,
-        load_worker_config, reload_custom_tags_setting, store_pull_query,
-        store_suspended_pull_query, update_min_version, Connection, WorkerConfig,
-        DEFAULT_TAGS_PER_WORKSPACE, DEFAULT_TAGS_WORKSPACES, INDEXER_CONFIG, SCRIPT_TOKEN_EXPIRY,
-        SMTP_CONFIG, TMP_DIR, WORKER_CONFIG, WORKER_GROUP,
+        load_worker_config, refresh_fair_shares, reload_custom_tags_setting, store_pull_query,
+        store_suspended_pull_query, update_min_version, Connection, FairShareConfig, WorkerConfig,
+        DEFAULT_TAGS_PER_WORKSPACE, DEFAULT_TAGS_WORKSPACES, FAIR_SHARE_USAGE_RETENTION_S,
+        INDEXER_CONFIG, SCRIPT_TOKEN_EXPIRY, SMTP_CONFIG, TMP_DIR, WORKER_CONFIG, WORKER_GROUP,
     },
     KillpillSender, BASE_URL, CRITICAL_ALERTS_ON_DB_OVERSIZE, CRITICAL_ALERT_MUTE_UI_ENABLED,
-    CRITICAL_ERROR_CHANNELS, DB, DEFAULT_HUB_BASE_URL, HUB_BASE_URL, JOB_RETENTION_SECS,
-    METRICS_DEBUG_ENABLED, METRICS_ENABLED, MONITOR_LOGS_ON_OBJECT_STORE, OTEL_LOGS_ENABLED,
-    OTEL_METRICS_ENABLED, OTEL_TRACING_ENABLED, SERVICE_LOG_RETENTION_SECS,
+    CRITICAL_ERROR_CHANNELS, DB, DEFAULT_HUB_BASE_URL, HUB_BASE_URL, JOB_ARCHIVE_ON_OBJECT_STORE,
+    JOB_RETENTION_SECS, METRICS_DEBUG_ENABLED, METRICS_ENABLED, MONITOR_LOGS_ON_OBJECT_STORE,
+    OTEL_LOGS_ENABLED, OTEL_METRICS_ENABLED, OTEL_TRACING_ENABLED, SERVICE_LOG_RETENTION_SECS,
+};
+use windmill_queue::{
+    cancel_job, cancel_module_jobs, job_dependencies::release_orphaned_dependencies, MiniPulledJob,
+    SameWorkerPayload,
 };
-use windmill_queue::{cancel_job, MiniPulledJob, SameWorkerPayload};
 use windmill_worker::{
     handle_job_error, AuthedClient, JobCompletedSender, SameWorkerSender, BUNFIG_INSTALL_SCOPES,
     INSTANCE_PYTHON_VERSION, JOB_DEFAULT_TIMEOUT, KEEP_JOB_DIR, MAVEN_REPOS, NO_DEFAULT_MAVEN,
@@ -219,6 +222,8 @@ pub async fn initial_load(
                     cache_clear: None,
                     additional_python_paths: None,
                     pip_local_dependencies: None,
+                    fair_share: None,
+                    resource_limits: None,
                 };
             }
         }
@@ -251,6 +256,7 @@ pub async fn initial_load(
 
     if server_mode {
         reload_retention_period_setting(&conn).await;
+        reload_job_archive_setting(&conn).await;
         reload_request_size(&conn).await;
         reload_saml_metadata_setting(&conn).await;
         reload_scim_token_setting(&conn).await;
@@ -777,6 +783,21 @@ pub async fn delete_expired_items(db: &DB) -> () {
         Err(e) => tracing::error!("Error deleting cache resource {}", e.to_string()),
     }
 
+    let deleted_idempotency_keys = sqlx::query_scalar!(
+        "DELETE FROM job_idempotency_key WHERE expires_at <= now() RETURNING key",
+    )
+    .fetch_all(db)
+    .await;
+
+    match deleted_idempotency_keys {
+        Ok(res) => {
+            if res.len() > 0 {
+                tracing::info!("deleted {} expired idempotency keys", res.len())
+            }
+        }
+        Err(e) => tracing::error!("Error deleting idempotency keys: {}", e.to_string()),
+    }
+
     let deleted_expired_variables = sqlx::query_scalar!(
         "DELETE FROM variable WHERE expires_at IS NOT NULL AND expires_at < now() RETURNING path",
     )
@@ -829,13 +850,31 @@ pub async fn delete_expired_items(db: &DB) -> () {
 
     let job_retention_secs = *JOB_RETENTION_SECS.read().await;
     if job_retention_secs > 0 {
+        let archive_jobs = *JOB_ARCHIVE_ON_OBJECT_STORE.read().await;
+        if archive_jobs {
+            #[cfg(feature = "parquet")]
+            match windmill_common::job_archive::archive_expired_jobs(db, job_retention_secs).await {
+                Ok(archived) if archived > 0 => {
+                    tracing::info!("archived {archived} jobs completed JOB_RETENTION_SECS {job_retention_secs} ago")
+                }
+                Ok(_) => (),
+                Err(e) => tracing::error!(
+                    "Error archiving expired jobs, they are kept until they can be archived: {e:#}"
+                ),
+            }
+            #[cfg(not(feature = "parquet"))]
+            tracing::error!("Job archival requires the parquet feature, expired jobs are kept");
+        }
         match db.begin().await {
             Ok(mut tx) => {
+                // when archiving, only the jobs already in the archive are deleted
                 let deleted_jobs = sqlx::query_scalar!(
                     "DELETE FROM v2_job_completed c
                     WHERE completed_at <= now() - ($1::bigint::text || ' s')::interval 
+                        AND (NOT $2::bool OR EXISTS (SELECT 1 FROM job_archive a WHERE a.id = c.id))
                     RETURNING c.id",
-                    job_retention_secs
+                    job_retention_secs,
+                    archive_jobs,
                 )
                 .fetch_all(&mut *tx)
                 .await;
@@ -1086,6 +1125,20 @@ pub async fn reload_retention_period_setting(conn: &Connection) {
         tracing::error!("Error reloading retention period: {:?}", e)
     }
 }
+pub async fn reload_job_archive_setting(conn: &Connection) {
+    if let Err(e) = reload_setting(
+        conn,
+        JOB_ARCHIVE_ON_OBJECT_STORE_SETTING,
+        "JOB_ARCHIVE_ON_OBJECT_STORE",
+        false,
+        JOB_ARCHIVE_ON_OBJECT_STORE.clone(),
+        |x| x,
+    )
+    .await
+    {
+        tracing::error!("Error reloading job archive setting: {:?}", e)
+    }
+}
 pub async fn reload_delete_logs_periodically_setting(conn: &Connection) {
     if let Err(e) = reload_setting(
         conn,
@@ -1383,6 +1436,9 @@ pub async fn monitor_db(
                     }
                     _ => {}
                 }
+                if let Err(err) = handle_expired_module_deadlines(db).await {
+                    tracing::error!("Error handling expired module deadlines: {:?}", err);
+                }
             }
         }
     };
@@ -1390,6 +1446,19 @@ pub async fn monitor_db(
         if server_mode && !initial_load {
             if let Some(db) = conn.as_sql() {
                 delete_expired_items(&db).await;
+                if let Err(err) = release_orphaned_dependencies(db).await {
+                    tracing::error!("Error releasing orphaned job dependencies: {:?}", err);
+                }
+            }
+        }
+    };
+
+    let fair_share_usage_f = async {
+        if server_mode && !initial_load {
+            if let Some(db) = conn.as_sql() {
+                if let Err(err) = refresh_fair_share_usage(db).await {
+                    tracing::error!("Error refreshing fair share usage: {:?}", err);
+                }
             }
         }
     };
@@ -1462,6 +1531,7 @@ pub async fn monitor_db(
     join!(
         expired_items_f,
         zombie_jobs_f,
+        fair_share_usage_f,
         expose_queue_metrics_f,
         verify_license_key_f,
         worker_groups_alerts_f,
@@ -2123,6 +2193,119 @@ Please check your worker logs for more details and feel free to report it to the
     Ok(())
 }
 
+/// Aggregates the worker time of the recently completed jobs per workspace, user and minute for
+/// the pull query of the worker groups with a fair share, then refreshes the shares of those
+/// groups from it. The last minutes are recomputed on each tick so that jobs committed late are
+/// accounted for.
+async fn refresh_fair_share_usage(db: &DB) -> error::Result<()> {
+    let configs = sqlx::query_as::<_, (String, sqlx::types::Json<FairShareConfig>)>(
+        "SELECT name, config->'fair_share' FROM config
+        WHERE name LIKE 'worker__%' AND jsonb_typeof(config->'fair_share') = 'object'",
+    )
+    .fetch_all(db)
+    .await?;
+    let worker_groups = configs
+        .iter()
+        .map(|(name, _)| name.trim_start_matches("worker__").to_string())
+        .collect::<Vec<_>>();
+    sqlx::query("DELETE FROM fair_share WHERE worker_group <> ALL($1)")
+        .bind(&worker_groups)
+        .execute(db)
+        .await?;
+    if configs.is_empty() {
+        return Ok(());
+    }
+
+    let retention_s = FAIR_SHARE_USAGE_RETENTION_S as i64;
+    sqlx::query!(
+        "INSERT INTO fair_share_usage (workspace_id, permissioned_as, bucket, duration_ms)
+        SELECT c.workspace_id, j.permissioned_as, date_trunc('minute', c.completed_at),
+            SUM(c.duration_ms)::BIGINT
+        FROM v2_job_completed c JOIN v2_job j ON j.id = c.id
+        WHERE c.completed_at >= (
+            SELECT date_trunc('minute', GREATEST(
+                LEAST(COALESCE(MAX(bucket), '-infinity'), now() - interval '5 minutes'),
+                now() - make_interval(secs => $1)
+            ))
+            FROM fair_share_usage
+        )
+        GROUP BY 1, 2, 3
+        ON CONFLICT (workspace_id, permissioned_as, bucket)
+        DO UPDATE SET duration_ms = EXCLUDED.duration_ms",
+        retention_s as f64,
+    )
+    .execute(db)
+    .await?;
+
+    sqlx::query!(
+        "DELETE FROM fair_share_usage WHERE bucket < now() - make_interval(secs => $1)",
+        retention_s as f64,
+    )
+    .execute(db)
+    .await?;
+
+    for (worker_group, (_, fair_share)) in worker_groups.iter().zip(configs.iter()) {
+        refresh_fair_shares(db, worker_group, fair_share).await?;
+    }
+    Ok(())
+}
+
+/// Cancel the in-flight iterations or branches of the loop and branch-all modules whose
+/// deadline expired. The flow itself applies the module's `on_timeout` action once they complete.
+async fn handle_expired_module_deadlines(db: &DB) -> error::Result<()> {
+    let flows = sqlx::query!(
+        r#"
+        SELECT
+            q.id, q.workspace_id, s.flow_status->'deadline'->>'id' AS "module_id!",
+            s.flow_status->'modules'->((s.flow_status->>'step')::int) AS "module_status: sqlx::types::Json<FlowStatusModule>"
+        FROM v2_job_queue q JOIN v2_job_status s USING (id)
+        WHERE q.running = true AND q.canceled_by IS NULL
+            AND s.flow_status ? 'deadline'
+            AND (s.flow_status->'deadline'->>'at')::timestamptz <= now()
+            AND NOT COALESCE((s.flow_status->'deadline'->>'canceled')::boolean, false)
+        "#
+    )
+    .fetch_all(db)
+    .await?;
+
+    for flow in flows {
+        let Some(sqlx::types::Json(module_status)) = flow.module_status else {
+            continue;
+        };
+        // `job` is the flow itself for parallel modules, the children are all in `flow_jobs`
+        let FlowStatusModule::InProgress { id, flow_jobs: Some(jobs), .. } = module_status else {
+            continue;
+        };
+        if id != flow.module_id {
+            continue;
+        }
+        tracing::info!(
+            "deadline of module {id} of flow {} in workspace {} exceeded, cancelling its jobs",
+            flow.id,
+            flow.workspace_id
+        );
+        cancel_module_jobs(
+            db,
+            &flow.workspace_id,
+            &jobs,
+            "deadline",
+            format!("deadline of module {id} exceeded"),
+        )
+        .await?;
+        // the jobs are canceled once, the flow applies the action when it processes them
+        sqlx::query!(
+            "UPDATE v2_job_status
+             SET flow_status = JSONB_SET(flow_status, ARRAY['deadline', 'canceled'], 'true')
+             WHERE id = $1 AND flow_status->'deadline'->>'id' = $2",
+            flow.id,
+            id,
+        )
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
 async fn cancel_zombie_flow_job(
     db: &Pool<Postgres>,
     id: Uuid,
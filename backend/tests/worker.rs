@@ -373,6 +373,120 @@ mod suspend_resume {
         );
     }
 
+    #[cfg(feature = "deno_core")]
+    #[sqlx::test(fixtures("base"))]
+    async fn resume_from_event(db: Pool<Postgres>) {
+        use std::collections::HashMap;
+        use windmill_api::resume_waiting_flows;
+        use windmill_common::{triggers::TriggerKind, worker::to_raw_value};
+
+        initialize_tracing().await;
+
+        let server = ApiServer::start(db.clone()).await;
+        let port = server.addr.port();
+
+        let flow: FlowValue = serde_json::from_value(json!({
+            "modules": [{
+                "id": "a",
+                "value": {
+                    "type": "rawscript",
+                    "language": "deno",
+                    "content": "export function main() { return 1 }",
+                },
+                "suspend": {
+                    "event": {
+                        "trigger_kind": "http",
+                        "trigger_path": "f/system/orders",
+                        "message_key": "order.id",
+                        "correlation_key": { "type": "javascript", "expr": "flow_input.order_id" },
+                    }
+                },
+            }, {
+                "id": "b",
+                "value": {
+                    "input_transforms": {
+                        "resume": { "type": "javascript", "expr": "resume", },
+                    },
+                    "type": "rawscript",
+                    "language": "deno",
+                    "content": "export function main(resume) { return resume }",
+                },
+            }],
+        }))
+        .unwrap();
+        flow.check_suspend_events().unwrap();
+
+        // kafka triggers never resume flows, waiting on them is rejected
+        let mut kafka = flow.clone();
+        kafka.modules[0]
+            .suspend
+            .as_mut()
+            .unwrap()
+            .event
+            .as_mut()
+            .unwrap()
+            .trigger_kind = TriggerKind::Kafka;
+        assert!(kafka.check_suspend_events().is_err());
+
+        let flow =
+            RunJob::from(JobPayload::RawFlow { value: flow, path: None, restarted_from: None })
+                .arg("order_id", json!(42))
+                .push(&db)
+                .await;
+
+        let completed = listen_for_completed_jobs(&db).await;
+        let queue = listen_for_queue(&db).await;
+        let db_ = db.clone();
+
+        in_test_worker(
+            &db,
+            async move {
+                let db = db_;
+                wait_until_flow_suspends(flow, queue, &db).await;
+
+                let message = |id: i64| {
+                    HashMap::from([("order".to_string(), to_raw_value(&json!({ "id": id })))])
+                };
+                let resume = |path: &'static str, id: i64| {
+                    let db = db.clone();
+                    async move {
+                        resume_waiting_flows(
+                            &db,
+                            "test-workspace",
+                            &TriggerKind::Http,
+                            path,
+                            &message(id),
+                        )
+                        .await
+                        .unwrap()
+                    }
+                };
+
+                /* only a message of the same trigger with the same key resumes the flow */
+                assert!(resume("f/system/orders", 41).await.is_empty());
+                assert!(resume("f/system/payments", 42).await.is_empty());
+                assert_eq!(resume("f/system/orders", 42).await, vec![flow]);
+                assert!(resume("f/system/orders", 42).await.is_empty());
+
+                completed.find(&flow).await.unwrap();
+            },
+            port,
+        )
+        .await;
+
+        server.close().await.unwrap();
+
+        let result = completed_job(flow, &db).await.json_result().unwrap();
+        assert_eq!(json!({ "order": { "id": 42 } }), result);
+        assert_eq!(
+            0,
+            sqlx::query_scalar::<_, i64>("SELECT count(*) FROM flow_event_wait")
+                .fetch_one(&db)
+                .await
+                .unwrap()
+        );
+    }
+
     #[cfg(feature = "deno_core")]
     #[sqlx::test(fixtures("base"))]
     async fn cancel_from_job(db: Pool<Postgres>) {
@@ -904,11 +1018,12 @@ async fn test_iteration_parallel(db: Pool<Postgres>) {
 struct RunJob {
     payload: JobPayload,
     args: serde_json::Map<String, serde_json::Value>,
+    depends_on: Vec<Uuid>,
 }
 
 impl From<JobPayload> for RunJob {
     fn from(payload: JobPayload) -> Self {
-        Self { payload, args: Default::default() }
+        Self { payload, args: Default::default(), depends_on: vec![] }
     }
 }
 
@@ -918,8 +1033,14 @@ impl RunJob {
         self
     }
 
+    /// wait for the success of the `upstream` jobs
+    fn depends_on(mut self, upstream: &[Uuid]) -> Self {
+        self.depends_on.extend_from_slice(upstream);
+        self
+    }
+
     async fn push(self, db: &Pool<Postgres>) -> Uuid {
-        let RunJob { payload, args } = self;
+        let RunJob { payload, args, depends_on } = self;
         let mut hm_args = std::collections::HashMap::new();
         for (k, v) in args {
             hm_args.insert(k, windmill_common::worker::to_raw_value(&v));
@@ -949,6 +1070,7 @@ impl RunJob {
             None,
             None,
             None,
+            /* depends_on */ Some((depends_on.as_slice(), Default::default())),
         )
         .await
         .expect("push has to succeed");
@@ -1161,6 +1283,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
                 FlowModule {
                     id: "b".to_string(),
@@ -1169,6 +1292,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                         skip_failures: false,
                         parallel: false,
                         parallelism: None,
+                        deadline: None,
                         modules: vec![FlowModule {
                             id: "c".to_string(),
                             value: FlowModuleValue::RawScript {
@@ -1203,6 +1327,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                             delete_after_use: None,
                             continue_on_error: None,
                             skip_if: None,
+                            compensation: None,
                         }],
                         modules_node: None,
                     }
@@ -1220,6 +1345,7 @@ async fn test_deno_flow(db: Pool<Postgres>) {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
             ],
             same_worker: false,
@@ -1277,6 +1403,84 @@ async fn test_identity(db: Pool<Postgres>) {
     assert_eq!(result, serde_json::json!(42));
 }
 
+#[sqlx::test(fixtures("base"))]
+async fn test_flow_sleep(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "type": "rawscript",
+                "language": "deno",
+                "content": "export function main() { return 42 }",
+            },
+        }, {
+            "id": "b",
+            "value": {
+                "type": "sleep",
+                "seconds": { "type": "static", "value": 5 },
+            },
+        }],
+    }))
+    .unwrap();
+    let flow = RunJob::from(JobPayload::RawFlow { value: flow, path: None, restarted_from: None })
+        .push(&db)
+        .await;
+    let mut completed = listen_for_completed_jobs(&db).await;
+
+    let wake_at = in_test_worker(
+        &db,
+        async {
+            // the flow is parked in the queue until it is due
+            let wake_at: chrono::DateTime<chrono::Utc> = loop {
+                let parked: Option<Option<chrono::DateTime<chrono::Utc>>> = sqlx::query_scalar(
+                    "SELECT suspend_until FROM v2_job_queue WHERE id = $1 AND suspend > 0",
+                )
+                .bind(flow)
+                .fetch_optional(&db)
+                .await
+                .unwrap();
+                if let Some(Some(wake_at)) = parked {
+                    break wake_at;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            };
+
+            // meanwhile the only worker is free to run other jobs
+            let other = RunJob::from(JobPayload::Code(RawCode {
+                hash: None,
+                content: "export function main() { return 'other' }".to_string(),
+                path: None,
+                lock: None,
+                language: ScriptLang::Deno,
+                custom_concurrency_key: None,
+                concurrent_limit: None,
+                concurrency_time_window_s: None,
+                cache_ttl: None,
+                dedicated_worker: None,
+            }))
+            .push(&db)
+            .await;
+            (&mut completed).find(&other).await;
+            assert!(completed_job(other, &db).await.started_at < wake_at);
+
+            // then the suspended pull query wakes the flow up
+            (&mut completed).find(&flow).await;
+            wake_at
+        },
+        port,
+    )
+    .await;
+
+    let job = completed_job(flow, &db).await;
+    assert!(job.success);
+    assert_eq!(job.json_result(), Some(json!(42)));
+    assert!(chrono::Utc::now() >= wake_at);
+}
+
 #[cfg(feature = "deno_core")]
 #[sqlx::test(fixtures("base"))]
 async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
@@ -1330,6 +1534,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
                 FlowModule {
                     id: "b".to_string(),
@@ -1338,6 +1543,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                         skip_failures: false,
                         parallel: false,
                         parallelism: None,
+                        deadline: None,
                         modules: vec![
                             FlowModule {
                                 id: "d".to_string(),
@@ -1382,6 +1588,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                                 delete_after_use: None,
                                 continue_on_error: None,
                                 skip_if: None,
+                                compensation: None,
                             },
                             FlowModule {
                                 id: "e".to_string(),
@@ -1421,6 +1628,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                                 delete_after_use: None,
                                 continue_on_error: None,
                                 skip_if: None,
+                                compensation: None,
                             },
                         ],
                         modules_node: None,
@@ -1438,6 +1646,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
                 FlowModule {
                     id: "c".to_string(),
@@ -1483,6 +1692,7 @@ async fn test_deno_flow_same_worker(db: Pool<Postgres>) {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
             ],
             same_worker: true,
@@ -1835,6 +2045,42 @@ fn main(world: String) -> Result<String, String> {
     assert_eq!(result, serde_json::json!("Hello Hyrule!"));
 }
 
+#[cfg(all(feature = "wasm", feature = "rust"))]
+#[sqlx::test(fixtures("base"))]
+async fn test_wasm_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
+fn main(world: String) -> Result<String, String> {
+    println!("Which world to greet today?");
+    Ok(format!("Hello {}!", world))
+}
+        "#
+    .to_owned();
+
+    let result = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        lock: None,
+        language: ScriptLang::Wasm,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .arg("world", json!("Hyrule"))
+    .run_until_complete(&db, port)
+    .await
+    .json_result()
+    .unwrap();
+
+    assert_eq!(result, serde_json::json!("Hello Hyrule!"));
+}
+
 // #[sqlx::test(fixtures("base"))]
 // async fn test_csharp_job(db: Pool<Postgres>) {
 //     initialize_tracing().await;
@@ -2010,27 +2256,17 @@ def main [
     assert_eq!(result, serde_json::json!(0));
 }
 
-#[cfg(feature = "java")]
+#[cfg(feature = "duckdb")]
 #[sqlx::test(fixtures("base"))]
-async fn test_java_job(db: Pool<Postgres>) {
+async fn test_duckdb_job(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
     let content = r#"
-public class Main {
-  public static Object main(
-    // Primitive
-    int a,
-    float b,
-    // Objects
-    Integer age,
-    Float d
-    ){
-    return "hello world";
-  }
-}
-
+-- $1 n
+-- $2 tags
+SELECT x, x * 2 AS doubled FROM range($1::BIGINT) t(x) WHERE 'a' = ANY($2::VARCHAR[]);
 "#
     .to_owned();
 
@@ -2039,136 +2275,350 @@ public class Main {
         content,
         path: None,
         lock: None,
-        language: ScriptLang::Java,
+        language: ScriptLang::Duckdb,
         custom_concurrency_key: None,
         concurrent_limit: None,
         concurrency_time_window_s: None,
         cache_ttl: None,
         dedicated_worker: None,
     }))
-    .arg("a", json!(3))
-    .arg("b", json!(3.0))
-    .arg("age", json!(30))
-    .arg("d", json!(3.0))
+    .arg("n", json!(2))
+    .arg("tags", json!(["a", "b"]))
     .run_until_complete(&db, port)
     .await;
-    assert_eq!(job.json_result(), Some(json!("hello world")));
+    assert_eq!(
+        job.json_result(),
+        Some(json!([{"x": 0, "doubled": 0}, {"x": 1, "doubled": 2}]))
+    );
 }
 
-#[cfg(feature = "python")]
+#[cfg(feature = "lua")]
 #[sqlx::test(fixtures("base"))]
-async fn test_python_job(db: Pool<Postgres>) {
+async fn test_lua_job(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
     let content = r#"
-def main():
-    return "hello world"
-        "#
+---@param name string
+---@param count? integer
+---@param values table
+function main(name, count, values)
+  count = count or 2
+  print("Hello " .. name)
+  local items = {}
+  for i = 1, count do
+    items[i] = name .. i
+  end
+  return { items = items, values = values }
+end
+"#
     .to_owned();
 
-    let job = JobPayload::Code(RawCode {
+    let job = RunJob::from(JobPayload::Code(RawCode {
         hash: None,
         content,
         path: None,
-        language: ScriptLang::Python3,
         lock: None,
+        language: ScriptLang::Lua,
         custom_concurrency_key: None,
         concurrent_limit: None,
         concurrency_time_window_s: None,
         cache_ttl: None,
         dedicated_worker: None,
-    });
-
-    let result = run_job_in_new_worker_until_complete(&db, job, port)
-        .await
-        .json_result()
-        .unwrap();
-
-    assert_eq!(result, serde_json::json!("hello world"));
+    }))
+    .arg("name", json!("world"))
+    .arg("values", json!({"a": [1, null, 2]}))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(
+        job.json_result(),
+        Some(json!({"items": ["world1", "world2"], "values": {"a": [1, null, 2]}}))
+    );
 }
 
-#[cfg(feature = "python")]
+#[cfg(feature = "lua")]
 #[sqlx::test(fixtures("base"))]
-async fn test_python_job_heavy_dep(db: Pool<Postgres>) {
+async fn test_lua_sandbox(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
     let content = r#"
-import numpy as np
-
-def main():
-    a = np.arange(15).reshape(3, 5)
-    return len(a)
-        "#
+function main()
+  local chunk, err = load(string.dump(function() return 1 end))
+  return {
+    dofile = dofile == nil,
+    loadfile = loadfile == nil,
+    collectgarbage = collectgarbage == nil,
+    bytecode = chunk == nil and err ~= nil,
+    text = load("return x", "text", "t", { x = 2 })(),
+  }
+end
+"#
     .to_owned();
 
-    let job = JobPayload::Code(RawCode {
+    let job = RunJob::from(JobPayload::Code(RawCode {
         hash: None,
         content,
         path: None,
-        language: ScriptLang::Python3,
         lock: None,
+        language: ScriptLang::Lua,
         custom_concurrency_key: None,
         concurrent_limit: None,
         concurrency_time_window_s: None,
         cache_ttl: None,
         dedicated_worker: None,
-    });
-
-    let result = run_job_in_new_worker_until_complete(&db, job, port)
-        .await
-        .json_result()
-        .unwrap();
-
-    assert_eq!(result, serde_json::json!(3));
+    }))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(
+        job.json_result(),
+        Some(json!({
+            "dofile": true,
+            "loadfile": true,
+            "collectgarbage": true,
+            "bytecode": true,
+            "text": 2,
+        }))
+    );
 }
 
-#[cfg(feature = "python")]
+#[cfg(feature = "java")]
 #[sqlx::test(fixtures("base"))]
-async fn test_python_job_with_imports(db: Pool<Postgres>) {
+async fn test_java_job(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
     let content = r#"
-import wmill
+public class Main {
+  public static Object main(
+    // Primitive
+    int a,
+    float b,
+    // Objects
+    Integer age,
+    Float d
+    ){
+    return "hello world";
+  }
+}
 
-def main():
-    return wmill.get_workspace()
-        "#
+"#
     .to_owned();
 
-    let job = JobPayload::Code(RawCode {
+    let job = RunJob::from(JobPayload::Code(RawCode {
         hash: None,
         content,
         path: None,
-        language: ScriptLang::Python3,
         lock: None,
+        language: ScriptLang::Java,
         custom_concurrency_key: None,
         concurrent_limit: None,
         concurrency_time_window_s: None,
         cache_ttl: None,
         dedicated_worker: None,
-    });
-
-    let result = run_job_in_new_worker_until_complete(&db, job, port)
-        .await
-        .json_result()
-        .unwrap();
-
-    assert_eq!(result, serde_json::json!("test-workspace"));
+    }))
+    .arg("a", json!(3))
+    .arg("b", json!(3.0))
+    .arg("age", json!(30))
+    .arg("d", json!(3.0))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(job.json_result(), Some(json!("hello world")));
 }
 
+#[cfg(feature = "ruby")]
 #[sqlx::test(fixtures("base"))]
-async fn test_bun_job_datetime(db: Pool<Postgres>) {
+async fn test_ruby_job(db: Pool<Postgres>) {
     initialize_tracing().await;
     let server = ApiServer::start(db.clone()).await;
     let port = server.addr.port();
 
-    let content = r#"
+    let content = r##"
+# @param name [String]
+# @param count [Integer]
+def main(name, count = 2, suffix: '!')
+  puts "Hello #{name}"
+  { items: (1..count).map { |i| "#{name}#{i}#{suffix}" } }
+end
+"##
+    .to_owned();
+
+    let job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        lock: None,
+        language: ScriptLang::Ruby,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .arg("name", json!("world"))
+    .arg("suffix", json!("?"))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(
+        job.json_result(),
+        Some(json!({"items": ["world1?", "world2?"]}))
+    );
+}
+
+#[cfg(feature = "r")]
+#[sqlx::test(fixtures("base"))]
+async fn test_r_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
+main <- function(name, count = 2L, suffix = "!") {
+  print(paste("Hello", name))
+  data.frame(i = seq_len(count), label = paste0(name, seq_len(count), suffix))
+}
+"#
+    .to_owned();
+
+    let job = RunJob::from(JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        lock: None,
+        language: ScriptLang::R,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    }))
+    .arg("name", json!("world"))
+    .arg("suffix", json!("?"))
+    .run_until_complete(&db, port)
+    .await;
+    assert_eq!(
+        job.json_result(),
+        Some(json!([{"i": 1, "label": "world1?"}, {"i": 2, "label": "world2?"}]))
+    );
+}
+
+#[cfg(feature = "python")]
+#[sqlx::test(fixtures("base"))]
+async fn test_python_job(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
+def main():
+    return "hello world"
+        "#
+    .to_owned();
+
+    let job = JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        language: ScriptLang::Python3,
+        lock: None,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    });
+
+    let result = run_job_in_new_worker_until_complete(&db, job, port)
+        .await
+        .json_result()
+        .unwrap();
+
+    assert_eq!(result, serde_json::json!("hello world"));
+}
+
+#[cfg(feature = "python")]
+#[sqlx::test(fixtures("base"))]
+async fn test_python_job_heavy_dep(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
+import numpy as np
+
+def main():
+    a = np.arange(15).reshape(3, 5)
+    return len(a)
+        "#
+    .to_owned();
+
+    let job = JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        language: ScriptLang::Python3,
+        lock: None,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    });
+
+    let result = run_job_in_new_worker_until_complete(&db, job, port)
+        .await
+        .json_result()
+        .unwrap();
+
+    assert_eq!(result, serde_json::json!(3));
+}
+
+#[cfg(feature = "python")]
+#[sqlx::test(fixtures("base"))]
+async fn test_python_job_with_imports(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
+import wmill
+
+def main():
+    return wmill.get_workspace()
+        "#
+    .to_owned();
+
+    let job = JobPayload::Code(RawCode {
+        hash: None,
+        content,
+        path: None,
+        language: ScriptLang::Python3,
+        lock: None,
+        custom_concurrency_key: None,
+        concurrent_limit: None,
+        concurrency_time_window_s: None,
+        cache_ttl: None,
+        dedicated_worker: None,
+    });
+
+    let result = run_job_in_new_worker_until_complete(&db, job, port)
+        .await
+        .json_result()
+        .unwrap();
+
+    assert_eq!(result, serde_json::json!("test-workspace"));
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_bun_job_datetime(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let content = r#"
 export async function main(a: Date) {
     return typeof a;
 }
@@ -2642,6 +3092,109 @@ async fn test_branchall_simple(db: Pool<Postgres>) {
     assert_eq!(result, serde_json::json!([[1, 2], [1, 3]]));
 }
 
+/// A branch that sleeps without holding the worker before returning `value`.
+fn sleeping_branch(id: &str, seconds: u32, value: &str) -> serde_json::Value {
+    json!({
+        "modules": [{
+            "id": format!("{id}_sleep"),
+            "value": { "type": "sleep", "seconds": { "type": "static", "value": seconds } },
+        }, {
+            "id": id,
+            "value": {
+                "type": "rawscript",
+                "language": "deno",
+                "content": format!("export function main() {{ return {value} }}"),
+            },
+        }],
+    })
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_branchall_parallelism(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "type": "branchall",
+                "parallel": true,
+                "parallelism": 1,
+                "branches": [
+                    sleeping_branch("b", 1, "1"),
+                    sleeping_branch("c", 1, "2"),
+                    sleeping_branch("d", 1, "3"),
+                ],
+            }
+        }],
+    }))
+    .unwrap();
+
+    let job = run_job_in_new_worker_until_complete(
+        &db,
+        JobPayload::RawFlow { value: flow, path: None, restarted_from: None },
+        port,
+    )
+    .await;
+    assert!(job.success);
+    assert_eq!(job.json_result(), Some(json!([1, 2, 3])));
+    // the sleeps would overlap if the branches all started together
+    assert!(job.duration_ms >= 3000);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_branchall_race(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "type": "branchall",
+                "parallel": true,
+                "race": true,
+                "branches": [
+                    sleeping_branch("b", 30, "'slow'"),
+                    { "modules": [module_failure()] },
+                    sleeping_branch("c", 0, "'fast'"),
+                ],
+            }
+        }],
+    }))
+    .unwrap();
+
+    let job = run_job_in_new_worker_until_complete(
+        &db,
+        JobPayload::RawFlow { value: flow, path: None, restarted_from: None },
+        port,
+    )
+    .await;
+    assert!(job.success);
+    assert_eq!(job.json_result(), Some(json!("fast")));
+
+    // the first branch to succeed wins, the failed one does not end the race
+    let flow_status = job.flow_status.unwrap();
+    assert_eq!(
+        flow_status["modules"][0]["branch_chosen"],
+        json!({ "type": "branch", "branch": 2 })
+    );
+    let losers = serde_json::from_value::<Vec<Uuid>>(flow_status["race_losers"].clone()).unwrap();
+    assert_eq!(losers.len(), 2);
+    let canceled_by = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT canceled_by FROM v2_job_completed WHERE id = $1
+         UNION ALL SELECT canceled_by FROM v2_job_queue WHERE id = $1",
+    )
+    .bind(losers[0])
+    .fetch_all(&db)
+    .await
+    .unwrap();
+    assert_eq!(canceled_by, vec![Some("race".to_string())]);
+}
+
 #[derive(Deserialize)]
 struct ErrorResult {
     error: NamedError,
@@ -2902,67 +3455,455 @@ async fn test_failure_module(db: Pool<Postgres>) {
                 }
             },
         }))
-        .unwrap();
-
-    let result =
-        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
-            .arg("n", json!(0))
-            .run_until_complete(&db, port)
-            .await
-            .json_result()
-            .unwrap();
-
-    assert!(result["from failure module"]["error"]
-        .as_object()
-        .unwrap()
-        .get("message")
-        .unwrap()
-        .as_str()
+        .unwrap();
+
+    let result =
+        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
+            .arg("n", json!(0))
+            .run_until_complete(&db, port)
+            .await
+            .json_result()
+            .unwrap();
+
+    assert!(result["from failure module"]["error"]
+        .as_object()
+        .unwrap()
+        .get("message")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .contains("[]"));
+
+    let result =
+        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
+            .arg("n", json!(1))
+            .run_until_complete(&db, port)
+            .await
+            .json_result()
+            .unwrap();
+
+    assert!(result["from failure module"]["error"]
+        .as_object()
+        .unwrap()
+        .get("message")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .contains("[0]"));
+
+    let result =
+        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
+            .arg("n", json!(2))
+            .run_until_complete(&db, port)
+            .await
+            .json_result()
+            .unwrap();
+
+    assert!(result["from failure module"]["error"]
+        .as_object()
+        .unwrap()
+        .get("message")
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .contains("[0,1]"));
+
+    let result =
+        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
+            .arg("n", json!(3))
+            .run_until_complete(&db, port)
+            .await
+            .json_result()
+            .unwrap();
+    assert_eq!(json!({ "l": [0, 1, 2] }), result);
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_flow_compensations(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    fn compensated_step(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "value": {
+                "type": "rawscript",
+                "language": "deno",
+                "content": format!("export function main() {{ return '{id}' }}"),
+            },
+            "compensation": {
+                "value": {
+                    "type": "rawscript",
+                    "language": "deno",
+                    "content": "export function main(input, result) { return `undo ${result}` }",
+                }
+            },
+        })
+    }
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [compensated_step("a"), compensated_step("b"), module_failure()],
+    }))
+    .unwrap();
+    flow.check_compensations().unwrap();
+
+    let job = run_job_in_new_worker_until_complete(
+        &db,
+        JobPayload::RawFlow { value: flow, path: None, restarted_from: None },
+        port,
+    )
+    .await;
+    assert!(!job.success);
+    assert_eq!(
+        serde_json::from_value::<ErrorResult>(job.json_result().unwrap())
+            .unwrap()
+            .error
+            .name,
+        "Error"
+    );
+
+    // the steps that succeeded are compensated, latest first, with their own result
+    let compensation = job.flow_status.unwrap()["compensation"].clone();
+    assert_eq!(compensation["success"], json!(true));
+    let steps = compensation["steps"].as_array().unwrap();
+    assert_eq!(
+        steps.iter().map(|s| s["id"].clone()).collect::<Vec<_>>(),
+        vec![json!("b"), json!("a")]
+    );
+    let mut compensation_jobs = vec![];
+    for step in steps {
+        assert_eq!(step["success"], json!(true));
+        let id = serde_json::from_value::<Uuid>(step["job"].clone()).unwrap();
+        compensation_jobs.push(completed_job(id, &db).await);
+    }
+    assert_eq!(compensation_jobs[0].json_result(), Some(json!("undo b")));
+    assert_eq!(compensation_jobs[1].json_result(), Some(json!("undo a")));
+    assert!(compensation_jobs[0].started_at < compensation_jobs[1].started_at);
+
+    // steps nested in loops and branches are never compensated, so they cannot have one
+    let nested: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "value": {
+                "type": "forloopflow",
+                "iterator": { "type": "static", "value": [1, 2] },
+                "parallel": false,
+                "modules": [compensated_step("a")],
+            }
+        }],
+    }))
+    .unwrap();
+    assert!(nested.check_compensations().is_err());
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_module_deadlines(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    // every iteration or branch outlives the deadline, so it expires with the first one
+    fn slow_step(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "value": {
+                "type": "rawscript",
+                "language": "deno",
+                "content": "export async function main() { await new Promise((r) => setTimeout(r, 1500)); return 'done' }",
+            }
+        })
+    }
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "type": "forloopflow",
+                "iterator": { "type": "static", "value": [1, 2, 3] },
+                "parallel": false,
+                "skip_failures": false,
+                "modules": [slow_step("b")],
+                "deadline": { "seconds": 1, "on_timeout": "continue" },
+            }
+        }],
+    }))
+    .unwrap();
+    let job = run_job_in_new_worker_until_complete(
+        &db,
+        JobPayload::RawFlow { value: flow, path: None, restarted_from: None },
+        port,
+    )
+    .await;
+    assert!(job.success);
+    assert_eq!(job.json_result(), Some(json!(["done"])));
+    assert!(job.flow_status.unwrap().get("deadline").is_none());
+
+    let flow: FlowValue = serde_json::from_value(json!({
+        "modules": [{
+            "id": "a",
+            "value": {
+                "type": "branchall",
+                "parallel": false,
+                "branches": [
+                    { "modules": [slow_step("b")], "skip_failure": false },
+                    { "modules": [slow_step("c")], "skip_failure": false },
+                ],
+                "deadline": { "seconds": 1 },
+            }
+        }],
+    }))
+    .unwrap();
+    let job = run_job_in_new_worker_until_complete(
+        &db,
+        JobPayload::RawFlow { value: flow, path: None, restarted_from: None },
+        port,
+    )
+    .await;
+    assert!(!job.success);
+    assert_eq!(
+        serde_json::from_value::<ErrorResult>(job.json_result().unwrap())
+            .unwrap()
+            .error
+            .name,
+        "DeadlineExceeded"
+    );
+}
+
+#[sqlx::test(fixtures("base", "hello"))]
+async fn test_idempotency_key(db: Pool<Postgres>) -> anyhow::Result<()> {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+
+    sqlx::query(
+        "INSERT INTO usr(workspace_id, email, username, is_admin, role)
+        VALUES ('test-workspace', 'other@windmill.dev', 'other-user', true, 'Admin')",
+    )
+    .execute(&db)
+    .await?;
+    sqlx::query(
+        "INSERT INTO token(token, email, label, super_admin)
+        VALUES ('OTHER_TOKEN', 'other@windmill.dev', 'other token', false)",
+    )
+    .execute(&db)
+    .await?;
+
+    let run = |path: &'static str, token: &'static str, key: &'static str| async move {
+        let uuid = reqwest::Client::new()
+            .post(format!(
+                "http://localhost:{port}/api/w/test-workspace/jobs/run/p/{path}"
+            ))
+            .bearer_auth(token)
+            .header("Idempotency-Key", key)
+            .json(&json!({ "world": "idempotent" }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        Uuid::parse_str(&uuid).unwrap()
+    };
+
+    // the same key returns the job of the first request
+    let first = run("f/system/hello", "SECRET_TOKEN", "k").await;
+    assert_eq!(run("f/system/hello", "SECRET_TOKEN", "k").await, first);
+
+    // another key, runnable or caller gets a new job
+    let other_key = run("f/system/hello", "SECRET_TOKEN", "k2").await;
+    let other_runnable = run("f/system/hello_with_preprocessor", "SECRET_TOKEN", "k").await;
+    let other_caller = run("f/system/hello", "OTHER_TOKEN", "k").await;
+    let jobs = [first, other_key, other_runnable, other_caller];
+    assert_eq!(
+        jobs.iter().collect::<std::collections::HashSet<_>>().len(),
+        4
+    );
+    assert_eq!(
+        run("f/system/hello", "OTHER_TOKEN", "k").await,
+        other_caller
+    );
+
+    let queued: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM v2_job_queue WHERE id = ANY($1)")
+        .bind(&jobs[..])
+        .fetch_one(&db)
+        .await?;
+    assert_eq!(queued, 4);
+
+    // the key of a deleted job is taken over
+    sqlx::query("DELETE FROM v2_job_queue WHERE id = $1")
+        .bind(first)
+        .execute(&db)
+        .await?;
+    sqlx::query("DELETE FROM v2_job WHERE id = $1")
+        .bind(first)
+        .execute(&db)
+        .await?;
+    let after_delete = run("f/system/hello", "SECRET_TOKEN", "k").await;
+    assert_ne!(after_delete, first);
+    assert_eq!(
+        run("f/system/hello", "SECRET_TOKEN", "k").await,
+        after_delete
+    );
+    Ok(())
+}
+
+#[cfg(all(feature = "flow_testing", feature = "deno_core"))]
+#[sqlx::test(fixtures("base"))]
+async fn test_flow_test_cases(db: Pool<Postgres>) {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let client = reqwest::Client::new();
+    let url = |route: &str| format!("http://localhost:{port}/api/w/test-workspace/flows/{route}");
+
+    client
+        .post(url("create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "u/test-user/tested",
+            "summary": "",
+            "value": {
+                "modules": [{
+                    "id": "a",
+                    "value": {
+                        "input_transforms": { "x": { "type": "javascript", "expr": "flow_input.x" } },
+                        "type": "rawscript",
+                        "language": "deno",
+                        "content": "export function main(x) { return x * 2 }",
+                    },
+                }, {
+                    "id": "c",
+                    "value": {
+                        "type": "forloopflow",
+                        "iterator": { "type": "javascript", "expr": "[1, 2]" },
+                        "skip_failures": false,
+                        "modules": [{
+                            "id": "d",
+                            "value": {
+                                "input_transforms": {},
+                                "type": "rawscript",
+                                "language": "deno",
+                                "content": "export function main() { throw new Error('not mocked') }",
+                            },
+                        }],
+                    },
+                }, {
+                    "id": "b",
+                    "value": {
+                        "input_transforms": {},
+                        "type": "rawscript",
+                        "language": "deno",
+                        "content": "export function main() { throw new Error('not mocked') }",
+                    },
+                }],
+            },
+        }))
+        .send()
+        .await
         .unwrap()
-        .contains("[]"));
-
-    let result =
-        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
-            .arg("n", json!(1))
-            .run_until_complete(&db, port)
-            .await
-            .json_result()
-            .unwrap();
+        .error_for_status()
+        .unwrap();
 
-    assert!(result["from failure module"]["error"]
-        .as_object()
-        .unwrap()
-        .get("message")
-        .unwrap()
-        .as_str()
+    let case = |name: &str, x: i64| {
+        json!({
+            "name": name,
+            "args": { "x": x },
+            "mocks": {
+                "b": { "enabled": true, "return_value": "mocked" },
+                "d": { "enabled": true, "return_value": "mocked" },
+            },
+            "assertions": [
+                { "step": "a", "expr": "result === 4" },
+                { "expr": "result === 'mocked'" },
+            ],
+        })
+    };
+    client
+        .post(url("tests/set/u/test-user/tested"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!([
+            case("double of 2", 2),
+            case("double of 3", 3),
+            {
+                "name": "unknown mock",
+                "mocks": { "z": { "enabled": true, "return_value": "mocked" } },
+            },
+            { "name": "unmocked", "args": { "x": 2 }, "assertions": [{ "expr": "true" }] },
+        ]))
+        .send()
+        .await
         .unwrap()
-        .contains("[0]"));
+        .error_for_status()
+        .unwrap();
 
-    let result =
-        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
-            .arg("n", json!(2))
-            .run_until_complete(&db, port)
-            .await
-            .json_result()
-            .unwrap();
+    let mut completed = listen_for_completed_jobs(&db).await;
+    let runs = in_test_worker(
+        &db,
+        async {
+            let runs = client
+                .post(url("tests/run/u/test-user/tested"))
+                .bearer_auth("SECRET_TOKEN")
+                .send()
+                .await
+                .unwrap()
+                .error_for_status()
+                .unwrap()
+                .json::<serde_json::Value>()
+                .await
+                .unwrap();
+            let mut pending = runs
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|run| serde_json::from_value::<Uuid>(run["job_id"].clone()).ok())
+                .collect::<std::collections::HashSet<_>>();
+            while !pending.is_empty() {
+                pending.remove(&completed.next().await.unwrap());
+            }
+            runs
+        },
+        port,
+    )
+    .await;
 
-    assert!(result["from failure module"]["error"]
-        .as_object()
-        .unwrap()
-        .get("message")
+    let report = client
+        .post(url("tests/report/u/test-user/tested"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&runs)
+        .send()
+        .await
         .unwrap()
-        .as_str()
+        .error_for_status()
         .unwrap()
-        .contains("[0,1]"));
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
 
-    let result =
-        RunJob::from(JobPayload::RawFlow { value: flow.clone(), path: None, restarted_from: None })
-            .arg("n", json!(3))
-            .run_until_complete(&db, port)
-            .await
-            .json_result()
-            .unwrap();
-    assert_eq!(json!({ "l": [0, 1, 2] }), result);
+    // the cases are reported by name, the mocked steps never run, including the ones nested in
+    // loops, and a failed flow fails its case
+    assert_eq!(report["passed"], json!(false));
+    assert_eq!(report["completed"], json!(true));
+    let cases = report["cases"].as_array().unwrap();
+    assert_eq!(cases[0]["name"], json!("double of 2"));
+    assert_eq!(cases[0]["passed"], json!(true));
+    assert_eq!(cases[1]["name"], json!("double of 3"));
+    assert_eq!(cases[1]["passed"], json!(false));
+    assert_eq!(
+        cases[1]["assertions"],
+        json!([
+            { "step": "a", "expr": "result === 4", "passed": false },
+            { "expr": "result === 'mocked'", "passed": true },
+        ])
+    );
+    assert_eq!(cases[2]["name"], json!("unknown mock"));
+    assert_eq!(cases[2]["passed"], json!(false));
+    assert!(cases[2]["error"].as_str().unwrap().contains("step z"));
+    assert_eq!(cases[3]["name"], json!("unmocked"));
+    assert_eq!(cases[3]["passed"], json!(false));
+    assert_eq!(cases[3]["assertions"], json!([]));
+    assert!(cases[3]["error"].as_str().unwrap().contains("not mocked"));
 }
 
 #[cfg(feature = "python")]
@@ -3315,6 +4256,82 @@ async fn test_rust_client(db: Pool<Postgres>) {
     .unwrap();
 }
 
+#[cfg(feature = "enterprise")]
+#[sqlx::test(fixtures("base", "hello"))]
+async fn test_schedule_catchup_defaults(db: Pool<Postgres>) -> anyhow::Result<()> {
+    initialize_tracing().await;
+    let server = ApiServer::start(db.clone()).await;
+    let port = server.addr.port();
+    let base = format!("http://localhost:{port}/api/w/test-workspace/schedules");
+    let client = reqwest::Client::new();
+
+    client
+        .post(format!("{base}/create"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({
+            "path": "f/system/hello_schedule",
+            "schedule": "0 * * * * *",
+            "timezone": "UTC",
+            "script_path": "f/system/hello",
+            "is_flow": false,
+            "args": { "world": "schedule" },
+            "enabled": true,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let pending: chrono::DateTime<chrono::Utc> = sqlx::query_scalar(
+        "SELECT scheduled_for FROM v2_job_queue JOIN v2_job j USING (id)
+        WHERE trigger_kind = 'schedule' AND trigger = 'f/system/hello_schedule'",
+    )
+    .fetch_one(&db)
+    .await?;
+
+    // nothing was missed since the last tick that ran
+    let ticks = client
+        .get(format!("{base}/catchup/f/system/hello_schedule"))
+        .bearer_auth("SECRET_TOKEN")
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<chrono::DateTime<chrono::Utc>>>()
+        .await?;
+    assert!(ticks.is_empty(), "unexpected catch-up ticks {ticks:?}");
+    let pushed = client
+        .post(format!("{base}/catchup/f/system/hello_schedule"))
+        .bearer_auth("SECRET_TOKEN")
+        .json(&json!({}))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<Uuid>>()
+        .await?;
+    assert!(pushed.is_empty());
+
+    // a range over the pending tick stops right before it
+    let from = pending - chrono::Duration::minutes(3);
+    let ticks = client
+        .get(format!("{base}/catchup/f/system/hello_schedule"))
+        .bearer_auth("SECRET_TOKEN")
+        .query(&[
+            ("from", from.to_rfc3339()),
+            ("to", (pending + chrono::Duration::minutes(3)).to_rfc3339()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Vec<chrono::DateTime<chrono::Utc>>>()
+        .await?;
+    assert_eq!(
+        ticks,
+        (0..3)
+            .map(|i| from + chrono::Duration::minutes(i))
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
 #[cfg(feature = "enterprise")]
 #[sqlx::test(fixtures("base", "schedule"))]
 async fn test_script_schedule_handlers(db: Pool<Postgres>) {
@@ -4214,6 +5231,548 @@ async fn test_job_labels(db: Pool<Postgres>) {
     test(&["z", "a", "x"]).await;
 }
 
+#[sqlx::test(fixtures("base"))]
+async fn test_circuit_breaker_cycle(db: Pool<Postgres>) -> anyhow::Result<()> {
+    use windmill_queue::circuit_breaker::{
+        check_circuit_breakers, invalidate_has_circuit_breakers, recheck_delayed_job,
+        record_circuit_breaker_outcome, ShortCircuit,
+    };
+
+    initialize_tracing().await;
+
+    sqlx::query(
+        "INSERT INTO circuit_breaker (workspace_id, kind, path, failure_threshold, window_s,
+            cooldown_s, edited_by)
+        VALUES ('test-workspace', 'script', 'f/system/flaky', 2, 60, 60, 'test-user')",
+    )
+    .execute(&db)
+    .await?;
+    invalidate_has_circuit_breakers("test-workspace");
+
+    fn job(id: Uuid) -> windmill_queue::MiniPulledJob {
+        serde_json::from_value(json!({
+            "workspace_id": "test-workspace",
+            "id": id,
+            "created_by": "test-user",
+            "scheduled_for": chrono::Utc::now(),
+            "runnable_path": "f/system/flaky",
+            "kind": "script",
+            "permissioned_as": "u/test-user",
+            "permissioned_as_email": "test@windmill.dev",
+            "tag": "deno",
+            "same_worker": false,
+            "visible_to_owner": true,
+        }))
+        .unwrap()
+    }
+    async fn state(db: &Pool<Postgres>) -> String {
+        sqlx::query_scalar("SELECT state::text FROM circuit_breaker")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+    async fn check(db: &Pool<Postgres>, id: Uuid) -> Option<ShortCircuit> {
+        let mut tx = db.begin().await.unwrap();
+        let short_circuit =
+            check_circuit_breakers(&mut tx, "test-workspace", Some("f/system/flaky"), &[], id)
+                .await
+                .unwrap();
+        tx.commit().await.unwrap();
+        short_circuit
+    }
+    async fn record(db: &Pool<Postgres>, id: Uuid, success: bool) {
+        let mut tx = db.begin().await.unwrap();
+        record_circuit_breaker_outcome(&mut tx, &job(id), success)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    // a failure is only counted once its completion is committed
+    let mut tx = db.begin().await?;
+    record_circuit_breaker_outcome(&mut tx, &job(Uuid::new_v4()), false).await?;
+    tx.rollback().await?;
+    assert_eq!(
+        sqlx::query_scalar::<_, i32>("SELECT failure_count FROM circuit_breaker")
+            .fetch_one(&db)
+            .await?,
+        0
+    );
+
+    // closed -> open once the threshold is reached within the window
+    record(&db, Uuid::new_v4(), false).await;
+    assert_eq!(state(&db).await, "closed");
+    assert!(check(&db, Uuid::new_v4()).await.is_none());
+    record(&db, Uuid::new_v4(), false).await;
+    assert_eq!(state(&db).await, "open");
+    assert!(matches!(
+        check(&db, Uuid::new_v4()).await,
+        Some(ShortCircuit::Fail(_))
+    ));
+
+    // open -> half open once the cooldown is over, with a single probe let through
+    sqlx::query("UPDATE circuit_breaker SET opened_at = now() - interval '2 minutes'")
+        .execute(&db)
+        .await?;
+    let probe = Uuid::new_v4();
+    assert!(check(&db, probe).await.is_none());
+    assert_eq!(state(&db).await, "half_open");
+    assert!(matches!(
+        check(&db, Uuid::new_v4()).await,
+        Some(ShortCircuit::Fail(_))
+    ));
+
+    // a probe that never completes is replaced after another cooldown
+    sqlx::query("UPDATE circuit_breaker SET probe_started_at = now() - interval '2 minutes'")
+        .execute(&db)
+        .await?;
+    let retried_probe = Uuid::new_v4();
+    assert!(check(&db, retried_probe).await.is_none());
+    assert_eq!(
+        sqlx::query_scalar::<_, Option<Uuid>>("SELECT probe_job FROM circuit_breaker")
+            .fetch_one(&db)
+            .await?,
+        Some(retried_probe)
+    );
+
+    // the outcome of the replaced probe is ignored, the one of the current probe closes it
+    record(&db, probe, true).await;
+    assert_eq!(state(&db).await, "half_open");
+    record(&db, retried_probe, true).await;
+    assert_eq!(state(&db).await, "closed");
+    assert!(check(&db, Uuid::new_v4()).await.is_none());
+
+    // delayed jobs are held while the breaker is open or another job is its probe
+    sqlx::query("UPDATE circuit_breaker SET on_open = 'delay'")
+        .execute(&db)
+        .await?;
+    record(&db, Uuid::new_v4(), false).await;
+    record(&db, Uuid::new_v4(), false).await;
+    assert_eq!(state(&db).await, "open");
+    let delayed = Uuid::new_v4();
+    let Some(ShortCircuit::Delay(until)) = check(&db, delayed).await else {
+        panic!("expected the job to be delayed");
+    };
+    assert!(until > chrono::Utc::now());
+    assert!(recheck_delayed_job(&db, &job(delayed)).await?.is_some());
+
+    sqlx::query("UPDATE circuit_breaker SET opened_at = now() - interval '2 minutes'")
+        .execute(&db)
+        .await?;
+    let probe = Uuid::new_v4();
+    assert!(check(&db, probe).await.is_none());
+    assert_eq!(state(&db).await, "half_open");
+    assert!(recheck_delayed_job(&db, &job(probe)).await?.is_none());
+    assert!(recheck_delayed_job(&db, &job(delayed)).await?.is_some());
+
+    record(&db, probe, true).await;
+    assert_eq!(state(&db).await, "closed");
+    assert!(recheck_delayed_job(&db, &job(delayed)).await?.is_none());
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_rate_limit_token_timing(db: Pool<Postgres>) -> anyhow::Result<()> {
+    use windmill_queue::rate_limit::{invalidate_has_rate_limits, reserve_rate_limit_token};
+
+    initialize_tracing().await;
+
+    sqlx::query(
+        "INSERT INTO rate_limit (workspace_id, key, rate, burst, edited_by)
+        VALUES ('test-workspace', 'rl', 10, 3, 'test-user')",
+    )
+    .execute(&db)
+    .await?;
+    invalidate_has_rate_limits("test-workspace");
+
+    async fn reserve(
+        db: &Pool<Postgres>,
+        key: &str,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Option<i64> {
+        let mut tx = db.begin().await.unwrap();
+        let start_at = reserve_rate_limit_token(&mut tx, "test-workspace", key, at)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+        start_at.map(|s| (s - at).num_milliseconds())
+    }
+
+    // 10 per second with a burst of 3: 3 jobs start at once, then one every 100ms
+    let t0 = chrono::DateTime::from_timestamp(chrono::Utc::now().timestamp(), 0).unwrap();
+    assert_eq!(reserve(&db, "rl", t0).await, None);
+    assert_eq!(reserve(&db, "rl", t0).await, None);
+    assert_eq!(reserve(&db, "rl", t0).await, None);
+    assert_eq!(reserve(&db, "rl", t0).await, Some(100));
+    assert_eq!(reserve(&db, "rl", t0).await, Some(200));
+
+    // the tokens come back at the rate, up to the burst
+    let t1 = t0 + chrono::Duration::milliseconds(250);
+    assert_eq!(reserve(&db, "rl", t1).await, Some(50));
+    let t2 = t0 + chrono::Duration::seconds(10);
+    for _ in 0..3 {
+        assert_eq!(reserve(&db, "rl", t2).await, None);
+    }
+    assert_eq!(reserve(&db, "rl", t2).await, Some(100));
+
+    // keys without a rate limit are never delayed
+    assert_eq!(reserve(&db, "other", t0).await, None);
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_rate_limited_jobs_are_delayed(db: Pool<Postgres>) -> anyhow::Result<()> {
+    initialize_tracing().await;
+
+    sqlx::query(
+        "INSERT INTO rate_limit (workspace_id, key, rate, burst, edited_by)
+        VALUES ('test-workspace', 'rl', 1, 1, 'test-user')",
+    )
+    .execute(&db)
+    .await?;
+    windmill_queue::rate_limit::invalidate_has_rate_limits("test-workspace");
+
+    let mut jobs = vec![];
+    for _ in 0..3 {
+        let job = RunJob::from(JobPayload::Code(RawCode {
+            hash: None,
+            content: "export function main() { return 1 }".to_string(),
+            path: None,
+            lock: None,
+            language: ScriptLang::Deno,
+            custom_concurrency_key: Some("rl".to_string()),
+            concurrent_limit: None,
+            concurrency_time_window_s: None,
+            cache_ttl: None,
+            dedicated_worker: None,
+        }))
+        .push(&db)
+        .await;
+        jobs.push(job);
+    }
+
+    // all the jobs are queued, each one scheduled a second after the previous one
+    let scheduled_for = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+        "SELECT scheduled_for FROM v2_job_queue
+        WHERE id = ANY($1) AND running = false AND canceled_by IS NULL
+        ORDER BY scheduled_for",
+    )
+    .bind(&jobs)
+    .fetch_all(&db)
+    .await?;
+    assert_eq!(scheduled_for.len(), 3);
+    for w in scheduled_for.windows(2) {
+        let gap = (w[1] - w[0]).num_milliseconds();
+        assert!((900..=1100).contains(&gap), "gap of {gap}ms between jobs");
+    }
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_dependent_jobs_reserve_rate_limit_once_unblocked(
+    db: Pool<Postgres>,
+) -> anyhow::Result<()> {
+    use windmill_queue::job_dependencies::release_dependent_jobs;
+
+    initialize_tracing().await;
+
+    sqlx::query(
+        "INSERT INTO rate_limit (workspace_id, key, rate, burst, edited_by)
+        VALUES ('test-workspace', 'rl', 1, 1, 'test-user')",
+    )
+    .execute(&db)
+    .await?;
+    windmill_queue::rate_limit::invalidate_has_rate_limits("test-workspace");
+
+    fn job(custom_concurrency_key: Option<&str>) -> RunJob {
+        RunJob::from(JobPayload::Code(RawCode {
+            hash: None,
+            content: "export function main() { return 1 }".to_string(),
+            path: None,
+            lock: None,
+            language: ScriptLang::Deno,
+            custom_concurrency_key: custom_concurrency_key.map(str::to_string),
+            concurrent_limit: None,
+            concurrency_time_window_s: None,
+            cache_ttl: None,
+            dedicated_worker: None,
+        }))
+    }
+    async fn scheduled_for(db: &Pool<Postgres>, job: Uuid) -> chrono::DateTime<chrono::Utc> {
+        sqlx::query_scalar("SELECT scheduled_for FROM v2_job_queue WHERE id = $1")
+            .bind(job)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    // the blocked job leaves the token to the job pushed after it
+    let upstream = job(None).push(&db).await;
+    let blocked = job(Some("rl")).depends_on(&[upstream]).push(&db).await;
+    let tat: Option<chrono::DateTime<chrono::Utc>> =
+        sqlx::query_scalar("SELECT tat FROM rate_limit WHERE key = 'rl'")
+            .fetch_one(&db)
+            .await?;
+    assert_eq!(tat, None);
+    let unblocked = job(Some("rl")).push(&db).await;
+    assert!(scheduled_for(&db, unblocked).await <= chrono::Utc::now());
+
+    // and reserves the next one once unblocked
+    let mut tx = db.begin().await?;
+    release_dependent_jobs(&mut tx, upstream, true).await?;
+    tx.commit().await?;
+    let gap = (scheduled_for(&db, blocked).await - scheduled_for(&db, unblocked).await)
+        .num_milliseconds();
+    assert!((900..=1100).contains(&gap), "gap of {gap}ms between jobs");
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_job_dependencies_release(db: Pool<Postgres>) -> anyhow::Result<()> {
+    use windmill_common::jobs::JobDependencyCondition;
+    use windmill_common::worker::make_pull_query;
+    use windmill_queue::job_dependencies::{
+        add_job_dependencies, release_dependent_jobs, release_orphaned_dependencies,
+    };
+
+    initialize_tracing().await;
+
+    async fn queued(db: &Pool<Postgres>, tag: &str) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query("INSERT INTO v2_job (id, workspace_id, tag) VALUES ($1, 'test-workspace', $2)")
+            .bind(id)
+            .bind(tag)
+            .execute(db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO v2_job_queue (id, workspace_id, scheduled_for, tag)
+            VALUES ($1, 'test-workspace', now(), $2)",
+        )
+        .bind(id)
+        .bind(tag)
+        .execute(db)
+        .await
+        .unwrap();
+        id
+    }
+    async fn depend(
+        db: &Pool<Postgres>,
+        job: Uuid,
+        upstream: &[Uuid],
+        condition: JobDependencyCondition,
+    ) {
+        let mut tx = db.begin().await.unwrap();
+        add_job_dependencies(&mut tx, "test-workspace", &[job], upstream, condition)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+    }
+    async fn release(db: &Pool<Postgres>, job: Uuid, success: bool) {
+        let mut tx = db.begin().await.unwrap();
+        release_dependent_jobs(&mut tx, job, success).await.unwrap();
+        tx.commit().await.unwrap();
+    }
+    // (blocked, suspend, canceled_by)
+    async fn state(db: &Pool<Postgres>, job: Uuid) -> (bool, i32, Option<String>) {
+        sqlx::query_as(
+            "SELECT blocked_by_dependencies, suspend, canceled_by FROM v2_job_queue WHERE id = $1",
+        )
+        .bind(job)
+        .fetch_one(db)
+        .await
+        .unwrap()
+    }
+    async fn pull(db: &Pool<Postgres>) -> Option<Uuid> {
+        sqlx::query_scalar(&make_pull_query(&["dependent".to_string()]))
+            .bind("test-worker")
+            .fetch_optional(db)
+            .await
+            .unwrap()
+    }
+
+    let a = queued(&db, "deno").await;
+    let b = queued(&db, "deno").await;
+    let on_success = queued(&db, "dependent").await;
+    let on_failure = queued(&db, "dependent").await;
+    let on_any = queued(&db, "dependent").await;
+    let downstream = queued(&db, "dependent").await;
+    depend(&db, on_success, &[a, b], JobDependencyCondition::Success).await;
+    depend(&db, on_failure, &[a], JobDependencyCondition::Failure).await;
+    depend(&db, on_any, &[a], JobDependencyCondition::Any).await;
+    depend(
+        &db,
+        downstream,
+        &[on_success],
+        JobDependencyCondition::Success,
+    )
+    .await;
+
+    assert_eq!(state(&db, on_success).await, (true, 2, None));
+    assert_eq!(state(&db, downstream).await, (true, 1, None));
+    assert_eq!(pull(&db).await, None);
+
+    // success of a: one upstream job less for on_success, on_failure is canceled
+    release(&db, a, true).await;
+    assert_eq!(state(&db, on_success).await, (true, 1, None));
+    assert_eq!(state(&db, on_any).await, (false, 0, None));
+    assert_eq!(
+        state(&db, on_failure).await,
+        (false, 0, Some("dependencies".to_string()))
+    );
+    assert_eq!(pull(&db).await, Some(on_failure));
+    assert_eq!(pull(&db).await, Some(on_any));
+    assert_eq!(pull(&db).await, None);
+
+    // failure of b: on_success is canceled, and so is downstream once on_success completes
+    release(&db, b, false).await;
+    assert_eq!(
+        state(&db, on_success).await,
+        (false, 0, Some("dependencies".to_string()))
+    );
+    assert_eq!(state(&db, downstream).await, (true, 1, None));
+    release(&db, on_success, false).await;
+    assert_eq!(
+        state(&db, downstream).await,
+        (false, 0, Some("dependencies".to_string()))
+    );
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM job_dependency")
+        .fetch_one(&db)
+        .await?;
+    assert_eq!(remaining, 0);
+
+    // an upstream job deleted without completing releases its dependents as if it had failed
+    let deleted = queued(&db, "deno").await;
+    let on_deleted_success = queued(&db, "dependent").await;
+    let on_deleted_any = queued(&db, "dependent").await;
+    depend(
+        &db,
+        on_deleted_success,
+        &[deleted],
+        JobDependencyCondition::Success,
+    )
+    .await;
+    depend(&db, on_deleted_any, &[deleted], JobDependencyCondition::Any).await;
+    sqlx::query("DELETE FROM v2_job_queue WHERE id = $1")
+        .bind(deleted)
+        .execute(&db)
+        .await?;
+    sqlx::query("DELETE FROM v2_job WHERE id = $1")
+        .bind(deleted)
+        .execute(&db)
+        .await?;
+    release_orphaned_dependencies(&db).await?;
+    assert_eq!(
+        state(&db, on_deleted_success).await,
+        (false, 0, Some("dependencies".to_string()))
+    );
+    assert_eq!(state(&db, on_deleted_any).await, (false, 0, None));
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_fair_share_pull_order(db: Pool<Postgres>) -> anyhow::Result<()> {
+    use windmill_common::worker::{
+        make_fair_share_pull_query, refresh_fair_shares, FairShareConfig,
+    };
+
+    initialize_tracing().await;
+
+    async fn queued(db: &Pool<Postgres>, permissioned_as: &str, priority: Option<i16>) -> Uuid {
+        let id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO v2_job (id, workspace_id, tag, permissioned_as)
+            VALUES ($1, 'test-workspace', 'fair', $2)",
+        )
+        .bind(id)
+        .bind(permissioned_as)
+        .execute(db)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO v2_job_queue (id, workspace_id, scheduled_for, tag, priority)
+            VALUES ($1, 'test-workspace', now(), 'fair', $2)",
+        )
+        .bind(id)
+        .bind(priority)
+        .execute(db)
+        .await
+        .unwrap();
+        id
+    }
+
+    sqlx::query(
+        "INSERT INTO fair_share_usage (workspace_id, permissioned_as, bucket, duration_ms)
+        VALUES ('test-workspace', 'u/heavy', date_trunc('minute', now()), 60000),
+            ('test-workspace', 'u/light', date_trunc('minute', now()), 1000),
+            ('test-workspace', 'u/weighted', date_trunc('minute', now()), 60000),
+            ('test-workspace', 'u/light', now() - interval '2 hours', 600000)",
+    )
+    .execute(&db)
+    .await?;
+
+    let heavy = queued(&db, "u/heavy", None).await;
+    let light = queued(&db, "u/light", None).await;
+    let weighted = queued(&db, "u/weighted", None).await;
+    let urgent = queued(&db, "u/heavy", Some(10)).await;
+
+    // priority first, then the usage within the window divided by the weight
+    let fair_share: FairShareConfig = serde_json::from_value(json!({
+        "by": "user",
+        "weights": { "u/weighted": 100 },
+        "window_s": 3600,
+    }))?;
+    refresh_fair_shares(&db, "fair", &fair_share).await?;
+    let query = make_fair_share_pull_query(&["fair".to_string()], "fair", &fair_share);
+    let mut pulled = vec![];
+    while let Some(id) = sqlx::query_scalar::<_, Uuid>(&query)
+        .bind("test-worker")
+        .fetch_optional(&db)
+        .await?
+    {
+        pulled.push(id);
+    }
+    assert_eq!(pulled, vec![urgent, weighted, light, heavy]);
+    Ok(())
+}
+
+#[sqlx::test(fixtures("base"))]
+async fn test_structured_logs_root_job(db: Pool<Postgres>) -> anyhow::Result<()> {
+    use windmill_worker::job_logger::{append_structured_logs, parse_log_line};
+
+    initialize_tracing().await;
+
+    // a step of a subflow of a flow, only pointing to its direct parent
+    let root = Uuid::new_v4();
+    let subflow = Uuid::new_v4();
+    let step = Uuid::new_v4();
+    for (id, parent) in [(root, None), (subflow, Some(root)), (step, Some(subflow))] {
+        sqlx::query(
+            "INSERT INTO v2_job (id, workspace_id, tag, parent_job)
+            VALUES ($1, 'test-workspace', 'deno', $2)",
+        )
+        .bind(id)
+        .bind(parent)
+        .execute(&db)
+        .await?;
+    }
+
+    let conn = windmill_common::worker::Connection::Sql(db.clone());
+    for job in [root, step] {
+        let line = parse_log_line("ERROR: failed", true, chrono::Utc::now());
+        append_structured_logs(&job, vec![line], &conn).await;
+    }
+
+    let roots = sqlx::query_as::<_, (Uuid, Uuid)>(
+        "SELECT job_id, root_job_id FROM job_log_line ORDER BY id",
+    )
+    .fetch_all(&db)
+    .await?;
+    assert_eq!(roots, vec![(root, root), (step, root)]);
+    Ok(())
+}
+
 #[cfg(feature = "python")]
 const WORKFLOW_AS_CODE: &str = r#"
 from wmill import task
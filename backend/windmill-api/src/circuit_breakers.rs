@@ -0,0 +1,221 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use axum::{
+    extract::{Extension, Path},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use windmill_audit::{audit_ee::audit_log, ActionKind};
+use windmill_common::{
+    circuit_breaker::{
+        CircuitBreaker, CircuitBreakerAction, CircuitBreakerKind, CircuitBreakerState,
+    },
+    error::{Error, JsonResult, Result},
+    utils::{not_found_if_none, require_admin, StripPath},
+};
+use windmill_queue::circuit_breaker::{
+    invalidate_has_circuit_breakers, set_circuit_breaker_metric,
+};
+
+use crate::db::{ApiAuthed, DB};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list", get(list_circuit_breakers))
+        .route("/get/:kind/*path", get(get_circuit_breaker))
+        .route("/set", post(set_circuit_breaker))
+        .route("/reset/:kind/*path", post(reset_circuit_breaker))
+        .route("/delete/:kind/*path", delete(delete_circuit_breaker))
+}
+
+#[derive(Deserialize)]
+struct NewCircuitBreaker {
+    kind: CircuitBreakerKind,
+    path: String,
+    failure_threshold: Option<i32>,
+    window_s: Option<i32>,
+    cooldown_s: Option<i32>,
+    on_open: Option<CircuitBreakerAction>,
+}
+
+async fn list_circuit_breakers(
+    _authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<CircuitBreaker>> {
+    let breakers = sqlx::query_as!(
+        CircuitBreaker,
+        r#"SELECT workspace_id, kind AS "kind: _", path, failure_threshold, window_s, cooldown_s,
+            on_open AS "on_open: _", state AS "state: _", failure_count, window_start, opened_at,
+            probe_job, probe_started_at, edited_by, edited_at
+        FROM circuit_breaker
+        WHERE workspace_id = $1
+        ORDER BY kind, path"#,
+        w_id,
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(breakers))
+}
+
+async fn get_circuit_breaker(
+    _authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, path)): Path<(String, CircuitBreakerKind, StripPath)>,
+) -> JsonResult<CircuitBreaker> {
+    let path = path.to_path();
+    let breaker = sqlx::query_as!(
+        CircuitBreaker,
+        r#"SELECT workspace_id, kind AS "kind: _", path, failure_threshold, window_s, cooldown_s,
+            on_open AS "on_open: _", state AS "state: _", failure_count, window_start, opened_at,
+            probe_job, probe_started_at, edited_by, edited_at
+        FROM circuit_breaker
+        WHERE workspace_id = $1 AND kind = $2 AND path = $3"#,
+        w_id,
+        kind as CircuitBreakerKind,
+        path,
+    )
+    .fetch_optional(&db)
+    .await?;
+    Ok(Json(not_found_if_none(breaker, "Circuit breaker", path)?))
+}
+
+async fn set_circuit_breaker(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(nb): Json<NewCircuitBreaker>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    if nb.failure_threshold.is_some_and(|x| x < 1)
+        || nb.window_s.is_some_and(|x| x < 1)
+        || nb.cooldown_s.is_some_and(|x| x < 1)
+    {
+        return Err(Error::BadRequest(
+            "failure_threshold, window_s and cooldown_s must be positive".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "INSERT INTO circuit_breaker
+            (workspace_id, kind, path, failure_threshold, window_s, cooldown_s, on_open, edited_by)
+        VALUES ($1, $2, $3, COALESCE($4, 5), COALESCE($5, 60), COALESCE($6, 60), COALESCE($7, 'fail'::circuit_breaker_action), $8)
+        ON CONFLICT (workspace_id, kind, path) DO UPDATE SET
+            failure_threshold = EXCLUDED.failure_threshold,
+            window_s = EXCLUDED.window_s,
+            cooldown_s = EXCLUDED.cooldown_s,
+            on_open = EXCLUDED.on_open,
+            edited_by = EXCLUDED.edited_by,
+            edited_at = now()",
+        w_id,
+        nb.kind as CircuitBreakerKind,
+        nb.path,
+        nb.failure_threshold,
+        nb.window_s,
+        nb.cooldown_s,
+        nb.on_open as Option<CircuitBreakerAction>,
+        authed.username,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "circuit_breakers.set",
+        ActionKind::Update,
+        &w_id,
+        Some(&nb.path),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    invalidate_has_circuit_breakers(&w_id);
+
+    Ok(format!("circuit breaker for {} set", nb.path))
+}
+
+/// Closes the breaker manually, e.g. once the downstream service is known to be back
+async fn reset_circuit_breaker(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, path)): Path<(String, CircuitBreakerKind, StripPath)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let path = path.to_path();
+
+    let mut tx = db.begin().await?;
+    let reset = sqlx::query_scalar!(
+        "UPDATE circuit_breaker SET
+            state = 'closed', failure_count = 0, window_start = NULL, opened_at = NULL,
+            probe_job = NULL, probe_started_at = NULL
+        WHERE workspace_id = $1 AND kind = $2 AND path = $3
+        RETURNING path",
+        w_id,
+        kind as CircuitBreakerKind,
+        path,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(reset, "Circuit breaker", path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "circuit_breakers.reset",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    set_circuit_breaker_metric(&w_id, kind, path, CircuitBreakerState::Closed);
+
+    Ok(format!("circuit breaker for {path} reset"))
+}
+
+async fn delete_circuit_breaker(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, kind, path)): Path<(String, CircuitBreakerKind, StripPath)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let path = path.to_path();
+
+    let mut tx = db.begin().await?;
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM circuit_breaker WHERE workspace_id = $1 AND kind = $2 AND path = $3
+        RETURNING path",
+        w_id,
+        kind as CircuitBreakerKind,
+        path,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Circuit breaker", path)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "circuit_breakers.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(path),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    invalidate_has_circuit_breakers(&w_id);
+    set_circuit_breaker_metric(&w_id, kind, path, CircuitBreakerState::Closed);
+
+    Ok(format!("circuit breaker for {path} deleted"))
+}
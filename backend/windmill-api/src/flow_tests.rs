@@ -0,0 +1,448 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Extension, Path},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sqlx::types::Json as SqlxJson;
+use uuid::Uuid;
+use windmill_audit::{audit_ee::audit_log, ActionKind};
+use windmill_common::{
+    db::UserDB,
+    error::{Error, JsonResult, Result},
+    flows::{FlowModule, FlowModuleValue, FlowTestAssertion, FlowTestCase, FlowValue, Mock},
+    jobs::JobPayload,
+    users::username_to_permissioned_as,
+    utils::{not_found_if_none, StripPath},
+    worker::to_raw_value,
+};
+use windmill_queue::{push, PushArgs, PushIsolationLevel};
+
+use crate::{
+    db::{ApiAuthed, DB},
+    flows::require_is_writer,
+    users::check_scopes,
+};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list/*path", get(list_flow_tests))
+        .route("/set/*path", post(set_flow_tests))
+        .route("/run/*path", post(run_flow_tests))
+        .route("/report/*path", post(report_flow_tests))
+}
+
+async fn get_flow_value(
+    authed: &ApiAuthed,
+    user_db: &UserDB,
+    w_id: &str,
+    path: &str,
+) -> Result<FlowValue> {
+    let mut tx = user_db.clone().begin(authed).await?;
+    let value = sqlx::query_scalar!(
+        "SELECT flow_version.value AS \"value!: SqlxJson<FlowValue>\"
+        FROM flow
+        LEFT JOIN flow_version ON flow_version.id = flow.versions[array_upper(flow.versions, 1)]
+        WHERE flow.path = $1 AND flow.workspace_id = $2",
+        path,
+        w_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(not_found_if_none(value, "Flow", path)?.0)
+}
+
+async fn list_flow_tests(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<Vec<FlowTestCase>> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM flow WHERE path = $1 AND workspace_id = $2)",
+        path,
+        w_id,
+    )
+    .fetch_one(&mut *tx)
+    .await?
+    .unwrap_or(false);
+    if !exists {
+        return Err(Error::NotFound(format!("Flow {path} not found")));
+    }
+
+    let cases = sqlx::query_scalar!(
+        "SELECT value AS \"value!: SqlxJson<FlowTestCase>\"
+        FROM flow_test_case
+        WHERE flow_path = $1 AND workspace_id = $2
+        ORDER BY name",
+        path,
+        w_id,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Json(cases.into_iter().map(|c| c.0).collect()))
+}
+
+async fn set_flow_tests(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(cases): Json<Vec<FlowTestCase>>,
+) -> Result<String> {
+    let path = path.to_path();
+    require_is_writer(&authed, path, &w_id, db).await?;
+
+    let mut tx = user_db.begin(&authed).await?;
+    sqlx::query!(
+        "DELETE FROM flow_test_case WHERE flow_path = $1 AND workspace_id = $2",
+        path,
+        w_id,
+    )
+    .execute(&mut *tx)
+    .await?;
+    for case in cases.iter() {
+        sqlx::query!(
+            "INSERT INTO flow_test_case (workspace_id, flow_path, name, value, edited_by)
+            VALUES ($1, $2, $3, $4, $5)",
+            w_id,
+            path,
+            case.name,
+            serde_json::json!(case),
+            authed.username,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "flows.update_tests",
+        ActionKind::Update,
+        &w_id,
+        Some(path),
+        Some([("workspace", w_id.as_str())].into()),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(format!("{} test cases set for flow {path}", cases.len()))
+}
+
+/// Id of the step appended to the flow of a test case to evaluate its assertions
+const ASSERTIONS_STEP_ID: &str = "wm_test_assertions";
+
+/// Reports the outcome of each assertion, the report being the result of the test case flow
+const ASSERTIONS_STEP_CONTENT: &str = r#"type Assertion = { step?: string; expr: string };
+type Outcome = { passed: boolean; message?: string };
+
+export function main(assertions: Assertion[], outcomes: Outcome[]) {
+  const reports = assertions.map((assertion, i) => {
+    const { passed, message } = outcomes[i];
+    return message === undefined
+      ? { ...assertion, passed }
+      : { ...assertion, passed, error: message };
+  });
+  return { passed: reports.every((r) => r.passed), assertions: reports };
+}
+"#;
+
+#[derive(Serialize, Deserialize)]
+struct FlowTestRun {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FlowTestReport {
+    passed: bool,
+    completed: bool,
+    cases: Vec<FlowTestCaseReport>,
+}
+
+#[derive(Serialize)]
+struct FlowTestCaseReport {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    job_id: Option<Uuid>,
+    completed: bool,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    assertions: Vec<FlowTestAssertionReport>,
+}
+
+/// Result of the assertions step
+#[derive(Deserialize)]
+struct FlowTestAssertions {
+    passed: bool,
+    assertions: Vec<FlowTestAssertionReport>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FlowTestAssertionReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    step: Option<String>,
+    expr: String,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Push every test case of the flow as a preview flow. The assertions are evaluated by the last
+/// step of the flow, the runs are then passed to `report_flow_tests`.
+async fn run_flow_tests(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+) -> JsonResult<Vec<FlowTestRun>> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("jobs:runflow"))?;
+    if authed.is_operator {
+        return Err(Error::NotAuthorized(
+            "Operators cannot run preview jobs for security reasons".to_string(),
+        ));
+    }
+
+    let flow = get_flow_value(&authed, &user_db, &w_id, path).await?;
+    let cases = sqlx::query_scalar!(
+        "SELECT value AS \"value!: SqlxJson<FlowTestCase>\"
+        FROM flow_test_case
+        WHERE flow_path = $1 AND workspace_id = $2
+        ORDER BY name",
+        path,
+        w_id,
+    )
+    .fetch_all(&db)
+    .await?;
+
+    let mut runs = vec![];
+    for case in cases {
+        let case = case.0;
+        let pushed = push_flow_test_case(&authed, &db, &user_db, &w_id, path, &flow, &case).await;
+        runs.push(FlowTestRun {
+            name: case.name,
+            job_id: pushed.as_ref().ok().copied(),
+            error: pushed.err().map(|e| e.to_string()),
+        });
+    }
+    Ok(Json(runs))
+}
+
+/// Pass/fail report of the runs of `run_flow_tests`, complete once all the case jobs completed. A
+/// case whose flow failed before its assertions could run is failed with the error of the flow.
+async fn report_flow_tests(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(runs): Json<Vec<FlowTestRun>>,
+) -> JsonResult<FlowTestReport> {
+    let path = path.to_path();
+    check_scopes(&authed, || format!("jobs:listjobs"))?;
+
+    let job_ids = runs.iter().filter_map(|r| r.job_id).collect::<Vec<_>>();
+    let jobs = sqlx::query!(
+        "SELECT j.id, c.id IS NOT NULL AS \"completed!\", c.status = 'success' AS success,
+            c.result AS \"result: SqlxJson<Box<RawValue>>\"
+        FROM v2_job j LEFT JOIN v2_job_completed c ON c.id = j.id
+        WHERE j.id = ANY($1) AND j.workspace_id = $2 AND j.runnable_path = $3",
+        &job_ids,
+        w_id,
+        path,
+    )
+    .fetch_all(&db)
+    .await?;
+
+    let cases = runs
+        .into_iter()
+        .map(|run| {
+            let mut report = FlowTestCaseReport {
+                name: run.name,
+                job_id: run.job_id,
+                completed: true,
+                passed: false,
+                error: run.error,
+                assertions: vec![],
+            };
+            let Some(job_id) = run.job_id else {
+                return report;
+            };
+            let Some(job) = jobs.iter().find(|j| j.id == job_id) else {
+                report.error = Some(format!("job {job_id} of the test case not found"));
+                return report;
+            };
+            report.completed = job.completed;
+            let result = job.result.as_ref().map_or("null", |r| r.get());
+            if !job.completed {
+                return report;
+            } else if !job.success.unwrap_or(false) {
+                report.error = Some(format!("flow failed: {result}"));
+                return report;
+            }
+            match serde_json::from_str::<FlowTestAssertions>(result) {
+                Ok(assertions) => {
+                    report.passed = assertions.passed;
+                    report.assertions = assertions.assertions;
+                }
+                Err(e) => report.error = Some(format!("invalid assertions report {result}: {e}")),
+            }
+            report
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(FlowTestReport {
+        passed: cases.iter().all(|c| c.passed),
+        completed: cases.iter().all(|c| c.completed),
+        cases,
+    }))
+}
+
+/// Step evaluating the assertions of the case on the worker, after all the steps of the flow. An
+/// assertion that throws fails without failing the others.
+fn assertions_step(assertions: &[FlowTestAssertion]) -> Result<FlowModule> {
+    let outcomes = assertions
+        .iter()
+        .map(|assertion| {
+            let result = match assertion.step.as_ref() {
+                Some(step) => format!("results[{}]", serde_json::json!(step)),
+                None => "previous_result".to_string(),
+            };
+            format!(
+                "(async () => {{\n  try {{\n    const result = {result};\n    return {{ passed: (\n{}\n) === true }};\n  }} catch (e) {{\n    return {{ passed: false, message: String(e) }};\n  }}\n}})()",
+                assertion.expr
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",\n");
+    let step = serde_json::json!({
+        "id": ASSERTIONS_STEP_ID,
+        "value": {
+            "type": "rawscript",
+            "language": "nativets",
+            "content": ASSERTIONS_STEP_CONTENT,
+            "input_transforms": {
+                "assertions": { "type": "static", "value": assertions },
+                "outcomes": {
+                    "type": "javascript",
+                    "expr": format!("return await Promise.all([\n{outcomes}\n])"),
+                },
+            },
+        },
+    });
+    Ok(serde_json::from_str(&step.to_string())?)
+}
+
+/// Overrides the mocks of the modules by id, recursing into the loops and branches, and collects
+/// the ids of the mocked modules into `mocked`.
+fn apply_mocks(
+    modules: &mut [FlowModule],
+    mocks: &HashMap<String, Mock>,
+    mocked: &mut HashSet<String>,
+) -> Result<()> {
+    use FlowModuleValue::*;
+
+    for module in modules {
+        if let Some(mock) = mocks.get(&module.id) {
+            module.mock = Some(mock.clone());
+            mocked.insert(module.id.clone());
+        }
+        let mut value = module.get_value()?;
+        match &mut value {
+            ForloopFlow { modules, .. } | WhileloopFlow { modules, .. } => {
+                apply_mocks(modules, mocks, mocked)?;
+            }
+            BranchOne { branches, default, .. } => {
+                apply_mocks(default, mocks, mocked)?;
+                for branch in branches {
+                    apply_mocks(&mut branch.modules, mocks, mocked)?;
+                }
+            }
+            BranchAll { branches, .. } => {
+                for branch in branches {
+                    apply_mocks(&mut branch.modules, mocks, mocked)?;
+                }
+            }
+            _ => continue,
+        }
+        module.value = to_raw_value(&value);
+    }
+    Ok(())
+}
+
+async fn push_flow_test_case(
+    authed: &ApiAuthed,
+    db: &DB,
+    user_db: &UserDB,
+    w_id: &str,
+    path: &str,
+    flow: &FlowValue,
+    case: &FlowTestCase,
+) -> Result<Uuid> {
+    let mut value = flow.clone();
+    let mut mocked = HashSet::new();
+    apply_mocks(&mut value.modules, &case.mocks, &mut mocked)?;
+    if let Some(failure_module) = value.failure_module.as_deref_mut() {
+        apply_mocks(
+            std::slice::from_mut(failure_module),
+            &case.mocks,
+            &mut mocked,
+        )?;
+    }
+    if let Some(id) = case.mocks.keys().find(|id| !mocked.contains(*id)) {
+        return Err(Error::BadRequest(format!(
+            "test case {} mocks step {id} which is not in the flow",
+            case.name
+        )));
+    }
+    value.modules.push(assertions_step(&case.assertions)?);
+
+    let tx = PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into());
+    let (uuid, tx) = push(
+        db,
+        tx,
+        w_id,
+        JobPayload::RawFlow { value, path: Some(path.to_string()), restarted_from: None },
+        PushArgs::from(&case.args),
+        authed.display_username(),
+        &authed.email,
+        username_to_permissioned_as(&authed.username),
+        None,
+        None,
+        None,
+        None,
+        None,
+        false,
+        false,
+        None,
+        true,
+        None,
+        None,
+        None,
+        None,
+        Some(&authed.clone().into()),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(uuid)
+}
@@ -9,6 +9,8 @@
 use std::collections::HashMap;
 
 use crate::db::ApiAuthed;
+#[cfg(feature = "flow_testing")]
+use crate::flow_tests;
 use crate::triggers::{
     get_triggers_count_internal, list_tokens_internal, TriggersCount, TruncatedTokenWithEmail,
 };
@@ -39,7 +41,7 @@ use windmill_common::HUB_BASE_URL;
 use windmill_common::{
     db::UserDB,
     error::{self, to_anyhow, Error, JsonResult, Result},
-    flows::{Flow, FlowWithStarred, ListFlowQuery, ListableFlow, NewFlow},
+    flows::{Flow, FlowValue, FlowWithStarred, ListFlowQuery, ListableFlow, NewFlow},
     jobs::JobPayload,
     schedule::Schedule,
     scripts::Schema,
@@ -49,11 +51,24 @@ use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
 use windmill_queue::{push, schedule::push_scheduled_job, PushIsolationLevel};
 
 pub fn workspaced_service() -> Router {
+    let tests_service = {
+        #[cfg(feature = "flow_testing")]
+        {
+            flow_tests::workspaced_service()
+        }
+
+        #[cfg(not(feature = "flow_testing"))]
+        {
+            Router::new()
+        }
+    };
+
     Router::new()
         .route("/list", get(list_flows))
         .route("/list_search", get(list_search_flows))
         .route("/create", post(create_flow))
         .route("/update/*path", post(update_flow))
+        .nest("/tests", tests_service)
         .route("/archive/*path", post(archive_flow_by_path))
         .route("/delete/*path", delete(delete_flow_by_path))
         .route("/get_triggers_count/*path", get(get_triggers_count))
@@ -370,6 +385,10 @@ async fn create_flow(
                 .to_string(),
         ));
     }
+    if let Ok(value) = FlowValue::deserialize(&nf.value) {
+        value.check_compensations()?;
+        value.check_suspend_events()?;
+    }
 
     // cron::Schedule::from_str(&ns.schedule).map_err(|e| error::Error::BadRequest(e.to_string()))?;
     let authed = maybe_refresh_folders(&nf.path, &w_id, authed, &db).await;
@@ -482,6 +501,7 @@ async fn create_flow(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
 
@@ -686,6 +706,10 @@ async fn update_flow(
                 .to_string(),
         ));
     }
+    if let Ok(value) = FlowValue::deserialize(&nf.value) {
+        value.check_compensations()?;
+        value.check_suspend_events()?;
+    }
 
     let flow_path = flow_path.to_path();
     let authed = maybe_refresh_folders(&flow_path, &w_id, authed, &db).await;
@@ -765,6 +789,15 @@ async fn update_flow(
             ))
         })?;
 
+        sqlx::query!(
+            "UPDATE flow_test_case SET flow_path = $1 WHERE flow_path = $2 AND workspace_id = $3",
+            nf.path,
+            flow_path,
+            w_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
         sqlx::query!(
             "DELETE FROM flow WHERE path = $1 AND workspace_id = $2",
             flow_path,
@@ -923,6 +956,7 @@ async fn update_flow(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
     sqlx::query!(
@@ -1288,8 +1322,8 @@ mod tests {
 
     use windmill_common::{
         flows::{
-            ConstantDelay, ExponentialDelay, FlowModule, FlowModuleValue, FlowValue,
-            InputTransform, Retry, StopAfterIf,
+            retry_after_hint, ConstantDelay, ExponentialDelay, FlowModule, FlowModuleValue,
+            FlowValue, InputTransform, Retry, RetryIf, StopAfterIf,
         },
         scripts,
     };
@@ -1328,6 +1362,7 @@ mod tests {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
                 FlowModule {
                     id: "b".to_string(),
@@ -1359,6 +1394,7 @@ mod tests {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
                 FlowModule {
                     id: "c".to_string(),
@@ -1371,6 +1407,7 @@ mod tests {
                         skip_failures: true,
                         parallel: false,
                         parallelism: None,
+                        deadline: None,
                     }),
                     stop_after_if: Some(StopAfterIf {
                         expr: "previous.isEmpty()".to_string(),
@@ -1388,6 +1425,7 @@ mod tests {
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 },
             ],
             failure_module: Some(Box::new(FlowModule {
@@ -1416,6 +1454,7 @@ mod tests {
                 delete_after_use: None,
                 continue_on_error: None,
                 skip_if: None,
+                compensation: None,
             })),
             preprocessor_module: None,
             same_worker: false,
@@ -1526,7 +1565,8 @@ mod tests {
                     multiplier: 1,
                     seconds: 123,
                     random_factor: None
-                }
+                },
+                retry_if: None,
             },
             serde_json::from_str(
                 r#"
@@ -1550,6 +1590,7 @@ mod tests {
                 seconds: 3,
                 random_factor: None,
             },
+            retry_if: None,
         };
         assert_eq!(
             vec![
@@ -1576,6 +1617,7 @@ mod tests {
                 seconds: 3,
                 random_factor: None,
             },
+            retry_if: None,
         };
         assert_eq!(
             vec![
@@ -1592,4 +1634,27 @@ mod tests {
 
         assert_eq!(Some(81 * SECOND), retry.max_interval());
     }
+
+    #[test]
+    fn retry_if_error() {
+        let retry_if = RetryIf {
+            names: vec!["HttpError".to_string()],
+            message: Some("^(429|5\\d\\d)".to_string()),
+            expr: None,
+        };
+        let error = serde_json::json!({
+            "name": "HttpError",
+            "message": "429 Too Many Requests",
+            "retry_after": 30
+        });
+        assert!(retry_if.matches_error(&error));
+        assert_eq!(Some(30 * SECOND), retry_after_hint(&error));
+
+        let error = serde_json::json!({ "name": "HttpError", "message": "400 Bad Request" });
+        assert!(!retry_if.matches_error(&error));
+        assert_eq!(None, retry_after_hint(&error));
+
+        let error = serde_json::json!({ "name": "ValidationError", "message": "429" });
+        assert!(!retry_if.matches_error(&error));
+    }
 }
@@ -3,7 +3,7 @@ use crate::http_trigger_args::{HttpMethod, RawHttpTriggerArgs};
 #[cfg(feature = "parquet")]
 use crate::job_helpers_ee::get_workspace_s3_resource;
 use crate::resources::try_get_resource_from_db_as;
-use crate::trigger_helpers::{get_runnable_format, RunnableId};
+use crate::trigger_helpers::{get_runnable_format, resume_waiting_flows, RunnableId};
 use crate::utils::{non_empty_str, ExpiringCacheEntry};
 use crate::{
     auth::{AuthCache, OptTokened},
@@ -1239,7 +1239,21 @@ async fn route_job(
         )
         .map_err(|e| e.into_response())?;
 
-    let run_query = RunJobQuery::default();
+    let resumed = resume_waiting_flows(
+        &db,
+        &trigger.workspace_id,
+        &TriggerKind::Http,
+        &trigger.path,
+        &args.args,
+    )
+    .await
+    .map_err(|e| e.into_response())?;
+    if !resumed.is_empty() {
+        // the ids of the resumed flows are not the caller's to see
+        return Ok((StatusCode::OK, "resumed waiting flow").into_response());
+    }
+
+    let run_query = RunJobQuery::default().with_idempotency_key_header(&headers);
 
     let response = if trigger.is_flow {
         if trigger.is_async {
@@ -0,0 +1,373 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Streaming variants of the run_wait_result endpoints.
+//!
+//! Instead of blocking until the job completes, the response is a stream of events, either
+//! Server-Sent Events or newline-delimited JSON: the new logs, the progress updates and the
+//! partial results the job appends through `/jobs/result_stream/append/:id`, then the final
+//! result. As for run_wait_result, the job is canceled if the client disconnects before the end,
+//! unless it is the job of a repeated idempotency key, which belongs to the first request.
+
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    extract::{Json, Path, Query},
+    response::Response,
+    Extension,
+};
+use futures::StreamExt;
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use uuid::Uuid;
+use windmill_common::{
+    db::UserDB,
+    error::{self, Error},
+    utils::StripPath,
+};
+
+use crate::{
+    args::RawWebhookArgs,
+    db::{ApiAuthed, DB},
+    jobs::{
+        check_queue_too_long, run_flow_by_path_inner, run_script_by_path_inner,
+        run_wait_result_internal, Guard, RunJobQuery, QUEUE_LIMIT_WAIT_RESULT, TIMEOUT_WAIT_RESULT,
+        WAIT_RESULT_FAST_POLL_DURATION_SECS, WAIT_RESULT_FAST_POLL_INTERVAL_MS,
+        WAIT_RESULT_SLOW_POLL_INTERVAL_MS,
+    },
+    trigger_helpers::RunnableId,
+};
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    /// Server-Sent Events, one `event:`/`data:` pair per event
+    #[default]
+    Sse,
+    /// One JSON object per line
+    Ndjson,
+}
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    pub stream_format: Option<StreamFormat>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobStreamEvent {
+    Log { logs: String },
+    Progress { progress: i32 },
+    Partial { value: Box<RawValue> },
+    Result { result: Box<RawValue>, success: bool },
+    Error { error: String },
+}
+
+impl JobStreamEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            JobStreamEvent::Log { .. } => "log",
+            JobStreamEvent::Progress { .. } => "progress",
+            JobStreamEvent::Partial { .. } => "partial",
+            JobStreamEvent::Result { .. } => "result",
+            JobStreamEvent::Error { .. } => "error",
+        }
+    }
+}
+
+impl StreamFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            StreamFormat::Sse => "text/event-stream",
+            StreamFormat::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn encode(&self, event: &JobStreamEvent) -> String {
+        self.encode_event(event.name(), event)
+    }
+
+    pub fn encode_event(&self, name: &str, event: &impl Serialize) -> String {
+        let data = serde_json::to_string(event).unwrap_or_else(|e| {
+            format!(r#"{{"type":"error","error":"could not serialize event: {e}"}}"#)
+        });
+        match self {
+            StreamFormat::Sse => format!("event: {name}\ndata: {data}\n\n"),
+            StreamFormat::Ndjson => format!("{data}\n"),
+        }
+    }
+}
+
+pub async fn run_wait_result_stream_script_by_path(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path((w_id, script_path)): Path<(String, StripPath)>,
+    Query(run_query): Query<RunJobQuery>,
+    Query(StreamQuery { stream_format }): Query<StreamQuery>,
+    headers: HeaderMap,
+    args: RawWebhookArgs,
+) -> error::Result<Response> {
+    let mut run_query = run_query.with_idempotency_key_header(&headers);
+    check_queue_too_long(&db, QUEUE_LIMIT_WAIT_RESULT.or(run_query.queue_limit)).await?;
+    // the id tells the job pushed by this request from the one of a repeated idempotency key
+    let job_id = *run_query
+        .job_id
+        .get_or_insert_with(|| ulid::Ulid::new().into());
+
+    let args = args
+        .to_args_from_runnable(
+            &authed,
+            &db,
+            &w_id,
+            RunnableId::from_script_path(script_path.to_path()),
+            run_query.skip_preprocessor,
+        )
+        .await?;
+
+    let username = authed.username.clone();
+    let (_, uuid) = run_script_by_path_inner(
+        authed,
+        db.clone(),
+        user_db,
+        w_id.clone(),
+        script_path,
+        run_query,
+        args,
+    )
+    .await?;
+    let uuid = Uuid::parse_str(&uuid).map_err(|e| Error::internal_err(e.to_string()))?;
+
+    Ok(job_stream_response(
+        db,
+        uuid,
+        w_id,
+        username,
+        stream_format.unwrap_or_default(),
+        uuid == job_id,
+    ))
+}
+
+pub async fn run_wait_result_stream_flow_by_path(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Extension(db): Extension<DB>,
+    Path((w_id, flow_path)): Path<(String, StripPath)>,
+    Query(run_query): Query<RunJobQuery>,
+    Query(StreamQuery { stream_format }): Query<StreamQuery>,
+    headers: HeaderMap,
+    args: RawWebhookArgs,
+) -> error::Result<Response> {
+    let mut run_query = run_query.with_idempotency_key_header(&headers);
+    check_queue_too_long(&db, QUEUE_LIMIT_WAIT_RESULT.or(run_query.queue_limit)).await?;
+    let job_id = *run_query
+        .job_id
+        .get_or_insert_with(|| ulid::Ulid::new().into());
+
+    let args = args
+        .to_args_from_runnable(
+            &authed,
+            &db,
+            &w_id,
+            RunnableId::from_flow_path(flow_path.to_path()),
+            run_query.skip_preprocessor,
+        )
+        .await?;
+
+    let username = authed.username.clone();
+    let (_, uuid) = run_flow_by_path_inner(
+        authed,
+        db.clone(),
+        user_db,
+        w_id.clone(),
+        flow_path,
+        run_query,
+        args,
+    )
+    .await?;
+    let uuid = Uuid::parse_str(&uuid).map_err(|e| Error::internal_err(e.to_string()))?;
+
+    Ok(job_stream_response(
+        db,
+        uuid,
+        w_id,
+        username,
+        stream_format.unwrap_or_default(),
+        uuid == job_id,
+    ))
+}
+
+/// Appends a partial result to the stream of a running job, called by the job itself through
+/// the `streamResult`/`stream_result` helpers of the clients. The job is looked up through the
+/// user db, so only a token that can see the job, such as its own, can append to its stream.
+pub async fn append_result_stream(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Json(value): Json<serde_json::Value>,
+) -> error::Result<String> {
+    let mut tx = user_db.begin(&authed).await?;
+    // locks the job so that concurrent appends get consecutive sequence numbers
+    let running = sqlx::query_scalar!(
+        "SELECT id FROM v2_job_queue WHERE id = $1 AND workspace_id = $2 AND running FOR UPDATE",
+        job_id,
+        w_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if running.is_none() {
+        return Err(Error::BadRequest(format!(
+            "job {job_id} is not running, cannot append to its result stream"
+        )));
+    }
+    sqlx::query!(
+        "INSERT INTO job_result_stream (job_id, seq, workspace_id, value)
+        SELECT $1, COALESCE(MAX(seq), 0) + 1, $2, $3 FROM job_result_stream WHERE job_id = $1",
+        job_id,
+        w_id,
+        value,
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(format!("appended to result stream of {job_id}"))
+}
+
+/// Streams the events of the job, canceling it on disconnect if `cancel_on_drop`, i.e. if the job
+/// was pushed by this request and not returned for a repeated idempotency key
+pub fn job_stream_response(
+    db: DB,
+    job_id: Uuid,
+    w_id: String,
+    username: String,
+    format: StreamFormat,
+    cancel_on_drop: bool,
+) -> Response {
+    let stream = job_event_stream(db, job_id, w_id, username, cancel_on_drop)
+        .map(move |event| Ok::<_, Infallible>(format.encode(&event)));
+    Response::builder()
+        .header(http::header::CONTENT_TYPE, format.content_type())
+        .header(http::header::CACHE_CONTROL, "no-cache")
+        // prevents reverse proxies such as nginx from buffering the whole response
+        .header("X-Accel-Buffering", "no")
+        .body(Body::from_stream(stream))
+        .unwrap()
+}
+
+/// Polls the job the same way as run_wait_result, emitting what changed since the last poll
+fn job_event_stream(
+    db: DB,
+    job_id: Uuid,
+    w_id: String,
+    username: String,
+    cancel_on_drop: bool,
+) -> impl futures::Stream<Item = JobStreamEvent> {
+    async_stream::stream! {
+        let mut g = Guard {
+            done: !cancel_on_drop,
+            id: job_id,
+            w_id: w_id.clone(),
+            db: db.clone(),
+            username: username.clone(),
+        };
+
+        let timeout = TIMEOUT_WAIT_RESULT.read().await.clone().unwrap_or(600);
+        let timeout_ms = if timeout <= 0 { 2000 } else { timeout * 1000 };
+        let fast_poll_duration = *WAIT_RESULT_FAST_POLL_DURATION_SECS as u64 * 1000;
+        let mut accumulated_delay = 0 as u64;
+
+        let mut log_offset = 0;
+        let mut stream_seq = 0;
+        let mut progress = None;
+        loop {
+            let update = match sqlx::query!(
+                "SELECT
+                    c.id IS NOT NULL AS \"completed!\",
+                    SUBSTR(l.logs, GREATEST($3 - l.log_offset, 0)) AS logs,
+                    l.log_offset + CHAR_LENGTH(l.logs) + 1 AS log_offset,
+                    (SELECT scalar_int FROM job_stats
+                        WHERE job_id = $1 AND metric_id = 'progress_perc') AS progress
+                FROM v2_job j
+                    LEFT JOIN v2_job_completed c USING (id)
+                    LEFT JOIN job_logs l ON l.job_id = j.id
+                WHERE j.id = $1 AND j.workspace_id = $2",
+                job_id,
+                &w_id,
+                log_offset,
+            )
+            .fetch_optional(&db)
+            .await
+            {
+                Ok(Some(update)) => update,
+                Ok(None) => {
+                    yield JobStreamEvent::Error { error: format!("Job not found: {job_id}") };
+                    break;
+                }
+                Err(e) => {
+                    yield JobStreamEvent::Error { error: e.to_string() };
+                    break;
+                }
+            };
+
+            if let Some(logs) = update.logs.filter(|l| !l.is_empty()) {
+                yield JobStreamEvent::Log { logs };
+            }
+            log_offset = update.log_offset.unwrap_or(log_offset);
+
+            if update.progress.is_some() && update.progress != progress {
+                progress = update.progress;
+                yield JobStreamEvent::Progress { progress: progress.unwrap_or_default() };
+            }
+
+            let partials = match sqlx::query!(
+                "SELECT seq, value AS \"value!: sqlx::types::Json<Box<RawValue>>\"
+                FROM job_result_stream
+                WHERE job_id = $1 AND seq > $2
+                ORDER BY seq",
+                job_id,
+                stream_seq,
+            )
+            .fetch_all(&db)
+            .await
+            {
+                Ok(partials) => partials,
+                Err(e) => {
+                    yield JobStreamEvent::Error { error: e.to_string() };
+                    break;
+                }
+            };
+            for partial in partials {
+                stream_seq = partial.seq;
+                yield JobStreamEvent::Partial { value: partial.value.0 };
+            }
+
+            if update.completed {
+                g.done = true;
+                match run_wait_result_internal(&db, job_id, w_id.clone(), None, &username).await {
+                    Ok((result, success)) => yield JobStreamEvent::Result { result, success },
+                    Err(e) => yield JobStreamEvent::Error { error: e.to_string() },
+                }
+                break;
+            }
+
+            let delay = if accumulated_delay <= fast_poll_duration {
+                *WAIT_RESULT_FAST_POLL_INTERVAL_MS
+            } else {
+                *WAIT_RESULT_SLOW_POLL_INTERVAL_MS
+            };
+            accumulated_delay += delay;
+            if accumulated_delay > timeout_ms {
+                yield JobStreamEvent::Error { error: format!("timeout after {timeout}s") };
+                break;
+            }
+            tokio::time::sleep(core::time::Duration::from_millis(delay)).await;
+        }
+    }
+}
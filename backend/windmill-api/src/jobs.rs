@@ -28,7 +28,9 @@ use tokio::time::Instant;
 use tower::ServiceBuilder;
 use windmill_common::error::JsonResult;
 use windmill_common::flow_status::{JobResult, RestartedFrom};
-use windmill_common::jobs::{format_completed_job_result, format_result, ENTRYPOINT_OVERRIDE};
+use windmill_common::jobs::{
+    format_completed_job_result, format_result, JobLogLevel, JobLogLine, ENTRYPOINT_OVERRIDE,
+};
 use windmill_common::worker::{Connection, CLOUD_HOSTED, TMP_DIR};
 
 use windmill_common::scripts::PREVIEW_IS_CODEBASE_HASH;
@@ -37,6 +39,10 @@ use windmill_common::variables::get_workspace_key;
 use crate::add_webhook_allowed_origin;
 use crate::concurrency_groups::join_concurrency_key;
 use crate::db::ApiAuthed;
+use crate::job_stream::{
+    append_result_stream, run_wait_result_stream_flow_by_path,
+    run_wait_result_stream_script_by_path,
+};
 
 use crate::trigger_helpers::RunnableId;
 use crate::users::get_scope_tags;
@@ -73,7 +79,10 @@ use windmill_common::{
     error::{self, to_anyhow, Error},
     flow_status::{Approval, FlowStatus, FlowStatusModule},
     flows::{add_virtual_items_if_necessary, resolve_maybe_value, FlowValue},
-    jobs::{script_path_to_payload, CompletedJob, JobKind, JobPayload, QueuedJob, RawCode},
+    jobs::{
+        script_path_to_payload, CompletedJob, JobDependencyCondition, JobKind, JobPayload,
+        QueuedJob, RawCode,
+    },
     oauth2::HmacSha256,
     scripts::{ScriptHash, ScriptLang},
     users::username_to_permissioned_as,
@@ -93,8 +102,8 @@ use windmill_common::{
     get_script_info_for_hash, FlowVersionInfo, ScriptHashInfo, BASE_URL,
 };
 use windmill_queue::{
-    cancel_job, get_result_and_success_by_id_from_flow, job_is_complete, push, PushArgs,
-    PushArgsOwned, PushIsolationLevel,
+    cancel_job, get_result_and_success_by_id_from_flow, job_dependencies::add_job_dependencies,
+    job_is_complete, push, PushArgs, PushArgsOwned, PushIsolationLevel,
 };
 
 #[cfg(feature = "prometheus")]
@@ -199,6 +208,21 @@ pub fn workspaced_service() -> Router {
                 .layer(cors.clone())
                 .layer(ce_headers.clone()),
         )
+        .route(
+            "/run_wait_result_stream/p/*script_path",
+            post(run_wait_result_stream_script_by_path)
+                .head(|| async { "" })
+                .layer(cors.clone())
+                .layer(ce_headers.clone()),
+        )
+        .route(
+            "/run_wait_result_stream/f/*script_path",
+            post(run_wait_result_stream_flow_by_path)
+                .head(|| async { "" })
+                .layer(cors.clone())
+                .layer(ce_headers.clone()),
+        )
+        .route("/result_stream/append/:id", post(append_result_stream))
         .route(
             "/run/h/:hash",
             post(run_job_by_hash)
@@ -213,6 +237,8 @@ pub fn workspaced_service() -> Router {
         )
         .route("/add_batch_jobs/:n", post(add_batch_jobs))
         .route("/run/preview_flow", post(run_preview_flow_job))
+        .route("/run/replay_flow/:job_id", post(replay_flow_job))
+        .route("/completed/get_replay_diff/:id", get(get_replay_diff))
         .route(
             "/list",
             get(list_jobs).layer(Extension(api_list_jobs_query_duration)),
@@ -310,6 +336,7 @@ pub fn workspace_unauthed_service() -> Router {
             get(get_completed_job_result_maybe),
         )
         .route("/getupdate/:id", get(get_job_update))
+        .route("/tail_logs", get(crate::log_tail::tail_job_logs))
         .route("/get_log_file/*file_path", get(get_log_file))
         .route("/queue/cancel/:id", post(cancel_job_api))
         .route(
@@ -1128,10 +1155,83 @@ async fn get_logs_from_disk(
     return None;
 }
 
+/// Filters on the structured log lines of the job, and of all its steps if it is a flow. Logs
+/// are only structured when the workers run with `STRUCTURED_JOB_LOGS`.
+#[derive(Deserialize)]
+struct GetLogsQuery {
+    /// minimum level, the lines without a level are excluded
+    level: Option<JobLogLevel>,
+    /// case insensitive substring of the message
+    search: Option<String>,
+    from: Option<chrono::DateTime<Utc>>,
+    to: Option<chrono::DateTime<Utc>>,
+    /// returns the log lines as json rows instead of text
+    structured: Option<bool>,
+}
+
+impl GetLogsQuery {
+    fn is_filtered(&self) -> bool {
+        self.level.is_some()
+            || self.search.is_some()
+            || self.from.is_some()
+            || self.to.is_some()
+            || self.structured.unwrap_or(false)
+    }
+}
+
+const MAX_STRUCTURED_LOG_LINES: i64 = 10000;
+
+async fn get_structured_job_logs(
+    db: &DB,
+    w_id: &str,
+    id: Uuid,
+    lq: &GetLogsQuery,
+) -> error::Result<Response> {
+    let lines = sqlx::query_as!(
+        JobLogLine,
+        r#"SELECT job_id, step_id, ts, level AS "level: _", stream AS "stream: _", message
+        FROM job_log_line
+        WHERE workspace_id = $1 AND (job_id = $2 OR root_job_id = $2)
+            AND ($3::JOB_LOG_LEVEL IS NULL OR level >= $3)
+            AND ($4::text IS NULL OR strpos(lower(message), lower($4)) > 0)
+            AND ($5::timestamptz IS NULL OR ts >= $5)
+            AND ($6::timestamptz IS NULL OR ts <= $6)
+        ORDER BY ts, id
+        LIMIT $7"#,
+        w_id,
+        id,
+        lq.level as Option<JobLogLevel>,
+        lq.search,
+        lq.from,
+        lq.to,
+        MAX_STRUCTURED_LOG_LINES,
+    )
+    .fetch_all(db)
+    .await?;
+
+    if lq.structured.unwrap_or(false) {
+        return Ok(Json(lines).into_response());
+    }
+    let logs = lines
+        .into_iter()
+        .map(|l| {
+            format!(
+                "{} {} {}{}",
+                l.ts.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                l.level.map(|l| l.as_str()).unwrap_or("-").to_uppercase(),
+                l.step_id.map(|s| format!("[{s}] ")).unwrap_or_default(),
+                l.message
+            )
+        })
+        .join("\n");
+    Ok(content_plain(Body::from(logs)))
+}
+
 async fn get_job_logs(
     OptAuthed(opt_authed): OptAuthed,
     Extension(db): Extension<DB>,
     Path((w_id, id)): Path<(String, Uuid)>,
+    Query(lq): Query<GetLogsQuery>,
 ) -> error::Result<Response> {
     // let audit_author: AuditAuthor = match opt_authed {
     //     Some(authed) => (&authed).into(),
@@ -1165,9 +1265,11 @@ async fn get_job_logs(
                 "As a non logged in user, you can only see jobs ran by anonymous users".to_string(),
             ));
         }
-        let logs = record.logs.unwrap_or_default();
-
         log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
+        if lq.is_filtered() {
+            return get_structured_job_logs(&db, &w_id, id, &lq).await;
+        }
+        let logs = record.logs.unwrap_or_default();
 
         #[cfg(all(feature = "enterprise", feature = "parquet"))]
         if let Some(r) = get_logs_from_store(record.log_offset, &logs, &record.log_file_index).await
@@ -1202,9 +1304,11 @@ async fn get_job_logs(
                 "As a non logged in user, you can only see jobs ran by anonymous users".to_string(),
             ));
         }
-        let logs = text.logs.unwrap_or_default();
-
         log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
+        if lq.is_filtered() {
+            return get_structured_job_logs(&db, &w_id, id, &lq).await;
+        }
+        let logs = text.logs.unwrap_or_default();
 
         #[cfg(all(feature = "enterprise", feature = "parquet"))]
         if let Some(r) =
@@ -1280,7 +1384,7 @@ async fn get_args(
     }
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+#[derive(Debug, sqlx::FromRow, Serialize, Deserialize)]
 pub struct ListableCompletedJob {
     pub r#type: String,
     pub workspace_id: String,
@@ -1341,9 +1445,72 @@ pub struct RunJobQuery {
     pub timeout: Option<i32>,
     pub cache_ttl: Option<i32>,
     pub skip_preprocessor: Option<bool>,
+    /// comma separated ids of the jobs that must complete before this one runs
+    pub depends_on: Option<String>,
+    pub depends_on_condition: Option<JobDependencyCondition>,
+    /// a repeated key within `IDEMPOTENCY_KEY_TTL_S` returns the job of the first request
+    pub idempotency_key: Option<String>,
 }
 
 impl RunJobQuery {
+    /// Takes the idempotency key from the `Idempotency-Key` header if not set in the query
+    pub fn with_idempotency_key_header(mut self, headers: &HeaderMap) -> Self {
+        if self.idempotency_key.is_none() {
+            self.idempotency_key = headers
+                .get(IDEMPOTENCY_KEY_HEADER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+        }
+        self
+    }
+
+    /// Idempotency key of the request for the runnable at `runnable_path`, if any
+    fn idempotency_key<'a>(
+        &'a self,
+        authed: &ApiAuthed,
+        runnable_path: &'a str,
+    ) -> error::Result<Option<IdempotencyKey<'a>>> {
+        let Some(key) = self.idempotency_key.as_deref() else {
+            return Ok(None);
+        };
+        if key.len() > 255 {
+            return Err(Error::BadRequest(
+                "idempotency key must be at most 255 characters".to_string(),
+            ));
+        }
+        Ok(Some(IdempotencyKey {
+            key,
+            runnable_path,
+            permissioned_as: username_to_permissioned_as(&authed.username),
+        }))
+    }
+
+    fn depends_on(&self) -> error::Result<Vec<Uuid>> {
+        self.depends_on
+            .as_deref()
+            .map(|ids| {
+                ids.split(',')
+                    .filter(|id| !id.trim().is_empty())
+                    .map(|id| {
+                        Uuid::parse_str(id.trim()).map_err(|e| {
+                            Error::BadRequest(format!("invalid upstream job id {id}: {e}"))
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(|| Ok(vec![]))
+    }
+
+    /// `depends_on` is only honored by the endpoints that return the id of the job right away
+    fn reject_depends_on(&self) -> error::Result<()> {
+        if !self.depends_on()?.is_empty() {
+            return Err(Error::BadRequest(
+                "depends_on is not supported by this endpoint".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     async fn get_scheduled_for<'c>(
         &self,
         db: &DB,
@@ -2254,6 +2421,46 @@ async fn insert_resume_job<'c>(
     Ok(())
 }
 
+/* Resume a flow suspended on a trigger event (see `Suspend::event`) with the message as the
+ * resume payload. The flow's queue row is locked for the same reason as in
+ * `resume_immediately_if_relevant`. */
+#[allow(dead_code)]
+pub(crate) async fn resume_suspended_job_from_event<'c>(
+    flow_id: Uuid,
+    job_id: Uuid,
+    value: serde_json::Value,
+    approver: String,
+    tx: &mut Transaction<'c, Postgres>,
+) -> error::Result<()> {
+    let flow = sqlx::query_as!(
+        FlowInfo,
+        r#"
+        SELECT q.id, f.flow_status, q.suspend, j.runnable_path AS script_path
+        FROM v2_job_queue q
+            JOIN v2_job j USING (id)
+            JOIN v2_job_status f USING (id)
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+        flow_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("suspended flow {flow_id} not found"))?;
+
+    insert_resume_job(
+        rand::random::<u32>(),
+        job_id,
+        &flow,
+        value,
+        Some(approver),
+        true,
+        tx,
+    )
+    .await?;
+    resume_immediately_if_relevant(flow, job_id, tx).await
+}
+
 #[derive(sqlx::FromRow)]
 struct FlowInfo {
     id: Uuid,
@@ -3081,6 +3288,12 @@ struct PreviewFlow {
     restarted_from: Option<RestartedFrom>,
 }
 
+#[derive(Deserialize)]
+struct ReplayFlow {
+    value: FlowValue,
+    tag: Option<String>,
+}
+
 pub struct QueryOrBody<D>(pub Option<D>);
 
 #[axum::async_trait]
@@ -3466,8 +3679,10 @@ pub async fn run_flow_by_path(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -3494,6 +3709,10 @@ pub async fn run_flow_by_path_inner(
     check_license_key_valid().await?;
     let flow_path = flow_path.to_path();
     check_scopes(&authed, || format!("run:flow/{flow_path}"))?;
+    let idempotency_key = run_query.idempotency_key(&authed, flow_path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
 
     let mut tx = user_db.clone().begin(&authed).await?;
 
@@ -3513,6 +3732,7 @@ pub async fn run_flow_by_path_inner(
 
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
+    let depends_on = run_query.depends_on()?;
 
     let (email, permissioned_as, push_authed, tx) =
         if let Some(on_behalf_of_email) = on_behalf_of_email.as_ref() {
@@ -3527,11 +3747,11 @@ pub async fn run_flow_by_path_inner(
                 &authed.email,
                 username_to_permissioned_as(&authed.username),
                 Some(authed.clone().into()),
-                PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+                PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -3560,8 +3780,24 @@ pub async fn run_flow_by_path_inner(
         None,
         None,
         push_authed.as_ref(),
+        Some((
+            depends_on.as_slice(),
+            run_query.depends_on_condition.unwrap_or_default(),
+        )),
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
     tx.commit().await?;
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
@@ -3598,6 +3834,7 @@ pub async fn restart_flow(
     Query(run_query): Query<RunJobQuery>,
 ) -> error::Result<(StatusCode, String)> {
     check_license_key_valid().await?;
+    run_query.reject_depends_on()?;
 
     let mut tx = user_db.clone().begin(&authed).await?;
     let completed_job = sqlx::query!(
@@ -3653,6 +3890,7 @@ pub async fn restart_flow(
         None,
         completed_job.priority,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -3665,8 +3903,10 @@ pub async fn run_script_by_path(
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -3695,12 +3935,17 @@ pub async fn run_script_by_path_inner(
     let script_path = script_path.to_path();
 
     check_scopes(&authed, || format!("run:script/{script_path}"))?;
+    let idempotency_key = run_query.idempotency_key(&authed, script_path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
 
     let mut tx = user_db.clone().begin(&authed).await?;
     let (job_payload, tag, _delete_after_use, timeout, on_behalf_of) =
         script_path_to_payload(script_path, &mut *tx, &w_id, run_query.skip_preprocessor).await?;
     drop(tx);
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
+    let depends_on = run_query.depends_on()?;
 
     let tag = run_query.tag.clone().or(tag);
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
@@ -3718,11 +3963,11 @@ pub async fn run_script_by_path_inner(
                 authed.email.as_str(),
                 username_to_permissioned_as(&authed.username),
                 Some(authed.clone().into()),
-                PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+                PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -3745,8 +3990,24 @@ pub async fn run_script_by_path_inner(
         None,
         None,
         push_authed.as_ref(),
+        Some((
+            depends_on.as_slice(),
+            run_query.depends_on_condition.unwrap_or_default(),
+        )),
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
     tx.commit().await?;
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
@@ -3765,6 +4026,7 @@ pub async fn run_workflow_as_code(
     Query(wkflow_query): Query<WorkflowAsCodeQuery>,
     Json(task): Json<WorkflowTask>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_depends_on()?;
     let mut i = 1;
 
     if *CLOUD_HOSTED {
@@ -3890,6 +4152,7 @@ pub async fn run_workflow_as_code(
         None,
         None,
         push_authed.as_ref(),
+        None,
     )
     .await?;
 
@@ -3939,12 +4202,12 @@ pub async fn run_workflow_as_code(
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
 
-struct Guard {
-    done: bool,
-    id: Uuid,
-    w_id: String,
-    db: DB,
-    username: String,
+pub(crate) struct Guard {
+    pub(crate) done: bool,
+    pub(crate) id: Uuid,
+    pub(crate) w_id: String,
+    pub(crate) db: DB,
+    pub(crate) username: String,
 }
 
 impl Drop for Guard {
@@ -4009,6 +4272,26 @@ pub async fn run_wait_result_internal(
     w_id: String,
     node_id_for_empty_return: Option<String>,
     username: &str,
+) -> error::Result<(Box<RawValue>, bool)> {
+    let mut g = Guard {
+        done: false,
+        id: uuid,
+        w_id: w_id.clone(),
+        db: db.clone(),
+        username: username.to_string(),
+    };
+    let result = wait_result(db, uuid, w_id, node_id_for_empty_return).await;
+    g.done = result.is_ok();
+    result
+}
+
+/// Polls for the result of the job until `TIMEOUT_WAIT_RESULT`, without canceling it if the
+/// request is dropped
+async fn wait_result(
+    db: &DB,
+    uuid: Uuid,
+    w_id: String,
+    node_id_for_empty_return: Option<String>,
 ) -> error::Result<(Box<RawValue>, bool)> {
     let mut result = None;
     let mut success = false;
@@ -4019,14 +4302,6 @@ pub async fn run_wait_result_internal(
         (timeout * 1000) as u64
     };
 
-    let mut g = Guard {
-        done: false,
-        id: uuid,
-        w_id: w_id.clone(),
-        db: db.clone(),
-        username: username.to_string(),
-    };
-
     let fast_poll_duration = *WAIT_RESULT_FAST_POLL_DURATION_SECS as u64 * 1000;
     let mut accumulated_delay = 0 as u64;
 
@@ -4087,7 +4362,6 @@ pub async fn run_wait_result_internal(
     }
 
     if let Some(result) = result {
-        g.done = true;
         Ok((result, success))
     } else {
         Err(Error::ExecutionErr(format!("timeout after {}s", timeout)))
@@ -4103,7 +4377,22 @@ pub async fn run_wait_result(
 ) -> error::Result<Response> {
     let (result, success) =
         run_wait_result_internal(db, uuid, w_id, node_id_for_empty_return, username).await?;
+    wait_result_response(result, success)
+}
+
+/// Same as [`run_wait_result`] for the job of a repeated idempotency key: the job belongs to the
+/// first request, so it is not canceled if this one is dropped
+async fn run_wait_result_replay(
+    db: &DB,
+    uuid: Uuid,
+    w_id: String,
+    node_id_for_empty_return: Option<String>,
+) -> error::Result<Response> {
+    let (result, success) = wait_result(db, uuid, w_id, node_id_for_empty_return).await?;
+    wait_result_response(result, success)
+}
 
+fn wait_result_response(result: Box<RawValue>, success: bool) -> error::Result<Response> {
     let composite_result = serde_json::from_str::<WindmillCompositeResult>(result.get());
     match composite_result {
         Ok(WindmillCompositeResult {
@@ -4235,7 +4524,92 @@ pub async fn check_queue_too_long(db: &DB, queue_limit: Option<i64>) -> error::R
     Ok(())
 }
 
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Idempotency key of a request, scoped to the runnable and to the user running it
+struct IdempotencyKey<'a> {
+    key: &'a str,
+    runnable_path: &'a str,
+    permissioned_as: String,
+}
+
+/// Job already created in the workspace with the idempotency key of the request for the same
+/// runnable and user, if any. The lookup goes through the user db so that only a job visible to
+/// the caller is returned.
+async fn idempotent_job(
+    user_db: &UserDB,
+    authed: &ApiAuthed,
+    w_id: &str,
+    idempotency_key: Option<&IdempotencyKey<'_>>,
+) -> error::Result<Option<Uuid>> {
+    let Some(idempotency_key) = idempotency_key else {
+        return Ok(None);
+    };
+    let mut tx = user_db.clone().begin(authed).await?;
+    let existing = sqlx::query_scalar!(
+        "SELECT k.job_id
+        FROM job_idempotency_key k JOIN v2_job j ON j.id = k.job_id
+        WHERE k.workspace_id = $1 AND k.key = $2 AND k.runnable_path = $3
+            AND k.permissioned_as = $4 AND k.expires_at > now()",
+        w_id,
+        idempotency_key.key,
+        idempotency_key.runnable_path,
+        idempotency_key.permissioned_as,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(existing)
+}
+
+/// Stores the idempotency key of the request with the job pushed in `tx`. If a concurrent
+/// request with the same key committed first, returns its job and `tx` must be rolled back.
+async fn claim_idempotency_key(
+    tx: &mut Transaction<'_, Postgres>,
+    user_db: &UserDB,
+    authed: &ApiAuthed,
+    w_id: &str,
+    idempotency_key: Option<&IdempotencyKey<'_>>,
+    job_id: Uuid,
+) -> error::Result<Option<Uuid>> {
+    let Some(key) = idempotency_key else {
+        return Ok(None);
+    };
+    let claimed = sqlx::query_scalar!(
+        "INSERT INTO job_idempotency_key
+            (workspace_id, key, runnable_path, permissioned_as, job_id, expires_at)
+        VALUES ($1, $2, $3, $4, $5, now() + make_interval(secs => $6))
+        ON CONFLICT (workspace_id, key, runnable_path, permissioned_as) DO UPDATE SET
+            job_id = EXCLUDED.job_id,
+            expires_at = EXCLUDED.expires_at
+        WHERE job_idempotency_key.expires_at <= now()
+        RETURNING job_id",
+        w_id,
+        key.key,
+        key.runnable_path,
+        key.permissioned_as,
+        job_id,
+        *IDEMPOTENCY_KEY_TTL_S as f64,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    if claimed.is_some() {
+        return Ok(None);
+    }
+    let existing = idempotent_job(user_db, authed, w_id, Some(key)).await?;
+    Ok(Some(not_found_if_none(
+        existing,
+        "Idempotency key",
+        key.key,
+    )?))
+}
+
 lazy_static::lazy_static! {
+    /// how long a job is returned for a repeated idempotency key instead of pushing a new one
+    pub static ref IDEMPOTENCY_KEY_TTL_S: i64 = std::env::var("IDEMPOTENCY_KEY_TTL_S")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(86400);
     pub static ref QUEUE_LIMIT_WAIT_RESULT: Option<i64> = std::env::var("QUEUE_LIMIT_WAIT_RESULT")
         .ok()
         .and_then(|x| x.parse().ok());
@@ -4332,6 +4706,7 @@ pub async fn run_wait_result_job_by_path_get(
     Query(run_query): Query<RunJobQuery>,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
+    run_query.reject_depends_on()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4413,6 +4788,7 @@ pub async fn run_wait_result_job_by_path_get(
         None,
         None,
         push_authed.as_ref(),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -4473,8 +4849,10 @@ pub async fn run_wait_result_script_by_path(
     Extension(db): Extension<DB>,
     Path((w_id, script_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4501,9 +4879,14 @@ pub async fn run_wait_result_script_by_path_internal(
     w_id: String,
     args: PushArgsOwned,
 ) -> error::Result<Response> {
+    run_query.reject_depends_on()?;
     check_queue_too_long(&db, QUEUE_LIMIT_WAIT_RESULT.or(run_query.queue_limit)).await?;
     let script_path = script_path.to_path();
     check_scopes(&authed, || format!("run:script/{script_path}"))?;
+    let idempotency_key = run_query.idempotency_key(&authed, script_path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return run_wait_result_replay(&db, uuid, w_id, None).await;
+    }
 
     let mut tx = user_db.clone().begin(&authed).await?;
     let (job_payload, tag, delete_after_use, timeout, on_behalf_of) =
@@ -4526,11 +4909,11 @@ pub async fn run_wait_result_script_by_path_internal(
                 authed.email.as_str(),
                 username_to_permissioned_as(&authed.username),
                 Some(authed.clone().into()),
-                PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+                PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4553,8 +4936,21 @@ pub async fn run_wait_result_script_by_path_internal(
         None,
         None,
         push_authed.as_ref(),
+        None,
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return run_wait_result_replay(&db, uuid, w_id, None).await;
+    }
     tx.commit().await?;
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
@@ -4570,8 +4966,11 @@ pub async fn run_wait_result_script_by_hash(
     Extension(db): Extension<DB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
+    run_query.reject_depends_on()?;
+    let run_query = run_query.with_idempotency_key_header(&headers);
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4610,6 +5009,10 @@ pub async fn run_wait_result_script_by_hash(
         cache_ttl = Some(run_query_cache_ttl);
     }
     check_scopes(&authed, || format!("run:script/{path}"))?;
+    let idempotency_key = run_query.idempotency_key(&authed, &path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return run_wait_result_replay(&db, uuid, w_id, None).await;
+    }
 
     let tag = run_query.tag.clone().or(tag);
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
@@ -4627,17 +5030,17 @@ pub async fn run_wait_result_script_by_hash(
             &authed.email,
             username_to_permissioned_as(&authed.username),
             Some(authed.clone().into()),
-            PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+            PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
         )
     };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
         JobPayload::ScriptHash {
             hash: ScriptHash(hash),
-            path: path,
+            path: path.clone(),
             custom_concurrency_key: concurrency_key,
             concurrent_limit: concurrent_limit,
             concurrency_time_window_s: concurrency_time_window_s,
@@ -4666,8 +5069,21 @@ pub async fn run_wait_result_script_by_hash(
         None,
         None,
         push_authed.as_ref(),
+        None,
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return run_wait_result_replay(&db, uuid, w_id, None).await;
+    }
     tx.commit().await?;
 
     let wait_result = run_wait_result(&db, uuid, w_id, None, &authed.username).await;
@@ -4683,8 +5099,10 @@ pub async fn run_wait_result_flow_by_path(
     Extension(db): Extension<DB>,
     Path((w_id, flow_path)): Path<(String, StripPath)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<Response> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4711,6 +5129,7 @@ pub async fn run_wait_result_flow_by_path_internal(
     args: PushArgsOwned,
     w_id: String,
 ) -> error::Result<Response> {
+    run_query.reject_depends_on()?;
     check_queue_too_long(&db, run_query.queue_limit).await?;
 
     let flow_path = flow_path.to_path();
@@ -4730,6 +5149,10 @@ pub async fn run_wait_result_flow_by_path_internal(
         version,
     } = get_latest_flow_version_info_for_path(&mut *tx, &w_id, &flow_path, true).await?;
     drop(tx);
+    let idempotency_key = run_query.idempotency_key(&authed, flow_path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return run_wait_result_replay(&db, uuid, w_id, early_return).await;
+    }
 
     let tag = run_query.tag.clone().or(tag);
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
@@ -4747,11 +5170,11 @@ pub async fn run_wait_result_flow_by_path_internal(
                 &authed.email,
                 username_to_permissioned_as(&authed.username),
                 Some(authed.clone().into()),
-                PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+                PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
             )
         };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
@@ -4780,8 +5203,21 @@ pub async fn run_wait_result_flow_by_path_internal(
         None,
         None,
         push_authed.as_ref(),
+        None,
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return run_wait_result_replay(&db, uuid, w_id, early_return).await;
+    }
     tx.commit().await?;
 
     run_wait_result(&db, uuid, w_id, early_return, &authed.username).await
@@ -4795,6 +5231,7 @@ async fn run_preview_script(
     Query(run_query): Query<RunJobQuery>,
     Json(preview): Json<Preview>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_depends_on()?;
     #[cfg(feature = "enterprise")]
     check_license_key_valid().await?;
 
@@ -4850,6 +5287,7 @@ async fn run_preview_script(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -4865,6 +5303,7 @@ async fn run_bundle_preview_script(
     Query(run_query): Query<RunJobQuery>,
     mut multipart: axum::extract::Multipart,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_depends_on()?;
     use windmill_common::scripts::PREVIEW_IS_TAR_CODEBASE_HASH;
 
     check_scopes(&authed, || format!("jobs:runscript"))?;
@@ -4938,6 +5377,7 @@ async fn run_bundle_preview_script(
                 None,
                 None,
                 Some(&authed.clone().into()),
+                None,
             )
             .await?;
             job_id = Some(uuid);
@@ -5108,6 +5548,7 @@ async fn run_dependencies_job(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -5165,6 +5606,7 @@ async fn run_flow_dependencies_job(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -5187,6 +5629,9 @@ struct BatchInfo {
     path: Option<String>,
     rawscript: Option<BatchRawScript>,
     tag: Option<String>,
+    #[serde(default)]
+    depends_on: Vec<Uuid>,
+    depends_on_condition: Option<JobDependencyCondition>,
 }
 
 #[tracing::instrument(level = "trace", skip_all)]
@@ -5454,6 +5899,15 @@ async fn add_batch_jobs(
         .await?;
     }
 
+    add_job_dependencies(
+        &mut tx,
+        &w_id,
+        &uuids,
+        &batch_info.depends_on,
+        batch_info.depends_on_condition.unwrap_or_default(),
+    )
+    .await?;
+
     tx.commit().await?;
 
     Ok(Json(uuids))
@@ -5467,12 +5921,15 @@ async fn run_preview_flow_job(
     Query(run_query): Query<RunJobQuery>,
     Json(raw_flow): Json<PreviewFlow>,
 ) -> error::Result<(StatusCode, String)> {
+    run_query.reject_depends_on()?;
     check_scopes(&authed, || format!("jobs:runflow"))?;
     if authed.is_operator {
         return Err(error::Error::NotAuthorized(
             "Operators cannot run preview jobs for security reasons".to_string(),
         ));
     }
+    raw_flow.value.check_compensations()?;
+    raw_flow.value.check_suspend_events()?;
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
     let tag = run_query.tag.clone().or(raw_flow.tag.clone());
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
@@ -5505,6 +5962,95 @@ async fn run_preview_flow_job(
         None,
         None,
         Some(&authed.clone().into()),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, uuid.to_string()))
+}
+
+async fn replay_flow_job(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, job_id)): Path<(String, Uuid)>,
+    Query(run_query): Query<RunJobQuery>,
+    Json(replay): Json<ReplayFlow>,
+) -> error::Result<(StatusCode, String)> {
+    run_query.reject_depends_on()?;
+    check_scopes(&authed, || format!("jobs:runflow"))?;
+    if authed.is_operator {
+        return Err(error::Error::NotAuthorized(
+            "Operators cannot run preview jobs for security reasons".to_string(),
+        ));
+    }
+
+    let mut tx = user_db.clone().begin(&authed).await?;
+    let completed_job = sqlx::query!(
+        "SELECT
+            script_path, args AS \"args: sqlx::types::Json<HashMap<String, Box<RawValue>>>\",
+            tag AS \"tag!\", job_kind AS \"job_kind!: JobKind\"
+        FROM v2_as_completed_job
+        WHERE id = $1 and workspace_id = $2",
+        job_id,
+        &w_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .with_context(|| "Unable to find completed job with the given job UUID")?;
+    drop(tx);
+
+    if !matches!(completed_job.job_kind, JobKind::Flow | JobKind::FlowPreview) {
+        return Err(Error::BadRequest(format!(
+            "Job {job_id} is not a flow and cannot be replayed"
+        )));
+    }
+
+    let scheduled_for = run_query.get_scheduled_for(&db).await?;
+    let tag = run_query
+        .tag
+        .clone()
+        .or(replay.tag)
+        .or(Some(completed_job.tag));
+    check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
+
+    let ehm = HashMap::new();
+    let push_args = completed_job
+        .args
+        .as_ref()
+        .map(|json| PushArgs { args: &json.0, extra: None })
+        .unwrap_or_else(|| PushArgs::from(&ehm));
+
+    let tx = PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into());
+    let (uuid, tx) = push(
+        &db,
+        tx,
+        &w_id,
+        JobPayload::ReplayedFlow {
+            completed_job_id: job_id,
+            value: replay.value,
+            path: completed_job.script_path,
+        },
+        push_args,
+        authed.display_username(),
+        &authed.email,
+        username_to_permissioned_as(&authed.username),
+        scheduled_for,
+        None,
+        None,
+        None,
+        run_query.job_id,
+        false,
+        false,
+        None,
+        true,
+        tag,
+        None,
+        None,
+        None,
+        Some(&authed.clone().into()),
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -5512,14 +6058,125 @@ async fn run_preview_flow_job(
     Ok((StatusCode::CREATED, uuid.to_string()))
 }
 
+async fn get_flow_status_in_tx(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    id: Uuid,
+) -> error::Result<FlowStatus> {
+    let flow_status = sqlx::query_scalar!(
+        "SELECT COALESCE(c.flow_status, s.flow_status)
+            AS \"flow_status!: sqlx::types::Json<FlowStatus>\"
+        FROM v2_job j
+            LEFT JOIN v2_job_completed c USING (id)
+            LEFT JOIN v2_job_status s USING (id)
+        WHERE j.id = $1 AND j.workspace_id = $2",
+        id,
+        w_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(not_found_if_none(flow_status, "Flow", id.to_string())?.0)
+}
+
+#[derive(Serialize)]
+struct ReplayStepDiff {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_job: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replay_job: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original_result: Option<Box<RawValue>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replay_result: Option<Box<RawValue>>,
+    reused: bool,
+    changed: bool,
+}
+
+/// Step by step comparison of the results of a replayed flow with the ones of the original run.
+async fn get_replay_diff(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, id)): Path<(String, Uuid)>,
+) -> JsonResult<Vec<ReplayStepDiff>> {
+    let mut tx = user_db.begin(&authed).await?;
+
+    let replay_status = get_flow_status_in_tx(&mut tx, &w_id, id).await?;
+    let replay = replay_status
+        .replay
+        .clone()
+        .ok_or_else(|| Error::BadRequest(format!("Flow {id} is not a replay")))?;
+    let original_status = get_flow_status_in_tx(&mut tx, &w_id, replay.flow_job_id).await?;
+
+    let mut steps: Vec<(String, Option<Uuid>, Option<Uuid>)> = replay_status
+        .modules
+        .iter()
+        .map(|m| {
+            let original = original_status.modules.iter().find(|o| o.id() == m.id());
+            (m.id(), original.and_then(|o| o.job()), m.job())
+        })
+        .collect();
+    steps.extend(
+        original_status
+            .modules
+            .iter()
+            .filter(|o| !replay_status.modules.iter().any(|m| m.id() == o.id()))
+            .map(|o| (o.id(), o.job(), None)),
+    );
+
+    let job_ids = steps
+        .iter()
+        .flat_map(|(_, o, r)| [*o, *r])
+        .flatten()
+        .collect::<Vec<_>>();
+    let results = sqlx::query!(
+        "SELECT id, result AS \"result: sqlx::types::Json<Box<RawValue>>\"
+        FROM v2_job_completed WHERE id = ANY($1) AND workspace_id = $2",
+        &job_ids,
+        &w_id,
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .filter_map(|r| r.result.map(|x| (r.id, x.0)))
+    .collect::<HashMap<_, _>>();
+    tx.commit().await?;
+
+    let result_of = |job: Option<Uuid>| job.and_then(|j| results.get(&j).cloned());
+    let as_value = |r: &Option<Box<RawValue>>| {
+        r.as_ref()
+            .and_then(|r| serde_json::from_str::<serde_json::Value>(r.get()).ok())
+    };
+    Ok(Json(
+        steps
+            .into_iter()
+            .map(|(id, original_job, replay_job)| {
+                let original_result = result_of(original_job);
+                let replay_result = result_of(replay_job);
+                ReplayStepDiff {
+                    reused: replay.reused.contains(&id),
+                    changed: as_value(&original_result) != as_value(&replay_result),
+                    id,
+                    original_job,
+                    replay_job,
+                    original_result,
+                    replay_result,
+                }
+            })
+            .collect(),
+    ))
+}
+
 pub async fn run_job_by_hash(
     authed: ApiAuthed,
     Extension(db): Extension<DB>,
     Extension(user_db): Extension<UserDB>,
     Path((w_id, script_hash)): Path<(String, ScriptHash)>,
     Query(run_query): Query<RunJobQuery>,
+    headers: HeaderMap,
     args: RawWebhookArgs,
 ) -> error::Result<(StatusCode, String)> {
+    let run_query = run_query.with_idempotency_key_header(&headers);
     let args = args
         .to_args_from_runnable(
             &authed,
@@ -5564,10 +6221,15 @@ pub async fn run_job_by_hash_inner(
         .. // delete_after_use not taken into account in async endpoints
     } = get_script_info_for_hash(&mut *tx, &w_id, hash).await?;
     check_scopes(&authed, || format!("run:script/{path}"))?;
+    let idempotency_key = run_query.idempotency_key(&authed, &path)?;
+    if let Some(uuid) = idempotent_job(&user_db, &authed, &w_id, idempotency_key.as_ref()).await? {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
     if let Some(run_query_cache_ttl) = run_query.cache_ttl {
         cache_ttl = Some(run_query_cache_ttl);
     }
     let scheduled_for = run_query.get_scheduled_for(&db).await?;
+    let depends_on = run_query.depends_on()?;
     let tag = run_query.tag.clone().or(tag);
 
     check_tag_available_for_workspace(&w_id, &tag, &authed).await?;
@@ -5585,17 +6247,17 @@ pub async fn run_job_by_hash_inner(
             &authed.email,
             username_to_permissioned_as(&authed.username),
             Some(authed.clone().into()),
-            PushIsolationLevel::Isolated(user_db, authed.clone().into()),
+            PushIsolationLevel::Isolated(user_db.clone(), authed.clone().into()),
         )
     };
 
-    let (uuid, tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &w_id,
         JobPayload::ScriptHash {
             hash: ScriptHash(hash),
-            path: path,
+            path: path.clone(),
             custom_concurrency_key: concurrency_key,
             concurrent_limit: concurrent_limit,
             concurrency_time_window_s: concurrency_time_window_s,
@@ -5624,8 +6286,24 @@ pub async fn run_job_by_hash_inner(
         None,
         None,
         push_authed.as_ref(),
+        Some((
+            depends_on.as_slice(),
+            run_query.depends_on_condition.unwrap_or_default(),
+        )),
     )
     .await?;
+    if let Some(uuid) = claim_idempotency_key(
+        &mut tx,
+        &user_db,
+        &authed,
+        &w_id,
+        idempotency_key.as_ref(),
+        uuid,
+    )
+    .await?
+    {
+        return Ok((StatusCode::CREATED, uuid.to_string()));
+    }
     tx.commit().await?;
 
     Ok((StatusCode::CREATED, uuid.to_string()))
@@ -6036,13 +6714,182 @@ async fn list_completed_jobs(
     )
     .sql()?;
     let mut tx = user_db.begin(&authed).await?;
-    let jobs = sqlx::query_as::<_, ListableCompletedJob>(&sql)
+    #[allow(unused_mut)]
+    let mut jobs = sqlx::query_as::<_, ListableCompletedJob>(&sql)
         .fetch_all(&mut *tx)
         .await?;
+
+    // the jobs past the retention period that were archived come after the ones still in the
+    // database, the page is completed with them
+    #[cfg(feature = "parquet")]
+    if jobs.len() < per_page && lq.order_desc.unwrap_or(true) && archive_supports_filters(&lq) {
+        let archive_offset = if jobs.is_empty() && offset > 0 {
+            let count_sql = list_completed_jobs_query(
+                &w_id,
+                None,
+                0,
+                &lq,
+                &["v2_job.id"],
+                false,
+                get_scope_tags(&authed),
+            )
+            .sql()?;
+            let count = sqlx::query_scalar::<_, i64>(&format!(
+                "SELECT COUNT(*) FROM ({}) AS t",
+                count_sql.trim_end_matches(';')
+            ))
+            .fetch_one(&mut *tx)
+            .await?;
+            offset.saturating_sub(count as usize)
+        } else {
+            0
+        };
+        let archived = list_archived_jobs(
+            &mut tx,
+            &w_id,
+            &lq,
+            get_scope_tags(&authed),
+            archive_offset,
+            per_page - jobs.len(),
+        )
+        .await?;
+        jobs.extend(archived);
+    }
+
     tx.commit().await?;
     Ok(Json(jobs))
 }
 
+/// Whether the filters of the query can be applied to the index of the job archive
+#[cfg(feature = "parquet")]
+fn archive_supports_filters(lq: &ListCompletedQuery) -> bool {
+    lq.label.is_none()
+        && lq.worker.is_none()
+        && lq.schedule_path.is_none()
+        && lq.args.is_none()
+        && lq.result.is_none()
+        && lq.concurrency_key.is_none()
+        && !lq.is_not_schedule.unwrap_or(false)
+        && !lq.all_workspaces.unwrap_or(false)
+}
+
+#[cfg(feature = "parquet")]
+async fn list_archived_jobs(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    w_id: &str,
+    lq: &ListCompletedQuery,
+    tags: Option<Vec<&str>>,
+    offset: usize,
+    limit: usize,
+) -> error::Result<Vec<ListableCompletedJob>> {
+    let mut sqlb = SqlBuilder::select_from("job_archive")
+        .fields(&["id", "object_path"])
+        .order_by("created_at", true)
+        .offset(offset)
+        .limit(limit)
+        .and_where_eq("workspace_id", "?".bind(&w_id))
+        .clone();
+
+    if let Some(tags) = tags {
+        sqlb.and_where_in("tag", &tags.iter().map(|x| quote(x)).collect::<Vec<_>>());
+    }
+    if let Some(ps) = &lq.script_path_start {
+        sqlb.and_where_like_left("runnable_path", ps);
+    }
+    if let Some(p) = &lq.script_path_exact {
+        sqlb.and_where_eq("runnable_path", "?".bind(p));
+    }
+    if let Some(h) = &lq.script_hash {
+        sqlb.and_where_eq("runnable_id", "?".bind(h));
+    }
+    if let Some(t) = &lq.tag {
+        if lq.allow_wildcards.unwrap_or(false) {
+            sqlb.and_where_like_left("tag", t.replace("*", "%"));
+        } else {
+            sqlb.and_where_eq("tag", "?".bind(t));
+        }
+    }
+    if let Some(cb) = &lq.created_by {
+        sqlb.and_where_eq("created_by", "?".bind(cb));
+    }
+    if let Some(r) = &lq.success {
+        if *r {
+            sqlb.and_where("status IN ('success', 'skipped')");
+        } else {
+            sqlb.and_where("status IN ('failure', 'canceled')");
+        }
+    }
+    if let Some(pj) = &lq.parent_job {
+        sqlb.and_where_eq("parent_job", "?".bind(pj));
+    }
+    for dt in [
+        &lq.started_after,
+        &lq.created_or_started_after,
+        &lq.created_or_started_after_completed_jobs,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        sqlb.and_where_ge("started_at", "?".bind(&dt.to_rfc3339()));
+    }
+    for dt in [&lq.started_before, &lq.created_or_started_before]
+        .into_iter()
+        .flatten()
+    {
+        sqlb.and_where_le("started_at", "?".bind(&dt.to_rfc3339()));
+    }
+    if let Some(dt) = &lq.created_before {
+        sqlb.and_where_le("created_at", "?".bind(&dt.to_rfc3339()));
+    }
+    if let Some(dt) = &lq.created_after {
+        sqlb.and_where_ge("created_at", "?".bind(&dt.to_rfc3339()));
+    }
+    if let Some(sk) = &lq.is_skipped {
+        if *sk {
+            sqlb.and_where_eq("status", "'skipped'");
+        } else {
+            sqlb.and_where_ne("status", "'skipped'");
+        }
+    }
+    if let Some(fs) = &lq.is_flow_step {
+        sqlb.and_where_eq("is_flow_step", fs);
+    }
+    if lq.has_null_parent.unwrap_or(false) {
+        sqlb.and_where_is_null("parent_job");
+    }
+    if let Some(jk) = &lq.job_kinds {
+        sqlb.and_where_in(
+            "kind",
+            &jk.split(',').into_iter().map(quote).collect::<Vec<_>>(),
+        );
+    }
+
+    let index = sqlx::query_as::<_, (Uuid, String)>(&sqlb.sql()?)
+        .fetch_all(&mut **tx)
+        .await?;
+
+    let mut ids_per_file: HashMap<&str, Vec<Uuid>> = HashMap::new();
+    for (id, object_path) in index.iter() {
+        ids_per_file
+            .entry(object_path.as_str())
+            .or_default()
+            .push(*id);
+    }
+    let mut jobs = HashMap::new();
+    for (object_path, ids) in ids_per_file {
+        let archived =
+            windmill_common::job_archive::read_archived_jobs(object_path, &ids, false).await?;
+        for job in archived {
+            let job = serde_json::from_value::<ListableCompletedJob>(job)?;
+            jobs.insert(job.id, job);
+        }
+    }
+    Ok(index
+        .into_iter()
+        .filter_map(|(id, _)| jobs.remove(&id))
+        .collect())
+}
+
 async fn get_completed_job<'a>(
     OptAuthed(opt_authed): OptAuthed,
     Extension(db): Extension<DB>,
@@ -6059,6 +6906,28 @@ async fn get_completed_job<'a>(
         .fetch_completed(&db, id, &w_id)
         .await?;
 
+    #[cfg(feature = "parquet")]
+    if job_o.is_none() {
+        if let Some(job) = windmill_common::job_archive::get_archived_job(&db, &w_id, id).await? {
+            let tag = job.get("tag").and_then(|x| x.as_str()).unwrap_or_default();
+            if tags.as_ref().is_some_and(|tags| !tags.contains(&tag)) {
+                return Err(Error::NotFound(format!(
+                    "Completed Job not found at name {id}"
+                )));
+            }
+            if opt_authed.is_none()
+                && job.get("created_by").and_then(|x| x.as_str()) != Some("anonymous")
+            {
+                return Err(Error::BadRequest(
+                    "As a non logged in user, you can only see jobs ran by anonymous users"
+                        .to_string(),
+                ));
+            }
+            log_job_view(&db, opt_authed.as_ref(), &w_id, &id).await?;
+            return Ok(Json(job).into_response());
+        }
+    }
+
     let cj = not_found_if_none(job_o, "Completed Job", id.to_string())?;
     let response = Json(cj).into_response();
     // let extra_log = query_scalar!(
@@ -69,6 +69,7 @@ pub mod args;
 mod audit;
 mod auth;
 mod capture;
+mod circuit_breakers;
 mod concurrency_groups;
 mod configs;
 mod db;
@@ -76,6 +77,8 @@ mod drafts;
 pub mod ee;
 pub mod embeddings;
 mod favorite;
+#[cfg(feature = "flow_testing")]
+mod flow_tests;
 mod flows;
 mod folders;
 mod granular_acls;
@@ -102,9 +105,11 @@ mod git_sync_ee;
 #[cfg(feature = "parquet")]
 mod job_helpers_ee;
 pub mod job_metrics;
+mod job_stream;
 pub mod jobs;
 #[cfg(all(feature = "enterprise", feature = "kafka"))]
 mod kafka_triggers_ee;
+mod log_tail;
 #[cfg(feature = "mqtt_trigger")]
 mod mqtt_triggers;
 #[cfg(all(feature = "enterprise", feature = "nats"))]
@@ -112,6 +117,7 @@ mod nats_triggers_ee;
 #[cfg(feature = "oauth2")]
 pub mod oauth2_ee;
 mod oidc_ee;
+mod rate_limits;
 mod raw_apps;
 mod resources;
 mod saml_ee;
@@ -150,6 +156,8 @@ mod workspaces_extra;
 #[cfg(feature = "mcp")]
 mod mcp;
 
+pub use trigger_helpers::resume_waiting_flows;
+
 pub const DEFAULT_BODY_LIMIT: usize = 2097152 * 100; // 200MB
 
 lazy_static::lazy_static! {
@@ -518,6 +526,7 @@ pub async fn run_server(
                         .nest("/apps", apps::workspaced_service())
                         .nest("/audit", audit::workspaced_service())
                         .nest("/capture", capture::workspaced_service())
+                        .nest("/circuit_breakers", circuit_breakers::workspaced_service())
                         .nest(
                             "/concurrency_groups",
                             concurrency_groups::workspaced_service(),
@@ -543,6 +552,7 @@ pub async fn run_server(
                         })
                         .nest("/ai", ai::workspaced_service())
                         .nest("/raw_apps", raw_apps::workspaced_service())
+                        .nest("/rate_limits", rate_limits::workspaced_service())
                         .nest("/resources", resources::workspaced_service())
                         .nest("/schedules", schedule::workspaced_service())
                         .nest("/scripts", scripts::workspaced_service())
@@ -0,0 +1,320 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Live tailing of the logs of running jobs, pushed as Server-Sent Events or NDJSON.
+//!
+//! Instead of each viewer polling `getupdate`, a single task per job and API server reads the
+//! new logs and broadcasts them to all the viewers of the job, whatever the number of viewers.
+//! Offsets are the ones of `getupdate`: a viewer starts from the `log_offset` it already has,
+//! and each chunk carries the `log_offset` to resume from, with either endpoint.
+
+use std::{collections::HashMap, convert::Infallible, sync::Mutex, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query},
+    response::Response,
+    Extension,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use windmill_common::error::{self, Error};
+
+use crate::{db::DB, job_stream::StreamFormat, users::OptAuthed};
+
+lazy_static::lazy_static! {
+    static ref LOG_TAILS: Mutex<HashMap<Uuid, broadcast::Sender<LogChunk>>> =
+        Mutex::new(HashMap::new());
+
+    pub static ref LOG_TAIL_POLL_INTERVAL_MS: u64 = std::env::var("LOG_TAIL_POLL_INTERVAL_MS")
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(500);
+}
+
+const LOG_TAIL_KEEPALIVE: Duration = Duration::from_secs(15);
+const MAX_TAILED_JOBS: usize = 100;
+
+#[derive(Deserialize)]
+pub struct TailLogsQuery {
+    /// comma separated job ids, each optionally followed by `:<log_offset>` to resume from
+    pub jobs: String,
+    pub stream_format: Option<StreamFormat>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LogChunk {
+    pub job_id: Uuid,
+    pub new_logs: String,
+    /// offset to resume from, with this endpoint or `getupdate`
+    pub log_offset: i32,
+    pub completed: bool,
+}
+
+impl LogChunk {
+    /// offset of the first char of `new_logs`
+    fn start_offset(&self) -> i32 {
+        self.log_offset - self.new_logs.chars().count() as i32
+    }
+
+    /// Drops the part of the chunk before `offset`, None if nothing is left to send
+    fn after(mut self, offset: i32) -> Option<LogChunk> {
+        let skip = offset.max(1) - self.start_offset();
+        if skip > 0 {
+            self.new_logs = self.new_logs.chars().skip(skip as usize).collect();
+        }
+        if self.new_logs.is_empty() && !self.completed {
+            None
+        } else {
+            Some(self)
+        }
+    }
+}
+
+pub async fn tail_job_logs(
+    OptAuthed(opt_authed): OptAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Query(TailLogsQuery { jobs, stream_format }): Query<TailLogsQuery>,
+) -> error::Result<Response> {
+    let jobs = jobs
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let (id, offset) = x.split_once(':').unwrap_or((x, "0"));
+            Ok((
+                Uuid::parse_str(id.trim())?,
+                offset.trim().parse::<i32>().map_err(|_| {
+                    Error::BadRequest(format!("invalid log offset for job {id}: {offset}"))
+                })?,
+            ))
+        })
+        .collect::<error::Result<Vec<_>>>()?;
+    if jobs.is_empty() || jobs.len() > MAX_TAILED_JOBS {
+        return Err(Error::BadRequest(format!(
+            "between 1 and {MAX_TAILED_JOBS} jobs can be tailed at once"
+        )));
+    }
+
+    let ids = jobs.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    let found = sqlx::query!(
+        "SELECT id, created_by FROM v2_job WHERE id = ANY($1) AND workspace_id = $2",
+        &ids,
+        &w_id,
+    )
+    .fetch_all(&db)
+    .await?;
+    for id in ids.iter() {
+        match found.iter().find(|j| j.id == *id) {
+            None => return Err(Error::NotFound(format!("Job not found: {id}"))),
+            Some(j) if opt_authed.is_none() && j.created_by != "anonymous" => {
+                return Err(Error::BadRequest(
+                    "As a non logged in user, you can only see jobs ran by anonymous users"
+                        .to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    let format = stream_format.unwrap_or_default();
+    let mut chunks = futures::stream::select_all(
+        jobs.into_iter()
+            .map(|(id, offset)| tail_logs(db.clone(), id, offset).boxed()),
+    );
+    let body = async_stream::stream! {
+        let mut keepalive = tokio::time::interval(LOG_TAIL_KEEPALIVE);
+        keepalive.tick().await;
+        loop {
+            // yielded outside of select! as the macro would hide the yield from stream!
+            let event = tokio::select! {
+                chunk = chunks.next() => match chunk {
+                    Some(chunk) => format.encode_event("logs", &chunk),
+                    // the logs of all the jobs were sent until their completion
+                    None => break,
+                },
+                // keeps proxies from closing the connection of jobs without new logs
+                _ = keepalive.tick(), if matches!(format, StreamFormat::Sse) => {
+                    ": keepalive\n\n".to_string()
+                }
+            };
+            yield event;
+        }
+    };
+
+    Ok(Response::builder()
+        .header(http::header::CONTENT_TYPE, format.content_type())
+        .header(http::header::CACHE_CONTROL, "no-cache")
+        .header("X-Accel-Buffering", "no")
+        .body(Body::from_stream(body.map(Ok::<_, Infallible>)))
+        .unwrap())
+}
+
+/// Logs of the job after `log_offset`, with the offset to resume from and whether the job is
+/// completed, read the same way as `getupdate`. None if the job does not exist anymore.
+async fn read_logs(db: &DB, job_id: Uuid, log_offset: i32) -> error::Result<Option<LogChunk>> {
+    let record = sqlx::query!(
+        "SELECT
+            c.id IS NOT NULL AS \"completed!\",
+            SUBSTR(l.logs, GREATEST($2 - l.log_offset, 0)) AS logs,
+            l.log_offset + CHAR_LENGTH(l.logs) + 1 AS log_offset
+        FROM v2_job j
+            LEFT JOIN v2_job_completed c USING (id)
+            LEFT JOIN job_logs l ON l.job_id = j.id
+        WHERE j.id = $1",
+        job_id,
+        log_offset,
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(record.map(|r| LogChunk {
+        job_id,
+        new_logs: r.logs.unwrap_or_default(),
+        // no logs yet, they will start from the beginning
+        log_offset: r.log_offset.unwrap_or(0),
+        completed: r.completed,
+    }))
+}
+
+/// Subscribes to the logs of the job, starting the task reading them if there is none yet
+fn subscribe(db: &DB, job_id: Uuid) -> broadcast::Receiver<LogChunk> {
+    let mut tails = LOG_TAILS.lock().unwrap();
+    if let Some(tx) = tails.get(&job_id) {
+        return tx.subscribe();
+    }
+    let (tx, rx) = broadcast::channel(64);
+    tails.insert(job_id, tx.clone());
+    tokio::spawn(broadcast_logs(db.clone(), job_id, tx));
+    rx
+}
+
+/// Reads the new logs of the job until it completes or has no viewers left, starting from the
+/// current end of the logs: the viewers read what came before on their own.
+async fn broadcast_logs(db: DB, job_id: Uuid, tx: broadcast::Sender<LogChunk>) {
+    let mut log_offset = i32::MAX;
+    loop {
+        let chunk = match read_logs(&db, job_id, log_offset).await {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::error!(%job_id, "error reading logs to tail: {e:#}");
+                None
+            }
+        };
+        let done = chunk.as_ref().map_or(true, |c| c.completed);
+        {
+            // removed under the lock so that no viewer can subscribe to a finished task
+            let mut tails = LOG_TAILS.lock().unwrap();
+            if done || tx.receiver_count() == 0 {
+                tails.remove(&job_id);
+            }
+        }
+        if let Some(chunk) = chunk {
+            // the first read only finds where the logs end
+            if chunk.completed || (log_offset != i32::MAX && !chunk.new_logs.is_empty()) {
+                let _ = tx.send(chunk.clone());
+            }
+            log_offset = chunk.log_offset;
+        }
+        if done || tx.receiver_count() == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(*LOG_TAIL_POLL_INTERVAL_MS)).await;
+    }
+}
+
+/// Logs of a single job from `log_offset` until its completion: what was logged before the
+/// subscription is read directly, the rest comes from the task shared by all the viewers.
+fn tail_logs(db: DB, job_id: Uuid, log_offset: i32) -> impl futures::Stream<Item = LogChunk> {
+    async_stream::stream! {
+        let mut rx = subscribe(&db, job_id);
+        let mut log_offset = log_offset;
+        let mut catch_up = true;
+        loop {
+            if catch_up {
+                catch_up = false;
+                match read_logs(&db, job_id, log_offset).await {
+                    Ok(Some(chunk)) => {
+                        log_offset = chunk.log_offset;
+                        let completed = chunk.completed;
+                        if !chunk.new_logs.is_empty() || completed {
+                            yield chunk;
+                        }
+                        if completed {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::error!(%job_id, "error reading logs to tail: {e:#}");
+                        break;
+                    }
+                }
+            }
+
+            match rx.recv().await {
+                // chunks read before the subscription may be missing, read them again
+                Ok(chunk) if chunk.start_offset() > log_offset.max(1) => catch_up = true,
+                Ok(chunk) => {
+                    let completed = chunk.completed;
+                    let end = chunk.log_offset;
+                    if let Some(chunk) = chunk.after(log_offset) {
+                        yield chunk;
+                    }
+                    log_offset = log_offset.max(end);
+                    if completed {
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => catch_up = true,
+                // the task stopped without the job completing, e.g. after a db error
+                Err(broadcast::error::RecvError::Closed) => {
+                    rx = subscribe(&db, job_id);
+                    catch_up = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(new_logs: &str, log_offset: i32, completed: bool) -> LogChunk {
+        LogChunk { job_id: Uuid::nil(), new_logs: new_logs.to_string(), log_offset, completed }
+    }
+
+    #[test]
+    fn test_log_chunk_after() {
+        // "héllo" read from the start of the logs, offsets count chars starting at 1
+        let c = chunk("héllo", 6, false);
+        assert_eq!(c.start_offset(), 1);
+
+        // 0 and 1 both mean from the start
+        assert_eq!(c.clone().after(0).unwrap().new_logs, "héllo");
+        assert_eq!(c.clone().after(1).unwrap().new_logs, "héllo");
+
+        // resuming mid-chunk, the viewer already has "hé"
+        let resumed = c.clone().after(3).unwrap();
+        assert_eq!(resumed.new_logs, "llo");
+        assert_eq!(resumed.log_offset, 6);
+
+        // the viewer already has the whole chunk
+        assert!(c.clone().after(6).is_none());
+        assert!(c.after(10).is_none());
+
+        // a completed chunk is sent even without new logs, to end the stream
+        let completed = chunk("", 6, true).after(6).unwrap();
+        assert!(completed.completed);
+        assert_eq!(completed.new_logs, "");
+        let completed = chunk("héllo", 6, true).after(10).unwrap();
+        assert_eq!(completed.new_logs, "");
+    }
+}
@@ -3,7 +3,7 @@ use crate::{
     db::{ApiAuthed, DB},
     jobs::{run_flow_by_path_inner, run_script_by_path_inner, RunJobQuery},
     resources::try_get_resource_from_db_as,
-    trigger_helpers::TriggerJobArgs,
+    trigger_helpers::{resume_waiting_flows, TriggerJobArgs},
     users::fetch_api_authed,
 };
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
@@ -97,6 +97,19 @@ async fn run_job(
     )
     .await?;
 
+    if !resume_waiting_flows(
+        db,
+        &trigger.workspace_id,
+        &TriggerKind::Mqtt,
+        &trigger.path,
+        &args.args,
+    )
+    .await?
+    .is_empty()
+    {
+        return Ok(());
+    }
+
     let authed = fetch_api_authed(
         trigger.edited_by.clone(),
         trigger.email.clone(),
@@ -2,7 +2,7 @@ use crate::{
     db::{ApiAuthed, DB},
     jobs::{run_flow_by_path_inner, run_script_by_path_inner, RunJobQuery},
     resources::try_get_resource_from_db_as,
-    trigger_helpers::TriggerJobArgs,
+    trigger_helpers::{resume_waiting_flows, TriggerJobArgs},
     users::fetch_api_authed,
 };
 use chrono::Utc;
@@ -30,7 +30,7 @@ use handler::{
     list_slot_name, set_enabled, test_postgres_connection, update_postgres_trigger, Postgres,
     Relations,
 };
-use windmill_common::{db::UserDB, error::Error, utils::StripPath};
+use windmill_common::{db::UserDB, error::Error, triggers::TriggerKind, utils::StripPath};
 mod bool;
 mod converter;
 mod handler;
@@ -263,6 +263,19 @@ async fn run_job(
     )
     .await?;
 
+    if !resume_waiting_flows(
+        db,
+        &trigger.workspace_id,
+        &TriggerKind::Postgres,
+        &trigger.path,
+        &args.args,
+    )
+    .await?
+    .is_empty()
+    {
+        return Ok(());
+    }
+
     let authed = fetch_api_authed(
         trigger.edited_by.clone(),
         trigger.email.clone(),
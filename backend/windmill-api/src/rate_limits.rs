@@ -0,0 +1,156 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use axum::{
+    extract::{Extension, Path},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use windmill_audit::{audit_ee::audit_log, ActionKind};
+use windmill_common::{
+    error::{Error, JsonResult, Result},
+    rate_limit::RateLimit,
+    utils::{not_found_if_none, require_admin, StripPath},
+};
+use windmill_queue::rate_limit::invalidate_has_rate_limits;
+
+use crate::db::{ApiAuthed, DB};
+
+pub fn workspaced_service() -> Router {
+    Router::new()
+        .route("/list", get(list_rate_limits))
+        .route("/get/*key", get(get_rate_limit))
+        .route("/set", post(set_rate_limit))
+        .route("/delete/*key", delete(delete_rate_limit))
+}
+
+#[derive(Deserialize)]
+struct NewRateLimit {
+    key: String,
+    rate: f64,
+    burst: Option<i32>,
+}
+
+async fn list_rate_limits(
+    _authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+) -> JsonResult<Vec<RateLimit>> {
+    let limits = sqlx::query_as!(
+        RateLimit,
+        "SELECT workspace_id, key, rate, burst, tat, edited_by, edited_at
+        FROM rate_limit
+        WHERE workspace_id = $1
+        ORDER BY key",
+        w_id,
+    )
+    .fetch_all(&db)
+    .await?;
+    Ok(Json(limits))
+}
+
+async fn get_rate_limit(
+    _authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, key)): Path<(String, StripPath)>,
+) -> JsonResult<RateLimit> {
+    let key = key.to_path();
+    let limit = sqlx::query_as!(
+        RateLimit,
+        "SELECT workspace_id, key, rate, burst, tat, edited_by, edited_at
+        FROM rate_limit
+        WHERE workspace_id = $1 AND key = $2",
+        w_id,
+        key,
+    )
+    .fetch_optional(&db)
+    .await?;
+    Ok(Json(not_found_if_none(limit, "Rate limit", key)?))
+}
+
+async fn set_rate_limit(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path(w_id): Path<String>,
+    Json(nl): Json<NewRateLimit>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    if !(nl.rate > 0.0 && nl.rate.is_finite()) || nl.burst.is_some_and(|x| x < 1) {
+        return Err(Error::BadRequest(
+            "rate and burst must be positive".to_string(),
+        ));
+    }
+
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "INSERT INTO rate_limit (workspace_id, key, rate, burst, edited_by)
+        VALUES ($1, $2, $3, COALESCE($4, 1), $5)
+        ON CONFLICT (workspace_id, key) DO UPDATE SET
+            rate = EXCLUDED.rate,
+            burst = EXCLUDED.burst,
+            edited_by = EXCLUDED.edited_by,
+            edited_at = now()",
+        w_id,
+        nl.key,
+        nl.rate,
+        nl.burst,
+        authed.username,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "rate_limits.set",
+        ActionKind::Update,
+        &w_id,
+        Some(&nl.key),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    invalidate_has_rate_limits(&w_id);
+
+    Ok(format!("rate limit for {} set", nl.key))
+}
+
+async fn delete_rate_limit(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Path((w_id, key)): Path<(String, StripPath)>,
+) -> Result<String> {
+    require_admin(authed.is_admin, &authed.username)?;
+    let key = key.to_path();
+
+    let mut tx = db.begin().await?;
+    let deleted = sqlx::query_scalar!(
+        "DELETE FROM rate_limit WHERE workspace_id = $1 AND key = $2 RETURNING key",
+        w_id,
+        key,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    not_found_if_none(deleted, "Rate limit", key)?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "rate_limits.delete",
+        ActionKind::Delete,
+        &w_id,
+        Some(key),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    invalidate_has_rate_limits(&w_id);
+
+    Ok(format!("rate limit for {key} deleted"))
+}
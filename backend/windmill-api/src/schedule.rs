@@ -22,17 +22,21 @@ use serde::{Deserialize, Serialize};
 use sql_builder::{prelude::Bind, SqlBuilder};
 use sqlx::{Postgres, Transaction};
 use std::str::FromStr;
+use uuid::Uuid;
 use windmill_audit::audit_ee::audit_log;
 use windmill_audit::ActionKind;
 use windmill_common::{
     db::UserDB,
     error::{Error, JsonResult, Result},
-    schedule::Schedule,
-    utils::{not_found_if_none, paginate, Pagination, ScheduleType, StripPath},
+    schedule::{CatchupPolicy, Schedule},
+    utils::{not_found_if_none, now_from_db, paginate, Pagination, ScheduleType, StripPath},
     worker::to_raw_value,
 };
 use windmill_git_sync::{handle_deployment_metadata, DeployedObject};
-use windmill_queue::schedule::push_scheduled_job;
+use windmill_queue::schedule::{
+    backfill_schedule, pending_schedule_tick, push_scheduled_job, schedule_tick_before,
+    schedule_ticks_between,
+};
 
 pub fn workspaced_service() -> Router {
     Router::new()
@@ -45,7 +49,7 @@ pub fn workspaced_service() -> Router {
         .route("/delete/*path", delete(delete_schedule))
         .route("/setenabled/*path", post(set_enabled))
         .route("/setdefaulthandler", post(set_default_error_handler))
-    // .route("/catchup/*path", post(do_catchup).get(list_catchup))
+        .route("/catchup/*path", post(do_catchup).get(list_catchup))
 }
 
 pub fn global_service() -> Router {
@@ -78,6 +82,8 @@ pub struct NewSchedule {
     pub tag: Option<String>,
     pub paused_until: Option<DateTime<Utc>>,
     pub cron_version: Option<String>,
+    pub catchup_policy: Option<CatchupPolicy>,
+    pub catchup_max_ticks: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -177,7 +183,8 @@ async fn create_schedule(
             on_recovery, on_recovery_times, on_recovery_extra_args,
             on_success, on_success_extra_args,
             ws_error_handler_muted, retry, summary, no_flow_overlap,
-            tag, paused_until, cron_version, description
+            tag, paused_until, cron_version, description,
+            catchup_policy, catchup_max_ticks
         ) VALUES (
             $1, $2, $3, $4, $5, $6,
             $7, $8, $9, $10,
@@ -185,7 +192,8 @@ async fn create_schedule(
             $15, $16, $17,
             $18, $19,
             $20, $21, $22, $23,
-            $24, $25, $26, $27
+            $24, $25, $26, $27,
+            $28, $29
         )
         RETURNING
             workspace_id,
@@ -217,7 +225,10 @@ async fn create_schedule(
             description,
             tag,
             paused_until,
-            cron_version
+            cron_version,
+            catchup_policy AS "catchup_policy: _",
+            catchup_max_ticks,
+            last_tick_at
         "#,
         w_id,
         ns.path,
@@ -249,7 +260,9 @@ async fn create_schedule(
         ns.tag,
         ns.paused_until,
         ns.cron_version.clone().unwrap_or_else(|| "v2".to_string()),
-        ns.description
+        ns.description,
+        ns.catchup_policy.unwrap_or_default() as CatchupPolicy,
+        ns.catchup_max_ticks
     )
     .fetch_one(&mut *tx)
     .await
@@ -334,7 +347,10 @@ async fn edit_schedule(
             path                    = $19,
             workspace_id            = $20,
             cron_version            = COALESCE($21, cron_version),
-            description             = $22
+            description             = $22,
+            catchup_policy          = COALESCE($23, catchup_policy),
+            catchup_max_ticks       = $24,
+            last_tick_at            = CASE WHEN schedule = $1::VARCHAR AND timezone = $2::VARCHAR THEN last_tick_at ELSE NULL END
         WHERE path = $19 AND workspace_id = $20
         RETURNING
             workspace_id,
@@ -366,7 +382,10 @@ async fn edit_schedule(
             description,
             tag,
             paused_until,
-            cron_version
+            cron_version,
+            catchup_policy AS "catchup_policy: _",
+            catchup_max_ticks,
+            last_tick_at
         "#,
         es.schedule,
         es.timezone,
@@ -393,7 +412,9 @@ async fn edit_schedule(
         path,
         w_id,
         es.cron_version,
-        es.description
+        es.description,
+        es.catchup_policy as Option<CatchupPolicy>,
+        es.catchup_max_ticks
     )
     .fetch_one(&mut *tx)
     .await
@@ -652,7 +673,10 @@ pub async fn set_enabled(
             description,
             tag,
             paused_until,
-            cron_version
+            cron_version,
+            catchup_policy AS "catchup_policy: _",
+            catchup_max_ticks,
+            last_tick_at
         "#,
         payload.enabled,
         authed.email,
@@ -699,52 +723,100 @@ pub async fn set_enabled(
     ))
 }
 
-// pub async fn do_catchup(
-//     authed: ApiAuthed,
-//     Extension(db): Extension<DB>,
-//     Extension(user_db): Extension<UserDB>,
-// //     Path((w_id, path)): Path<(String, StripPath)>,
-//     Json(payload): Json<SetEnabled>,
-// ) -> Result<String> {
-//     let mut tx: QueueTransaction<'_, rsmq_async::MultiplexedRsmq> =
-//         (user_db.begin(&authed).await?).into();
-//     let path = path.to_path();
-//     let schedule_o = sqlx::query_as!(
-//         Schedule,
-//         "UPDATE schedule SET enabled = $1, email = $2 WHERE path = $3 AND workspace_id = $4 RETURNING *",
-//         &payload.enabled,
-//         authed.email,
-//         path,
-//         w_id
-//     )
-//     .fetch_optional(&mut *tx)
-//     .await?;
-
-//     let schedule = not_found_if_none(schedule_o, "Schedule", path)?;
-
-//     clear_schedule(&mut tx, path, &w_id).await?;
-
-//     audit_log(
-//         &mut *tx,
-//         &authed,
-//         "schedule.setenabled",
-//         ActionKind::Update,
-//         &w_id,
-//         Some(path),
-//         Some([("enabled", payload.enabled.to_string().as_ref())].into()),
-//     )
-//     .await?;
-
-//     if payload.enabled {
-//         tx = push_scheduled_job(&db, tx, &schedule, None).await?;
-//     }
-//     tx.commit().await?;
-
-//     Ok(format!(
-//         "succesfully updated schedule at path {} to status {}",
-//         path, payload.enabled
-//     ))
-// }
+/// The range defaults to the ticks after the last one that ran, until now. The end of the range
+/// is kept before the tick of the job already queued for the schedule so that it is not pushed
+/// twice.
+async fn catchup_range(
+    tx: &mut Transaction<'_, Postgres>,
+    schedule: &Schedule,
+    catchup: &Catchup,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let pending = pending_schedule_tick(tx, schedule).await?;
+    let last_run = match pending {
+        // the pending tick is the last one pushed, the one before it is the last that ran
+        Some(pending) => schedule_tick_before(schedule, pending)?,
+        None => schedule.last_tick_at,
+    };
+    let from = match catchup.from {
+        Some(from) => from,
+        None => {
+            last_run.ok_or_else(|| {
+                Error::BadRequest(format!(
+                    "schedule {} has no previous tick, a start of range is required",
+                    schedule.path
+                ))
+            })? + chrono::Duration::milliseconds(1)
+        }
+    };
+    let mut to = match catchup.to {
+        Some(to) => to,
+        None => now_from_db(&mut **tx).await?,
+    };
+    if let Some(pending) = pending {
+        to = to.min(pending - chrono::Duration::milliseconds(1));
+    }
+    if catchup.from.is_none() && to < from {
+        // nothing was missed since the last tick that ran
+        to = from;
+    }
+    Ok((from, to))
+}
+
+async fn list_catchup(
+    authed: ApiAuthed,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Query(catchup): Query<Catchup>,
+) -> JsonResult<Vec<DateTime<Utc>>> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+
+    let schedule_o = windmill_queue::schedule::get_schedule_opt(&mut *tx, &w_id, path).await?;
+    let schedule = not_found_if_none(schedule_o, "Schedule", path)?;
+    let (from, to) = catchup_range(&mut tx, &schedule, &catchup).await?;
+    tx.commit().await?;
+
+    let ticks = schedule_ticks_between(&schedule, from, to)?;
+    Ok(Json(ticks))
+}
+
+async fn do_catchup(
+    authed: ApiAuthed,
+    Extension(db): Extension<DB>,
+    Extension(user_db): Extension<UserDB>,
+    Path((w_id, path)): Path<(String, StripPath)>,
+    Json(catchup): Json<Catchup>,
+) -> JsonResult<Vec<Uuid>> {
+    let path = path.to_path();
+    let mut tx = user_db.begin(&authed).await?;
+
+    let schedule_o = windmill_queue::schedule::get_schedule_opt(&mut *tx, &w_id, path).await?;
+    let schedule = not_found_if_none(schedule_o, "Schedule", path)?;
+    let (from, to) = catchup_range(&mut tx, &schedule, &catchup).await?;
+
+    audit_log(
+        &mut *tx,
+        &authed,
+        "schedule.catchup",
+        ActionKind::Execute,
+        &w_id,
+        Some(path),
+        Some(
+            [
+                ("from", from.to_rfc3339().as_str()),
+                ("to", to.to_rfc3339().as_str()),
+            ]
+            .into(),
+        ),
+    )
+    .await?;
+
+    let (uuids, tx) =
+        backfill_schedule(&db, tx, &schedule, Some(&authed.clone().into()), from, to).await?;
+    tx.commit().await?;
+
+    Ok(Json(uuids))
+}
 
 async fn delete_schedule(
     authed: ApiAuthed,
@@ -1000,6 +1072,8 @@ pub struct EditSchedule {
     pub tag: Option<String>,
     pub paused_until: Option<DateTime<Utc>>,
     pub cron_version: Option<String>,
+    pub catchup_policy: Option<CatchupPolicy>,
+    pub catchup_max_ticks: Option<i32>,
 }
 
 pub async fn clear_schedule<'c>(
@@ -1008,6 +1082,8 @@ pub async fn clear_schedule<'c>(
     w_id: &str,
 ) -> Result<()> {
     tracing::info!("Clearing schedule {}", path);
+    // the ticks of the deleted pending jobs never fired: rewind the catch-up anchor right
+    // before the earliest of them so that they count as missed when the schedule resumes
     sqlx::query!(
         "WITH to_delete AS (
             SELECT id FROM v2_job_queue
@@ -1021,7 +1097,12 @@ pub async fn clear_schedule<'c>(
         ), deleted AS (
             DELETE FROM v2_job_queue
             WHERE id IN (SELECT id FROM to_delete)
-            RETURNING id
+            RETURNING id, scheduled_for
+        ), rewind AS (
+            UPDATE schedule SET last_tick_at = (SELECT MIN(scheduled_for) FROM deleted) - INTERVAL '1 millisecond'
+            WHERE path = $1 AND workspace_id = $2
+                AND last_tick_at IS NOT NULL
+                AND EXISTS (SELECT 1 FROM deleted)
         ) DELETE FROM v2_job WHERE id IN (SELECT id FROM deleted)",
         path,
         w_id
@@ -1036,8 +1117,8 @@ pub struct SetEnabled {
     pub enabled: bool,
 }
 
-// #[derive(Deserialize)]
-// pub struct Catchup {
-//     pub from: DateTime<Utc>,
-//     pub to: Option<DateTime<Utc>>,
-// }
+#[derive(Deserialize)]
+pub struct Catchup {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
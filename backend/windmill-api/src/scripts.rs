@@ -652,6 +652,8 @@ async fn create_script_internal<'c>(
             || ns.language == ScriptLang::Nu
             || ns.language == ScriptLang::Php
             || ns.language == ScriptLang::Java
+            || ns.language == ScriptLang::Ruby
+            || ns.language == ScriptLang::R
         // for related places search: ADD_NEW_LANG
     ) {
         Some(String::new())
@@ -926,6 +928,7 @@ async fn create_script_internal<'c>(
             None,
             None,
             Some(&authed.clone().into()),
+            None,
         )
         .await?;
         Ok((hash, new_tx))
@@ -1,6 +1,7 @@
 use serde::Deserialize;
 use serde_json::value::RawValue;
 use std::collections::HashMap;
+use uuid::Uuid;
 use windmill_common::{
     error::Result,
     flows::FlowModuleValue,
@@ -17,7 +18,7 @@ use windmill_common::{
 };
 use windmill_queue::PushArgsOwned;
 
-use crate::{db::DB, HTTP_CLIENT};
+use crate::{db::DB, jobs::resume_suspended_job_from_event, HTTP_CLIENT};
 
 struct ScriptInfo {
     has_preprocessor: Option<bool>,
@@ -349,6 +350,69 @@ pub async fn get_runnable_format(
     Ok(runnable_format)
 }
 
+/// Resume the flows suspended on an event of this trigger whose correlation key matches the
+/// value at their `message_key` path in `args`. The args are passed as the resume payload.
+/// Returns the ids of the resumed flows, when non-empty the message must not start a new job.
+pub async fn resume_waiting_flows(
+    db: &DB,
+    w_id: &str,
+    trigger_kind: &TriggerKind,
+    trigger_path: &str,
+    args: &HashMap<String, Box<RawValue>>,
+) -> Result<Vec<Uuid>> {
+    // most messages have no flow waiting on them, skip the write transaction for those
+    let any_waiting = sqlx::query_scalar!(
+        "SELECT EXISTS(
+            SELECT 1 FROM flow_event_wait
+            WHERE workspace_id = $1 AND trigger_kind = $2 AND trigger_path = $3
+         ) AS \"exists!\"",
+        w_id,
+        trigger_kind as &TriggerKind,
+        trigger_path,
+    )
+    .fetch_one(db)
+    .await?;
+    if !any_waiting {
+        return Ok(vec![]);
+    }
+
+    let message = serde_json::json!(args);
+    let mut tx = db.begin().await?;
+    let waiting = sqlx::query!(
+        "DELETE FROM flow_event_wait
+         WHERE workspace_id = $1 AND trigger_kind = $2 AND trigger_path = $3
+            AND $4::jsonb #> string_to_array(message_key, '.') = correlation_key
+         RETURNING flow, job",
+        w_id,
+        trigger_kind as &TriggerKind,
+        trigger_path,
+        message,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let approver = format!("{}-{}", trigger_kind.to_key(), trigger_path);
+    for row in waiting.iter() {
+        tracing::info!(
+            "resuming flow {} suspended on {} trigger {}",
+            row.flow,
+            trigger_kind,
+            trigger_path
+        );
+        resume_suspended_job_from_event(
+            row.flow,
+            row.job,
+            message.clone(),
+            approver.clone(),
+            &mut tx,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(waiting.into_iter().map(|row| row.flow).collect())
+}
+
 #[allow(dead_code)]
 pub trait TriggerJobArgs<T: Clone> {
     fn v1_payload_fn(payload: T) -> HashMap<String, Box<RawValue>>;
@@ -38,7 +38,7 @@ use crate::{
     jobs::{
         run_flow_by_path_inner, run_script_by_path_inner, run_wait_result_internal, RunJobQuery,
     },
-    trigger_helpers::TriggerJobArgs,
+    trigger_helpers::{resume_waiting_flows, TriggerJobArgs},
     users::fetch_api_authed,
 };
 
@@ -1381,6 +1381,19 @@ async fn run_job(
     )
     .await?;
 
+    if !resume_waiting_flows(
+        db,
+        &trigger.workspace_id,
+        &TriggerKind::Websocket,
+        &trigger.path,
+        &args.args,
+    )
+    .await?
+    .is_empty()
+    {
+        return Ok(());
+    }
+
     let authed = fetch_api_authed(
         trigger.edited_by.clone(),
         trigger.email.clone(),
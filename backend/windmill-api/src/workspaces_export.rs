@@ -376,6 +376,11 @@ pub(crate) async fn tarball_workspace(
                 ScriptLang::Nu => "nu",
                 ScriptLang::OracleDB => "odb.sql",
                 ScriptLang::Java => "java",
+                ScriptLang::Duckdb => "duckdb.sql",
+                ScriptLang::Wasm => "wasm.src",
+                ScriptLang::Lua => "lua",
+                ScriptLang::Ruby => "rb",
+                ScriptLang::R => "r",
                 // for related places search: ADD_NEW_LANG
             };
             archive
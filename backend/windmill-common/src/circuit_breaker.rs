@@ -0,0 +1,87 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// What a circuit breaker is keyed by: the path of the script or flow a job runs, or the path
+/// of a resource passed to the job as a `$res:` argument.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "CIRCUIT_BREAKER_KIND", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CircuitBreakerKind {
+    Script,
+    Resource,
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[sqlx(type_name = "CIRCUIT_BREAKER_STATE", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitBreakerState {
+    /// Jobs are pushed normally and their failures are counted
+    #[default]
+    Closed,
+    /// Jobs are short-circuited until the cooldown is over
+    Open,
+    /// A single probe job is let through, its outcome closes or re-opens the breaker
+    HalfOpen,
+}
+
+/// What happens to the jobs pushed while the breaker is open.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[sqlx(type_name = "CIRCUIT_BREAKER_ACTION", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CircuitBreakerAction {
+    /// The job is created but fails as soon as it is pulled
+    #[default]
+    Fail,
+    /// The job is scheduled for the end of the cooldown, and again while a probe is running
+    Delay,
+}
+
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct CircuitBreaker {
+    pub workspace_id: String,
+    pub kind: CircuitBreakerKind,
+    pub path: String,
+    /// number of failures within `window_s` that opens the breaker
+    pub failure_threshold: i32,
+    pub window_s: i32,
+    /// how long the breaker stays open before letting a probe job through
+    pub cooldown_s: i32,
+    pub on_open: CircuitBreakerAction,
+    pub state: CircuitBreakerState,
+    pub failure_count: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_start: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_job: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_started_at: Option<DateTime<Utc>>,
+    pub edited_by: String,
+    pub edited_at: DateTime<Utc>,
+}
+
+impl CircuitBreaker {
+    /// When the breaker will let a probe job through, if it is open.
+    pub fn reopens_at(&self) -> Option<DateTime<Utc>> {
+        self.opened_at
+            .map(|opened_at| opened_at + chrono::Duration::seconds(self.cooldown_s as i64))
+    }
+
+    /// When a new probe is let through if the current one has not completed by then, so that a
+    /// probe that was deleted or never completes does not keep the breaker half open.
+    pub fn probe_expires_at(&self) -> Option<DateTime<Utc>> {
+        self.probe_started_at
+            .map(|started_at| started_at + chrono::Duration::seconds(self.cooldown_s as i64))
+    }
+}
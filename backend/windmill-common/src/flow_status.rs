@@ -9,6 +9,7 @@
 use std::collections::HashMap;
 use std::time::Duration;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -43,6 +44,19 @@ pub struct FlowStatus {
     pub approval_conditions: Option<ApprovalConditions>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restarted_from: Option<RestartedFrom>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compensation: Option<CompensationStatus>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DeadlineStatus>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replay: Option<ReplayStatus>,
+    /// branches canceled after losing the race of a branch-all module, their completion is ignored
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub race_losers: Vec<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -50,6 +64,16 @@ pub struct FlowStatus {
 pub struct RetryStatus {
     pub fail_count: u32,
     pub failed_jobs: Vec<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<RetryPredicate>,
+}
+
+/// Outcome of the `retry_if` predicate of a module for its failed job `job`, evaluated once
+/// per failure.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RetryPredicate {
+    pub job: Uuid,
+    pub retry: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -68,6 +92,57 @@ pub struct RestartedFrom {
     pub branch_or_iteration_n: Option<usize>,
 }
 
+/// Progress of the compensations run after a step failed, in reverse order of the
+/// compensated steps.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompensationStatus {
+    /// flow job running the compensation modules
+    pub job: Uuid,
+    pub failed_step: String,
+    pub failed_job: Uuid,
+    pub failed_result: Box<serde_json::value::RawValue>,
+    pub steps: Vec<CompensationStep>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompensationStep {
+    /// id of the compensated step
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+/// Deadline of the loop or branch-all module currently running, set when it starts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeadlineStatus {
+    /// id of the module
+    pub id: String,
+    pub at: DateTime<Utc>,
+    /// set by the monitor once it canceled the jobs of the module
+    #[serde(default)]
+    pub canceled: bool,
+}
+
+impl DeadlineStatus {
+    pub fn is_expired(&self, module_id: &str) -> bool {
+        self.id == module_id && self.at <= Utc::now()
+    }
+}
+
+/// Replay of a completed flow: top-level steps whose definition and args are unchanged reuse
+/// the result recorded by the original run instead of executing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayStatus {
+    pub flow_job_id: Uuid,
+    /// ids of the steps that reused their recorded result
+    #[serde(default)]
+    pub reused: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Iterator {
     pub index: usize,
@@ -388,9 +463,13 @@ impl FlowStatus {
                 None
             },
             cleanup_module: FlowCleanupModule { flow_jobs_to_clean: vec![] },
-            retry: RetryStatus { fail_count: 0, failed_jobs: vec![] },
+            retry: RetryStatus { fail_count: 0, failed_jobs: vec![], predicate: None },
             restarted_from: None,
             user_states: HashMap::new(),
+            compensation: None,
+            deadline: None,
+            replay: None,
+            race_losers: vec![],
         }
     }
 
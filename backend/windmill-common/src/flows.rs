@@ -22,6 +22,7 @@ use crate::{
     error::Error,
     more_serde::{default_empty_string, default_id, default_null, default_true, is_default},
     scripts::{Schema, ScriptHash, ScriptLang},
+    triggers::TriggerKind,
     worker::{to_raw_value, Connection},
     DB,
 };
@@ -136,6 +137,109 @@ pub struct FlowValue {
     pub concurrency_key: Option<String>,
 }
 
+impl FlowValue {
+    /// Compensations are only run for the top-level script and flow steps: the input of a loop
+    /// or branch step cannot be replayed, and the steps nested in them are never compensated.
+    pub fn check_compensations(&self) -> Result<(), Error> {
+        for module in &self.modules {
+            // invalid steps are reported when the flow runs
+            let Ok(value) = module.get_value() else {
+                continue;
+            };
+            if module.compensation.is_some()
+                && !matches!(
+                    value,
+                    FlowModuleValue::Script { .. }
+                        | FlowModuleValue::RawScript { .. }
+                        | FlowModuleValue::FlowScript { .. }
+                        | FlowModuleValue::Flow { .. }
+                )
+            {
+                return Err(Error::BadRequest(format!(
+                    "step {} cannot have a compensation, only script and flow steps can",
+                    module.id
+                )));
+            }
+            check_no_nested_compensations(&value)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_no_nested_compensations(value: &FlowModuleValue) -> Result<(), Error> {
+    let modules = match value {
+        FlowModuleValue::ForloopFlow { modules, .. }
+        | FlowModuleValue::WhileloopFlow { modules, .. } => modules.iter().collect::<Vec<_>>(),
+        FlowModuleValue::BranchOne { branches, default, .. } => branches
+            .iter()
+            .flat_map(|b| b.modules.iter())
+            .chain(default.iter())
+            .collect(),
+        FlowModuleValue::BranchAll { branches, .. } => {
+            branches.iter().flat_map(|b| b.modules.iter()).collect()
+        }
+        _ => return Ok(()),
+    };
+    for module in modules {
+        if module.compensation.is_some() {
+            return Err(Error::BadRequest(format!(
+                "step {} cannot have a compensation, only top-level steps are compensated",
+                module.id
+            )));
+        }
+        if let Ok(value) = module.get_value() {
+            check_no_nested_compensations(&value)?;
+        }
+    }
+    Ok(())
+}
+
+impl FlowValue {
+    /// Flows can only wait on the kinds of triggers that resume them, see `SuspendEvent`
+    pub fn check_suspend_events(&self) -> Result<(), Error> {
+        let modules = self
+            .modules
+            .iter()
+            .chain(self.failure_module.as_deref())
+            .chain(self.preprocessor_module.as_deref());
+        for module in modules {
+            check_suspend_event(module)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_suspend_event(module: &FlowModule) -> Result<(), Error> {
+    if let Some(event) = module.suspend.as_ref().and_then(|s| s.event.as_ref()) {
+        if !event.trigger_kind.can_resume_flows() {
+            return Err(Error::BadRequest(format!(
+                "step {} cannot wait on a {} trigger, only http, websocket, mqtt and postgres triggers resume flows",
+                module.id,
+                event.trigger_kind.to_key()
+            )));
+        }
+    }
+    let Ok(value) = module.get_value() else {
+        return Ok(());
+    };
+    match value {
+        FlowModuleValue::ForloopFlow { modules, .. }
+        | FlowModuleValue::WhileloopFlow { modules, .. } => {
+            modules.iter().try_for_each(check_suspend_event)
+        }
+        FlowModuleValue::BranchOne { branches, default, .. } => branches
+            .iter()
+            .flat_map(|b| b.modules.iter())
+            .chain(default.iter())
+            .try_for_each(check_suspend_event),
+        FlowModuleValue::BranchAll { branches, .. } => branches
+            .iter()
+            .flat_map(|b| b.modules.iter())
+            .try_for_each(check_suspend_event),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Default, Deserialize, Serialize, Debug, Clone)]
 pub struct StopAfterIf {
     pub expr: String,
@@ -148,6 +252,9 @@ pub struct StopAfterIf {
 pub struct Retry {
     pub constant: ConstantDelay,
     pub exponential: ExponentialDelay,
+    /// only retry when the error matches, retry on any error if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_if: Option<RetryIf>,
 }
 
 impl Retry {
@@ -155,7 +262,7 @@ impl Retry {
     ///
     /// May return [`Duration::ZERO`] to retry immediately.
     pub fn interval(&self, previous_attempts: u32, silent: bool) -> Option<Duration> {
-        let Self { constant, exponential } = self;
+        let Self { constant, exponential, .. } = self;
 
         if previous_attempts < constant.attempts {
             Some(Duration::from_secs(constant.seconds as u64))
@@ -198,6 +305,62 @@ impl Retry {
     }
 }
 
+/// Conditions on the error of a failed job for it to be retried, all the set conditions must match.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct RetryIf {
+    /// retry only on errors with one of these names
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    /// retry only on errors whose message matches this regex
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// javascript predicate evaluated by the worker with the error as `error`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expr: Option<String>,
+}
+
+impl RetryIf {
+    /// Checks the name and message conditions against the error object of a failed job.
+    /// The `expr` predicate needs a js runtime and is evaluated by the worker.
+    pub fn matches_error(&self, error: &serde_json::Value) -> bool {
+        if !self.names.is_empty() {
+            let name = error
+                .get("name")
+                .and_then(|x| x.as_str())
+                .unwrap_or_default();
+            if !self.names.iter().any(|n| n == name) {
+                return false;
+            }
+        }
+        if let Some(pattern) = self.message.as_ref() {
+            let message = error
+                .get("message")
+                .and_then(|x| x.as_str())
+                .unwrap_or_default();
+            match regex::Regex::new(pattern) {
+                Ok(re) if re.is_match(message) => (),
+                Ok(_) => return false,
+                Err(e) => {
+                    tracing::warn!("invalid retry message pattern {pattern}: {e:#}");
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Delay hint that a failed job can set in its error as `retry_after`, in seconds,
+/// to override the delay until its next retry (e.g. the Retry-After header of a 429).
+pub fn retry_after_hint(error: &serde_json::Value) -> Option<Duration> {
+    error
+        .get("retry_after")
+        .and_then(|x| x.as_f64())
+        .filter(|x| x.is_finite() && *x >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
 #[serde(default)]
 pub struct ConstantDelay {
@@ -239,6 +402,27 @@ pub struct Suspend {
     pub hide_cancel: Option<bool>,
     #[serde(skip_serializing_if = "false_or_empty")]
     pub continue_on_disapprove_timeout: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<SuspendEvent>,
+}
+
+impl Suspend {
+    /// Number of resume messages to wait for, an event suspend waits for one by default.
+    pub fn events_to_wait_for(&self) -> u32 {
+        self.required_events
+            .unwrap_or_else(|| if self.event.is_some() { 1 } else { 0 })
+    }
+}
+
+/// Resume the suspended flow when a message received by the trigger at `trigger_path` has the
+/// value of `correlation_key` at the dotted `message_key` path of its payload. Only http,
+/// websocket, mqtt and postgres triggers resume flows.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SuspendEvent {
+    pub trigger_kind: TriggerKind,
+    pub trigger_path: String,
+    pub message_key: String,
+    pub correlation_key: InputTransform,
 }
 
 fn false_or_empty(v: &Option<bool>) -> bool {
@@ -252,6 +436,30 @@ pub struct Mock {
     pub return_value: Option<serde_json::Value>,
 }
 
+/// Test case of a flow, run as a preview of the deployed flow with the mocks of its steps
+/// overridden by `mocks`, including the steps nested in loops and branches. A mock of a step that
+/// is not in the flow fails the case.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FlowTestCase {
+    pub name: String,
+    #[serde(default)]
+    pub args: HashMap<String, Box<JsonRawValue>>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub mocks: HashMap<String, Mock>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<FlowTestAssertion>,
+}
+
+/// JS expression that must evaluate to `true`, evaluated on the worker once all the steps of the
+/// flow succeeded. `result` is the result of `step`, or the result of the flow if no step is
+/// given, `results` the results by step id and `flow_input` the args.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct FlowTestAssertion {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step: Option<String>,
+    pub expr: String,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct FlowModule {
     #[serde(default = "default_id")]
@@ -284,6 +492,11 @@ pub struct FlowModule {
     pub continue_on_error: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub skip_if: Option<SkipIf>,
+    /// Runnable undoing the effects of this step. When a later step fails, the compensations
+    /// of the steps that succeeded are run in reverse order, each receiving the original
+    /// `input` and `result` of the step it compensates.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compensation: Option<Box<FlowModule>>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -304,6 +517,12 @@ pub struct FlowModuleValueWithSkipFailures {
     pub skip_failures: Option<bool>,
     pub parallel: Option<bool>,
     pub parallelism: Option<u16>,
+    pub race: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct FlowModuleValueWithDeadline {
+    pub deadline: Option<ModuleDeadline>,
 }
 
 #[derive(Deserialize)]
@@ -329,6 +548,12 @@ impl FlowModule {
             .map_err(crate::error::to_anyhow)
     }
 
+    pub fn get_deadline(&self) -> Option<ModuleDeadline> {
+        serde_json::from_str::<FlowModuleValueWithDeadline>(self.value.get())
+            .ok()
+            .and_then(|x| x.deadline)
+    }
+
     pub fn get_branches_skip_failures(&self) -> anyhow::Result<FlowModuleWithBranches> {
         serde_json::from_str::<FlowModuleWithBranches>(self.value.get())
             .map_err(crate::error::to_anyhow)
@@ -463,6 +688,8 @@ pub enum FlowModuleValue {
         parallel: bool,
         #[serde(skip_serializing_if = "Option::is_none")]
         parallelism: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        deadline: Option<ModuleDeadline>,
     },
     WhileloopFlow {
         modules: Vec<FlowModule>,
@@ -470,6 +697,8 @@ pub enum FlowModuleValue {
         modules_node: Option<FlowNodeId>,
         #[serde(default = "default_false")]
         skip_failures: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        deadline: Option<ModuleDeadline>,
     },
     BranchOne {
         branches: Vec<Branch>,
@@ -481,6 +710,13 @@ pub enum FlowModuleValue {
         branches: Vec<Branch>,
         #[serde(default = "default_true")]
         parallel: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        parallelism: Option<u16>,
+        /// the first successful branch wins, the remaining ones are cancelled
+        #[serde(default, skip_serializing_if = "is_default")]
+        race: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        deadline: Option<ModuleDeadline>,
     },
     RawScript {
         #[serde(default)]
@@ -504,6 +740,14 @@ pub enum FlowModuleValue {
         is_trigger: Option<bool>,
     },
     Identity,
+    /// Park the flow without holding a worker, for `seconds` or until the RFC 3339 timestamp
+    /// `until`. The result of the previous step is passed through.
+    Sleep {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seconds: Option<InputTransform>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        until: Option<InputTransform>,
+    },
     // Internal only, never exposed to the frontend.
     FlowScript {
         #[serde(default)]
@@ -524,6 +768,25 @@ pub enum FlowModuleValue {
     },
 }
 
+/// Wall-clock limit of a loop or branch-all module, counted from the start of its first
+/// iteration or branch.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ModuleDeadline {
+    pub seconds: u32,
+    #[serde(default)]
+    pub on_timeout: DeadlineAction,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadlineAction {
+    /// cancel the in-flight iterations or branches and fail the module
+    #[default]
+    Fail,
+    /// cancel the in-flight iterations or branches and continue with the results gathered so far
+    Continue,
+}
+
 fn is_none_or_empty(expr: &Option<String>) -> bool {
     expr.is_none() || expr.as_ref().unwrap().is_empty()
 }
@@ -555,6 +818,10 @@ struct UntaggedFlowModuleValue {
     id: Option<FlowNodeId>,
     default_node: Option<FlowNodeId>,
     modules_node: Option<FlowNodeId>,
+    deadline: Option<ModuleDeadline>,
+    seconds: Option<InputTransform>,
+    until: Option<InputTransform>,
+    race: Option<bool>,
 }
 
 impl<'de> Deserialize<'de> for FlowModuleValue {
@@ -591,6 +858,7 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                 skip_failures: untagged.skip_failures.unwrap_or(true),
                 parallel: untagged.parallel.unwrap_or(false),
                 parallelism: untagged.parallelism,
+                deadline: untagged.deadline,
             }),
             "whileloopflow" => Ok(FlowModuleValue::WhileloopFlow {
                 modules: untagged
@@ -598,6 +866,7 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                     .ok_or_else(|| serde::de::Error::missing_field("modules"))?,
                 modules_node: untagged.modules_node,
                 skip_failures: untagged.skip_failures.unwrap_or(false),
+                deadline: untagged.deadline,
             }),
             "branchone" => Ok(FlowModuleValue::BranchOne {
                 branches: untagged
@@ -613,6 +882,9 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                     .branches
                     .ok_or_else(|| serde::de::Error::missing_field("branches"))?,
                 parallel: untagged.parallel.unwrap_or(true),
+                parallelism: untagged.parallelism,
+                race: untagged.race.unwrap_or(false),
+                deadline: untagged.deadline,
             }),
             "rawscript" => Ok(FlowModuleValue::RawScript {
                 input_transforms: untagged.input_transforms.unwrap_or_default(),
@@ -645,6 +917,12 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                 is_trigger: untagged.is_trigger,
             }),
             "identity" => Ok(FlowModuleValue::Identity),
+            "sleep" => {
+                if untagged.seconds.is_none() && untagged.until.is_none() {
+                    return Err(serde::de::Error::missing_field("seconds"));
+                }
+                Ok(FlowModuleValue::Sleep { seconds: untagged.seconds, until: untagged.until })
+            }
             other => Err(serde::de::Error::unknown_variant(
                 other,
                 &[
@@ -656,6 +934,7 @@ impl<'de> Deserialize<'de> for FlowModuleValue {
                     "branchall",
                     "rawscript",
                     "identity",
+                    "sleep",
                 ],
             )),
         }
@@ -710,6 +989,7 @@ pub fn add_virtual_items_if_necessary(modules: &mut Vec<FlowModule>) {
             delete_after_use: None,
             continue_on_error: None,
             skip_if: None,
+            compensation: None,
         });
     }
 }
@@ -0,0 +1,426 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Archival of the completed jobs past the retention period to the instance object store.
+//!
+//! When `job_archive_on_s3` is enabled, the monitor writes the expired jobs, with their args,
+//! result and logs, as Parquet files partitioned by workspace and completion day
+//! (`job_archive/workspace_id=<w_id>/date=<YYYY-MM-DD>/<batch>.parquet`) before deleting them.
+//! Each archived job keeps a row in the `job_archive` table with the columns needed to list and
+//! filter it and the file holding it, so that the API can transparently read it back.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use datafusion::{
+    arrow::{
+        array::{AsArray, BooleanArray},
+        compute::cast,
+        datatypes::DataType,
+        json,
+    },
+    parquet::arrow::{
+        arrow_reader::{ArrowPredicateFn, ParquetRecordBatchReaderBuilder, RowFilter},
+        ProjectionMask,
+    },
+};
+use futures::StreamExt;
+use object_store::{path::Path, ObjectStore};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+use uuid::Uuid;
+use windmill_parser_sql::S3ModeFormat;
+
+use crate::{
+    error::{self, to_anyhow, Error},
+    s3_helpers::{attempt_fetch_bytes, convert_json_line_stream, OBJECT_STORE_CACHE_SETTINGS},
+    scripts::ScriptHash,
+    DB,
+};
+
+pub const JOB_ARCHIVE_PREFIX: &str = "job_archive";
+const ARCHIVE_BATCH_SIZE: i64 = 1000;
+/// the remaining expired jobs are archived at the next monitor ticks
+const MAX_ARCHIVE_BATCHES_PER_RUN: usize = 10;
+
+/// Fields stored as json text, the schema of each file being inferred from its rows
+const JSON_FIELDS: [&str; 4] = ["args", "result", "flow_status", "labels"];
+/// Fields not decoded when listing the archived jobs
+const FULL_JOB_FIELDS: [&str; 4] = ["args", "result", "flow_status", "logs"];
+
+#[derive(sqlx::FromRow, Serialize)]
+struct ArchivedJob {
+    id: Uuid,
+    workspace_id: String,
+    parent_job: Option<Uuid>,
+    created_by: String,
+    created_at: DateTime<Utc>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: DateTime<Utc>,
+    duration_ms: i64,
+    status: String,
+    success: bool,
+    is_skipped: bool,
+    canceled: bool,
+    canceled_by: Option<String>,
+    canceled_reason: Option<String>,
+    script_hash: Option<ScriptHash>,
+    script_path: Option<String>,
+    job_kind: String,
+    language: Option<String>,
+    schedule_path: Option<String>,
+    permissioned_as: String,
+    email: String,
+    visible_to_owner: bool,
+    is_flow_step: bool,
+    mem_peak: Option<i32>,
+    tag: String,
+    priority: Option<i16>,
+    worker: Option<String>,
+    args: Option<String>,
+    result: Option<String>,
+    flow_status: Option<String>,
+    labels: Option<String>,
+    logs: Option<String>,
+    #[serde(skip)]
+    log_offset: Option<i32>,
+    #[serde(skip)]
+    log_file_index: Option<Vec<String>>,
+}
+
+async fn archive_store() -> error::Result<Arc<dyn ObjectStore>> {
+    OBJECT_STORE_CACHE_SETTINGS
+        .read()
+        .await
+        .clone()
+        .ok_or_else(|| {
+            Error::BadConfig("the job archive requires an instance object storage".to_string())
+        })
+}
+
+/// Archives the jobs completed more than `retention_secs` ago that are not archived yet, at most
+/// `MAX_ARCHIVE_BATCHES_PER_RUN` batches at a time. Returns the number of jobs archived.
+pub async fn archive_expired_jobs(db: &DB, retention_secs: i64) -> error::Result<usize> {
+    let os = archive_store().await?;
+    let mut archived = 0;
+    for _ in 0..MAX_ARCHIVE_BATCHES_PER_RUN {
+        let jobs = sqlx::query_as::<_, ArchivedJob>(
+            "SELECT
+                c.id, c.workspace_id, j.parent_job, j.created_by, j.created_at, c.started_at,
+                c.completed_at, c.duration_ms, c.status::text AS status,
+                c.status = 'success' OR c.status = 'skipped' AS success,
+                c.status = 'skipped' AS is_skipped, c.status = 'canceled' AS canceled,
+                c.canceled_by, c.canceled_reason, j.runnable_id AS script_hash,
+                j.runnable_path AS script_path, j.kind::text AS job_kind,
+                j.script_lang::text AS language,
+                CASE WHEN j.trigger_kind = 'schedule'::job_trigger_kind THEN j.trigger END
+                    AS schedule_path,
+                j.permissioned_as, j.permissioned_as_email AS email, j.visible_to_owner,
+                j.flow_step_id IS NOT NULL AS is_flow_step, c.memory_peak AS mem_peak, j.tag,
+                j.priority, c.worker, j.args::text AS args, c.result::text AS result,
+                COALESCE(c.flow_status, c.workflow_as_code_status)::text AS flow_status,
+                (c.result->'wm_labels')::text AS labels, l.logs, l.log_offset, l.log_file_index
+            FROM v2_job_completed c
+                JOIN v2_job j USING (id)
+                LEFT JOIN job_logs l ON l.job_id = c.id
+            WHERE c.completed_at <= now() - ($1::bigint::text || ' s')::interval
+                AND NOT EXISTS (SELECT 1 FROM job_archive a WHERE a.id = c.id)
+            ORDER BY c.completed_at
+            LIMIT $2",
+        )
+        .bind(retention_secs)
+        .bind(ARCHIVE_BATCH_SIZE)
+        .fetch_all(db)
+        .await?;
+        let count = jobs.len();
+
+        let mut partitions: HashMap<(String, NaiveDate), Vec<ArchivedJob>> = HashMap::new();
+        for mut job in jobs {
+            if job
+                .log_file_index
+                .as_ref()
+                .is_some_and(|index| !index.is_empty())
+            {
+                job.logs = read_full_logs(&job).await;
+            }
+            partitions
+                .entry((job.workspace_id.clone(), job.completed_at.date_naive()))
+                .or_default()
+                .push(job);
+        }
+        for ((w_id, date), jobs) in partitions {
+            let object_path = format!(
+                "{JOB_ARCHIVE_PREFIX}/workspace_id={w_id}/date={date}/{}.parquet",
+                Uuid::new_v4()
+            );
+            write_parquet(&os, &object_path, &jobs).await?;
+            index_archived_jobs(db, &object_path, &jobs).await?;
+        }
+
+        archived += count;
+        if count < ARCHIVE_BATCH_SIZE as usize {
+            break;
+        }
+    }
+    Ok(archived)
+}
+
+/// The logs of the job including the ones compacted to the log files, which are deleted along with
+/// the job. Falls back to the logs kept in the database if the files cannot be read.
+async fn read_full_logs(job: &ArchivedJob) -> Option<String> {
+    let log_offset = job.log_offset.unwrap_or(0);
+    let logs = job.logs.as_deref().unwrap_or_default();
+
+    #[cfg(feature = "enterprise")]
+    if let Some(stream) =
+        crate::jobs::get_logs_from_store(log_offset, logs, &job.log_file_index).await
+    {
+        let chunks = stream.collect::<Vec<_>>().await;
+        if let Ok(chunks) = chunks.into_iter().collect::<Result<Vec<_>, _>>() {
+            return Some(String::from_utf8_lossy(&chunks.concat()).into_owned());
+        }
+    }
+    if let Some(stream) =
+        crate::jobs::get_logs_from_disk(log_offset, logs, &job.log_file_index).await
+    {
+        let chunks = stream.collect::<Vec<_>>().await;
+        if let Ok(chunks) = chunks.into_iter().collect::<Result<Vec<_>, _>>() {
+            return Some(String::from_utf8_lossy(&chunks.concat()).into_owned());
+        }
+    }
+    tracing::warn!(
+        "could not read the log files of job {}, only the logs kept in the database are archived",
+        job.id
+    );
+    job.logs.clone()
+}
+
+async fn write_parquet(
+    os: &Arc<dyn ObjectStore>,
+    object_path: &str,
+    jobs: &[ArchivedJob],
+) -> error::Result<()> {
+    let rows = jobs
+        .iter()
+        .map(|job| serde_json::to_value(job))
+        .collect::<Vec<_>>();
+    let parquet = convert_json_line_stream(futures::stream::iter(rows), S3ModeFormat::Parquet)
+        .await?
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .concat();
+    os.put(&Path::from(object_path), parquet.into())
+        .await
+        .map_err(to_anyhow)?;
+    Ok(())
+}
+
+async fn index_archived_jobs(
+    db: &DB,
+    object_path: &str,
+    jobs: &[ArchivedJob],
+) -> error::Result<()> {
+    let mut ids = vec![];
+    let mut workspace_ids = vec![];
+    let mut created_ats = vec![];
+    let mut started_ats = vec![];
+    let mut completed_ats = vec![];
+    let mut created_bys = vec![];
+    let mut runnable_paths = vec![];
+    let mut runnable_ids = vec![];
+    let mut kinds = vec![];
+    let mut statuses = vec![];
+    let mut tags = vec![];
+    let mut parent_jobs = vec![];
+    let mut is_flow_steps = vec![];
+    let mut permissioned_ases = vec![];
+    let mut visible_to_owners = vec![];
+    for job in jobs {
+        ids.push(job.id);
+        workspace_ids.push(job.workspace_id.clone());
+        created_ats.push(job.created_at);
+        started_ats.push(job.started_at);
+        completed_ats.push(job.completed_at);
+        created_bys.push(job.created_by.clone());
+        runnable_paths.push(job.script_path.clone());
+        runnable_ids.push(job.script_hash.map(|h| h.0));
+        kinds.push(job.job_kind.clone());
+        statuses.push(job.status.clone());
+        tags.push(job.tag.clone());
+        parent_jobs.push(job.parent_job);
+        is_flow_steps.push(job.is_flow_step);
+        permissioned_ases.push(job.permissioned_as.clone());
+        visible_to_owners.push(job.visible_to_owner);
+    }
+    sqlx::query!(
+        "INSERT INTO job_archive (id, workspace_id, created_at, started_at, completed_at,
+            created_by, runnable_path, runnable_id, kind, status, tag, parent_job, is_flow_step,
+            permissioned_as, visible_to_owner, object_path)
+        SELECT id, workspace_id, created_at, started_at, completed_at, created_by, runnable_path,
+            runnable_id, kind::job_kind, status::job_status, tag, parent_job, is_flow_step,
+            permissioned_as, visible_to_owner, $16
+        FROM UNNEST($1::uuid[], $2::varchar[], $3::timestamptz[], $4::timestamptz[],
+            $5::timestamptz[], $6::varchar[], $7::varchar[], $8::bigint[], $9::text[], $10::text[],
+            $11::varchar[], $12::uuid[], $13::bool[], $14::varchar[], $15::bool[])
+            AS t(id, workspace_id, created_at, started_at, completed_at, created_by,
+                runnable_path, runnable_id, kind, status, tag, parent_job, is_flow_step,
+                permissioned_as, visible_to_owner)
+        ON CONFLICT (id) DO NOTHING",
+        &ids,
+        &workspace_ids,
+        &created_ats,
+        &started_ats as &[Option<DateTime<Utc>>],
+        &completed_ats,
+        &created_bys,
+        &runnable_paths as &[Option<String>],
+        &runnable_ids as &[Option<i64>],
+        &kinds,
+        &statuses,
+        &tags,
+        &parent_jobs as &[Option<Uuid>],
+        &is_flow_steps,
+        &permissioned_ases,
+        &visible_to_owners,
+        object_path,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The archived job, shaped as the completed jobs returned by the API, None if it was not
+/// archived
+pub async fn get_archived_job(db: &DB, w_id: &str, job_id: Uuid) -> error::Result<Option<Value>> {
+    let object_path = sqlx::query_scalar!(
+        "SELECT object_path FROM job_archive WHERE id = $1 AND workspace_id = $2",
+        job_id,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?;
+    let Some(object_path) = object_path else {
+        return Ok(None);
+    };
+    Ok(read_archived_jobs(&object_path, &[job_id], true)
+        .await?
+        .into_iter()
+        .next())
+}
+
+/// Reads the jobs `ids` from the archive file `object_path`. Only the id column is decoded for
+/// the other jobs of the file, and the args, result, flow status and logs are only decoded if
+/// `full` is set.
+pub async fn read_archived_jobs(
+    object_path: &str,
+    ids: &[Uuid],
+    full: bool,
+) -> error::Result<Vec<Value>> {
+    let bytes = attempt_fetch_bytes(archive_store().await?, object_path).await?;
+    let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let object_path = object_path.to_string();
+    let rows = tokio::task::spawn_blocking(move || {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(bytes).map_err(to_anyhow)?;
+        let schema = builder.parquet_schema();
+        let id_column = schema
+            .columns()
+            .iter()
+            .position(|c| c.name() == "id")
+            .ok_or_else(|| anyhow::anyhow!("archive file {object_path} has no id column"))?;
+        let id_filter =
+            ArrowPredicateFn::new(ProjectionMask::leaves(schema, [id_column]), move |batch| {
+                let batch_ids = cast(batch.column(0), &DataType::Utf8)?;
+                Ok(batch_ids
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|id| Some(id.is_some_and(|id| ids.iter().any(|x| x == id))))
+                    .collect::<BooleanArray>())
+            });
+        let projection = ProjectionMask::leaves(
+            schema,
+            schema
+                .columns()
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| full || !FULL_JOB_FIELDS.contains(&c.name()))
+                .map(|(i, _)| i),
+        );
+        let reader = builder
+            .with_projection(projection)
+            .with_row_filter(RowFilter::new(vec![Box::new(id_filter)]))
+            .build()
+            .map_err(to_anyhow)?;
+        let mut writer = json::ArrayWriter::new(vec![]);
+        for batch in reader {
+            writer
+                .write(&batch.map_err(to_anyhow)?)
+                .map_err(to_anyhow)?;
+        }
+        writer.finish().map_err(to_anyhow)?;
+        let buf = writer.into_inner();
+        if buf.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok::<_, Error>(serde_json::from_slice::<Vec<serde_json::Map<String, Value>>>(&buf)?)
+    })
+    .await
+    .map_err(to_anyhow)??;
+
+    Ok(rows.into_iter().map(archived_job_to_value).collect())
+}
+
+fn archived_job_to_value(mut row: serde_json::Map<String, Value>) -> Value {
+    for field in JSON_FIELDS {
+        if let Some(Value::String(s)) = row.get(field) {
+            let parsed = serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.clone()));
+            row.insert(field.to_string(), parsed);
+        }
+    }
+    if !row.contains_key("labels") {
+        if let Some(labels) = row.get("result").and_then(|r| r.get("wm_labels")).cloned() {
+            row.insert("labels".to_string(), labels);
+        }
+    }
+    row.insert("deleted".to_string(), Value::Bool(false));
+    row.insert("archived".to_string(), Value::Bool(true));
+    row.insert(
+        "type".to_string(),
+        Value::String("CompletedJob".to_string()),
+    );
+    Value::Object(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn row(value: Value) -> serde_json::Map<String, Value> {
+        match value {
+            Value::Object(row) => row,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn archived_labels_are_read_without_the_result() {
+        let job = archived_job_to_value(row(json!({ "labels": "[\"a\",\"b\"]" })));
+        assert_eq!(job["labels"], json!(["a", "b"]));
+        assert!(job.get("result").is_none());
+    }
+
+    #[test]
+    fn archived_labels_fall_back_to_the_result() {
+        let job = archived_job_to_value(row(json!({ "result": "{\"wm_labels\":[\"a\"]}" })));
+        assert_eq!(job["labels"], json!(["a"]));
+        assert_eq!(job["result"], json!({ "wm_labels": ["a"] }));
+    }
+}
@@ -63,6 +63,99 @@ impl JobKind {
     }
 }
 
+/// When a job that depends on other jobs may run, given how its upstream jobs completed.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[sqlx(type_name = "JOB_DEPENDENCY_CONDITION", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobDependencyCondition {
+    /// all upstream jobs succeeded
+    #[default]
+    Success,
+    /// all upstream jobs failed or were canceled
+    Failure,
+    /// all upstream jobs completed, whatever their outcome
+    Any,
+}
+
+impl JobDependencyCondition {
+    pub fn is_met_by(&self, success: bool) -> bool {
+        match self {
+            JobDependencyCondition::Success => success,
+            JobDependencyCondition::Failure => !success,
+            JobDependencyCondition::Any => true,
+        }
+    }
+}
+
+/// Level of a structured log line, ordered from the least to the most severe
+#[derive(
+    sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone,
+)]
+#[sqlx(type_name = "JOB_LOG_LEVEL", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobLogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl JobLogLevel {
+    pub fn parse(level: &str) -> Option<JobLogLevel> {
+        match level.to_lowercase().as_str() {
+            "debug" | "trace" => Some(JobLogLevel::Debug),
+            "info" | "notice" => Some(JobLogLevel::Info),
+            "warning" | "warn" => Some(JobLogLevel::Warning),
+            "error" | "err" => Some(JobLogLevel::Error),
+            "critical" | "fatal" | "panic" => Some(JobLogLevel::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobLogLevel::Debug => "debug",
+            JobLogLevel::Info => "info",
+            JobLogLevel::Warning => "warning",
+            JobLogLevel::Error => "error",
+            JobLogLevel::Critical => "critical",
+        }
+    }
+}
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[sqlx(type_name = "JOB_LOG_STREAM", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum JobLogStream {
+    Stdout,
+    Stderr,
+}
+
+impl JobLogStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobLogStream::Stdout => "stdout",
+            JobLogStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// A log line of a job stored as a row, in addition to the plain text logs, when the worker
+/// parses structured logs
+#[derive(sqlx::FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct JobLogLine {
+    pub job_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_id: Option<String>,
+    pub ts: chrono::DateTime<chrono::Utc>,
+    /// None for the lines in which no level could be found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<JobLogLevel>,
+    pub stream: JobLogStream,
+    pub message: String,
+}
+
 #[derive(sqlx::FromRow, Debug, Serialize, Clone)]
 pub struct QueuedJob {
     pub workspace_id: String,
@@ -346,6 +439,11 @@ pub enum JobPayload {
         path: Option<String>,
         restarted_from: Option<RestartedFrom>,
     },
+    ReplayedFlow {
+        completed_job_id: Uuid,
+        value: FlowValue,
+        path: Option<String>,
+    },
     SingleScriptFlow {
         path: String,
         hash: ScriptHash,
@@ -30,6 +30,7 @@ pub mod auth;
 #[cfg(feature = "benchmark")]
 pub mod bench;
 pub mod cache;
+pub mod circuit_breaker;
 pub mod db;
 pub mod ee;
 pub mod email_ee;
@@ -39,6 +40,8 @@ pub mod flow_status;
 pub mod flows;
 pub mod global_settings;
 pub mod indexer;
+#[cfg(feature = "parquet")]
+pub mod job_archive;
 pub mod job_metrics;
 #[cfg(feature = "parquet")]
 pub mod job_s3_helpers_ee;
@@ -49,6 +52,7 @@ pub mod more_serde;
 pub mod oauth2;
 pub mod otel_ee;
 pub mod queue;
+pub mod rate_limit;
 pub mod s3_helpers;
 pub mod schedule;
 pub mod schema;
@@ -124,6 +128,8 @@ lazy_static::lazy_static! {
 
     pub static ref MONITOR_LOGS_ON_OBJECT_STORE: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
 
+    pub static ref JOB_ARCHIVE_ON_OBJECT_STORE: Arc<RwLock<bool>> = Arc::new(RwLock::new(false));
+
     pub static ref INSTANCE_NAME: String = rd_string(5);
 
     pub static ref DEPLOYED_SCRIPT_HASH_CACHE: Cache<(String, String), ExpiringLatestVersionId> = Cache::new(1000);
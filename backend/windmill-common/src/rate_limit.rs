@@ -0,0 +1,30 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// Token-bucket rate limit applied to the jobs whose concurrency key is `key`, i.e. the
+/// interpolated custom concurrency key of the script, flow or flow step, or by default
+/// `<workspace>/script/<path>` and `<workspace>/flow/<path>`.
+#[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
+pub struct RateLimit {
+    pub workspace_id: String,
+    pub key: String,
+    /// tokens refilled per second
+    pub rate: f64,
+    /// size of the bucket, i.e. how many jobs can start at once after an idle period
+    pub burst: i32,
+    /// theoretical arrival time of the next job: the bucket is full again once it is
+    /// `burst / rate` seconds in the past
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tat: Option<DateTime<Utc>>,
+    pub edited_by: String,
+    pub edited_at: DateTime<Utc>,
+}
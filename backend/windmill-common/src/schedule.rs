@@ -12,6 +12,24 @@ use sqlx::FromRow;
 
 use crate::flows::Retry;
 
+/// What to do with the cron ticks a schedule missed while it was disabled, paused or while
+/// no server was around to push its next job.
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[sqlx(type_name = "SCHEDULE_CATCHUP_POLICY", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CatchupPolicy {
+    /// Missed ticks are dropped, only the next upcoming tick is scheduled
+    #[default]
+    Skip,
+    /// Only the most recent missed tick is run
+    Latest,
+    /// Every missed tick is run, up to `catchup_max_ticks` of the most recent ones
+    All,
+}
+
+/// Upper bound on the number of jobs a single catch-up or backfill can push
+pub const MAX_CATCHUP_TICKS: usize = 1000;
+
 #[derive(FromRow, Serialize, Deserialize, Debug, Clone)]
 pub struct Schedule {
     pub workspace_id: String,
@@ -60,14 +78,145 @@ pub struct Schedule {
     pub paused_until: Option<DateTime<chrono::Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cron_version: Option<String>,
+    #[serde(default)]
+    pub catchup_policy: CatchupPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub catchup_max_ticks: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_tick_at: Option<DateTime<chrono::Utc>>,
 }
 
 impl Schedule {
     pub fn parse_retry(self) -> Option<Retry> {
         self.retry.map(|r| serde_json::from_value(r).ok()).flatten()
     }
+
+    /// Maximum number of missed ticks to run when catching up
+    pub fn catchup_limit(&self) -> usize {
+        match self.catchup_policy {
+            CatchupPolicy::Skip => 0,
+            CatchupPolicy::Latest => 1,
+            CatchupPolicy::All => self
+                .catchup_max_ticks
+                .map(|n| n.max(0) as usize)
+                .unwrap_or(MAX_CATCHUP_TICKS)
+                .min(MAX_CATCHUP_TICKS),
+        }
+    }
 }
 
 pub fn schedule_to_user(path: &str) -> String {
     format!("schedule-{}", path.replace('/', "-"))
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::utils::ScheduleType;
+
+    fn schedule_with_policy(policy: &str, max_ticks: Option<i32>) -> Schedule {
+        serde_json::from_value(json!({
+            "workspace_id": "test-workspace",
+            "path": "f/test/schedule",
+            "edited_by": "test-user",
+            "edited_at": "2025-01-01T00:00:00Z",
+            "schedule": "* * * * * *",
+            "timezone": "UTC",
+            "enabled": true,
+            "script_path": "f/test/script",
+            "is_flow": false,
+            "extra_perms": {},
+            "email": "test@windmill.dev",
+            "ws_error_handler_muted": false,
+            "no_flow_overlap": false,
+            "catchup_policy": policy,
+            "catchup_max_ticks": max_ticks,
+        }))
+        .unwrap()
+    }
+
+    fn every_minute() -> ScheduleType {
+        ScheduleType::from_str("0 * * * * *", None, false).unwrap()
+    }
+
+    #[test]
+    fn test_events_between_bounds() {
+        let after = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2025, 1, 1, 10, 5, 0).unwrap();
+        let events = every_minute().events_between(chrono_tz::UTC, after, until, 10, 100);
+        // exclusive on `after`, inclusive on `until`
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[0], Utc.with_ymd_and_hms(2025, 1, 1, 10, 1, 0).unwrap());
+        assert_eq!(events[4], until);
+    }
+
+    #[test]
+    fn test_events_between_keeps_most_recent() {
+        let after = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2025, 1, 1, 10, 5, 0).unwrap();
+        let events = every_minute().events_between(chrono_tz::UTC, after, until, 2, 100);
+        assert_eq!(
+            events,
+            vec![
+                Utc.with_ymd_and_hms(2025, 1, 1, 10, 4, 0).unwrap(),
+                Utc.with_ymd_and_hms(2025, 1, 1, 10, 5, 0).unwrap(),
+            ]
+        );
+        assert!(every_minute()
+            .events_between(chrono_tz::UTC, after, until, 0, 100)
+            .is_empty());
+        assert!(every_minute()
+            .events_between(chrono_tz::UTC, until, after, 10, 100)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_events_between_max_scan() {
+        let after = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        let until = Utc.with_ymd_and_hms(2025, 1, 1, 11, 0, 0).unwrap();
+        let events = every_minute().events_between(chrono_tz::UTC, after, until, 100, 3);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2], Utc.with_ymd_and_hms(2025, 1, 1, 10, 3, 0).unwrap());
+    }
+
+    #[test]
+    fn test_catchup_limit() {
+        assert_eq!(schedule_with_policy("skip", Some(10)).catchup_limit(), 0);
+        assert_eq!(schedule_with_policy("latest", Some(10)).catchup_limit(), 1);
+        assert_eq!(schedule_with_policy("all", Some(10)).catchup_limit(), 10);
+        assert_eq!(schedule_with_policy("all", Some(-1)).catchup_limit(), 0);
+        assert_eq!(
+            schedule_with_policy("all", None).catchup_limit(),
+            MAX_CATCHUP_TICKS
+        );
+        assert_eq!(
+            schedule_with_policy("all", Some(5 * MAX_CATCHUP_TICKS as i32)).catchup_limit(),
+            MAX_CATCHUP_TICKS
+        );
+    }
+
+    #[test]
+    fn test_catchup_capped_to_max_ticks() {
+        let schedule = schedule_with_policy("all", Some(5 * MAX_CATCHUP_TICKS as i32));
+        let sched = ScheduleType::from_str(&schedule.schedule, None, false).unwrap();
+        let after = Utc.with_ymd_and_hms(2025, 1, 1, 10, 0, 0).unwrap();
+        // every second for two hours, far more ticks than the cap
+        let until = Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap();
+        let missed = sched.events_between(
+            chrono_tz::UTC,
+            after,
+            until,
+            schedule.catchup_limit(),
+            100_000,
+        );
+        assert_eq!(missed.len(), MAX_CATCHUP_TICKS);
+        assert_eq!(missed[MAX_CATCHUP_TICKS - 1], until);
+        assert_eq!(
+            missed[0],
+            until - chrono::Duration::seconds(MAX_CATCHUP_TICKS as i64 - 1)
+        );
+    }
+}
@@ -347,9 +347,9 @@ pub fn should_validate_schema(code: &str, lang: &ScriptLang) -> bool {
     let annotation = "schema_validation";
     use ScriptLang::*;
     let comment = match lang {
-        Nativets | Bun | Bunnative | Deno | Php | CSharp | Java => "//",
-        Python3 | Go | Bash | Powershell | Graphql | Ansible | Nu => "#",
-        Postgresql | Mysql | Bigquery | Snowflake | Mssql | OracleDB => "--",
+        Nativets | Bun | Bunnative | Deno | Php | CSharp | Java | Wasm => "//",
+        Python3 | Go | Bash | Powershell | Graphql | Ansible | Nu | Ruby | R => "#",
+        Postgresql | Mysql | Bigquery | Snowflake | Mssql | OracleDB | Duckdb | Lua => "--",
         Rust => "//!",
         // for related places search: ADD_NEW_LANG
     };
@@ -52,6 +52,11 @@ pub enum ScriptLang {
     CSharp,
     Nu,
     Java,
+    Duckdb,
+    Wasm,
+    Lua,
+    Ruby,
+    R,
     // for related places search: ADD_NEW_LANG
 }
 
@@ -79,6 +84,11 @@ impl ScriptLang {
             ScriptLang::CSharp => "csharp",
             ScriptLang::Nu => "nu",
             ScriptLang::Java => "java",
+            ScriptLang::Duckdb => "duckdb",
+            ScriptLang::Wasm => "wasm",
+            ScriptLang::Lua => "lua",
+            ScriptLang::Ruby => "ruby",
+            ScriptLang::R => "r",
             // for related places search: ADD_NEW_LANG
         }
     }
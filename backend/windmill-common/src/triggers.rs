@@ -44,6 +44,14 @@ pub enum TriggerKind {
 }
 
 impl TriggerKind {
+    /// Whether the messages of the triggers of this kind resume the flows waiting on them
+    pub fn can_resume_flows(&self) -> bool {
+        matches!(
+            self,
+            TriggerKind::Http | TriggerKind::Websocket | TriggerKind::Mqtt | TriggerKind::Postgres
+        )
+    }
+
     pub fn to_key(&self) -> String {
         match self {
             TriggerKind::Webhook => "webhook".to_string(),
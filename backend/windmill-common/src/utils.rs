@@ -667,6 +667,36 @@ impl ScheduleType {
 
         Ok(events)
     }
+
+    /// Returns the last `limit` events strictly after `after` and up to `until` (inclusive),
+    /// in chronological order. At most `max_scan` events are enumerated so that a very
+    /// frequent schedule with an old `after` cannot stall the caller.
+    pub fn events_between(
+        &self,
+        tz: chrono_tz::Tz,
+        after: chrono::DateTime<Utc>,
+        until: chrono::DateTime<Utc>,
+        limit: usize,
+        max_scan: usize,
+    ) -> Vec<chrono::DateTime<Utc>> {
+        if limit == 0 {
+            return vec![];
+        }
+        let mut events = std::collections::VecDeque::with_capacity(limit.min(max_scan));
+        let mut cursor = after.with_timezone(&tz);
+        for _ in 0..max_scan {
+            let next = self.find_next(&cursor);
+            if next.with_timezone(&Utc) > until {
+                break;
+            }
+            if events.len() == limit {
+                events.pop_front();
+            }
+            events.push_back(next.with_timezone(&Utc));
+            cursor = next;
+        }
+        events.into()
+    }
 }
 
 use std::future::Future;
@@ -52,6 +52,13 @@ lazy_static::lazy_static! {
         }
     });
 
+    /// wait for the notifications of the jobs ready on the worker tags instead of polling every
+    /// `SLEEP_QUEUE`. Must also be set on the servers pushing jobs, which only notify with it.
+    pub static ref QUEUE_LISTEN: bool = std::env::var("QUEUE_LISTEN")
+        .ok()
+        .and_then(|x| x.parse::<bool>().ok())
+        .unwrap_or(false);
+
     pub static ref NO_LOGS: bool = std::env::var("NO_LOGS").ok().is_some_and(|x| x == "1" || x == "true");
 
     pub static ref CGROUP_V2_PATH_RE: Regex = Regex::new(r#"(?m)^0::(/.*)$"#).unwrap();
@@ -79,6 +86,11 @@ lazy_static::lazy_static! {
         "csharp".to_string(),
         "nu".to_string(),
         "java".to_string(),
+        "duckdb".to_string(),
+        "wasm".to_string(),
+        "lua".to_string(),
+        "ruby".to_string(),
+        "r".to_string(),
         // for related places search: ADD_NEW_LANG
         "dependency".to_string(),
         "flow".to_string(),
@@ -107,6 +119,8 @@ lazy_static::lazy_static! {
         additional_python_paths: Default::default(),
         pip_local_dependencies: Default::default(),
         env_vars: Default::default(),
+        fair_share: Default::default(),
+        resource_limits: Default::default(),
     }));
 
     pub static ref WORKER_PULL_QUERIES: Arc<RwLock<Vec<String>>> = Arc::new(RwLock::new(vec![]));
@@ -311,6 +325,7 @@ pub fn make_pull_query(tags: &[String]) -> String {
         "SELECT id
         FROM v2_job_queue
         WHERE running = false AND tag IN ({}) AND scheduled_for <= now()
+            AND blocked_by_dependencies = false
         ORDER BY priority DESC NULLS LAST, scheduled_for
         FOR UPDATE SKIP LOCKED
         LIMIT 1",
@@ -318,6 +333,118 @@ pub fn make_pull_query(tags: &[String]) -> String {
     ))
 }
 
+/// How many of the next jobs in queue order [`make_fair_share_pull_query`] ranks by share, so
+/// that a pull does not sort the whole queue of the tags
+pub const FAIR_SHARE_CANDIDATES: u32 = 1000;
+
+/// Same as [`make_pull_query`] but among the next `FAIR_SHARE_CANDIDATES` jobs of the same
+/// priority, prefers the jobs of the workspace or user whose share in `fair_share` is the lowest.
+/// The shares of the worker group are computed by the monitor with [`refresh_fair_shares`].
+pub fn make_fair_share_pull_query(
+    tags: &[String],
+    worker_group: &str,
+    fair_share: &FairShareConfig,
+) -> String {
+    let key = match fair_share.by {
+        FairShareKey::Workspace => "j.workspace_id",
+        FairShareKey::User => "j.permissioned_as",
+    };
+    format_pull_query(format!(
+        "WITH candidate AS (
+            SELECT id
+            FROM v2_job_queue
+            WHERE running = false AND tag IN ({tags}) AND scheduled_for <= now()
+                AND blocked_by_dependencies = false
+            ORDER BY priority DESC NULLS LAST, scheduled_for
+            LIMIT {FAIR_SHARE_CANDIDATES}
+        )
+        SELECT q.id
+        FROM candidate JOIN v2_job_queue q ON q.id = candidate.id JOIN v2_job j ON j.id = q.id
+            LEFT JOIN fair_share s ON s.worker_group = '{worker_group}' AND s.key = {key}
+        WHERE q.running = false
+        ORDER BY q.priority DESC NULLS LAST, COALESCE(s.share, 0), q.scheduled_for
+        FOR UPDATE OF q SKIP LOCKED
+        LIMIT 1",
+        worker_group = worker_group.replace('\'', "''"),
+        tags = tags.iter().map(|x| format!("'{x}'")).join(", ")
+    ))
+}
+
+/// Refreshes the shares of the worker group in `fair_share`: the worker time of each workspace or
+/// user over the window, completed jobs from `fair_share_usage` and running ones, divided by its
+/// weight. Workspaces and users without usage have no row and a share of 0.
+pub async fn refresh_fair_shares(
+    db: &DB,
+    worker_group: &str,
+    fair_share: &FairShareConfig,
+) -> error::Result<()> {
+    let (key, usage_key) = match fair_share.by {
+        FairShareKey::Workspace => ("j.workspace_id", "workspace_id"),
+        FairShareKey::User => ("j.permissioned_as", "permissioned_as"),
+    };
+    let (keys, weights): (Vec<String>, Vec<i64>) = fair_share
+        .weights
+        .iter()
+        .map(|(k, w)| (k.clone(), (*w).max(1) as i64))
+        .unzip();
+    // upserted in key order and stale keys deleted after, so that the concurrent refreshes of
+    // the monitors of several servers neither conflict nor deadlock
+    let mut tx = db.begin().await?;
+    let refreshed = sqlx::query_scalar::<_, String>(&format!(
+        "WITH weight AS (
+            SELECT * FROM UNNEST($2::VARCHAR[], $3::BIGINT[]) AS w (key, weight)
+        ),
+        usage AS (
+            SELECT key, SUM(ms) AS ms FROM (
+                SELECT {usage_key} AS key, SUM(duration_ms) AS ms
+                FROM fair_share_usage
+                WHERE bucket > now() - make_interval(secs => $4)
+                GROUP BY 1
+                UNION ALL
+                SELECT {key} AS key,
+                    SUM(EXTRACT(EPOCH FROM now() - GREATEST(q.started_at, now() - make_interval(secs => $4))) * 1000) AS ms
+                FROM v2_job_queue q JOIN v2_job j ON j.id = q.id
+                WHERE q.running = true AND q.started_at IS NOT NULL
+                GROUP BY 1
+            ) u GROUP BY key
+        )
+        INSERT INTO fair_share (worker_group, key, share)
+        SELECT $1, usage.key, usage.ms::FLOAT8 / COALESCE(weight.weight, 1)
+        FROM usage LEFT JOIN weight ON weight.key = usage.key
+        ORDER BY usage.key
+        ON CONFLICT (worker_group, key) DO UPDATE SET share = EXCLUDED.share
+        RETURNING key"
+    ))
+    .bind(worker_group)
+    .bind(keys)
+    .bind(weights)
+    .bind(fair_share.window_s.clamp(1, FAIR_SHARE_USAGE_RETENTION_S) as f64)
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query("DELETE FROM fair_share WHERE worker_group = $1 AND key <> ALL($2)")
+        .bind(worker_group)
+        .bind(&refreshed)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Channel notified with `QUEUE_LISTEN` when a job is ready to be pulled on `tag`, by `push` or
+/// when its dependencies are released. Postgres limits channel names to 63 bytes, longer tags are
+/// truncated which at worst wakes up unrelated workers.
+pub fn queue_notify_channel(tag: &str) -> String {
+    let mut channel = format!("queue_{tag}");
+    if channel.len() > 63 {
+        let mut end = 63;
+        while !channel.is_char_boundary(end) {
+            end -= 1;
+        }
+        channel.truncate(end);
+    }
+    channel
+}
+
 pub async fn store_pull_query(wc: &WorkerConfig) {
     let mut queries = vec![];
     for tags in wc.priority_tags_sorted.iter() {
@@ -325,7 +452,11 @@ pub async fn store_pull_query(wc: &WorkerConfig) {
             tracing::error!("Empty tags in priority tags, skipping");
             continue;
         }
-        let query = make_pull_query(&tags.tags);
+        let query = if let Some(fair_share) = wc.fair_share.as_ref() {
+            make_fair_share_pull_query(&tags.tags, &WORKER_GROUP, fair_share)
+        } else {
+            make_pull_query(&tags.tags)
+        };
         queries.push(query);
     }
     let mut l = WORKER_PULL_QUERIES.write().await;
@@ -540,6 +671,41 @@ pub struct SqlAnnotations {
 pub struct BashAnnotations {
     pub docker: bool,
 }
+
+#[annotations("//")]
+pub struct WasmAnnotations {
+    pub go: bool,
+}
+
+/// Precompiled components are stored as the content of the script, base64 encoded behind this prefix
+pub const WASM_COMPONENT_PREFIX: &str = "data:application/wasm;base64,";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WasmSource {
+    Component,
+    Rust,
+    Go,
+}
+
+impl WasmSource {
+    /// Go sources are recognized by their `package` clause or by a `// go` annotation,
+    /// anything else that is not a precompiled component is built as Rust
+    pub fn of(code: &str) -> Self {
+        if code.trim_start().starts_with(WASM_COMPONENT_PREFIX) {
+            WasmSource::Component
+        } else if WasmAnnotations::parse(code).go
+            || code
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty() && !l.starts_with("//"))
+                .is_some_and(|l| l.starts_with("package "))
+        {
+            WasmSource::Go
+        } else {
+            WasmSource::Rust
+        }
+    }
+}
 /// length = 5
 /// value  = "foo"
 /// output = "foo  "
@@ -760,7 +926,7 @@ fn write_binary_file(main_path: &str, byts: &mut bytes::Bytes) -> error::Result<
     Ok(())
 }
 
-fn get_cgroupv2_path() -> Option<String> {
+pub fn get_cgroupv2_path() -> Option<String> {
     let cgroup_path: String = parse_file("/proc/self/cgroup")?;
 
     CGROUP_V2_PATH_RE
@@ -1422,6 +1588,8 @@ pub async fn load_worker_config(
             .additional_python_paths
             .or_else(|| load_additional_python_paths_from_env()),
         env_vars: resolved_env_vars,
+        fair_share: config.fair_share,
+        resource_limits: config.resource_limits,
     })
 }
 
@@ -1488,6 +1656,8 @@ pub struct WorkerConfigOpt {
     pub pip_local_dependencies: Option<Vec<String>>,
     pub env_vars_static: Option<HashMap<String, String>>,
     pub env_vars_allowlist: Option<Vec<String>>,
+    pub fair_share: Option<FairShareConfig>,
+    pub resource_limits: Option<ResourceLimitsConfig>,
 }
 
 impl Default for WorkerConfigOpt {
@@ -1502,6 +1672,8 @@ impl Default for WorkerConfigOpt {
             pip_local_dependencies: Default::default(),
             env_vars_static: Default::default(),
             env_vars_allowlist: Default::default(),
+            fair_share: Default::default(),
+            resource_limits: Default::default(),
         }
     }
 }
@@ -1516,12 +1688,91 @@ pub struct WorkerConfig {
     pub additional_python_paths: Option<Vec<String>>,
     pub pip_local_dependencies: Option<Vec<String>>,
     pub env_vars: HashMap<String, String>,
+    pub fair_share: Option<FairShareConfig>,
+    pub resource_limits: Option<ResourceLimitsConfig>,
 }
 
 impl std::fmt::Debug for WorkerConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "WorkerConfig {{ worker_tags: {:?}, priority_tags_sorted: {:?}, dedicated_worker: {:?}, init_bash: {:?}, cache_clear: {:?}, additional_python_paths: {:?}, pip_local_dependencies: {:?}, env_vars: {:?} }}", 
-        self.worker_tags, self.priority_tags_sorted, self.dedicated_worker, self.init_bash, self.cache_clear, self.additional_python_paths, self.pip_local_dependencies, self.env_vars.iter().map(|(k, v)| format!("{}: {}{} ({} chars)", k, &v[..3.min(v.len())], "***", v.len())).collect::<Vec<String>>().join(", "))
+        write!(f, "WorkerConfig {{ worker_tags: {:?}, priority_tags_sorted: {:?}, dedicated_worker: {:?}, init_bash: {:?}, cache_clear: {:?}, additional_python_paths: {:?}, pip_local_dependencies: {:?}, env_vars: {:?}, fair_share: {:?}, resource_limits: {:?} }}", 
+        self.worker_tags, self.priority_tags_sorted, self.dedicated_worker, self.init_bash, self.cache_clear, self.additional_python_paths, self.pip_local_dependencies, self.env_vars.iter().map(|(k, v)| format!("{}: {}{} ({} chars)", k, &v[..3.min(v.len())], "***", v.len())).collect::<Vec<String>>().join(", "), self.fair_share, self.resource_limits)
+    }
+}
+
+/// Fair share of the workers of a group between the workspaces or users pushing jobs to its tags
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FairShareConfig {
+    #[serde(default)]
+    pub by: FairShareKey,
+    /// relative share of each workspace id or `permissioned_as` (e.g. `u/admin`), 1 by default
+    #[serde(default)]
+    pub weights: HashMap<String, u32>,
+    /// worker time older than this is not taken into account, in seconds, at most one day
+    #[serde(default = "default_fair_share_window_s")]
+    pub window_s: u32,
+}
+
+/// How long the monitor keeps the worker time aggregated in `fair_share_usage`, in seconds
+pub const FAIR_SHARE_USAGE_RETENTION_S: u32 = 86400;
+
+fn default_fair_share_window_s() -> u32 {
+    3600
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FairShareKey {
+    #[default]
+    Workspace,
+    User,
+}
+
+/// Limits applied to the processes of a job through a cgroup v2 of its own
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Copy, Default)]
+pub struct ResourceLimits {
+    /// memory.max, in MB
+    pub memory_max_mb: Option<u64>,
+    /// cpu.max as a share of one cpu, 1.0 being a full cpu
+    pub cpu_quota: Option<f64>,
+    /// pids.max
+    pub pids_max: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory_max_mb.is_none() && self.cpu_quota.is_none() && self.pids_max.is_none()
+    }
+
+    /// Each limit of `self`, falling back to the one of `other`
+    pub fn or(self, other: ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            memory_max_mb: self.memory_max_mb.or(other.memory_max_mb),
+            cpu_quota: self.cpu_quota.or(other.cpu_quota),
+            pids_max: self.pids_max.or(other.pids_max),
+        }
+    }
+}
+
+/// Resource limits of the jobs run by a worker group. The limits of the script path take
+/// precedence over the ones of the tag, which take precedence over the default ones.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ResourceLimitsConfig {
+    #[serde(default)]
+    pub default: ResourceLimits,
+    #[serde(default)]
+    pub per_tag: HashMap<String, ResourceLimits>,
+    /// keyed by script path, e.g. `f/etl/import`
+    #[serde(default)]
+    pub per_script: HashMap<String, ResourceLimits>,
+}
+
+impl ResourceLimitsConfig {
+    pub fn limits_for(&self, tag: &str, script_path: Option<&str>) -> ResourceLimits {
+        let per_script = script_path
+            .and_then(|p| self.per_script.get(p).copied())
+            .unwrap_or_default();
+        let per_tag = self.per_tag.get(tag).copied().unwrap_or_default();
+        per_script.or(per_tag).or(self.default)
     }
 }
 
@@ -1540,3 +1791,46 @@ pub fn to_raw_value_owned(result: serde_json::Value) -> Box<RawValue> {
     serde_json::value::to_raw_value(&result)
         .unwrap_or_else(|_| RawValue::from_string("{}".to_string()).unwrap())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_resource_limits_precedence() {
+        let config: ResourceLimitsConfig = serde_json::from_value(json!({
+            "default": { "memory_max_mb": 512, "cpu_quota": 1.0, "pids_max": 100 },
+            "per_tag": { "heavy": { "memory_max_mb": 2048, "cpu_quota": 2.0 } },
+            "per_script": { "f/etl/import": { "memory_max_mb": 4096 } },
+        }))
+        .unwrap();
+
+        let limits = |memory_max_mb, cpu_quota, pids_max| ResourceLimits {
+            memory_max_mb: Some(memory_max_mb),
+            cpu_quota: Some(cpu_quota),
+            pids_max: Some(pids_max),
+        };
+
+        // the script limits win over the tag ones, which win over the default ones
+        assert_eq!(
+            config.limits_for("heavy", Some("f/etl/import")),
+            limits(4096, 2.0, 100)
+        );
+        assert_eq!(
+            config.limits_for("default", Some("f/etl/import")),
+            limits(4096, 1.0, 100)
+        );
+        assert_eq!(
+            config.limits_for("heavy", Some("f/other")),
+            limits(2048, 2.0, 100)
+        );
+        assert_eq!(config.limits_for("heavy", None), limits(2048, 2.0, 100));
+        assert_eq!(config.limits_for("default", None), limits(512, 1.0, 100));
+
+        assert!(ResourceLimitsConfig::default()
+            .limits_for("heavy", Some("f/etl/import"))
+            .is_empty());
+    }
+}
@@ -0,0 +1,348 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Circuit breakers keyed by the path of a script/flow or of a resource passed as argument.
+//!
+//! Failures of scripts and flows, including the steps of flows and their inline scripts, are
+//! counted per breaker within a sliding window. Once the threshold
+//! is reached the breaker opens and the jobs pushed for its key are short-circuited, either
+//! failed fast or delayed, until the cooldown is over. The first job pushed after that is let
+//! through as a probe (half-open): its success closes the breaker, its failure re-opens it.
+//! A probe that has not completed within another cooldown is replaced by the next job pushed.
+//! Delayed jobs check their breakers again when they are pulled, and are delayed again while
+//! the breaker is still open or another job is its probe. A short-circuited flow step fails or
+//! waits like any other step, so the flow applies its retries and error handler.
+//! The jobs of the workspaces without any breaker skip all of this, see `HAS_CIRCUIT_BREAKERS`.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde_json::value::RawValue;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+use windmill_common::{
+    circuit_breaker::{
+        CircuitBreaker, CircuitBreakerAction, CircuitBreakerKind, CircuitBreakerState,
+    },
+    error::{Error, Result},
+    jobs::JobKind,
+    DB,
+};
+
+use crate::{workspace_flag::WorkspaceFlag, MiniPulledJob};
+
+lazy_static::lazy_static! {
+    static ref HAS_CIRCUIT_BREAKERS: WorkspaceFlag = WorkspaceFlag::new();
+}
+
+#[cfg(feature = "prometheus")]
+lazy_static::lazy_static! {
+    static ref CIRCUIT_BREAKER_STATE: prometheus::IntGaugeVec = prometheus::register_int_gauge_vec!(
+        "circuit_breaker_state",
+        "State of the circuit breakers: 0 closed, 1 open, 2 half open.",
+        &["workspace_id", "kind", "path"]
+    )
+    .unwrap();
+}
+
+/// Whether the workspace has circuit breakers, cached
+async fn has_circuit_breakers<'c, E: sqlx::PgExecutor<'c>>(e: E, w_id: &str) -> Result<bool> {
+    if let Some(has) = HAS_CIRCUIT_BREAKERS.get(w_id) {
+        return Ok(has);
+    }
+    let has = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM circuit_breaker WHERE workspace_id = $1)",
+    )
+    .bind(w_id)
+    .fetch_one(e)
+    .await?;
+    HAS_CIRCUIT_BREAKERS.set(w_id, has);
+    Ok(has)
+}
+
+/// To be called once the breakers of the workspace were created or deleted
+pub fn invalidate_has_circuit_breakers(w_id: &str) {
+    HAS_CIRCUIT_BREAKERS.invalidate(w_id);
+}
+
+/// Whether the breakers apply to the jobs of this kind: scripts and flows, root or steps of a
+/// flow, and the inline scripts of flows, which may be passed resources with breakers
+pub fn uses_circuit_breakers(kind: JobKind) -> bool {
+    matches!(kind, JobKind::Script | JobKind::Flow | JobKind::FlowScript)
+}
+
+/// How a job pushed while one of its circuit breakers is open is short-circuited
+pub enum ShortCircuit {
+    Fail(Error),
+    Delay(DateTime<Utc>),
+}
+
+/// Paths of the resources passed to a job as top-level `$res:` arguments
+pub fn resource_args(args: &HashMap<String, Box<RawValue>>) -> Vec<String> {
+    args.values()
+        .filter_map(|v| serde_json::from_str::<String>(v.get()).ok())
+        .filter_map(|v| v.strip_prefix("$res:").map(str::to_string))
+        .collect()
+}
+
+pub fn set_circuit_breaker_metric(
+    _w_id: &str,
+    _kind: CircuitBreakerKind,
+    _path: &str,
+    _state: CircuitBreakerState,
+) {
+    #[cfg(feature = "prometheus")]
+    if windmill_common::METRICS_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+        let kind = match _kind {
+            CircuitBreakerKind::Script => "script",
+            CircuitBreakerKind::Resource => "resource",
+        };
+        CIRCUIT_BREAKER_STATE
+            .with_label_values(&[_w_id, kind, _path])
+            .set(match _state {
+                CircuitBreakerState::Closed => 0,
+                CircuitBreakerState::Open => 1,
+                CircuitBreakerState::HalfOpen => 2,
+            });
+    }
+}
+
+/// Checks the breakers of a job about to be pushed. Returns how the job must be short-circuited
+/// if one of them is open, otherwise lets the job through, making it the probe of the breakers
+/// whose cooldown is over.
+pub async fn check_circuit_breakers(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    script_path: Option<&str>,
+    resources: &[String],
+    job_id: Uuid,
+) -> Result<Option<ShortCircuit>> {
+    if !has_circuit_breakers(&mut **tx, w_id).await? {
+        return Ok(None);
+    }
+    check_circuit_breakers_inner(tx, w_id, script_path, resources, job_id, false).await
+}
+
+/// Checks again the breakers that delay a pulled job. Returns when the job must be
+/// re-scheduled for if one of them is still open or has another probe, otherwise lets the job
+/// through, making it the probe of the breakers whose cooldown is over.
+pub async fn recheck_delayed_job(db: &DB, job: &MiniPulledJob) -> Result<Option<DateTime<Utc>>> {
+    if !has_circuit_breakers(db, &job.workspace_id).await? {
+        return Ok(None);
+    }
+    let resources = job
+        .args
+        .as_ref()
+        .map(|args| resource_args(&args.0))
+        .unwrap_or_default();
+    let mut tx = db.begin().await?;
+    let short_circuit = check_circuit_breakers_inner(
+        &mut tx,
+        &job.workspace_id,
+        job.runnable_path.as_deref(),
+        &resources,
+        job.id,
+        true,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(match short_circuit {
+        Some(ShortCircuit::Delay(until)) => Some(until),
+        _ => None,
+    })
+}
+
+async fn check_circuit_breakers_inner(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    script_path: Option<&str>,
+    resources: &[String],
+    job_id: Uuid,
+    delay_only: bool,
+) -> Result<Option<ShortCircuit>> {
+    let breakers = sqlx::query_as!(
+        CircuitBreaker,
+        r#"SELECT workspace_id, kind AS "kind: _", path, failure_threshold, window_s, cooldown_s,
+            on_open AS "on_open: _", state AS "state: _", failure_count, window_start, opened_at,
+            probe_job, probe_started_at, edited_by, edited_at
+        FROM circuit_breaker
+        WHERE workspace_id = $1 AND state != 'closed'
+            AND ((kind = 'script' AND path = $2) OR (kind = 'resource' AND path = ANY($3)))
+            AND (NOT $4 OR on_open = 'delay') AND probe_job IS DISTINCT FROM $5
+        FOR UPDATE"#,
+        w_id,
+        script_path,
+        resources,
+        delay_only,
+        job_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    if breakers.is_empty() {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let mut fail = None;
+    let mut delay_until: Option<DateTime<Utc>> = None;
+    let mut probes = vec![];
+    for breaker in breakers.iter() {
+        let until = match breaker.state {
+            CircuitBreakerState::HalfOpen => breaker.probe_expires_at(),
+            _ => breaker.reopens_at(),
+        }
+        .unwrap_or(now);
+        if until <= now {
+            probes.push(breaker);
+            continue;
+        }
+
+        match breaker.on_open {
+            CircuitBreakerAction::Fail => {
+                fail.get_or_insert_with(|| {
+                    Error::ExecutionErr(format!(
+                        "circuit breaker for {:?} {} is open until {until}, job was not run",
+                        breaker.kind, breaker.path
+                    ))
+                });
+            }
+            CircuitBreakerAction::Delay => {
+                delay_until = Some(delay_until.map_or(until, |d| d.max(until)));
+            }
+        }
+    }
+
+    if let Some(e) = fail {
+        return Ok(Some(ShortCircuit::Fail(e)));
+    }
+    if let Some(until) = delay_until {
+        return Ok(Some(ShortCircuit::Delay(until)));
+    }
+
+    for breaker in probes {
+        sqlx::query!(
+            "UPDATE circuit_breaker SET state = 'half_open', probe_job = $4, probe_started_at = now()
+            WHERE workspace_id = $1 AND kind = $2 AND path = $3",
+            w_id,
+            breaker.kind as CircuitBreakerKind,
+            breaker.path,
+            job_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+        tracing::info!(
+            "circuit breaker for {:?} {} in {w_id} is half open, job {job_id} is the probe",
+            breaker.kind,
+            breaker.path
+        );
+        set_circuit_breaker_metric(
+            w_id,
+            breaker.kind,
+            &breaker.path,
+            CircuitBreakerState::HalfOpen,
+        );
+    }
+    Ok(None)
+}
+
+/// Updates the breakers of a completed job in its completion transaction: counts its failure,
+/// opens the breakers that reached their threshold, and closes or re-opens the breakers it was
+/// the probe of. The resources passed to a flow are counted by the steps they are passed to.
+pub async fn record_circuit_breaker_outcome(
+    tx: &mut Transaction<'_, Postgres>,
+    job: &MiniPulledJob,
+    success: bool,
+) -> Result<()> {
+    let w_id = &job.workspace_id;
+    if !has_circuit_breakers(&mut **tx, w_id).await? {
+        return Ok(());
+    }
+    let script_path = job.runnable_path.as_deref();
+    let resources = match job.kind {
+        JobKind::Flow => vec![],
+        _ => job
+            .args
+            .as_ref()
+            .map(|args| resource_args(&args.0))
+            .unwrap_or_default(),
+    };
+
+    let probed = sqlx::query!(
+        r#"UPDATE circuit_breaker SET
+            state = CASE WHEN $5 THEN 'closed'::CIRCUIT_BREAKER_STATE ELSE 'open'::CIRCUIT_BREAKER_STATE END,
+            opened_at = CASE WHEN $5 THEN NULL ELSE now() END,
+            failure_count = 0,
+            window_start = NULL,
+            probe_job = NULL,
+            probe_started_at = NULL
+        WHERE workspace_id = $1 AND state = 'half_open' AND probe_job = $4
+            AND ((kind = 'script' AND path = $2) OR (kind = 'resource' AND path = ANY($3)))
+        RETURNING kind AS "kind: CircuitBreakerKind", path, state AS "state: CircuitBreakerState""#,
+        w_id,
+        script_path,
+        &resources,
+        job.id,
+        success,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    for b in probed {
+        tracing::info!(
+            "probe job {} of circuit breaker for {:?} {} in {w_id} is done, breaker is now {:?}",
+            job.id,
+            b.kind,
+            b.path,
+            b.state
+        );
+        set_circuit_breaker_metric(w_id, b.kind, &b.path, b.state);
+    }
+
+    // cancelled jobs do not say anything about the health of what they call
+    if success || job.canceled_by.is_some() {
+        return Ok(());
+    }
+
+    let counted = sqlx::query!(
+        r#"UPDATE circuit_breaker SET
+            failure_count = CASE WHEN window_start + make_interval(secs => window_s) >= now()
+                THEN failure_count + 1 ELSE 1 END,
+            window_start = CASE WHEN window_start + make_interval(secs => window_s) >= now()
+                THEN window_start ELSE now() END,
+            state = CASE WHEN (CASE WHEN window_start + make_interval(secs => window_s) >= now()
+                    THEN failure_count + 1 ELSE 1 END) >= failure_threshold
+                THEN 'open'::CIRCUIT_BREAKER_STATE ELSE 'closed'::CIRCUIT_BREAKER_STATE END,
+            opened_at = CASE WHEN (CASE WHEN window_start + make_interval(secs => window_s) >= now()
+                    THEN failure_count + 1 ELSE 1 END) >= failure_threshold
+                THEN now() ELSE NULL END
+        WHERE workspace_id = $1 AND state = 'closed'
+            AND ((kind = 'script' AND path = $2) OR (kind = 'resource' AND path = ANY($3)))
+        RETURNING kind AS "kind: CircuitBreakerKind", path, failure_count,
+            state AS "state: CircuitBreakerState""#,
+        w_id,
+        script_path,
+        &resources,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    let opened = counted
+        .into_iter()
+        .filter(|b| b.state == CircuitBreakerState::Open);
+    for b in opened {
+        tracing::warn!(
+            "circuit breaker for {:?} {} in {w_id} opened after {} failures, last failed job was {}",
+            b.kind,
+            b.path,
+            b.failure_count,
+            job.id
+        );
+        set_circuit_breaker_metric(w_id, b.kind, &b.path, CircuitBreakerState::Open);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,284 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Dependencies between independent jobs, outside of flows.
+//!
+//! A job pushed with upstream jobs is flagged `blocked_by_dependencies`, which the pull queries
+//! skip, with `suspend` set to the number of upstream jobs still to complete. Each completion of an
+//! upstream job decrements it if the condition is met, and the flag is cleared once it reaches 0 so
+//! that the job is pulled as any other job once it is scheduled. Blocked jobs only reserve the
+//! token of their rate limit, if any, once unblocked. If the condition cannot be met anymore, the
+//! job is canceled instead, which in turn releases its own dependents. Either way the workers
+//! listening on the tag of the job are notified. The upstream jobs deleted without completing are
+//! swept by the monitor, which releases their dependents as if they had failed.
+
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+use windmill_common::{
+    error::{Error, Result},
+    jobs::JobDependencyCondition,
+    DB,
+};
+
+use crate::{jobs::notify_queue, rate_limit::reserve_rate_limit_token};
+
+/// Makes the jobs `job_ids`, pushed within `tx`, wait for the completion of the `upstream` jobs.
+/// Fails if an upstream job does not exist or already completed without meeting the condition.
+pub async fn add_job_dependencies(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    job_ids: &[Uuid],
+    upstream: &[Uuid],
+    condition: JobDependencyCondition,
+) -> Result<()> {
+    let pending = pending_upstream_jobs(tx, w_id, job_ids, upstream, condition).await?;
+    block_jobs(tx, w_id, job_ids, &pending, condition, None).await
+}
+
+/// The `upstream` jobs that the jobs `job_ids` about to be pushed within `tx` have to wait for,
+/// the ones still queued. Fails if an upstream job does not exist or already completed without
+/// meeting the condition.
+pub async fn pending_upstream_jobs(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    job_ids: &[Uuid],
+    upstream: &[Uuid],
+    condition: JobDependencyCondition,
+) -> Result<Vec<Uuid>> {
+    if upstream.is_empty() || job_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    if let Some(id) = upstream.iter().find(|id| job_ids.contains(id)) {
+        return Err(Error::BadRequest(format!(
+            "job {id} cannot depend on itself"
+        )));
+    }
+
+    // lock the upstream jobs still queued so that they cannot complete before the dependencies
+    // are committed, which would leave the dependents parked forever
+    sqlx::query!(
+        "SELECT id FROM v2_job_queue WHERE id = ANY($1) AND workspace_id = $2 FOR SHARE",
+        upstream,
+        w_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let upstream_jobs = sqlx::query!(
+        "SELECT j.id, c.id IS NOT NULL AS \"completed!\", c.status = 'success' AS success
+        FROM v2_job j LEFT JOIN v2_job_completed c ON c.id = j.id
+        WHERE j.id = ANY($1) AND j.workspace_id = $2",
+        upstream,
+        w_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    if let Some(id) = upstream
+        .iter()
+        .find(|id| !upstream_jobs.iter().any(|j| j.id == **id))
+    {
+        return Err(Error::NotFound(format!("upstream job {id} not found")));
+    }
+
+    let mut pending = vec![];
+    for job in upstream_jobs {
+        if !job.completed {
+            pending.push(job.id);
+        } else if !condition.is_met_by(job.success.unwrap_or(false)) {
+            return Err(Error::BadRequest(format!(
+                "upstream job {} already completed and does not meet the {condition:?} condition",
+                job.id
+            )));
+        }
+    }
+    Ok(pending)
+}
+
+/// Blocks the queued jobs `job_ids` until the `pending` upstream jobs complete. Jobs subject to
+/// rate limits pass the key of their limit, they reserve their token once unblocked rather than
+/// when pushed.
+pub async fn block_jobs(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    job_ids: &[Uuid],
+    pending: &[Uuid],
+    condition: JobDependencyCondition,
+    rate_limit_key: Option<&str>,
+) -> Result<()> {
+    if pending.is_empty() || job_ids.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO job_dependency (job_id, upstream_id, workspace_id, condition, rate_limit_key)
+        SELECT job_id, upstream_id, $3, $4, $5
+        FROM unnest($1::uuid[]) AS job_id CROSS JOIN unnest($2::uuid[]) AS upstream_id
+        ON CONFLICT DO NOTHING",
+        job_ids,
+        pending,
+        w_id,
+        condition as JobDependencyCondition,
+        rate_limit_key,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE v2_job_queue SET
+            blocked_by_dependencies = true,
+            suspend = (SELECT COUNT(*)::int FROM job_dependency WHERE job_id = v2_job_queue.id)
+        WHERE id = ANY($1) AND workspace_id = $2",
+        job_ids,
+        w_id,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Releases the dependents of a completed job: the ones whose condition is met get one upstream
+/// job less to wait for, the others are canceled.
+pub async fn release_dependent_jobs(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: Uuid,
+    success: bool,
+) -> Result<()> {
+    // a job canceled while still waiting for its own upstream jobs
+    sqlx::query!("DELETE FROM job_dependency WHERE job_id = $1", job_id)
+        .execute(&mut **tx)
+        .await?;
+
+    let dependents = sqlx::query!(
+        "DELETE FROM job_dependency WHERE upstream_id = $1
+        RETURNING job_id, condition AS \"condition: JobDependencyCondition\", rate_limit_key",
+        job_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    if dependents.is_empty() {
+        return Ok(());
+    }
+
+    let (released, canceled): (Vec<_>, Vec<_>) = dependents
+        .into_iter()
+        .partition(|d| d.condition.is_met_by(success));
+    let canceled = canceled.into_iter().map(|d| d.job_id).collect::<Vec<_>>();
+
+    let mut ready_tags = vec![];
+    if !released.is_empty() {
+        let unblocked = sqlx::query!(
+            "UPDATE v2_job_queue SET
+                suspend = suspend - 1,
+                blocked_by_dependencies = suspend > 1
+            WHERE id = ANY($1) AND blocked_by_dependencies
+            RETURNING id, workspace_id, tag, scheduled_for, blocked_by_dependencies",
+            &released.iter().map(|d| d.job_id).collect::<Vec<_>>(),
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let now = chrono::Utc::now();
+        for job in unblocked.into_iter().filter(|j| !j.blocked_by_dependencies) {
+            let rate_limit_key = released
+                .iter()
+                .find(|d| d.job_id == job.id)
+                .and_then(|d| d.rate_limit_key.as_deref());
+            let mut scheduled_for = job.scheduled_for;
+            if let Some(key) = rate_limit_key {
+                if let Some(start_at) =
+                    reserve_rate_limit_token(tx, &job.workspace_id, key, scheduled_for.max(now))
+                        .await?
+                {
+                    tracing::info!(
+                        "Job {} with key {key} is over its rate limit, scheduled for {start_at}",
+                        job.id
+                    );
+                    sqlx::query!(
+                        "UPDATE v2_job_queue SET scheduled_for = $2 WHERE id = $1",
+                        job.id,
+                        start_at,
+                    )
+                    .execute(&mut **tx)
+                    .await?;
+                    scheduled_for = start_at;
+                }
+            }
+            if scheduled_for <= now {
+                ready_tags.push(Some(job.tag));
+            }
+        }
+    }
+
+    if !canceled.is_empty() {
+        tracing::info!(
+            "canceling jobs {canceled:?} whose dependency condition on job {job_id} is not met"
+        );
+        sqlx::query!(
+            "DELETE FROM job_dependency WHERE job_id = ANY($1)",
+            &canceled,
+        )
+        .execute(&mut **tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE v2_job_queue SET
+                canceled_by = 'dependencies', canceled_reason = $2,
+                suspend = 0, blocked_by_dependencies = false
+            WHERE id = ANY($1) AND canceled_by IS NULL",
+            &canceled,
+            format!(
+                "upstream job {job_id} {} which does not meet the dependency condition",
+                if success { "succeeded" } else { "failed" }
+            ),
+        )
+        .execute(&mut **tx)
+        .await?;
+        ready_tags.extend(
+            sqlx::query_scalar!(
+                "SELECT tag FROM v2_job_queue WHERE id = ANY($1) AND scheduled_for <= now()",
+                &canceled,
+            )
+            .fetch_all(&mut **tx)
+            .await?
+            .into_iter()
+            .map(Some),
+        );
+    }
+
+    let mut ready_tags = ready_tags.into_iter().flatten().collect::<Vec<_>>();
+    ready_tags.sort();
+    ready_tags.dedup();
+    for tag in ready_tags {
+        notify_queue(tx, &tag).await?;
+    }
+
+    Ok(())
+}
+
+/// Releases the dependents of the upstream jobs that left the queue without completing, e.g.
+/// deleted along with their schedule, as if these had failed.
+pub async fn release_orphaned_dependencies(db: &DB) -> Result<()> {
+    let orphaned = sqlx::query_scalar!(
+        "SELECT DISTINCT d.upstream_id FROM job_dependency d
+        WHERE NOT EXISTS (SELECT 1 FROM v2_job_queue q WHERE q.id = d.upstream_id)
+        LIMIT 100"
+    )
+    .fetch_all(db)
+    .await?;
+
+    for upstream_id in orphaned {
+        tracing::warn!(
+            "upstream job {upstream_id} was deleted without completing, releasing its dependents"
+        );
+        let mut tx = db.begin().await?;
+        release_dependent_jobs(&mut tx, upstream_id, false).await?;
+        tx.commit().await?;
+    }
+    Ok(())
+}
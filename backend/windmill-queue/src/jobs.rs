@@ -34,7 +34,7 @@ use windmill_common::auth::JobPerms;
 #[cfg(feature = "benchmark")]
 use windmill_common::bench::BenchmarkIter;
 use windmill_common::utils::now_from_db;
-use windmill_common::worker::{Connection, SCRIPT_TOKEN_EXPIRY};
+use windmill_common::worker::{queue_notify_channel, Connection, QUEUE_LISTEN, SCRIPT_TOKEN_EXPIRY};
 use windmill_common::{
     auth::{fetch_authed_from_permissioned_as, permissioned_as_to_username},
     cache::{self, FlowData},
@@ -42,13 +42,16 @@ use windmill_common::{
     error::{self, to_anyhow, Error},
     flow_status::{
         BranchAllStatus, FlowCleanupModule, FlowStatus, FlowStatusModule, FlowStatusModuleWParent,
-        Iterator as FlowIterator, JobResult, RestartedFrom, RetryStatus, MAX_RETRY_ATTEMPTS,
-        MAX_RETRY_INTERVAL,
+        Iterator as FlowIterator, JobResult, ReplayStatus, RestartedFrom, RetryStatus,
+        MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL,
     },
     flows::{
         add_virtual_items_if_necessary, FlowModule, FlowModuleValue, FlowValue, InputTransform,
     },
-    jobs::{get_payload_tag_from_prefixed_path, JobKind, JobPayload, QueuedJob, RawCode},
+    jobs::{
+        get_payload_tag_from_prefixed_path, JobDependencyCondition, JobKind, JobPayload, QueuedJob,
+        RawCode,
+    },
     schedule::Schedule,
     scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang},
     users::{SUPERADMIN_NOTIFICATION_EMAIL, SUPERADMIN_SECRET_EMAIL},
@@ -70,8 +73,13 @@ use windmill_common::BASE_URL;
 #[cfg(feature = "cloud")]
 use windmill_common::users::SUPERADMIN_SYNC_EMAIL;
 
+use crate::circuit_breaker::{
+    record_circuit_breaker_outcome, recheck_delayed_job, uses_circuit_breakers, ShortCircuit,
+};
 use crate::flow_status::{update_flow_status_in_progress, update_workflow_as_code_status};
+use crate::job_dependencies::{block_jobs, pending_upstream_jobs, release_dependent_jobs};
 use crate::jobs_ee::update_concurrency_counter;
+use crate::rate_limit::reserve_rate_limit_token;
 use crate::schedule::{get_schedule_opt, push_scheduled_job};
 use crate::tags::per_workspace_tag;
 
@@ -441,12 +449,31 @@ pub async fn push_init_job<'c>(
         None,
         None,
         None,
+        None,
     )
     .await?;
     inner_tx.commit().await?;
     Ok(uuid)
 }
 
+/// Cancel the in-flight iterations or branches of a module whose deadline expired. Their
+/// completion lets the parent flow apply the `on_timeout` action of the module.
+pub async fn cancel_module_jobs(
+    db: &Pool<Postgres>,
+    w_id: &str,
+    jobs: &[Uuid],
+    canceled_by: &str,
+    reason: String,
+) -> error::Result<()> {
+    let reason = Some(reason);
+    let mut tx = db.begin().await?;
+    for id in jobs {
+        (tx, _) = cancel_job(canceled_by, reason.clone(), *id, w_id, tx, db, false, false).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
 pub async fn cancel_persistent_script_jobs<'c>(
     username: &str,
     reason: Option<String>,
@@ -788,6 +815,10 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
 
         let mut _skip_downstream_error_handlers = false;
         tx = delete_job(tx, &job_id).await?;
+
+        if queued_job.pre_run_error.is_none() && uses_circuit_breakers(queued_job.kind) {
+            record_circuit_breaker_outcome(&mut tx, queued_job, success).await?;
+        }
         // tracing::error!("3 {:?}", start.elapsed());
 
         if queued_job.is_flow_step() {
@@ -824,6 +855,8 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
                 }
             }
         } else {
+            release_dependent_jobs(&mut tx, queued_job.id, success).await?;
+
             if queued_job.schedule_path().is_some() && queued_job.runnable_path.is_some() {
                 let schedule_path = queued_job.schedule_path().unwrap();
                 let script_path = queued_job.runnable_path.as_ref().unwrap();
@@ -1215,6 +1248,7 @@ pub async fn add_completed_job<T: Serialize + Send + Sync + ValidableJson>(
                     None,
                     queued_job.priority,
                     None,
+                    None,
                 )
                 .await?;
                 if let Err(e) = tx.commit().await {
@@ -1738,6 +1772,7 @@ pub async fn push_error_handler<'a, 'c, T: Serialize + Send + Sync>(
         None,
         priority,
         None,
+        None,
     )
     .await?;
     tx.commit().await?;
@@ -1845,6 +1880,7 @@ async fn handle_recovered_schedule<'a, 'c, T: Serialize + Send + Sync>(
         None,
         None,
         None,
+        None,
     )
     .await?;
     tracing::info!(
@@ -1933,6 +1969,7 @@ async fn handle_successful_schedule<'a, 'c, T: Serialize + Send + Sync>(
         None,
         None,
         None,
+        None,
     )
     .await?;
     tracing::info!(
@@ -2332,6 +2369,34 @@ pub async fn pull(
             return Ok(PulledJobResult { job: None, suspended });
         };
 
+        // jobs delayed by a circuit breaker run only once it is closed or they are its probe
+        if !suspended && job.canceled_by.is_none() && uses_circuit_breakers(job.kind) {
+            if let Some(until) = recheck_delayed_job(db, &job).await? {
+                let _ = append_logs(
+                    &job.id,
+                    &job.workspace_id,
+                    format!("\nRe-scheduled job to {until}, its circuit breaker is still open\n"),
+                    &Connection::from(db.clone()),
+                )
+                .await;
+                sqlx::query!(
+                    "WITH ping AS (
+                        UPDATE v2_job_runtime SET ping = null WHERE id = $2
+                    )
+                    UPDATE v2_job_queue SET
+                        running = false,
+                        started_at = null,
+                        scheduled_for = $1
+                    WHERE id = $2",
+                    until,
+                    job.id,
+                )
+                .execute(db)
+                .await?;
+                continue;
+            }
+        }
+
 
         let has_concurent_limit = job.concurrent_limit.is_some();
 
@@ -3246,6 +3311,7 @@ pub async fn push<'c, 'd>(
     flow_step_id: Option<String>,
     _priority_override: Option<i16>,
     authed: Option<&Authed>,
+    depends_on: Option<(&[Uuid], JobDependencyCondition)>,
 ) -> Result<(Uuid, Transaction<'c, Postgres>), Error> {
     #[cfg(feature = "cloud")]
     if *CLOUD_HOSTED {
@@ -3710,7 +3776,7 @@ pub async fn push<'c, 'd>(
                         }),
                         cleanup_module,
                         // retry status is reset
-                        retry: RetryStatus { fail_count: 0, failed_jobs: vec![] },
+                        retry: RetryStatus { fail_count: 0, failed_jobs: vec![], predicate: None },
                         // TODO: for now, flows with approval conditions aren't supported for restart
                         approval_conditions: None,
                         restarted_from: Some(RestartedFrom {
@@ -3720,6 +3786,10 @@ pub async fn push<'c, 'd>(
                         }),
                         user_states,
                         preprocessor_module: None,
+                        compensation: None,
+                        deadline: None,
+                        replay: None,
+                        race_losers: vec![],
                     }
                 }
                 _ => {
@@ -3787,6 +3857,7 @@ pub async fn push<'c, 'd>(
                     delete_after_use: None,
                     continue_on_error: None,
                     skip_if: None,
+                    compensation: None,
                 }],
                 same_worker: false,
                 failure_module: None,
@@ -3883,6 +3954,33 @@ pub async fn push<'c, 'd>(
                 priority,
             )
         }
+        JobPayload::ReplayedFlow { completed_job_id, mut value, path } => {
+            add_virtual_items_if_necessary(&mut value.modules);
+            value.preprocessor_module = None;
+            let mut flow_status = FlowStatus::new(&value);
+            flow_status.replay =
+                Some(ReplayStatus { flow_job_id: completed_job_id, reused: vec![] });
+            let concurrency_key = value.concurrency_key.clone();
+            let concurrent_limit = value.concurrent_limit;
+            let concurrency_time_window_s = value.concurrency_time_window_s;
+            let cache_ttl = value.cache_ttl.map(|x| x as i32);
+            let priority = value.priority;
+            (
+                None,
+                path,
+                None,
+                JobKind::FlowPreview,
+                Some(value),
+                Some(flow_status),
+                None,
+                concurrency_key,
+                concurrent_limit,
+                concurrency_time_window_s,
+                cache_ttl,
+                None,
+                priority,
+            )
+        }
         JobPayload::RestartedFlow { completed_job_id, step_id, branch_or_iteration_n } => {
             let (
                 version,
@@ -3912,7 +4010,7 @@ pub async fn push<'c, 'd>(
                 }),
                 cleanup_module,
                 // retry status is reset
-                retry: RetryStatus { fail_count: 0, failed_jobs: vec![] },
+                retry: RetryStatus { fail_count: 0, failed_jobs: vec![], predicate: None },
                 // TODO: for now, flows with approval conditions aren't supported for restart
                 approval_conditions: None,
                 restarted_from: Some(RestartedFrom {
@@ -3922,6 +4020,10 @@ pub async fn push<'c, 'd>(
                 }),
                 user_states,
                 preprocessor_module: None,
+                compensation: None,
+                deadline: None,
+                replay: None,
+                race_losers: vec![],
             };
             let value = flow_data.value();
             let priority = value.priority;
@@ -4127,10 +4229,77 @@ pub async fn push<'c, 'd>(
         Ulid::new().into()
     };
 
+    // the upstream jobs still to complete, the job is blocked until then
+    let pending_upstream = match depends_on {
+        Some((upstream, condition)) => {
+            pending_upstream_jobs(&mut tx, workspace_id, &[job_id], upstream, condition).await?
+        }
+        None => vec![],
+    };
+    let blocked = !pending_upstream.is_empty();
+
+    let rate_limit_key = job_concurrency_key(
+        workspace_id,
+        &args,
+        &script_path,
+        job_kind,
+        custom_concurrency_key.clone(),
+    );
     if concurrent_limit.is_some() {
         insert_concurrency_key(workspace_id, &args, &script_path, job_kind, custom_concurrency_key, &mut tx, job_id).await?;
     }
 
+    let short_circuit_error;
+    let mut pre_run_error = pre_run_error;
+    let mut scheduled_for_o = scheduled_for_o;
+    if pre_run_error.is_none() && uses_circuit_breakers(job_kind) {
+        match crate::circuit_breaker::check_circuit_breakers(
+            &mut tx,
+            workspace_id,
+            script_path.as_deref(),
+            &crate::circuit_breaker::resource_args(args.args),
+            job_id,
+        )
+        .await?
+        {
+            Some(ShortCircuit::Fail(e)) => {
+                short_circuit_error = e;
+                pre_run_error = Some(&short_circuit_error);
+            }
+            Some(ShortCircuit::Delay(until)) => {
+                scheduled_for_o = Some(scheduled_for_o.map_or(until, |s| s.max(until)));
+            }
+            None => (),
+        }
+    }
+
+    let rate_limited = !is_running
+        && pre_run_error.is_none()
+        && matches!(
+            job_kind,
+            JobKind::Script
+                | JobKind::Script_Hub
+                | JobKind::Preview
+                | JobKind::Flow
+                | JobKind::FlowPreview
+                | JobKind::SingleScriptFlow
+                | JobKind::FlowScript
+                | JobKind::FlowNode
+                | JobKind::AppScript
+        );
+    // a blocked job reserves its token once unblocked, when it can actually start
+    if rate_limited && !blocked {
+        let scheduled_for = scheduled_for_o.unwrap_or_else(chrono::Utc::now);
+        if let Some(start_at) =
+            reserve_rate_limit_token(&mut tx, workspace_id, &rate_limit_key, scheduled_for).await?
+        {
+            tracing::info!(
+                "Job {job_id} with key {rate_limit_key} is over its rate limit, scheduled for {start_at}"
+            );
+            scheduled_for_o = Some(start_at);
+        }
+    }
+
     let stringified_args = if *JOB_ARGS_AUDIT_LOGS {
         Some(serde_json::to_string(&args).map_err(|e| {
             Error::internal_err(format!(
@@ -4297,6 +4466,20 @@ pub async fn push<'c, 'd>(
         .await?;
     }
 
+    if let Some((_, condition)) = depends_on.filter(|_| blocked) {
+        block_jobs(
+            &mut tx,
+            workspace_id,
+            &[job_id],
+            &pending_upstream,
+            condition,
+            rate_limited.then_some(rate_limit_key.as_str()),
+        )
+        .await?;
+    } else if !is_running && scheduled_for_o.map_or(true, |s| s <= chrono::Utc::now()) {
+        notify_queue(&mut tx, &tag).await?;
+    }
+
     tracing::debug!("Pushed {job_id}");
     // TODO: technically the job isn't queued yet, as the transaction can be rolled back. Should be solved when moving these metrics to the queue abstraction.
     #[cfg(feature = "prometheus")]
@@ -4369,14 +4552,34 @@ pub async fn push<'c, 'd>(
     Ok((job_id, tx))
 }
 
-pub async fn insert_concurrency_key<'d, 'c>(workspace_id: &str, args: &PushArgs<'d>, script_path: &Option<String>, job_kind: JobKind, custom_concurrency_key: Option<String>, tx: &mut Transaction<'c, Postgres>, job_id: Uuid) -> Result<(), Error> {
-    let concurrency_key = custom_concurrency_key
+/// Key shared by the concurrency limits and the rate limits of a job: the interpolated custom
+/// concurrency key if any, the path of the script or flow otherwise
+fn job_concurrency_key(
+    workspace_id: &str,
+    args: &PushArgs,
+    script_path: &Option<String>,
+    job_kind: JobKind,
+    custom_concurrency_key: Option<String>,
+) -> String {
+    custom_concurrency_key
         .map(|x| interpolate_args(x, args, workspace_id))
-        .unwrap_or(fullpath_with_workspace(
-            workspace_id,
-            script_path.as_ref(),
-            &job_kind,
-        ));
+        .unwrap_or_else(|| fullpath_with_workspace(workspace_id, script_path.as_ref(), &job_kind))
+}
+
+/// Wakes up the workers listening on `tag` with `QUEUE_LISTEN` once `tx` is committed. The payload
+/// is empty so that the notifications of a transaction readying many jobs are collapsed into one.
+pub async fn notify_queue(tx: &mut Transaction<'_, Postgres>, tag: &str) -> Result<(), Error> {
+    if *QUEUE_LISTEN {
+        sqlx::query("SELECT pg_notify($1, '')")
+            .bind(queue_notify_channel(tag))
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn insert_concurrency_key<'d, 'c>(workspace_id: &str, args: &PushArgs<'d>, script_path: &Option<String>, job_kind: JobKind, custom_concurrency_key: Option<String>, tx: &mut Transaction<'c, Postgres>, job_id: Uuid) -> Result<(), Error> {
+    let concurrency_key = job_concurrency_key(workspace_id, args, script_path, job_kind, custom_concurrency_key);
     sqlx::query!(
         "WITH inserted_concurrency_counter AS (
                 INSERT INTO concurrency_counter (concurrency_id, job_uuids) 
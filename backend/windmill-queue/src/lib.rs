@@ -6,9 +6,13 @@
  * LICENSE-AGPL for a copy of the license.
  */
 
+pub mod circuit_breaker;
+pub mod job_dependencies;
 mod jobs;
 pub mod jobs_ee;
+pub mod rate_limit;
 pub mod schedule;
 pub use jobs::*;
 pub mod flow_status;
 pub mod tags;
+mod workspace_flag;
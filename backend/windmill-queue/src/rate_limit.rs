@@ -0,0 +1,98 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Token-bucket rate limits keyed by concurrency key.
+//!
+//! The bucket is implemented as a generic cell rate algorithm: instead of a token count, each
+//! limit stores the theoretical arrival time (tat) of the next job. Every pushed job reserves
+//! the next token and is scheduled for the exact time it becomes available, so a limit of 10
+//! per second with a burst of 20 lets 20 jobs start at once and then one every 100ms, without
+//! the bursts at window boundaries of the concurrency time windows.
+//!
+//! The jobs of the workspaces without any rate limit skip the reservation, see `HAS_RATE_LIMITS`.
+
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use windmill_common::error::Result;
+
+use crate::workspace_flag::WorkspaceFlag;
+
+lazy_static::lazy_static! {
+    static ref HAS_RATE_LIMITS: WorkspaceFlag = WorkspaceFlag::new();
+}
+
+#[cfg(feature = "prometheus")]
+lazy_static::lazy_static! {
+    static ref RATE_LIMITED_JOBS: prometheus::IntCounterVec = prometheus::register_int_counter_vec!(
+        "rate_limited_jobs",
+        "Number of jobs delayed by a rate limit.",
+        &["workspace_id", "key"]
+    )
+    .unwrap();
+}
+
+/// Whether the workspace has rate limits, cached
+async fn has_rate_limits<'c, E: sqlx::PgExecutor<'c>>(e: E, w_id: &str) -> Result<bool> {
+    if let Some(has) = HAS_RATE_LIMITS.get(w_id) {
+        return Ok(has);
+    }
+    let has = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM rate_limit WHERE workspace_id = $1)",
+    )
+    .bind(w_id)
+    .fetch_one(e)
+    .await?;
+    HAS_RATE_LIMITS.set(w_id, has);
+    Ok(has)
+}
+
+/// To be called once the rate limits of the workspace were created or deleted
+pub fn invalidate_has_rate_limits(w_id: &str) {
+    HAS_RATE_LIMITS.invalidate(w_id);
+}
+
+/// Reserves a token of the rate limit of `key`, if any, for a job that wants to start at
+/// `scheduled_for`. Returns when the job may start if that is later than `scheduled_for`.
+pub async fn reserve_rate_limit_token(
+    tx: &mut Transaction<'_, Postgres>,
+    w_id: &str,
+    key: &str,
+    scheduled_for: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>> {
+    if !has_rate_limits(&mut **tx, w_id).await? {
+        return Ok(None);
+    }
+    let start_at = sqlx::query_scalar!(
+        r#"WITH prev AS (
+            SELECT workspace_id, key, COALESCE(tat, $3) AS tat,
+                GREATEST($3, COALESCE(tat, $3) - make_interval(secs => (burst - 1) / rate)) AS start_at
+            FROM rate_limit
+            WHERE workspace_id = $1 AND key = $2
+            FOR UPDATE
+        )
+        UPDATE rate_limit SET tat = GREATEST(prev.tat, prev.start_at) + make_interval(secs => 1 / rate)
+        FROM prev
+        WHERE rate_limit.workspace_id = prev.workspace_id AND rate_limit.key = prev.key
+        RETURNING prev.start_at AS "start_at!""#,
+        w_id,
+        key,
+        scheduled_for,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some(start_at) = start_at.filter(|s| *s > scheduled_for) else {
+        return Ok(None);
+    };
+
+    #[cfg(feature = "prometheus")]
+    if windmill_common::METRICS_ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+        RATE_LIMITED_JOBS.with_label_values(&[w_id, key]).inc();
+    }
+    Ok(Some(start_at))
+}
@@ -9,15 +9,17 @@
 use crate::push;
 use crate::PushIsolationLevel;
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::{PgExecutor, Postgres, Transaction};
 use std::collections::HashMap;
 use std::str::FromStr;
+use uuid::Uuid;
 use windmill_common::db::Authed;
 use windmill_common::ee::LICENSE_KEY_VALID;
 use windmill_common::flows::Retry;
 use windmill_common::get_latest_flow_version_info_for_path;
 use windmill_common::jobs::JobPayload;
-use windmill_common::schedule::schedule_to_user;
+use windmill_common::schedule::{schedule_to_user, MAX_CATCHUP_TICKS};
 use windmill_common::FlowVersionInfo;
 use windmill_common::DB;
 use windmill_common::{
@@ -27,6 +29,9 @@ use windmill_common::{
     utils::{now_from_db, ScheduleType, StripPath},
 };
 
+/// Upper bound on the number of cron events enumerated when looking for missed ticks
+const MAX_CATCHUP_SCAN: usize = 100_000;
+
 pub async fn push_scheduled_job<'c>(
     db: &DB,
     mut tx: Transaction<'c, Postgres>,
@@ -48,8 +53,8 @@ pub async fn push_scheduled_job<'c>(
 
     let now = now_from_db(&mut *tx).await?;
 
-    let starting_from = match schedule.paused_until {
-        Some(paused_until) if paused_until > now => paused_until.with_timezone(&tz),
+    let (starting_from, paused) = match schedule.paused_until {
+        Some(paused_until) if paused_until > now => (paused_until.with_timezone(&tz), true),
         paused_until_o => {
             if paused_until_o.is_some() {
                 sqlx::query!(
@@ -61,17 +66,181 @@ pub async fn push_scheduled_job<'c>(
                 .await
                 .context("Failed to clear paused_until for schedule")?;
             }
-            now.with_timezone(&tz)
+            (now.with_timezone(&tz), false)
         }
     };
 
+    // Ticks between the last pushed tick and now were missed (schedule disabled or paused,
+    // or no server to push the next job). They are only caught up once the schedule is
+    // effectively running again, hence not while it is still paused.
+    if !paused {
+        // read it back from the db as the caller's copy may predate a `clear_schedule`
+        let last_tick_at = sqlx::query_scalar!(
+            "SELECT last_tick_at FROM schedule WHERE workspace_id = $1 AND path = $2",
+            &schedule.workspace_id,
+            &schedule.path
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        if let Some(last_tick_at) = last_tick_at {
+            let missed = sched.events_between(
+                tz,
+                last_tick_at,
+                now,
+                schedule.catchup_limit(),
+                MAX_CATCHUP_SCAN,
+            );
+            if !missed.is_empty() {
+                tracing::info!(
+                    "Schedule {} catching up {} missed tick(s) with policy {:?}",
+                    &schedule.path,
+                    missed.len(),
+                    schedule.catchup_policy
+                );
+            }
+            for tick in missed {
+                let (_, ntx) = push_scheduled_job_at(db, tx, schedule, authed, tick).await?;
+                tx = ntx;
+            }
+        }
+    }
+
     let next = sched.find_next(&starting_from);
     // println!("next event ({:?}): {}", tz, next);
     // println!("next event(UTC): {}", next.with_timezone(&chrono::Utc));
 
     // Scheduled events must be stored in the database in UTC
     let next = next.with_timezone(&chrono::Utc);
-    // panic!("next: {}", next);
+
+    let (_, mut tx) = push_scheduled_job_at(db, tx, schedule, authed, next).await?;
+
+    // while paused, the anchor stays on the last tick before the pause so that the paused
+    // ticks can be caught up once the pause is over
+    if !paused {
+        sqlx::query!(
+            "UPDATE schedule SET last_tick_at = $1 WHERE workspace_id = $2 AND path = $3",
+            next,
+            &schedule.workspace_id,
+            &schedule.path
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Failed to update last tick of schedule")?;
+    }
+
+    Ok(tx)
+}
+
+/// Pushes one job per tick of `[from, to]` for the schedule, each with `scheduled_for` set
+/// to its logical tick time. Ticks that already have a job are skipped.
+pub async fn backfill_schedule<'c>(
+    db: &DB,
+    mut tx: Transaction<'c, Postgres>,
+    schedule: &Schedule,
+    authed: Option<&Authed>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<(Vec<Uuid>, Transaction<'c, Postgres>)> {
+    let ticks = schedule_ticks_between(schedule, from, to)?;
+    let mut uuids = vec![];
+    for tick in ticks {
+        let (uuid, ntx) = push_scheduled_job_at(db, tx, schedule, authed, tick).await?;
+        tx = ntx;
+        uuids.extend(uuid);
+    }
+    Ok((uuids, tx))
+}
+
+/// Ticks of the schedule in `[from, to]`, bounded by `MAX_CATCHUP_TICKS`
+pub fn schedule_ticks_between(
+    schedule: &Schedule,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>> {
+    if to < from {
+        return Err(error::Error::BadRequest(
+            "backfill range end must be after its start".to_string(),
+        ));
+    }
+    let sched =
+        ScheduleType::from_str(&schedule.schedule, schedule.cron_version.as_deref(), false)?;
+    let tz = chrono_tz::Tz::from_str(&schedule.timezone)
+        .map_err(|e| error::Error::BadRequest(e.to_string()))?;
+
+    // events_between is exclusive on its lower bound
+    let ticks = sched.events_between(
+        tz,
+        from - chrono::Duration::milliseconds(1),
+        to,
+        MAX_CATCHUP_TICKS + 1,
+        MAX_CATCHUP_SCAN,
+    );
+    if ticks.len() > MAX_CATCHUP_TICKS {
+        return Err(error::Error::BadRequest(format!(
+            "backfill range contains more than {MAX_CATCHUP_TICKS} ticks, split it in smaller ranges"
+        )));
+    }
+    Ok(ticks)
+}
+
+/// Tick of the job of the schedule waiting in the queue, if any. It is the last tick pushed
+/// while the schedule is enabled.
+pub async fn pending_schedule_tick<'c>(
+    tx: &mut Transaction<'c, Postgres>,
+    schedule: &Schedule,
+) -> Result<Option<DateTime<Utc>>> {
+    let tick = sqlx::query_scalar!(
+        "SELECT MIN(scheduled_for) FROM v2_job j JOIN v2_job_queue q USING (id)
+        WHERE j.workspace_id = $1 AND trigger_kind = 'schedule' AND trigger = $2
+            AND runnable_path = $3 AND parent_job IS NULL AND q.running = false",
+        &schedule.workspace_id,
+        &schedule.path,
+        &schedule.script_path,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(tick)
+}
+
+/// Last tick of the schedule strictly before `before`, looking back at most `MAX_CATCHUP_SCAN`
+/// events
+pub fn schedule_tick_before(
+    schedule: &Schedule,
+    before: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>> {
+    let sched =
+        ScheduleType::from_str(&schedule.schedule, schedule.cron_version.as_deref(), false)?;
+    let tz = chrono_tz::Tz::from_str(&schedule.timezone)
+        .map_err(|e| error::Error::BadRequest(e.to_string()))?;
+
+    // widen the window until it contains a tick, cron schedules fire at least yearly
+    let mut window = chrono::Duration::hours(1);
+    while window <= chrono::Duration::days(8 * 366) {
+        let tick = sched
+            .events_between(
+                tz,
+                before - window,
+                before - chrono::Duration::milliseconds(1),
+                1,
+                MAX_CATCHUP_SCAN,
+            )
+            .pop();
+        if tick.is_some() {
+            return Ok(tick);
+        }
+        window = window * 8;
+    }
+    Ok(None)
+}
+
+async fn push_scheduled_job_at<'c>(
+    db: &DB,
+    mut tx: Transaction<'c, Postgres>,
+    schedule: &Schedule,
+    authed: Option<&Authed>,
+    next: DateTime<Utc>,
+) -> Result<(Option<Uuid>, Transaction<'c, Postgres>)> {
     let already_exists: bool = sqlx::query_scalar!(
         // Query plan:
         // - use of the `ix_v2_job_root_by_path` index; hence the `parent_job IS NULL` clause.
@@ -98,7 +267,7 @@ pub async fn push_scheduled_job<'c>(
             &schedule.path,
             next
         );
-        return Ok(tx);
+        return Ok((None, tx));
     }
 
     let mut args: HashMap<String, Box<serde_json::value::RawValue>> = HashMap::new();
@@ -262,7 +431,7 @@ pub async fn push_scheduled_job<'c>(
     };
 
     let tx = PushIsolationLevel::Transaction(tx);
-    let (_, mut tx) = push(
+    let (uuid, mut tx) = push(
         &db,
         tx,
         &schedule.workspace_id,
@@ -285,6 +454,7 @@ pub async fn push_scheduled_job<'c>(
         None,
         None,
         push_authed,
+        None,
     )
     .await?;
 
@@ -294,7 +464,7 @@ pub async fn push_scheduled_job<'c>(
             .await?;
     }
 
-    Ok(tx) // TODO: Bubble up pushed UUID from here
+    Ok((Some(uuid), tx))
 }
 
 pub async fn get_schedule_opt<'c>(
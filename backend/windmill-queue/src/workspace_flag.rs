@@ -0,0 +1,48 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Per-workspace flags cached in memory, such as whether a workspace has circuit breakers, so
+//! that the push and pull of the jobs of the workspaces without any skip their queries.
+//!
+//! A flag is reloaded once expired or after it was invalidated by a change on this server. A
+//! change made through another server is seen after at most `WORKSPACE_FLAG_TTL`.
+
+use std::time::{Duration, Instant};
+
+use windmill_common::cache::Cache;
+
+const WORKSPACE_FLAG_TTL: Duration = Duration::from_secs(10);
+
+pub struct WorkspaceFlag {
+    cache: Cache<String, (bool, Instant)>,
+}
+
+impl WorkspaceFlag {
+    pub fn new() -> Self {
+        Self { cache: Cache::new(1000) }
+    }
+
+    /// The flag of the workspace, None if it is not cached or expired
+    pub fn get(&self, w_id: &str) -> Option<bool> {
+        self.cache
+            .get(w_id)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(flag, _)| flag)
+    }
+
+    pub fn set(&self, w_id: &str, flag: bool) {
+        self.cache.insert(
+            w_id.to_string(),
+            (flag, Instant::now() + WORKSPACE_FLAG_TTL),
+        );
+    }
+
+    pub fn invalidate(&self, w_id: &str) {
+        self.cache.remove(w_id);
+    }
+}
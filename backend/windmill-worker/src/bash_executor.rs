@@ -192,7 +192,7 @@ exit $exit_status
             .args(cmd_args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         let mut cmd_args = vec!["wrapper.sh"];
         cmd_args.extend(&args);
@@ -208,7 +208,7 @@ exit $exit_status
             .args(cmd_args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(bash_cmd, BIN_BASH.as_str()).await?
+        start_child_process(bash_cmd, BIN_BASH.as_str(), &job.id, conn).await?
     };
     handle_child(
         &job.id,
@@ -17,7 +17,8 @@ use crate::common::build_envs_map;
 use crate::{
     common::{
         create_args_and_out_file, get_reserved_variables, parse_npm_config, read_file,
-        read_file_content, read_result, start_child_process, write_file_binary, OccupancyMetrics,
+        read_file_content, read_result, spawn_process, start_child_process, write_file_binary,
+        OccupancyMetrics,
     },
     handle_child::handle_child,
     AuthedClient, BUNFIG_INSTALL_SCOPES, BUN_BUNDLE_CACHE_DIR, BUN_CACHE_DIR, BUN_PATH,
@@ -150,7 +151,10 @@ pub async fn gen_bun_lockfile(
         #[cfg(windows)]
         child_cmd.env("SystemRoot", SYSTEM_ROOT.as_str());
 
-        let mut child_process = start_child_process(child_cmd, &*BUN_PATH).await?;
+        let mut child_process = match db {
+            Some(db) => start_child_process(child_cmd, &*BUN_PATH, job_id, db).await?,
+            None => spawn_process(child_cmd, &*BUN_PATH)?,
+        };
 
         if let Some(db) = db {
             handle_child(
@@ -344,7 +348,10 @@ pub async fn install_bun_lockfile(
         }
     }
 
-    let mut child_process = start_child_process(child_cmd, &*BUN_PATH).await?;
+    let mut child_process = match db {
+        Some(db) => start_child_process(child_cmd, &*BUN_PATH, job_id, db).await?,
+        None => spawn_process(child_cmd, &*BUN_PATH)?,
+    };
 
     gen_bunfig(job_dir).await?;
     if let Some(db) = db {
@@ -519,7 +526,7 @@ pub async fn generate_wrapper_mjs(
     #[cfg(windows)]
     child.env("SystemRoot", SYSTEM_ROOT.as_str());
 
-    let child_process = start_child_process(child, &*BUN_PATH).await?;
+    let child_process = start_child_process(child, &*BUN_PATH, job_id, db).await?;
     handle_child(
         job_id,
         db,
@@ -569,7 +576,10 @@ pub async fn generate_bun_bundle(
     #[cfg(windows)]
     child.env("SystemRoot", SYSTEM_ROOT.as_str());
 
-    let mut child_process = start_child_process(child, &*BUN_PATH).await?;
+    let mut child_process = match db {
+        Some(db) => start_child_process(child, &*BUN_PATH, job_id, db).await?,
+        None => spawn_process(child, &*BUN_PATH)?,
+    };
     if let Some(db) = db {
         handle_child(
             job_id,
@@ -1379,7 +1389,7 @@ try {{
             .args(args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         let cmd = if annotation.nodejs {
             let script_path = format!("{job_dir}/wrapper.mjs");
@@ -1438,6 +1448,8 @@ try {{
             } else {
                 &*BUN_PATH
             },
+            &job.id,
+            conn,
         )
         .await?
     };
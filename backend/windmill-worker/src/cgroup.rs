@@ -0,0 +1,342 @@
+/*
+ * Author: Ruben Fiszel
+ * Copyright: Windmill Labs, Inc 2025
+ * This file and its contents are licensed under the AGPLv3 License.
+ * Please see the included NOTICE for copyright information and
+ * LICENSE-AGPL for a copy of the license.
+ */
+
+//! Resource limits of job processes, enforced with a cgroup v2 per job.
+//!
+//! cgroup v2 only allows to enable controllers for the children of a cgroup without processes
+//! of its own, so the first time limits are needed the processes of the worker's cgroup are moved
+//! to a `worker` leaf and the cpu, memory and pids controllers are enabled for its children. Each
+//! job process is then spawned in a `job-<id>-<n>` sibling holding the limits of the job, which
+//! it joins between fork and exec so that it never runs without them, and which is removed once
+//! the process exited. If any of this fails (cgroup v1, read-only cgroupfs, ...), limits are not
+//! enforced and a warning is logged once.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio::process::Command;
+use uuid::Uuid;
+use windmill_common::{
+    error,
+    worker::{Connection, ResourceLimits, WORKER_CONFIG},
+};
+
+#[cfg(target_os = "linux")]
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+lazy_static::lazy_static! {
+    /// cgroups of the child processes spawned by start_child_process, by pid, until handle_child
+    /// is done with them
+    static ref CHILD_CGROUPS: Mutex<HashMap<u32, JobCgroup>> = Mutex::new(HashMap::new());
+}
+
+#[cfg(target_os = "linux")]
+lazy_static::lazy_static! {
+    static ref CGROUP_ROOT: tokio::sync::OnceCell<Option<PathBuf>> = tokio::sync::OnceCell::new();
+}
+
+/// jobs can have several processes running at once, e.g. parallel dependency installs
+#[cfg(target_os = "linux")]
+static CGROUP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// cpu.max period, in microseconds
+#[cfg(target_os = "linux")]
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Limits configured for the job in the worker group config, if any
+pub async fn resolve_resource_limits(
+    job_id: &Uuid,
+    conn: &Connection,
+) -> error::Result<Option<ResourceLimits>> {
+    let Some(config) = WORKER_CONFIG.read().await.resource_limits.clone() else {
+        return Ok(None);
+    };
+    let limits = match conn {
+        Connection::Sql(db) => {
+            let job = sqlx::query!(
+                "SELECT tag, runnable_path FROM v2_job WHERE id = $1",
+                job_id
+            )
+            .fetch_optional(db)
+            .await?;
+            match job {
+                Some(job) => config.limits_for(&job.tag, job.runnable_path.as_deref()),
+                None => config.default,
+            }
+        }
+        // agent workers cannot look up the job, only the default limits apply
+        Connection::Http(_) => config.default,
+    };
+    Ok(if limits.is_empty() {
+        None
+    } else {
+        Some(limits)
+    })
+}
+
+pub struct JobCgroup {
+    #[cfg(target_os = "linux")]
+    path: PathBuf,
+}
+
+impl JobCgroup {
+    /// Creates a cgroup with the limits of the job for a process about to be spawned, if the job
+    /// has any
+    pub async fn for_job(job_id: &Uuid, conn: &Connection) -> Option<JobCgroup> {
+        if job_id.is_nil() {
+            return None;
+        }
+        match resolve_resource_limits(job_id, conn).await {
+            Ok(Some(limits)) => JobCgroup::create(job_id, &limits).await,
+            Ok(None) => None,
+            Err(e) => {
+                tracing::error!(%job_id, "could not resolve resource limits: {e:#}");
+                None
+            }
+        }
+    }
+
+    /// Creates a cgroup holding the limits of the job. Returns None if cgroups v2 cannot be used
+    /// by the worker.
+    #[cfg(target_os = "linux")]
+    pub async fn create(job_id: &Uuid, limits: &ResourceLimits) -> Option<JobCgroup> {
+        let root = CGROUP_ROOT.get_or_init(init_cgroup_root).await.clone()?;
+        let n = CGROUP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = root.join(format!("job-{job_id}-{n}"));
+        let cgroup = JobCgroup { path };
+        if let Err(e) = cgroup.setup(limits).await {
+            tracing::error!(%job_id, "could not apply resource limits {limits:?}: {e:#}");
+            cgroup.remove().await;
+            return None;
+        }
+        tracing::info!(%job_id, "applied resource limits {limits:?}");
+        Some(cgroup)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub async fn create(_job_id: &Uuid, _limits: &ResourceLimits) -> Option<JobCgroup> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn setup(&self, limits: &ResourceLimits) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(&self.path).await?;
+        if let Some(memory_max_mb) = limits.memory_max_mb {
+            self.write("memory.max", (memory_max_mb * 1024 * 1024).to_string())
+                .await?;
+            // without it, the job would swap instead of being oom killed
+            let _ = self.write("memory.swap.max", "0".to_string()).await;
+            // kill all the processes of the job at once instead of only the largest one
+            let _ = self.write("memory.oom.group", "1".to_string()).await;
+        }
+        if let Some(cpu_quota) = limits.cpu_quota {
+            let quota = ((cpu_quota * CPU_PERIOD_US as f64) as u64).max(1000);
+            self.write("cpu.max", format!("{quota} {CPU_PERIOD_US}"))
+                .await?;
+        }
+        if let Some(pids_max) = limits.pids_max {
+            self.write("pids.max", pids_max.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    /// Makes the process spawned by `cmd` join the cgroup before it executes anything
+    #[cfg(target_os = "linux")]
+    pub fn attach(&self, cmd: &mut Command) -> std::io::Result<()> {
+        use nix::{fcntl::OFlag, sys::stat::Mode};
+        use std::os::unix::ffi::OsStringExt;
+
+        let procs =
+            std::ffi::CString::new(self.path.join("cgroup.procs").into_os_string().into_vec())?;
+        // SAFETY: the closure runs in the forked child, it only makes async-signal-safe syscalls
+        // on the path allocated beforehand
+        unsafe {
+            cmd.pre_exec(move || {
+                // "0" is the writing process itself
+                let fd = nix::fcntl::open(procs.as_c_str(), OFlag::O_WRONLY, Mode::empty())?;
+                let written = nix::unistd::write(fd, b"0");
+                let _ = nix::unistd::close(fd);
+                written?;
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn attach(&self, _cmd: &mut Command) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Keeps the cgroup of the spawned process `pid` for handle_child, and removes the cgroups of
+    /// the processes that exited without reaching it, e.g. when writing to their stdin failed
+    pub async fn register(self, pid: u32) {
+        let orphaned = {
+            let mut cgroups = CHILD_CGROUPS.lock().unwrap();
+            cgroups.insert(pid, self);
+            let exited = cgroups
+                .keys()
+                .copied()
+                .filter(|pid| !process_exists(*pid))
+                .collect::<Vec<_>>();
+            exited
+                .into_iter()
+                .filter_map(|pid| cgroups.remove(&pid))
+                .collect::<Vec<_>>()
+        };
+        for cgroup in orphaned {
+            cgroup.remove().await;
+        }
+    }
+
+    pub fn take(pid: u32) -> Option<JobCgroup> {
+        CHILD_CGROUPS.lock().unwrap().remove(&pid)
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn write(&self, file: &str, value: String) -> std::io::Result<()> {
+        tokio::fs::write(self.path.join(file), value).await
+    }
+
+    /// Removes the cgroup once its process exited. Fails if the process failed after processes of
+    /// the job were killed for reaching its memory limit.
+    pub async fn release(
+        self,
+        success: bool,
+        child_name: &str,
+        mem_peak: i32,
+    ) -> error::Result<()> {
+        let oom_killed = !success && self.oom_killed().await;
+        self.remove().await;
+        if oom_killed {
+            return Err(error::Error::OutOfMemory(format!(
+                "child process '{child_name}' was killed after reaching the memory limit of the job (peak memory: {mem_peak}kB)"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether processes of the job were killed because it reached its memory limit
+    #[cfg(target_os = "linux")]
+    pub async fn oom_killed(&self) -> bool {
+        tokio::fs::read_to_string(self.path.join("memory.events"))
+            .await
+            .ok()
+            .and_then(|events| {
+                events
+                    .lines()
+                    .find_map(|l| l.strip_prefix("oom_kill "))
+                    .and_then(|x| x.trim().parse::<u64>().ok())
+            })
+            .is_some_and(|x| x > 0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub async fn oom_killed(&self) -> bool {
+        false
+    }
+
+    /// Removes the cgroup, killing the processes the job may have left behind
+    #[cfg(target_os = "linux")]
+    pub async fn remove(&self) {
+        let _ = self.write("cgroup.kill", "1".to_string()).await;
+        for _ in 0..10 {
+            match tokio::fs::remove_dir(&self.path).await {
+                Ok(()) => return,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(100)).await,
+            }
+        }
+        tracing::warn!("could not remove cgroup {}", self.path.display());
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub async fn remove(&self) {}
+}
+
+/// Whether the process `pid` was not reaped yet
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+    std::path::Path::new(&format!("/proc/{pid}")).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(target_os = "linux")]
+async fn init_cgroup_root() -> Option<PathBuf> {
+    let init = async {
+        let root = PathBuf::from(
+            windmill_common::worker::get_cgroupv2_path()
+                .ok_or_else(|| anyhow::anyhow!("cgroup v2 is not available"))?,
+        );
+        let leaf = root.join("worker");
+        tokio::fs::create_dir_all(&leaf).await?;
+        let procs = tokio::fs::read_to_string(root.join("cgroup.procs")).await?;
+        for pid in procs.lines().filter(|l| !l.is_empty()) {
+            // processes may have exited in the meantime
+            let _ = tokio::fs::write(leaf.join("cgroup.procs"), pid).await;
+        }
+        tokio::fs::write(root.join("cgroup.subtree_control"), "+cpu +memory +pids").await?;
+        Ok::<_, anyhow::Error>(root)
+    };
+    match init.await {
+        Ok(root) => {
+            tracing::info!("job resource limits enabled in cgroup {}", root.display());
+            Some(root)
+        }
+        Err(e) => {
+            tracing::warn!("job resource limits are configured but cannot be enforced: {e:#}");
+            None
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    async fn cgroup_with_events(events: &str) -> JobCgroup {
+        let path = std::env::temp_dir().join(format!("windmill-cgroup-{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&path).await.unwrap();
+        tokio::fs::write(path.join("memory.events"), events)
+            .await
+            .unwrap();
+        JobCgroup { path }
+    }
+
+    #[tokio::test]
+    async fn test_release_oom_killed() {
+        let oom_events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 1\n";
+
+        let cgroup = cgroup_with_events(oom_events).await;
+        let path = cgroup.path.clone();
+        let err = cgroup.release(false, "python", 2048).await.unwrap_err();
+        assert!(
+            matches!(&err, error::Error::OutOfMemory(msg) if msg.contains("'python'") && msg.contains("2048kB")),
+            "{err:?}"
+        );
+        let _ = tokio::fs::remove_dir_all(path).await;
+
+        // a process that succeeded despite an oom kill of another process of the job
+        let cgroup = cgroup_with_events(oom_events).await;
+        let path = cgroup.path.clone();
+        assert!(cgroup.release(true, "python", 2048).await.is_ok());
+        let _ = tokio::fs::remove_dir_all(path).await;
+
+        // a failure without oom kill is left to the exit status of the process
+        let cgroup = cgroup_with_events("oom 0\noom_kill 0\n").await;
+        let path = cgroup.path.clone();
+        assert!(cgroup.release(false, "python", 2048).await.is_ok());
+        let _ = tokio::fs::remove_dir_all(path).await;
+    }
+}
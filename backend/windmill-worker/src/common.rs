@@ -44,6 +44,7 @@ use windmill_common::{variables, DB};
 use tokio::{io::AsyncWriteExt, process::Child, time::Instant};
 
 use crate::agent_workers::UPDATE_PING_URL;
+use crate::cgroup::JobCgroup;
 use crate::{
     AuthedClient, DISABLE_NSJAIL, JOB_DEFAULT_TIMEOUT, MAX_RESULT_SIZE, MAX_TIMEOUT_DURATION,
     PATH_ENV,
@@ -613,10 +614,33 @@ impl OccupancyMetrics {
     }
 }
 
-pub async fn start_child_process(mut cmd: Command, executable: &str) -> Result<Child, Error> {
-    return cmd
-        .spawn()
-        .map_err(|err| tentatively_improve_error(err.into(), executable));
+/// Spawns the process of a job, in a cgroup holding the resource limits of the job if it has any
+pub async fn start_child_process(
+    mut cmd: Command,
+    executable: &str,
+    job_id: &Uuid,
+    conn: &Connection,
+) -> Result<Child, Error> {
+    let cgroup = JobCgroup::for_job(job_id, conn).await;
+    if let Some(cgroup) = cgroup.as_ref() {
+        if let Err(e) = cgroup.attach(&mut cmd) {
+            cgroup.remove().await;
+            return Err(e.into());
+        }
+    }
+    let child = spawn_process(cmd, executable);
+    match (cgroup, child.as_ref().ok().and_then(|c| c.id())) {
+        (Some(cgroup), Some(pid)) => cgroup.register(pid).await,
+        (Some(cgroup), None) => cgroup.remove().await,
+        (None, _) => (),
+    }
+    child
+}
+
+/// Spawns a process that is not run on behalf of a job, without resource limits
+pub fn spawn_process(mut cmd: Command, executable: &str) -> Result<Child, Error> {
+    cmd.spawn()
+        .map_err(|err| tentatively_improve_error(err.into(), executable))
 }
 
 pub async fn resolve_job_timeout(
@@ -1290,7 +1314,7 @@ pub async fn par_install_language_dependencies<'a>(
                     short_name: short_name.clone(),
                 })?;
                 tracing::debug!("{:?}", &cmd);
-                Some(start_child_process(cmd, &installer_executable_name).await?)
+                Some(start_child_process(cmd, &installer_executable_name, job_id, conn).await?)
             } else {
                 None
             }
@@ -1491,7 +1515,7 @@ pub async fn par_install_language_dependencies<'a>(
             .await;
             let cmd = callback(not_pulled_copy.clone())?;
             tracing::debug!("{:?}", &cmd);
-            let child = start_child_process(cmd, &installer_executable_name).await?;
+            let child = spawn_process(cmd, &installer_executable_name)?;
             let mut buf = "".to_owned();
             let pipe_stdout = if stdout_on_err { Some(&mut buf) } else { None };
             if let Err(e) = crate::handle_child::handle_child(
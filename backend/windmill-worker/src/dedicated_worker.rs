@@ -29,7 +29,7 @@ use windmill_queue::MiniPulledJob;
 
 use anyhow::Context;
 
-use crate::{common::start_child_process, JobCompletedSender, MAX_BUFFERED_DEDICATED_JOBS};
+use crate::{common::spawn_process, JobCompletedSender, MAX_BUFFERED_DEDICATED_JOBS};
 
 use futures::{future, Future};
 use std::{collections::HashMap, task::Poll};
@@ -98,7 +98,7 @@ pub async fn handle_dedicated_process(
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(cmd, command_path).await?
+        spawn_process(cmd, command_path)?
     };
 
     let stdout = child
@@ -434,6 +434,7 @@ async fn spawn_dedicated_workers_for_flow(
                 }
                 FlowModuleValue::Flow { .. } => (),
                 FlowModuleValue::Identity => (),
+                FlowModuleValue::Sleep { .. } => (),
             }
         } else {
             tracing::error!("failed to get value for module: {:?}", module);
@@ -8,7 +8,7 @@ use windmill_queue::{append_logs, CanceledBy, MiniPulledJob};
 use crate::{
     common::{
         create_args_and_out_file, get_reserved_variables, parse_npm_config, read_file, read_result,
-        start_child_process, OccupancyMetrics,
+        spawn_process, start_child_process, OccupancyMetrics,
     },
     handle_child::handle_child,
     AuthedClient, DENO_CACHE_DIR, DENO_PATH, DISABLE_NSJAIL, HOME_ENV, NPM_CONFIG_REGISTRY,
@@ -143,7 +143,10 @@ pub async fn generate_deno_lock(
         .envs(deno_envs)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
-    let mut child_process = start_child_process(child_cmd, DENO_PATH.as_str()).await?;
+    let mut child_process = match db {
+        Some(db) => start_child_process(child_cmd, DENO_PATH.as_str(), job_id, db).await?,
+        None => spawn_process(child_cmd, DENO_PATH.as_str())?,
+    };
 
     if let Some(db) = db {
         handle_child(
@@ -402,7 +405,7 @@ try {{
             .args(args)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(deno_cmd, DENO_PATH.as_str()).await?
+        start_child_process(deno_cmd, DENO_PATH.as_str(), &job.id, conn).await?
     };
     // logs.push_str(format!("prepare: {:?}\n", start.elapsed().as_micros()).as_str());
     // start = Instant::now();
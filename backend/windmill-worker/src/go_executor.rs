@@ -97,91 +97,7 @@ pub async fn handle_go_job(
         .await?;
 
         create_args_and_out_file(client, job, job_dir, conn).await?;
-        {
-            let sig = windmill_parser_go::parse_go_sig(&inner_content)?;
-
-            const WRAPPER_CONTENT: &str = r#"package main
-
-import (
-    "encoding/json"
-    "os"
-    "fmt"
-    "mymod/inner"
-)
-
-func main() {{
-
-    dat, err := os.ReadFile("args.json")
-    if err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-
-    var req inner.Req
-
-    if err := json.Unmarshal(dat, &req); err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-
-    res, err := inner.Run(req)
-    if err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-    res_json, err := json.Marshal(res)
-    if err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-    f, err := os.OpenFile("result.json", os.O_APPEND|os.O_WRONLY, os.ModeAppend)
-    if err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-    _, err = f.WriteString(string(res_json))
-    if err != nil {{
-        fmt.Println(err)
-        os.Exit(1)
-    }}
-}}"#;
-
-            write_file(job_dir, "main.go", WRAPPER_CONTENT)?;
-
-            {
-                let spread = &sig
-                    .args
-                    .clone()
-                    .into_iter()
-                    .map(|x| format!("req.{}", capitalize(&x.name)))
-                    .join(", ");
-                let req_body = &sig
-                    .args
-                    .into_iter()
-                    .map(|x| {
-                        format!(
-                            "{} {} `json:\"{}\"`",
-                            capitalize(&x.name),
-                            windmill_parser_go::otyp_to_string(x.otyp),
-                            x.name
-                        )
-                    })
-                    .join("\n");
-                let runner_content: String = format!(
-                    r#"package inner
-type Req struct {{
-    {req_body}
-}}
-
-func Run(req Req) (interface{{}}, error){{
-    return main({spread})
-}}
-
-"#,
-                );
-                write_file(&format!("{job_dir}/inner"), "runner.go", &runner_content)?;
-            }
-        }
+        gen_go_wrapper(inner_content, job_dir)?;
 
         let mut build_go_cmd = Command::new(GO_PATH.as_str());
         build_go_cmd
@@ -199,7 +115,8 @@ func Run(req Req) (interface{{}}, error){{
         #[cfg(windows)]
         build_go_cmd.env("USERPROFILE", crate::USERPROFILE_ENV.as_str());
 
-        let build_go_process = start_child_process(build_go_cmd, GO_PATH.as_str()).await?;
+        let build_go_process =
+            start_child_process(build_go_cmd, GO_PATH.as_str(), &job.id, conn).await?;
         handle_child(
             &job.id,
             conn,
@@ -276,7 +193,7 @@ func Run(req Req) (interface{{}}, error){{
             .args(vec!["--config", "run.config.proto", "--", "/tmp/go/main"])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         let compiled_executable_name = "./main";
         let mut run_go = Command::new(compiled_executable_name);
@@ -302,7 +219,7 @@ func Run(req Req) (interface{{}}, error){{
         run_go.env("USERPROFILE", crate::USERPROFILE_ENV.as_str());
 
         run_go.stdout(Stdio::piped()).stderr(Stdio::piped());
-        start_child_process(run_go, compiled_executable_name).await?
+        start_child_process(run_go, compiled_executable_name, &job.id, conn).await?
     };
     handle_child(
         &job.id,
@@ -324,6 +241,95 @@ func Run(req Req) (interface{{}}, error){{
     read_result(job_dir).await
 }
 
+/// Writes the main package calling the `main` of the script with the args read from `args.json`
+/// and writing its result to `result.json`
+pub(crate) fn gen_go_wrapper(inner_content: &str, job_dir: &str) -> error::Result<()> {
+    let sig = windmill_parser_go::parse_go_sig(inner_content)?;
+
+    const WRAPPER_CONTENT: &str = r#"package main
+
+import (
+    "encoding/json"
+    "os"
+    "fmt"
+    "mymod/inner"
+)
+
+func main() {{
+
+    dat, err := os.ReadFile("args.json")
+    if err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+
+    var req inner.Req
+
+    if err := json.Unmarshal(dat, &req); err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+
+    res, err := inner.Run(req)
+    if err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+    res_json, err := json.Marshal(res)
+    if err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+    f, err := os.OpenFile("result.json", os.O_APPEND|os.O_WRONLY, os.ModeAppend)
+    if err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+    _, err = f.WriteString(string(res_json))
+    if err != nil {{
+        fmt.Println(err)
+        os.Exit(1)
+    }}
+}}"#;
+
+    write_file(job_dir, "main.go", WRAPPER_CONTENT)?;
+
+    {
+        let spread = &sig
+            .args
+            .clone()
+            .into_iter()
+            .map(|x| format!("req.{}", capitalize(&x.name)))
+            .join(", ");
+        let req_body = &sig
+            .args
+            .into_iter()
+            .map(|x| {
+                format!(
+                    "{} {} `json:\"{}\"`",
+                    capitalize(&x.name),
+                    windmill_parser_go::otyp_to_string(x.otyp),
+                    x.name
+                )
+            })
+            .join("\n");
+        let runner_content: String = format!(
+            r#"package inner
+type Req struct {{
+    {req_body}
+}}
+
+func Run(req Req) (interface{{}}, error){{
+    return main({spread})
+}}
+
+"#,
+        );
+        write_file(&format!("{job_dir}/inner"), "runner.go", &runner_content)?;
+    }
+    Ok(())
+}
+
 async fn gen_go_mod(
     inner_content: &str,
     job_dir: &str,
@@ -367,7 +373,7 @@ pub async fn install_go_dependencies(
             .args(vec!["mod", "init", "mymod"])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        let child_process = start_child_process(child_cmd, GO_PATH.as_str()).await?;
+        let child_process = start_child_process(child_cmd, GO_PATH.as_str(), job_id, conn).await?;
 
         handle_child(
             job_id,
@@ -436,7 +442,7 @@ pub async fn install_go_dependencies(
         .args(vec!["mod", mod_command])
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
-    let child_process = start_child_process(child_cmd, GO_PATH.as_str()).await?;
+    let child_process = start_child_process(child_cmd, GO_PATH.as_str(), job_id, conn).await?;
 
     handle_child(
         job_id,
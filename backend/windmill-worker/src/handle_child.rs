@@ -31,6 +31,7 @@ use std::sync::atomic::AtomicU32;
 use std::sync::Arc;
 use std::{io, panic, time::Duration};
 
+use chrono::Utc;
 use tracing::{trace_span, Instrument};
 use uuid::Uuid;
 
@@ -51,8 +52,11 @@ use futures::{
     stream, StreamExt,
 };
 
+use crate::cgroup::JobCgroup;
 use crate::common::{resolve_job_timeout, OccupancyMetrics};
-use crate::job_logger::{append_job_logs, append_with_limit};
+use crate::job_logger::{
+    append_job_logs, append_structured_logs, append_with_limit, parse_log_line, STRUCTURED_JOB_LOGS,
+};
 use crate::job_logger_ee::process_streaming_log_lines;
 use crate::worker_utils::{ping_job_status, update_worker_ping_from_job};
 use crate::{MAX_RESULT_SIZE, MAX_WAIT_FOR_SIGINT, MAX_WAIT_FOR_SIGTERM};
@@ -130,6 +134,8 @@ pub async fn handle_child(
     } else {
         tracing::info!("could not get child pid");
     }
+
+    let cgroup = pid.and_then(JobCgroup::take);
     let (mut set_too_many_logs, mut too_many_logs) = watch::channel::<bool>(false);
     let (tx, rx) = broadcast::channel::<()>(3);
     let mut rx2: broadcast::Receiver<()> = tx.subscribe();
@@ -317,6 +323,10 @@ pub async fn handle_child(
         && wait_result.as_ref().unwrap().as_ref().unwrap().success();
     tracing::info!(%job_id, %success, %mem_peak, %worker, "child process '{child_name}' took {}ms", start.elapsed().as_millis());
 
+    if let Some(cgroup) = cgroup {
+        cgroup.release(success, child_name, *mem_peak).await?;
+    }
+
     match wait_result {
         _ if *too_many_logs.borrow() => Err(Error::ExecutionErr(format!(
             "logs or result reached limit. (current max size: {MAX_RESULT_SIZE} characters)"
@@ -335,7 +345,7 @@ pub async fn handle_child(
 }
 
 pub async fn write_lines(
-    output: impl stream::Stream<Item = io::Result<String>> + Send,
+    output: impl stream::Stream<Item = io::Result<(String, bool)>> + Send,
     job_id: &Uuid,
     w_id: &str,
     worker: &str,
@@ -398,15 +408,25 @@ pub async fn write_lines(
         /* Read up until an error is encountered,
          * handle log lines first and then the error... */
         let mut joined = String::new();
+        let mut structured = vec![];
 
         let job_id = job_id.clone();
         while let Some(line) = read_lines.next().await {
             match line {
-                Ok(line) => {
+                Ok((line, stderr)) => {
                     if line.is_empty() {
                         continue;
                     }
+                    let appended_at = joined.len();
                     append_with_limit(&mut joined, &line, &mut log_remaining);
+                    if *STRUCTURED_JOB_LOGS && pipe_stdout.is_none() {
+                        // only the part of the line that fit in the log limit
+                        let appended = &joined[appended_at..];
+                        let appended = appended.strip_prefix('\n').unwrap_or(appended);
+                        if !appended.is_empty() {
+                            structured.push(parse_log_line(appended, stderr, Utc::now()));
+                        }
+                    }
                     if log_remaining == 0 {
                         tracing::info!(%job_id, "Too many logs lines for job {job_id}");
                         let _ = set_too_many_logs.send(true);
@@ -470,6 +490,7 @@ pub async fn write_lines(
                     &worker_name,
                 )
                 .await;
+                append_structured_logs(&job_id, structured, &conn).await;
             })
             .remote_handle();
         }
@@ -725,12 +746,13 @@ where
 
 /// takes stdout and stderr from Child, panics if either are not present
 ///
-/// builds a stream joining both stdout and stderr each read line by line
+/// builds a stream joining both stdout and stderr each read line by line, along with whether
+/// the line was read from stderr
 fn child_joined_output_stream(
     child: &mut Child,
     job_id: Uuid,
     w_id: String,
-) -> impl stream::FusedStream<Item = io::Result<String>> {
+) -> impl stream::FusedStream<Item = io::Result<(String, bool)>> {
     let stderr = child
         .stderr
         .take()
@@ -744,8 +766,9 @@ fn child_joined_output_stream(
     let stdout = BufReader::new(stdout).lines();
     let stderr = BufReader::new(stderr).lines();
     stream::select(
-        lines_to_stream(stderr, true, job_id.clone(), w_id.clone()),
-        lines_to_stream(stdout, false, job_id, w_id),
+        lines_to_stream(stderr, true, job_id.clone(), w_id.clone())
+            .map(|line| line.map(|line| (line, true))),
+        lines_to_stream(stdout, false, job_id, w_id).map(|line| line.map(|line| (line, false))),
     )
 }
 
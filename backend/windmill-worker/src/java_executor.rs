@@ -516,7 +516,7 @@ async fn compile<'a>(
                         std::env::var("TMP").unwrap_or_else(|_| String::from("/tmp")),
                     );
             }
-            start_child_process(cmd, "javac").await?
+            start_child_process(cmd, "javac", &job.id, conn).await?
         };
         handle_child::handle_child(
             &job.id,
@@ -640,7 +640,7 @@ async fn run<'a>(
         cmd.args(vec!["-classpath", &classpath, "net.script.App"]);
         cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 
-        start_child_process(cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         append_logs(
             &job.id,
@@ -699,7 +699,7 @@ async fn run<'a>(
                     std::env::var("TMP").unwrap_or_else(|_| String::from("/tmp")),
                 );
         }
-        start_child_process(cmd, "java").await?
+        start_child_process(cmd, "java", &job.id, conn).await?
     };
     handle_child::handle_child(
         &job.id,
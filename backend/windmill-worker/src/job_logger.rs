@@ -1,6 +1,8 @@
+use chrono::{DateTime, Utc};
 use regex::Regex;
 
 pub use windmill_common::jobs::LARGE_LOG_THRESHOLD_SIZE;
+use windmill_common::jobs::{JobLogLevel, JobLogStream};
 use windmill_common::utils::WarnAfterExt;
 use windmill_common::worker::{Connection, CLOUD_HOSTED};
 
@@ -142,3 +144,185 @@ pub fn append_with_limit(dst: &mut String, src: &str, limit: &mut usize) {
         *limit = 0;
     }
 }
+
+lazy_static::lazy_static! {
+    /// Also store the log lines of the jobs as rows, with the level and timestamp parsed from
+    /// JSON logs or from the usual python logging formats, to filter and search them
+    pub static ref STRUCTURED_JOB_LOGS: bool = std::env::var("STRUCTURED_JOB_LOGS").ok().is_some_and(|x| x == "1" || x == "true");
+
+    // python logging.basicConfig default format: `WARNING:root:message`
+    static ref RE_PYTHON_LOG_DEFAULT: Regex = Regex::new(
+        r"^(?P<level>DEBUG|INFO|WARNING|ERROR|CRITICAL):(?P<msg>.*)$"
+    ).unwrap();
+    // `2025-01-01 12:00:00,123 - name - WARNING - message`, `2025-01-01T12:00:00 [WARNING] message`, ...
+    static ref RE_TIMESTAMPED_LOG: Regex = Regex::new(
+        r"^(?P<ts>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(?:[.,]\d{1,9})?(?:Z|[+-]\d{2}:?\d{2})?)\s+(?:-\s+)?(?:[\w.]+\s+-\s+)?\[?(?P<level>DEBUG|INFO|WARNING|WARN|ERROR|CRITICAL|FATAL)\]?(?:\s+-\s+|:\s*|\s+)(?P<msg>.*)$"
+    ).unwrap();
+}
+
+pub struct StructuredLogLine {
+    pub ts: DateTime<Utc>,
+    pub level: Option<JobLogLevel>,
+    pub stream: JobLogStream,
+    pub message: String,
+}
+
+/// None if the timestamp has no timezone, as the one of the job is unknown
+fn parse_log_timestamp(ts: &str) -> Option<DateTime<Utc>> {
+    let ts = ts.replace(',', ".").replace('T', " ");
+    DateTime::parse_from_rfc3339(&ts)
+        .or_else(|_| DateTime::parse_from_str(&ts, "%Y-%m-%d %H:%M:%S%.f%z"))
+        .ok()
+        .map(|ts| ts.with_timezone(&Utc))
+}
+
+/// Parses the level and timestamp of a log line, from a JSON object (`level`/`levelname`/
+/// `severity`, `message`/`msg`, `timestamp`/`time`/`ts`) or from the python logging formats.
+/// The timestamp defaults to `read_at` and the level to None if none can be found.
+pub fn parse_log_line(line: &str, stderr: bool, read_at: DateTime<Utc>) -> StructuredLogLine {
+    let stream = if stderr {
+        JobLogStream::Stderr
+    } else {
+        JobLogStream::Stdout
+    };
+    let unparsed =
+        || StructuredLogLine { ts: read_at, level: None, stream, message: line.to_string() };
+
+    let trimmed = line.trim();
+    if trimmed.starts_with('{') {
+        let Ok(serde_json::Value::Object(obj)) = serde_json::from_str(trimmed) else {
+            return unparsed();
+        };
+        let str_field = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|k| obj.get(*k).and_then(|v| v.as_str()))
+        };
+        return StructuredLogLine {
+            ts: str_field(&["timestamp", "time", "ts", "asctime"])
+                .and_then(parse_log_timestamp)
+                .unwrap_or(read_at),
+            level: str_field(&["level", "levelname", "severity"]).and_then(JobLogLevel::parse),
+            stream,
+            message: str_field(&["message", "msg"])
+                .map(str::to_string)
+                .unwrap_or_else(|| line.to_string()),
+        };
+    }
+
+    if let Some(captures) = RE_PYTHON_LOG_DEFAULT.captures(trimmed) {
+        return StructuredLogLine {
+            ts: read_at,
+            level: JobLogLevel::parse(&captures["level"]),
+            stream,
+            message: captures["msg"].to_string(),
+        };
+    }
+
+    if let Some(captures) = RE_TIMESTAMPED_LOG.captures(trimmed) {
+        return StructuredLogLine {
+            ts: parse_log_timestamp(&captures["ts"]).unwrap_or(read_at),
+            level: JobLogLevel::parse(&captures["level"]),
+            stream,
+            message: captures["msg"].to_string(),
+        };
+    }
+
+    unparsed()
+}
+
+/// Stores the parsed log lines of a job, keyed by the root flow of the job, found by walking up
+/// the parent jobs since the steps of nested flows only point to their direct parent
+pub async fn append_structured_logs(
+    job_id: &Uuid,
+    lines: Vec<StructuredLogLine>,
+    conn: &Connection,
+) {
+    // agent workers only send the plain text logs
+    let Connection::Sql(db) = conn else {
+        return;
+    };
+    if lines.is_empty() {
+        return;
+    }
+
+    let mut ts = Vec::with_capacity(lines.len());
+    let mut levels = Vec::with_capacity(lines.len());
+    let mut streams = Vec::with_capacity(lines.len());
+    let mut messages = Vec::with_capacity(lines.len());
+    for line in lines {
+        ts.push(line.ts);
+        levels.push(line.level.map(|l| l.as_str().to_string()));
+        streams.push(line.stream.as_str().to_string());
+        messages.push(line.message);
+    }
+
+    if let Err(err) = sqlx::query!(
+        "WITH RECURSIVE ancestor AS (
+            SELECT id, parent_job, 0 AS depth FROM v2_job WHERE id = $1
+            UNION ALL
+            SELECT p.id, p.parent_job, a.depth + 1
+            FROM v2_job p JOIN ancestor a ON p.id = a.parent_job
+            WHERE a.depth < 100
+        )
+        INSERT INTO job_log_line (job_id, root_job_id, workspace_id, step_id, ts, level, stream, message)
+        SELECT j.id, (SELECT id FROM ancestor ORDER BY depth DESC LIMIT 1), j.workspace_id,
+            j.flow_step_id, l.ts, l.level::JOB_LOG_LEVEL, l.stream::JOB_LOG_STREAM, l.message
+        FROM v2_job j, UNNEST($2::timestamptz[], $3::text[], $4::text[], $5::text[]) AS l(ts, level, stream, message)
+        WHERE j.id = $1",
+        job_id,
+        &ts,
+        &levels as &[Option<String>],
+        &streams,
+        &messages,
+    )
+    .execute(db)
+    .warn_after_seconds(1)
+    .await
+    {
+        tracing::error!(%job_id, %err, "error inserting structured logs for job {job_id}: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_line() {
+        let now = Utc::now();
+
+        let l = parse_log_line(
+            r#"{"level": "warn", "msg": "disk almost full", "time": "2025-05-30T10:00:00Z"}"#,
+            false,
+            now,
+        );
+        assert_eq!(l.level, Some(JobLogLevel::Warning));
+        assert_eq!(l.message, "disk almost full");
+        assert_eq!(l.ts.to_rfc3339(), "2025-05-30T10:00:00+00:00");
+
+        let l = parse_log_line("ERROR:root:connection refused", true, now);
+        assert_eq!(l.level, Some(JobLogLevel::Error));
+        assert_eq!(l.stream, JobLogStream::Stderr);
+        assert_eq!(l.message, "root:connection refused");
+        assert_eq!(l.ts, now);
+
+        let l = parse_log_line(
+            "2025-05-30 10:00:00,123 - etl.import - INFO - 42 rows imported",
+            true,
+            now,
+        );
+        assert_eq!(l.level, Some(JobLogLevel::Info));
+        assert_eq!(l.message, "42 rows imported");
+        // no timezone, the time the line was read is kept
+        assert_eq!(l.ts, now);
+
+        let l = parse_log_line("2025-05-30T12:00:00,123+0200 [WARN] retrying", false, now);
+        assert_eq!(l.level, Some(JobLogLevel::Warning));
+        assert_eq!(l.message, "retrying");
+        assert_eq!(l.ts.to_rfc3339(), "2025-05-30T10:00:00.123+00:00");
+
+        let l = parse_log_line("hello world", false, now);
+        assert_eq!(l.level, None);
+        assert_eq!(l.message, "hello world");
+    }
+}
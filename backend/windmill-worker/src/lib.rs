@@ -14,12 +14,15 @@ mod bash_executor;
 mod java_executor;
 
 mod bun_executor;
+mod cgroup;
 pub mod common;
 mod config;
 mod csharp_executor;
 #[cfg(feature = "enterprise")]
 mod dedicated_worker;
 mod deno_executor;
+#[cfg(feature = "duckdb")]
+mod duckdb_executor;
 mod global_cache;
 mod go_executor;
 mod graphql_executor;
@@ -27,6 +30,8 @@ mod handle_child;
 pub mod job_logger;
 mod job_logger_ee;
 mod js_eval;
+#[cfg(feature = "lua")]
+mod lua_executor;
 #[cfg(feature = "mysql")]
 mod mysql_executor;
 #[cfg(feature = "nu")]
@@ -39,11 +44,17 @@ mod pg_executor;
 mod php_executor;
 #[cfg(feature = "python")]
 mod python_executor;
+#[cfg(feature = "r")]
+mod r_executor;
 pub mod result_processor;
+#[cfg(feature = "ruby")]
+mod ruby_executor;
 #[cfg(feature = "rust")]
 mod rust_executor;
 mod sanitized_sql_params;
 mod schema;
+#[cfg(feature = "wasm")]
+mod wasm_executor;
 mod worker;
 mod worker_flow;
 mod worker_lockfiles;
@@ -0,0 +1,281 @@
+//! Lua scripts, run in-process with an embedded Lua 5.4 interpreter.
+//!
+//! Only the table, string, utf8, math and coroutine libraries are available, `os` is reduced to its
+//! time functions and a `getenv` reading the reserved variables, and `print` goes to the job logs.
+//! `dofile`, `loadfile` and `collectgarbage` are removed and `load` only accepts text chunks, as
+//! crafted bytecode can break out of the interpreter. The `wmill` global exposes the same helpers
+//! as the ops of `js_eval`: `wmill.variable`, `wmill.resource` and `wmill.get_result`. JSON nulls
+//! nested in args are `wmill.null`, so that arrays keep their length, while a null or missing arg
+//! is `nil`.
+//!
+//! The interpreter runs on a blocking thread. A hook checked every few instructions interrupts it
+//! once the job is canceled or timed out, and memory is capped by the allocator of the state.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use mlua::{
+    Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, MultiValue, StdLib, Table,
+    Value as LuaValue, Variadic,
+};
+use serde_json::{value::RawValue, Value};
+use uuid::Uuid;
+use windmill_common::{
+    error::{self, to_anyhow, Error},
+    worker::{to_raw_value, Connection},
+};
+use windmill_parser_lua::parse_lua_sig;
+use windmill_queue::{append_logs, CanceledBy, MiniPulledJob};
+
+use crate::{
+    cgroup::resolve_resource_limits,
+    common::{build_args_values, OccupancyMetrics},
+    handle_child::run_future_with_polling_update_job_poller,
+    AuthedClient,
+};
+
+const HOOK_INSTRUCTIONS: u32 = 1000;
+
+lazy_static::lazy_static! {
+    static ref LUA_MAX_MEMORY_MB: u64 = std::env::var("LUA_MAX_MEMORY_MB")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(128);
+}
+
+/// Interrupts the running script when dropped, i.e. when the job is canceled or times out
+struct InterruptOnDrop(Arc<AtomicBool>);
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+pub async fn do_lua(
+    job: &MiniPulledJob,
+    client: &AuthedClient,
+    code: &str,
+    conn: &Connection,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    worker_name: &str,
+    reserved_variables: HashMap<String, String>,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> error::Result<Box<RawValue>> {
+    let sig = parse_lua_sig(code)?;
+    let mut args = build_args_values(job, client, conn).await?;
+    let spread = sig
+        .args
+        .iter()
+        .map(|arg| args.remove(&arg.name))
+        .collect::<Vec<_>>();
+
+    let max_memory_mb = resolve_resource_limits(&job.id, conn)
+        .await?
+        .and_then(|l| l.memory_max_mb)
+        .unwrap_or(*LUA_MAX_MEMORY_MB);
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let logs = Arc::new(Mutex::new(String::new()));
+
+    let state = LuaJob {
+        code: code.to_string(),
+        spread,
+        reserved_variables,
+        client: client.clone(),
+        job_id: job.id,
+        max_memory: usize::try_from(max_memory_mb * 1024 * 1024).unwrap_or(usize::MAX),
+        interrupted: interrupted.clone(),
+        peak: peak.clone(),
+        logs: logs.clone(),
+    };
+    let result_f = async move {
+        let _interrupt = InterruptOnDrop(interrupted);
+        let handle = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || state.run(handle))
+            .await
+            .map_err(to_anyhow)?
+            .map_err(|e| Error::ExecutionErr(e.to_string()))
+    };
+
+    let get_mem = peak.clone();
+    let result = run_future_with_polling_update_job_poller(
+        job.id,
+        job.timeout,
+        conn,
+        mem_peak,
+        canceled_by,
+        result_f,
+        worker_name,
+        &job.workspace_id,
+        &mut Some(occupancy_metrics),
+        Box::pin(futures::stream::repeat_with(move || {
+            (get_mem.load(Ordering::Relaxed) / 1024) as i32
+        })),
+    )
+    .await;
+
+    *mem_peak = (*mem_peak).max((peak.load(Ordering::Relaxed) / 1024) as i32);
+    let output = std::mem::take(&mut *logs.lock().unwrap());
+    append_logs(&job.id, &job.workspace_id, output, conn).await;
+
+    Ok(to_raw_value(&result?))
+}
+
+struct LuaJob {
+    code: String,
+    spread: Vec<Option<Value>>,
+    reserved_variables: HashMap<String, String>,
+    client: AuthedClient,
+    job_id: Uuid,
+    max_memory: usize,
+    interrupted: Arc<AtomicBool>,
+    peak: Arc<AtomicUsize>,
+    logs: Arc<Mutex<String>>,
+}
+
+impl LuaJob {
+    fn run(self, handle: tokio::runtime::Handle) -> mlua::Result<Value> {
+        let lua = Lua::new_with(
+            StdLib::TABLE
+                | StdLib::STRING
+                | StdLib::UTF8
+                | StdLib::MATH
+                | StdLib::COROUTINE
+                | StdLib::OS,
+            LuaOptions::default(),
+        )?;
+        lua.set_memory_limit(self.max_memory)?;
+
+        let (interrupted, peak) = (self.interrupted.clone(), self.peak.clone());
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+            move |lua, _| {
+                peak.fetch_max(lua.used_memory(), Ordering::Relaxed);
+                if interrupted.load(Ordering::Relaxed) {
+                    Err(mlua::Error::runtime("job was interrupted"))
+                } else {
+                    Ok(())
+                }
+            },
+        );
+
+        self.set_globals(&lua, handle)?;
+
+        let main: Function = lua
+            .load(format!("{}\nreturn main", self.code))
+            .set_name("main")
+            .eval()?;
+        let args = self
+            .spread
+            .iter()
+            .map(|arg| match arg {
+                Some(Value::Null) | None => Ok(LuaValue::Nil),
+                Some(arg) => lua.to_value(arg),
+            })
+            .collect::<mlua::Result<Vec<_>>>()?;
+        let result: LuaValue = main.call(MultiValue::from_vec(args))?;
+
+        self.peak.fetch_max(lua.used_memory(), Ordering::Relaxed);
+        lua.from_value(result)
+    }
+
+    fn set_globals(&self, lua: &Lua, handle: tokio::runtime::Handle) -> mlua::Result<()> {
+        let globals = lua.globals();
+
+        let os: Table = globals.get("os")?;
+        let sandboxed_os = lua.create_table()?;
+        for f in ["clock", "date", "difftime", "time"] {
+            sandboxed_os.set(f, os.get::<_, LuaValue>(f)?)?;
+        }
+        let reserved_variables = self.reserved_variables.clone();
+        sandboxed_os.set(
+            "getenv",
+            lua.create_function(move |_, name: String| Ok(reserved_variables.get(&name).cloned()))?,
+        )?;
+        globals.set("os", sandboxed_os)?;
+
+        for f in ["dofile", "loadfile", "collectgarbage"] {
+            globals.set(f, LuaValue::Nil)?;
+        }
+        let text_load: Function = lua
+            .load(
+                r##"
+                local load = load
+                return function(chunk, chunkname, _, ...)
+                    if select("#", ...) > 0 then
+                        return load(chunk, chunkname, "t", ...)
+                    end
+                    return load(chunk, chunkname, "t")
+                end
+                "##,
+            )
+            .eval()?;
+        globals.set("load", text_load)?;
+
+        let logs = self.logs.clone();
+        globals.set(
+            "print",
+            lua.create_function(move |lua, values: Variadic<LuaValue>| {
+                let tostring: Function = lua.globals().get("tostring")?;
+                let line = values
+                    .into_iter()
+                    .map(|v| tostring.call::<_, String>(v))
+                    .collect::<mlua::Result<Vec<_>>>()?
+                    .join("\t");
+                let mut logs = logs.lock().unwrap();
+                logs.push_str(&line);
+                logs.push('\n');
+                Ok(())
+            })?,
+        )?;
+
+        let wmill = lua.create_table()?;
+
+        let (client, h) = (self.client.clone(), handle.clone());
+        wmill.set(
+            "variable",
+            lua.create_function(move |_, path: String| {
+                h.block_on(client.get_variable_value(&path))
+                    .map_err(mlua::Error::external)
+            })?,
+        )?;
+
+        let (client, h, job_id) = (self.client.clone(), handle.clone(), self.job_id);
+        wmill.set(
+            "resource",
+            lua.create_function(move |lua, path: String| {
+                let value = h
+                    .block_on(client.get_resource_value_interpolated::<Option<Value>>(
+                        &path,
+                        Some(job_id.to_string()),
+                    ))
+                    .map_err(mlua::Error::external)?;
+                lua.to_value(&value)
+            })?,
+        )?;
+
+        let (client, h) = (self.client.clone(), handle);
+        wmill.set(
+            "get_result",
+            lua.create_function(move |lua, id: String| {
+                let value = h
+                    .block_on(client.get_completed_job_result::<Value>(&id, None))
+                    .map_err(mlua::Error::external)?;
+                lua.to_value(&value)
+            })?,
+        )?;
+
+        wmill.set("null", lua.null())?;
+
+        globals.set("wmill", wmill)?;
+        Ok(())
+    }
+}
@@ -271,7 +271,7 @@ async fn run<'a>(
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         append_logs(
             &job.id,
@@ -322,7 +322,7 @@ async fn run<'a>(
                     std::env::var("TMP").unwrap_or_else(|_| String::from("/tmp")),
                 );
         }
-        start_child_process(cmd, "nu").await?
+        start_child_process(cmd, "nu", &job.id, conn).await?
     };
     handle_child::handle_child(
         &job.id,
@@ -73,7 +73,7 @@ use windmill_common::s3_helpers::OBJECT_STORE_CACHE_SETTINGS;
 
 use crate::{
     common::{
-        create_args_and_out_file, get_reserved_variables, read_file, read_result,
+        create_args_and_out_file, get_reserved_variables, read_file, read_result, spawn_process,
         start_child_process, OccupancyMetrics,
     },
     handle_child::handle_child,
@@ -337,7 +337,7 @@ impl PyVersion {
                 );
         }
 
-        let child_process = start_child_process(child_cmd, "uv").await?;
+        let child_process = start_child_process(child_cmd, "uv", job_id, conn).await?;
 
         append_logs(&job_id, &w_id, logs, conn).await;
         handle_child(
@@ -634,7 +634,7 @@ pub async fn uv_pip_compile(
                 );
         }
 
-        let child_process = start_child_process(child_cmd, uv_cmd).await?;
+        let child_process = start_child_process(child_cmd, uv_cmd, job_id, conn).await?;
         append_logs(&job_id, &w_id, logs, conn).await;
         handle_child(
             job_id,
@@ -1115,7 +1115,7 @@ mount {{
             ])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         let mut python_cmd = Command::new(&python_path);
 
@@ -1144,7 +1144,7 @@ mount {{
             );
         }
 
-        start_child_process(python_cmd, &python_path).await?
+        start_child_process(python_cmd, &python_path, &job.id, conn).await?
     };
 
     handle_child(
@@ -1620,7 +1620,7 @@ async fn spawn_uv_install(
             .args(vec!["--config", &nsjail_proto])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await
+        spawn_process(nsjail_cmd, NSJAIL_PATH.as_str())
     } else {
         #[cfg(unix)]
         let req = req.to_owned();
@@ -1703,7 +1703,7 @@ async fn spawn_uv_install(
                 .args(&command_args[1..])
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped());
-            start_child_process(cmd, UV_PATH.as_str()).await
+            spawn_process(cmd, UV_PATH.as_str())
         }
 
         #[cfg(windows)]
@@ -1726,7 +1726,7 @@ async fn spawn_uv_install(
                 .args(&command_args[1..])
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped());
-            start_child_process(cmd, "uv").await
+            spawn_process(cmd, "uv")
         }
     }
 }
@@ -0,0 +1,450 @@
+use std::{collections::HashMap, process::Stdio, sync::Arc};
+
+use futures::StreamExt;
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::{value::RawValue, Value};
+use tokio::process::Command;
+use uuid::Uuid;
+use windmill_common::{
+    error::{self, Error, Result},
+    s3_helpers::convert_json_line_stream,
+    utils::calculate_hash,
+    worker::{to_raw_value, write_file, Connection},
+};
+use windmill_parser_sql::parse_s3_mode_args;
+use windmill_queue::{append_logs, CanceledBy, MiniPulledJob};
+
+use crate::{
+    common::{
+        check_executor_binary_exists, create_args_and_out_file, get_reserved_variables,
+        par_install_language_dependencies, read_result, s3_mode_args_to_worker_data,
+        start_child_process, InstallStrategy, OccupancyMetrics, RequiredDependency,
+    },
+    handle_child::handle_child,
+    AuthedClient, DISABLE_NSJAIL, DISABLE_NUSER, HOME_ENV, NSJAIL_PATH, PATH_ENV, PROXY_ENVS,
+    R_CACHE_DIR,
+};
+
+lazy_static::lazy_static! {
+    static ref RSCRIPT_PATH: String = std::env::var("RSCRIPT_PATH").unwrap_or_else(|_| "/usr/bin/Rscript".to_string());
+    static ref R_REPOSITORY: String = std::env::var("R_REPOSITORY").unwrap_or_else(|_| "https://cloud.r-project.org".to_string());
+
+    // library(pkg), require(pkg), requireNamespace("pkg") and pkg::fn
+    static ref RE_LIBRARY: Regex = Regex::new(r#"\b(?:library|require|requireNamespace)\(\s*["']?([A-Za-z][A-Za-z0-9.]*)["']?"#).unwrap();
+    static ref RE_NAMESPACE: Regex = Regex::new(r#"\b([A-Za-z][A-Za-z0-9.]*):::?[A-Za-z._]"#).unwrap();
+
+    // # s3 prefix=... format=..., the result rows are streamed to the object storage as with sql
+    static ref RE_S3_MODE: Regex = Regex::new(r#"(?m)^# s3( (.+))? *(?:\r|\n|$)"#).unwrap();
+}
+
+const NSJAIL_CONFIG_RUN_R_CONTENT: &str = include_str!("../nsjail/run.r.config.proto");
+
+/// Packages shipped with R itself, they are never locked nor installed
+const BASE_PACKAGES: &[&str] = &[
+    "base",
+    "compiler",
+    "datasets",
+    "graphics",
+    "grDevices",
+    "grid",
+    "methods",
+    "parallel",
+    "splines",
+    "stats",
+    "stats4",
+    "tcltk",
+    "tools",
+    "utils",
+];
+
+/// Resolves the packages and their recursive dependencies against the repository into a
+/// `renv.lock`, listing the packages in installation order
+const LOCK_R: &str = r#"
+repo <- Sys.getenv("WM_R_REPOSITORY")
+requested <- strsplit(Sys.getenv("WM_R_PACKAGES"), ",", fixed = TRUE)[[1]]
+which <- c("Depends", "Imports", "LinkingTo")
+
+ap <- available.packages(repos = repo)
+base <- c("R", rownames(installed.packages(priority = "base")))
+requested <- setdiff(requested, base)
+missing <- setdiff(requested, rownames(ap))
+if (length(missing) > 0) {
+  stop("packages not found in ", repo, ": ", paste(missing, collapse = ", "))
+}
+
+deps <- tools::package_dependencies(requested, db = ap, which = which, recursive = TRUE)
+todo <- setdiff(unique(c(requested, unlist(deps))), base)
+direct <- tools::package_dependencies(todo, db = ap, which = which)
+
+ordered <- character(0)
+while (length(todo) > 0) {
+  ready <- todo[vapply(todo, function(p) all(direct[[p]] %in% c(ordered, base)), logical(1))]
+  if (length(ready) == 0) ready <- todo
+  ordered <- c(ordered, ready)
+  todo <- setdiff(todo, ready)
+}
+
+packages <- structure(list(), names = character(0))
+for (p in ordered) {
+  packages[[p]] <- list(
+    Package = p,
+    Version = unname(ap[p, "Version"]),
+    Source = "Repository",
+    Repository = "CRAN"
+  )
+}
+lock <- list(
+  R = list(
+    Version = paste(R.version$major, R.version$minor, sep = "."),
+    Repositories = list(list(Name = "CRAN", URL = repo))
+  ),
+  Packages = packages
+)
+writeLines(jsonlite::toJSON(lock, auto_unbox = TRUE, pretty = TRUE), "renv.lock")
+"#;
+
+/// Installs the exact versions of `renv.lock` from source, the versions that are not the latest
+/// anymore being in the archive of the repository
+const INSTALL_R: &str = r#"
+options(warn = 2)
+lib <- Sys.getenv("WM_R_LIBRARY")
+dir.create(lib, recursive = TRUE, showWarnings = FALSE)
+.libPaths(c(lib, .libPaths()))
+
+lock <- jsonlite::fromJSON("renv.lock", simplifyVector = FALSE)
+repo <- lock$R$Repositories[[1]]$URL
+current <- available.packages(repos = repo)[, "Version"]
+
+for (p in lock$Packages) {
+  url <- if (identical(unname(current[p$Package]), p$Version)) {
+    sprintf("%s/src/contrib/%s_%s.tar.gz", repo, p$Package, p$Version)
+  } else {
+    sprintf("%s/src/contrib/Archive/%s/%s_%s.tar.gz", repo, p$Package, p$Package, p$Version)
+  }
+  install.packages(url, repos = NULL, type = "source", lib = lib)
+}
+"#;
+
+/// Json arrays of objects are passed as data frames, and data frames are returned as arrays of
+/// records
+const WRAPPER_R: &str = r#"
+library(jsonlite)
+source("main.R")
+
+args <- fromJSON("args.json", simplifyVector = TRUE)
+if (!("..." %in% names(formals(main)))) {
+  args <- args[names(args) %in% names(formals(main))]
+}
+
+res <- tryCatch(do.call(main, args), error = function(e) {
+  err <- list(
+    message = conditionMessage(e),
+    name = class(e)[[1]],
+    stack = paste(deparse(conditionCall(e)), collapse = "\n")
+  )
+  step_id <- Sys.getenv("WM_FLOW_STEP_ID")
+  if (nzchar(step_id)) err$step_id <- step_id
+  writeLines(toJSON(err, auto_unbox = TRUE), "result.json")
+  quit(status = 1)
+})
+
+json <- if (is.null(res)) {
+  "null"
+} else {
+  toJSON(res, auto_unbox = TRUE, dataframe = "rows", null = "null", na = "null", digits = NA)
+}
+writeLines(json, "result.json")
+"#;
+
+/// The packages loaded by the script, without the base packages
+pub fn parse_r_requirements(code: &str) -> Vec<String> {
+    code.lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .flat_map(|l| {
+            RE_LIBRARY
+                .captures_iter(l)
+                .chain(RE_NAMESPACE.captures_iter(l))
+                .map(|cap| cap[1].to_string())
+                .collect_vec()
+        })
+        .filter(|p| !BASE_PACKAGES.contains(&p.as_str()))
+        .unique()
+        .sorted()
+        .collect()
+}
+
+fn rscript_command(job_dir: &str) -> Command {
+    let mut cmd = Command::new(RSCRIPT_PATH.as_str());
+    cmd.env_clear()
+        .current_dir(job_dir)
+        .env("PATH", PATH_ENV.as_str())
+        .env("HOME", HOME_ENV.as_str())
+        .envs(PROXY_ENVS.clone())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+/// Generates the `renv.lock` of the packages, which is used as is as the lock of the script
+pub async fn resolve_renv_lock(
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job_id: &Uuid,
+    w_id: &str,
+    conn: &Connection,
+    job_dir: &str,
+    worker_name: &str,
+    packages: Vec<String>,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> Result<String> {
+    check_executor_binary_exists("Rscript", RSCRIPT_PATH.as_str(), "r")?;
+
+    write_file(job_dir, "lock.R", LOCK_R)?;
+
+    let mut child_cmd = rscript_command(job_dir);
+    child_cmd
+        .env("WM_R_REPOSITORY", R_REPOSITORY.as_str())
+        .env("WM_R_PACKAGES", packages.join(","))
+        .arg("lock.R");
+    let child_process = start_child_process(child_cmd, RSCRIPT_PATH.as_str(), job_id, conn).await?;
+
+    handle_child(
+        job_id,
+        conn,
+        mem_peak,
+        canceled_by,
+        child_process,
+        false,
+        worker_name,
+        w_id,
+        "renv lock",
+        None,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+
+    Ok(tokio::fs::read_to_string(format!("{job_dir}/renv.lock")).await?)
+}
+
+/// Installs the packages of the lockfile in their own library of the cache, shared by all the
+/// jobs with the same lockfile and pushed to the S3 global cache. Returns that library
+async fn install_r_library(
+    job: &MiniPulledJob,
+    conn: &Connection,
+    job_dir: &str,
+    worker_name: &str,
+    lock: &str,
+) -> Result<String> {
+    write_file(job_dir, "renv.lock", lock)?;
+    write_file(job_dir, "install.R", INSTALL_R)?;
+
+    let hash = calculate_hash(lock);
+    let library = format!("{R_CACHE_DIR}/{hash}");
+
+    let job_dir = job_dir.to_owned();
+    par_install_language_dependencies(
+        vec![RequiredDependency {
+            path: library.clone(),
+            custom_name: Some(hash),
+            short_name: Some("renv.lock".to_string()),
+        }],
+        "r",
+        "Rscript",
+        // packages can have compiled code
+        false,
+        1,
+        true,
+        InstallStrategy::AllAtOnce(Arc::new(move |dependencies| {
+            let library = dependencies
+                .into_iter()
+                .next()
+                .map(|dep| dep.path)
+                .ok_or_else(|| anyhow::anyhow!("Internal Error: no library to install to"))?;
+            let mut cmd = rscript_command(&job_dir);
+            cmd.env("WM_R_LIBRARY", library).arg("install.R");
+            Ok(cmd)
+        })),
+        async |_| Ok(()),
+        &job.id,
+        &job.workspace_id,
+        worker_name,
+        conn,
+    )
+    .await?;
+
+    Ok(library)
+}
+
+#[tracing::instrument(level = "trace", skip_all)]
+pub async fn handle_r_job(
+    requirements_o: Option<&String>,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job: &MiniPulledJob,
+    conn: &Connection,
+    client: &AuthedClient,
+    parent_runnable_path: Option<String>,
+    job_dir: &str,
+    inner_content: &String,
+    base_internal_url: &str,
+    worker_name: &str,
+    envs: HashMap<String, String>,
+    shared_mount: &str,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> error::Result<Box<RawValue>> {
+    check_executor_binary_exists("Rscript", RSCRIPT_PATH.as_str(), "r")?;
+
+    let s3 = RE_S3_MODE
+        .captures(inner_content)
+        .map(|cap| parse_s3_mode_args(cap.get(1).map(|x| x.as_str()).unwrap_or_default()))
+        .transpose()?
+        .map(|s3| s3_mode_args_to_worker_data(s3, client.clone(), job));
+
+    let lock = match requirements_o {
+        Some(lock) if !lock.is_empty() => Some(lock.to_string()),
+        _ => {
+            let packages = parse_r_requirements(inner_content);
+            if packages.is_empty() {
+                None
+            } else {
+                append_logs(
+                    &job.id,
+                    &job.workspace_id,
+                    "\n\n--- RENV LOCK ---\n".to_string(),
+                    conn,
+                )
+                .await;
+                Some(
+                    resolve_renv_lock(
+                        mem_peak,
+                        canceled_by,
+                        &job.id,
+                        &job.workspace_id,
+                        conn,
+                        job_dir,
+                        worker_name,
+                        packages,
+                        occupancy_metrics,
+                    )
+                    .await?,
+                )
+            }
+        }
+    };
+
+    let library = match lock {
+        Some(lock) => Some(install_r_library(job, conn, job_dir, worker_name, &lock).await?),
+        None => None,
+    };
+
+    let init_logs = "\n\n--- R CODE EXECUTION ---\n".to_string();
+    append_logs(&job.id, &job.workspace_id, init_logs, conn).await;
+
+    write_file(job_dir, "main.R", inner_content)?;
+    write_file(job_dir, "wrapper.R", WRAPPER_R)?;
+
+    let reserved_variables_args_out_f = async {
+        let args_and_out_f = async {
+            create_args_and_out_file(&client, job, job_dir, conn).await?;
+            Ok(()) as Result<()>
+        };
+        let reserved_variables_f = async {
+            let vars =
+                get_reserved_variables(job, &client.token, conn, parent_runnable_path.clone())
+                    .await?;
+            Ok(vars) as Result<HashMap<String, String>>
+        };
+        let (_, reserved_variables) = tokio::try_join!(args_and_out_f, reserved_variables_f)?;
+        Ok(reserved_variables) as error::Result<HashMap<String, String>>
+    };
+    let reserved_variables = reserved_variables_args_out_f.await?;
+
+    let library_envs = library
+        .map(|library| vec![("R_LIBS", library)])
+        .unwrap_or_default();
+
+    let child = if !*DISABLE_NSJAIL {
+        let _ = write_file(
+            job_dir,
+            "run.config.proto",
+            &NSJAIL_CONFIG_RUN_R_CONTENT
+                .replace("{JOB_DIR}", job_dir)
+                .replace("{CACHE_DIR}", R_CACHE_DIR)
+                .replace("{CLONE_NEWUSER}", &(!*DISABLE_NUSER).to_string())
+                .replace("{SHARED_MOUNT}", shared_mount),
+        )?;
+
+        let mut nsjail_cmd = Command::new(NSJAIL_PATH.as_str());
+        let args = vec![
+            "--config",
+            "run.config.proto",
+            "--",
+            &RSCRIPT_PATH,
+            "/tmp/wrapper.R",
+        ];
+        nsjail_cmd
+            .current_dir(job_dir)
+            .env_clear()
+            .envs(envs)
+            .envs(reserved_variables)
+            .envs(library_envs)
+            .env("BASE_INTERNAL_URL", base_internal_url)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
+    } else {
+        let script_path = format!("{job_dir}/wrapper.R");
+
+        let mut r_cmd = Command::new(RSCRIPT_PATH.as_str());
+        r_cmd
+            .current_dir(job_dir)
+            .env_clear()
+            .envs(envs)
+            .envs(reserved_variables)
+            .envs(library_envs)
+            .env("PATH", PATH_ENV.as_str())
+            .env("HOME", HOME_ENV.as_str())
+            .env("BASE_INTERNAL_URL", base_internal_url)
+            .arg(script_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        start_child_process(r_cmd, RSCRIPT_PATH.as_str(), &job.id, conn).await?
+    };
+
+    handle_child(
+        &job.id,
+        conn,
+        mem_peak,
+        canceled_by,
+        child,
+        !*DISABLE_NSJAIL,
+        worker_name,
+        &job.workspace_id,
+        "r run",
+        job.timeout,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+
+    if let Some(s3) = s3 {
+        let result = tokio::fs::read_to_string(format!("{job_dir}/result.json")).await?;
+        let rows = match serde_json::from_str::<Value>(&result)
+            .map_err(|e| Error::ExecutionErr(format!("result of the R script is not json: {e}")))?
+        {
+            Value::Array(rows) => rows,
+            row => vec![row],
+        };
+        let rows_stream = futures::stream::iter(rows.into_iter().map(Ok::<_, anyhow::Error>));
+        let stream = convert_json_line_stream(rows_stream.boxed(), s3.format).await?;
+        s3.upload(stream.boxed()).await?;
+        return Ok(to_raw_value(&s3.to_return_s3_obj()));
+    }
+
+    read_result(job_dir).await
+}
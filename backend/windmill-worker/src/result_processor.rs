@@ -356,6 +356,12 @@ pub async fn process_result(
                     }
                 }
                 Error::ExecutionRawError(e) => to_raw_value(&e),
+                Error::OutOfMemory(message) => to_raw_value(&SerializedError {
+                    message,
+                    name: "OutOfMemory".to_string(),
+                    step_id: job.flow_step_id.clone(),
+                    exit_code: None,
+                }),
                 err @ _ => to_raw_value(&SerializedError {
                     message: format!("execution error:\n{err:#}",),
                     name: "ExecutionErr".to_string(),
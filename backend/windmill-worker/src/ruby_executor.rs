@@ -0,0 +1,381 @@
+use std::{collections::HashMap, process::Stdio, sync::Arc};
+
+use itertools::Itertools;
+use serde_json::value::RawValue;
+use tokio::process::Command;
+use uuid::Uuid;
+use windmill_common::{
+    error::{self, Error, Result},
+    utils::calculate_hash,
+    worker::{write_file, Connection},
+};
+use windmill_queue::{append_logs, CanceledBy, MiniPulledJob};
+
+use crate::{
+    common::{
+        check_executor_binary_exists, create_args_and_out_file, get_reserved_variables,
+        par_install_language_dependencies, read_result, start_child_process, InstallStrategy,
+        OccupancyMetrics, RequiredDependency,
+    },
+    handle_child::handle_child,
+    AuthedClient, DISABLE_NSJAIL, DISABLE_NUSER, HOME_ENV, NSJAIL_PATH, PATH_ENV, PROXY_ENVS,
+    RUBY_CACHE_DIR,
+};
+
+lazy_static::lazy_static! {
+    static ref RUBY_PATH: String = std::env::var("RUBY_PATH").unwrap_or_else(|_| "/usr/bin/ruby".to_string());
+    static ref BUNDLER_PATH: String = std::env::var("BUNDLER_PATH").unwrap_or_else(|_| "/usr/bin/bundle".to_string());
+    static ref RUBYGEMS_SOURCE: String = std::env::var("RUBYGEMS_SOURCE").unwrap_or_else(|_| "https://rubygems.org".to_string());
+}
+
+const NSJAIL_CONFIG_RUN_RUBY_CONTENT: &str = include_str!("../nsjail/run.ruby.config.proto");
+
+const GEMFILE_LOCK_SPLIT: &str = "\nLOCK\n";
+
+/// Where bundler keeps the compact index of the gem sources between jobs
+const BUNDLE_USER_HOME: &str = const_format::concatcp!(RUBY_CACHE_DIR, "/bundler");
+
+/// The Gemfile declared by the comments following `# requirements:`, e.g.
+///
+/// ```ruby
+/// # requirements:
+/// # gem 'httparty', '~> 0.21'
+/// ```
+pub fn parse_ruby_requirements(code: &str) -> Option<String> {
+    let (pos, _) = code
+        .lines()
+        .find_position(|x| x.starts_with("#requirements:") || x.starts_with("# requirements:"))?;
+
+    let lines = code
+        .lines()
+        .skip(pos + 1)
+        .map_while(|x| x.strip_prefix('#'))
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .collect_vec();
+
+    if lines.is_empty() {
+        return None;
+    }
+    let source = if lines.iter().any(|x| x.starts_with("source ")) {
+        String::new()
+    } else {
+        format!("source '{}'\n", *RUBYGEMS_SOURCE)
+    };
+    Some(format!("{source}{}\n", lines.join("\n")))
+}
+
+fn bundler_command(job_dir: &str) -> Command {
+    let mut cmd = Command::new(BUNDLER_PATH.as_str());
+    cmd.env_clear()
+        .current_dir(job_dir)
+        .env("PATH", PATH_ENV.as_str())
+        .env("HOME", HOME_ENV.as_str())
+        .env("BUNDLE_GEMFILE", format!("{job_dir}/Gemfile"))
+        .env("BUNDLE_USER_HOME", BUNDLE_USER_HOME)
+        .env("BUNDLE_APP_CONFIG", format!("{job_dir}/.bundle"))
+        .envs(PROXY_ENVS.clone())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    cmd
+}
+
+/// Resolves the Gemfile with `bundle lock`. The lock is stored as the Gemfile and the
+/// Gemfile.lock separated by [GEMFILE_LOCK_SPLIT], as composer.json and composer.lock are for php
+pub async fn bundle_lock(
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job_id: &Uuid,
+    w_id: &str,
+    conn: &Connection,
+    job_dir: &str,
+    worker_name: &str,
+    gemfile: String,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> Result<String> {
+    check_executor_binary_exists("bundle", BUNDLER_PATH.as_str(), "ruby")?;
+
+    write_file(job_dir, "Gemfile", &gemfile)?;
+
+    let mut child_cmd = bundler_command(job_dir);
+    child_cmd.arg("lock");
+    let child_process = start_child_process(child_cmd, BUNDLER_PATH.as_str(), job_id, conn).await?;
+
+    handle_child(
+        job_id,
+        conn,
+        mem_peak,
+        canceled_by,
+        child_process,
+        false,
+        worker_name,
+        w_id,
+        "bundle lock",
+        None,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+
+    let lock = tokio::fs::read_to_string(format!("{job_dir}/Gemfile.lock")).await?;
+    Ok(format!("{gemfile}{GEMFILE_LOCK_SPLIT}{lock}"))
+}
+
+/// Installs the gems of the lockfile in their own directory of the cache, shared by all the jobs
+/// with the same lockfile and pushed to the S3 global cache. Returns that directory
+async fn bundle_install(
+    job: &MiniPulledJob,
+    conn: &Connection,
+    job_dir: &str,
+    worker_name: &str,
+    lock: &str,
+) -> Result<String> {
+    write_file(job_dir, "Gemfile.lock", lock)?;
+
+    let hash = calculate_hash(lock);
+    let bundle_path = format!("{RUBY_CACHE_DIR}/{hash}");
+
+    let job_dir = job_dir.to_owned();
+    par_install_language_dependencies(
+        vec![RequiredDependency {
+            path: bundle_path.clone(),
+            custom_name: Some(hash),
+            short_name: Some("Gemfile.lock".to_string()),
+        }],
+        "ruby",
+        "bundle install",
+        // gems can have native extensions
+        false,
+        1,
+        true,
+        InstallStrategy::AllAtOnce(Arc::new(move |dependencies| {
+            let bundle_path = dependencies
+                .into_iter()
+                .next()
+                .map(|dep| dep.path)
+                .ok_or_else(|| anyhow::anyhow!("Internal Error: no bundle path to install to"))?;
+            let mut cmd = bundler_command(&job_dir);
+            cmd.env("BUNDLE_PATH", bundle_path)
+                .env("BUNDLE_FROZEN", "true")
+                .arg("install");
+            Ok(cmd)
+        })),
+        async |_| Ok(()),
+        &job.id,
+        &job.workspace_id,
+        worker_name,
+        conn,
+    )
+    .await?;
+
+    Ok(bundle_path)
+}
+
+fn gen_wrapper(bundler_setup: bool) -> String {
+    let bundler_setup = if bundler_setup {
+        "require 'bundler/setup'"
+    } else {
+        ""
+    };
+    format!(
+        r#"
+require 'json'
+{bundler_setup}
+require_relative 'main'
+
+args = JSON.parse(File.read('args.json'))
+
+positional = []
+keywords = {{}}
+skip_optional = false
+method(:main).parameters.each do |kind, name|
+  key = name.to_s
+  case kind
+  when :req
+    positional << args.delete(key)
+  when :opt
+    # an optional positional arg can only be given if all the previous ones are
+    skip_optional ||= !args.key?(key)
+    positional << args.delete(key) unless skip_optional
+  when :keyreq
+    keywords[name] = args.delete(key)
+  when :key
+    keywords[name] = args.delete(key) if args.key?(key)
+  when :keyrest
+    args.each {{ |k, v| keywords[k.to_sym] = v }}
+  end
+end
+
+begin
+  res = main(*positional, **keywords)
+  File.write('result.json', JSON.generate(res))
+rescue Exception => e
+  err = {{
+    message: e.message,
+    name: e.class.name,
+    stack: (e.backtrace || []).join("\n")
+  }}
+  step_id = ENV['WM_FLOW_STEP_ID']
+  err[:step_id] = step_id if step_id
+  File.write('result.json', JSON.generate(err))
+  exit 1
+end
+"#,
+    )
+}
+
+#[tracing::instrument(level = "trace", skip_all)]
+pub async fn handle_ruby_job(
+    requirements_o: Option<&String>,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job: &MiniPulledJob,
+    conn: &Connection,
+    client: &AuthedClient,
+    parent_runnable_path: Option<String>,
+    job_dir: &str,
+    inner_content: &String,
+    base_internal_url: &str,
+    worker_name: &str,
+    envs: HashMap<String, String>,
+    shared_mount: &str,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> error::Result<Box<RawValue>> {
+    check_executor_binary_exists("ruby", RUBY_PATH.as_str(), "ruby")?;
+
+    let gemfile_and_lock = match requirements_o {
+        Some(reqs_and_lock) if !reqs_and_lock.is_empty() => Some(reqs_and_lock.to_string()),
+        _ => match parse_ruby_requirements(inner_content) {
+            Some(gemfile) => {
+                append_logs(
+                    &job.id,
+                    &job.workspace_id,
+                    "\n\n--- BUNDLE LOCK ---\n".to_string(),
+                    conn,
+                )
+                .await;
+                Some(
+                    bundle_lock(
+                        mem_peak,
+                        canceled_by,
+                        &job.id,
+                        &job.workspace_id,
+                        conn,
+                        job_dir,
+                        worker_name,
+                        gemfile,
+                        occupancy_metrics,
+                    )
+                    .await?,
+                )
+            }
+            None => None,
+        },
+    };
+
+    let bundle_path = if let Some(gemfile_and_lock) = gemfile_and_lock {
+        let Some((gemfile, lock)) = gemfile_and_lock.split_once(GEMFILE_LOCK_SPLIT) else {
+            return Err(Error::ExecutionErr(format!(
+                "Invalid requirements, expected to find LOCK split pattern in reqs. Found: |{gemfile_and_lock}|"
+            )));
+        };
+        write_file(job_dir, "Gemfile", gemfile)?;
+        Some(bundle_install(job, conn, job_dir, worker_name, lock).await?)
+    } else {
+        None
+    };
+
+    let init_logs = "\n\n--- RUBY CODE EXECUTION ---\n".to_string();
+    append_logs(&job.id, &job.workspace_id, init_logs, conn).await;
+
+    write_file(job_dir, "main.rb", inner_content)?;
+    write_file(job_dir, "wrapper.rb", &gen_wrapper(bundle_path.is_some()))?;
+
+    let reserved_variables_args_out_f = async {
+        let args_and_out_f = async {
+            create_args_and_out_file(&client, job, job_dir, conn).await?;
+            Ok(()) as Result<()>
+        };
+        let reserved_variables_f = async {
+            let vars =
+                get_reserved_variables(job, &client.token, conn, parent_runnable_path.clone())
+                    .await?;
+            Ok(vars) as Result<HashMap<String, String>>
+        };
+        let (_, reserved_variables) = tokio::try_join!(args_and_out_f, reserved_variables_f)?;
+        Ok(reserved_variables) as error::Result<HashMap<String, String>>
+    };
+    let reserved_variables = reserved_variables_args_out_f.await?;
+
+    let bundle_envs = bundle_path
+        .map(|path| vec![("BUNDLE_PATH", path), ("BUNDLE_FROZEN", "true".to_string())])
+        .unwrap_or_default();
+
+    let child = if !*DISABLE_NSJAIL {
+        let _ = write_file(
+            job_dir,
+            "run.config.proto",
+            &NSJAIL_CONFIG_RUN_RUBY_CONTENT
+                .replace("{JOB_DIR}", job_dir)
+                .replace("{CACHE_DIR}", RUBY_CACHE_DIR)
+                .replace("{CLONE_NEWUSER}", &(!*DISABLE_NUSER).to_string())
+                .replace("{SHARED_MOUNT}", shared_mount),
+        )?;
+
+        let mut nsjail_cmd = Command::new(NSJAIL_PATH.as_str());
+        let args = vec![
+            "--config",
+            "run.config.proto",
+            "--",
+            &RUBY_PATH,
+            "/tmp/wrapper.rb",
+        ];
+        nsjail_cmd
+            .current_dir(job_dir)
+            .env_clear()
+            .envs(envs)
+            .envs(reserved_variables)
+            .envs(bundle_envs)
+            .env("BASE_INTERNAL_URL", base_internal_url)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
+    } else {
+        let script_path = format!("{job_dir}/wrapper.rb");
+
+        let mut ruby_cmd = Command::new(RUBY_PATH.as_str());
+        ruby_cmd
+            .current_dir(job_dir)
+            .env_clear()
+            .envs(envs)
+            .envs(reserved_variables)
+            .envs(bundle_envs)
+            .env("PATH", PATH_ENV.as_str())
+            .env("HOME", HOME_ENV.as_str())
+            .env("BASE_INTERNAL_URL", base_internal_url)
+            .arg(script_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        start_child_process(ruby_cmd, RUBY_PATH.as_str(), &job.id, conn).await?
+    };
+
+    handle_child(
+        &job.id,
+        conn,
+        mem_peak,
+        canceled_by,
+        child,
+        !*DISABLE_NSJAIL,
+        worker_name,
+        &job.workspace_id,
+        "ruby run",
+        job.timeout,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+    read_result(job_dir).await
+}
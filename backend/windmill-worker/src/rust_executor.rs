@@ -30,9 +30,9 @@ const NSJAIL_CONFIG_RUN_RUST_CONTENT: &str = include_str!("../nsjail/run.rust.co
 
 lazy_static::lazy_static! {
     static ref HOME_DIR: String = std::env::var("HOME").expect("Could not find the HOME environment variable");
-    static ref CARGO_HOME: String = std::env::var("CARGO_HOME").unwrap_or_else(|_| { CARGO_HOME_DEFAULT.clone() });
-    static ref RUSTUP_HOME: String = std::env::var("RUSTUP_HOME").unwrap_or_else(|_| { RUSTUP_HOME_DEFAULT.clone() });
-    static ref CARGO_PATH: String = std::env::var("CARGO_PATH").unwrap_or_else(|_| format!("{}/bin/cargo", CARGO_HOME.as_str()));
+    pub(crate) static ref CARGO_HOME: String = std::env::var("CARGO_HOME").unwrap_or_else(|_| { CARGO_HOME_DEFAULT.clone() });
+    pub(crate) static ref RUSTUP_HOME: String = std::env::var("RUSTUP_HOME").unwrap_or_else(|_| { RUSTUP_HOME_DEFAULT.clone() });
+    pub(crate) static ref CARGO_PATH: String = std::env::var("CARGO_PATH").unwrap_or_else(|_| format!("{}/bin/cargo", CARGO_HOME.as_str()));
 }
 
 #[cfg(windows)]
@@ -49,7 +49,7 @@ lazy_static::lazy_static! {
 
 const RUST_OBJECT_STORE_PREFIX: &str = "rustbin/";
 
-fn gen_cargo_crate(code: &str, job_dir: &str) -> anyhow::Result<()> {
+pub(crate) fn gen_cargo_crate(code: &str, job_dir: &str) -> anyhow::Result<()> {
     let manifest = parse_rust_deps_into_manifest(code)?;
     write_file(job_dir, "Cargo.toml", &manifest)?;
 
@@ -150,7 +150,8 @@ pub async fn generate_cargo_lockfile(
             std::env::var("TMP").unwrap_or_else(|_| "C:\\tmp".to_string()),
         );
     }
-    let gen_lockfile_process = start_child_process(gen_lockfile_cmd, CARGO_PATH.as_str()).await?;
+    let gen_lockfile_process =
+        start_child_process(gen_lockfile_cmd, CARGO_PATH.as_str(), job_id, conn).await?;
     handle_child(
         job_id,
         conn,
@@ -213,7 +214,8 @@ pub async fn build_rust_crate(
         build_rust_cmd.env("USERPROFILE", crate::USERPROFILE_ENV.as_str());
     }
 
-    let build_rust_process = start_child_process(build_rust_cmd, CARGO_PATH.as_str()).await?;
+    let build_rust_process =
+        start_child_process(build_rust_cmd, CARGO_PATH.as_str(), job_id, conn).await?;
     handle_child(
         job_id,
         conn,
@@ -374,7 +376,7 @@ pub async fn handle_rust_job(
             .args(vec!["--config", "run.config.proto", "--", "/tmp/main"])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
-        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str()).await?
+        start_child_process(nsjail_cmd, NSJAIL_PATH.as_str(), &job.id, conn).await?
     } else {
         let compiled_executable_name = "./main";
         let mut run_rust = Command::new(compiled_executable_name);
@@ -396,7 +398,7 @@ pub async fn handle_rust_job(
             run_rust.env("USERPROFILE", crate::USERPROFILE_ENV.as_str());
         }
 
-        start_child_process(run_rust, compiled_executable_name).await?
+        start_child_process(run_rust, compiled_executable_name, &job.id, conn).await?
     };
     handle_child(
         &job.id,
@@ -0,0 +1,573 @@
+//! Wasm scripts, run in-process as WASI preview 2 components with an embedded wasmtime runtime.
+//!
+//! The content of a script is either a precompiled component (base64 encoded behind
+//! [`WASM_COMPONENT_PREFIX`]), or Rust/Go code built to `wasm32-wasip2` with the same wrappers as
+//! the native executors. Built components are cached locally and in the object store, and
+//! precompiled once for the worker's engine. The precompiled native code is only cached locally:
+//! it is loaded without validation, so it is never fetched from the shared object store.
+//!
+//! The job directory is preopened as the current directory of the component so that the
+//! `args.json`/`result.json` wrappers work unchanged, and the `windmill:script/host` interface
+//! gives components access to the args, the result, variables and resources. Execution is bounded
+//! by fuel, by epoch interruption (the store yields at every tick so that the job can be canceled
+//! and timed out like any other) and by a memory cap.
+
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use base64::Engine as _;
+use serde_json::value::RawValue;
+use tokio::process::Command;
+use uuid::Uuid;
+use wasmtime::{
+    component::{Component, Linker, ResourceTable},
+    Config, Engine, ResourceLimiter, Store, Trap,
+};
+use wasmtime_wasi::{
+    bindings::Command as WasiCommand, pipe::MemoryOutputPipe, DirPerms, FilePerms, I32Exit, IoView,
+    WasiCtx, WasiCtxBuilder, WasiView,
+};
+use windmill_common::{
+    error::{self, to_anyhow, Error},
+    utils::calculate_hash,
+    worker::{load_cache, save_cache, to_raw_value, Connection, WasmSource, WASM_COMPONENT_PREFIX},
+};
+use windmill_queue::{append_logs, CanceledBy, MiniPulledJob};
+
+use crate::{
+    cgroup::resolve_resource_limits,
+    common::{
+        check_executor_binary_exists, create_args_and_out_file, get_reserved_variables,
+        start_child_process, OccupancyMetrics,
+    },
+    go_executor::{gen_go_wrapper, install_go_dependencies},
+    handle_child::{handle_child, run_future_with_polling_update_job_poller},
+    AuthedClient, GO_CACHE_DIR, HOME_ENV, PATH_ENV, PROXY_ENVS, WASM_CACHE_DIR,
+};
+
+wasmtime::component::bindgen!({
+    inline: r#"
+        package windmill:script;
+
+        interface host {
+            get-args: func() -> string;
+            set-result: func(value: string);
+            get-variable: func(path: string) -> result<string, string>;
+            get-resource: func(path: string) -> result<string, string>;
+        }
+
+        world script {
+            import host;
+        }
+    "#,
+    world: "script",
+    async: true,
+});
+
+const WASM_OBJECT_STORE_PREFIX: &str = "wasmcomponent/";
+const WASM_RUST_TARGET: &str = "wasm32-wasip2";
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+const MAX_OUTPUT_BYTES: usize = 10 * 1024 * 1024;
+
+lazy_static::lazy_static! {
+    static ref TINYGO_PATH: String = std::env::var("TINYGO_PATH").unwrap_or_else(|_| "/usr/bin/tinygo".to_string());
+
+    static ref WASM_MAX_FUEL: u64 = std::env::var("WASM_MAX_FUEL")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(u64::MAX);
+
+    static ref WASM_MAX_MEMORY_MB: u64 = std::env::var("WASM_MAX_MEMORY_MB")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(512);
+
+    static ref ENGINE: Engine = {
+        let mut config = Config::new();
+        config
+            .wasm_component_model(true)
+            .async_support(true)
+            .epoch_interruption(true)
+            .consume_fuel(true);
+        let engine = Engine::new(&config).expect("could not create the wasm engine");
+
+        let ticker = engine.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(EPOCH_TICK);
+            ticker.increment_epoch();
+        });
+
+        engine
+    };
+}
+
+/// Caps the linear memories of a component, keeping track of the peak for the job's mem_peak
+struct MemoryLimiter {
+    max_bytes: usize,
+    used: usize,
+    peak: Arc<AtomicUsize>,
+}
+
+impl ResourceLimiter for MemoryLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let used = self.used.saturating_sub(current).saturating_add(desired);
+        if used > self.max_bytes {
+            anyhow::bail!(
+                "memory limit of {}MB exceeded",
+                self.max_bytes / (1024 * 1024)
+            );
+        }
+        self.used = used;
+        self.peak.fetch_max(used, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: usize,
+        _desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        Ok(true)
+    }
+}
+
+struct WasmState {
+    wasi: WasiCtx,
+    table: ResourceTable,
+    limiter: MemoryLimiter,
+    client: AuthedClient,
+    job_id: Uuid,
+    args: String,
+    result: Option<String>,
+}
+
+impl IoView for WasmState {
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl WasiView for WasmState {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+}
+
+impl windmill::script::host::Host for WasmState {
+    async fn get_args(&mut self) -> String {
+        self.args.clone()
+    }
+
+    async fn set_result(&mut self, result: String) {
+        self.result = Some(result);
+    }
+
+    async fn get_variable(&mut self, path: String) -> Result<String, String> {
+        self.client
+            .get_variable_value(&path)
+            .await
+            .map_err(|e| format!("{e:#}"))
+    }
+
+    async fn get_resource(&mut self, path: String) -> Result<String, String> {
+        self.client
+            .get_resource_value_interpolated::<serde_json::Value>(
+                &path,
+                Some(self.job_id.to_string()),
+            )
+            .await
+            .map(|v| v.to_string())
+            .map_err(|e| format!("{e:#}"))
+    }
+}
+
+#[tracing::instrument(level = "trace", skip_all)]
+pub async fn handle_wasm_job(
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job: &MiniPulledJob,
+    conn: &Connection,
+    client: &AuthedClient,
+    parent_runnable_path: Option<String>,
+    inner_content: &str,
+    job_dir: &str,
+    worker_name: &str,
+    envs: HashMap<String, String>,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> Result<Box<RawValue>, Error> {
+    let hash = calculate_hash(inner_content);
+    let cwasm_path = format!("{WASM_CACHE_DIR}/{hash}.cwasm");
+    let wasm_path = format!("{WASM_CACHE_DIR}/{hash}.wasm");
+    let remote_path = format!("{WASM_OBJECT_STORE_PREFIX}{hash}");
+
+    // SAFETY: the file is only ever written below by this worker, from a component it
+    // precompiled. A file precompiled by another version or configuration of the engine is
+    // rejected by wasmtime and precompiled again.
+    let cached = tokio::fs::metadata(&cwasm_path)
+        .await
+        .is_ok()
+        .then(|| unsafe { Component::deserialize_file(&ENGINE, &cwasm_path) })
+        .and_then(Result::ok);
+
+    let component = if let Some(component) = cached {
+        append_logs(
+            &job.id,
+            &job.workspace_id,
+            format!("loaded from local cache: {cwasm_path}\n"),
+            conn,
+        )
+        .await;
+        component
+    } else {
+        let wasm = match WasmSource::of(inner_content) {
+            WasmSource::Component => base64::engine::general_purpose::STANDARD
+                .decode(
+                    inner_content
+                        .trim()
+                        .strip_prefix(WASM_COMPONENT_PREFIX)
+                        .unwrap_or_default(),
+                )
+                .map_err(|e| Error::ExecutionErr(format!("invalid base64 component: {e}")))?,
+            source => {
+                // only the component is shared through the object store, it is validated when
+                // precompiled
+                let (cache, cache_logs) = load_cache(&wasm_path, &remote_path, false).await;
+                if cache {
+                    append_logs(&job.id, &job.workspace_id, cache_logs, conn).await;
+                    tokio::fs::read(&wasm_path).await?
+                } else {
+                    let logs1 = format!("{cache_logs}\n\n--- WASM BUILD ---\n");
+                    append_logs(&job.id, &job.workspace_id, logs1, conn).await;
+                    let wasm = match source {
+                        WasmSource::Rust => {
+                            #[cfg(not(feature = "rust"))]
+                            return Err(Error::internal_err(
+                                "Building Rust to wasm requires the rust feature to be enabled"
+                                    .to_string(),
+                            ));
+
+                            #[cfg(feature = "rust")]
+                            build_rust_component(
+                                inner_content,
+                                job,
+                                conn,
+                                mem_peak,
+                                canceled_by,
+                                job_dir,
+                                worker_name,
+                                occupancy_metrics,
+                            )
+                            .await?
+                        }
+                        _ => {
+                            build_go_component(
+                                inner_content,
+                                job,
+                                conn,
+                                mem_peak,
+                                canceled_by,
+                                job_dir,
+                                worker_name,
+                                occupancy_metrics,
+                            )
+                            .await?
+                        }
+                    };
+
+                    tokio::fs::write(format!("{job_dir}/main.wasm"), &wasm).await?;
+                    let logs = match save_cache(
+                        &wasm_path,
+                        &remote_path,
+                        &format!("{job_dir}/main.wasm"),
+                        false,
+                    )
+                    .await
+                    {
+                        Err(e) => {
+                            let em = format!("could not save {wasm_path} to wasm cache: {e:?}");
+                            tracing::error!(em);
+                            em
+                        }
+                        Ok(logs) => logs,
+                    };
+                    append_logs(&job.id, &job.workspace_id, format!("{logs}\n\n"), conn).await;
+                    wasm
+                }
+            }
+        };
+
+        let cwasm = tokio::task::spawn_blocking(move || ENGINE.precompile_component(&wasm))
+            .await
+            .map_err(to_anyhow)?
+            .map_err(|e| Error::ExecutionErr(format!("invalid wasm component: {e:#}")))?;
+
+        if let Err(e) = write_local_cwasm(&cwasm_path, &cwasm).await {
+            tracing::error!("could not save {cwasm_path} to the local wasm cache: {e:#}");
+        }
+
+        // SAFETY: the bytes were just precompiled by the same engine
+        unsafe { Component::deserialize(&ENGINE, &cwasm) }
+            .map_err(|e| Error::ExecutionErr(format!("could not load wasm component: {e:#}")))?
+    };
+
+    create_args_and_out_file(client, job, job_dir, conn).await?;
+    let args = tokio::fs::read_to_string(format!("{job_dir}/args.json")).await?;
+
+    let reserved_variables =
+        get_reserved_variables(job, &client.token, conn, parent_runnable_path).await?;
+
+    let max_memory_mb = resolve_resource_limits(&job.id, conn)
+        .await?
+        .and_then(|l| l.memory_max_mb)
+        .unwrap_or(*WASM_MAX_MEMORY_MB);
+
+    let stdout = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+    let stderr = MemoryOutputPipe::new(MAX_OUTPUT_BYTES);
+    let wasi = WasiCtxBuilder::new()
+        .stdout(stdout.clone())
+        .stderr(stderr.clone())
+        .envs(&envs.into_iter().collect::<Vec<_>>())
+        .envs(&reserved_variables.into_iter().collect::<Vec<_>>())
+        .preopened_dir(job_dir, ".", DirPerms::all(), FilePerms::all())
+        .map_err(|e| Error::internal_err(format!("could not preopen job dir: {e:#}")))?
+        .build();
+
+    let peak = Arc::new(AtomicUsize::new(0));
+    let mut store = Store::new(
+        &ENGINE,
+        WasmState {
+            wasi,
+            table: ResourceTable::new(),
+            limiter: MemoryLimiter {
+                max_bytes: usize::try_from(max_memory_mb * 1024 * 1024).unwrap_or(usize::MAX),
+                used: 0,
+                peak: peak.clone(),
+            },
+            client: client.clone(),
+            job_id: job.id,
+            args,
+            result: None,
+        },
+    );
+    store.limiter(|s| &mut s.limiter);
+    store.set_fuel(*WASM_MAX_FUEL)?;
+    store.epoch_deadline_async_yield_and_update(1);
+
+    let mut linker = Linker::new(&ENGINE);
+    wasmtime_wasi::add_to_linker_async(&mut linker)?;
+    Script::add_to_linker(&mut linker, |s| s)?;
+
+    let run_f = async move {
+        let command = WasiCommand::instantiate_async(&mut store, &component, &linker)
+            .await
+            .map_err(|e| Error::ExecutionErr(format!("could not instantiate component: {e:#}")))?;
+        let exit = command.wasi_cli_run().call_run(&mut store).await;
+        Ok::<_, Error>((exit, store.into_data().result))
+    };
+
+    let get_mem = peak.clone();
+    let run = run_future_with_polling_update_job_poller(
+        job.id,
+        job.timeout,
+        conn,
+        mem_peak,
+        canceled_by,
+        run_f,
+        worker_name,
+        &job.workspace_id,
+        &mut Some(occupancy_metrics),
+        Box::pin(futures::stream::repeat_with(move || {
+            (get_mem.load(Ordering::Relaxed) / 1024) as i32
+        })),
+    )
+    .await;
+
+    *mem_peak = (*mem_peak).max((peak.load(Ordering::Relaxed) / 1024) as i32);
+
+    let output = [stdout.contents(), stderr.contents()]
+        .iter()
+        .map(|x| String::from_utf8_lossy(x).to_string())
+        .collect::<String>();
+    append_logs(&job.id, &job.workspace_id, output, conn).await;
+
+    let (exit, result) = run?;
+    match exit {
+        Ok(Ok(())) => (),
+        Ok(Err(())) => {
+            return Err(Error::ExecutionErr(
+                "wasm component exited with an error".to_string(),
+            ))
+        }
+        Err(e) => match (e.downcast_ref::<I32Exit>(), e.downcast_ref::<Trap>()) {
+            (Some(I32Exit(0)), _) => (),
+            (Some(I32Exit(code)), _) => {
+                return Err(Error::ExecutionErr(format!(
+                    "wasm component exited with code {code}"
+                )))
+            }
+            (_, Some(Trap::OutOfFuel)) => {
+                return Err(Error::ExecutionErr(format!(
+                    "wasm component ran out of fuel ({} units)",
+                    *WASM_MAX_FUEL
+                )))
+            }
+            _ => return Err(Error::ExecutionErr(format!("wasm trap: {e:#}"))),
+        },
+    }
+
+    let result = match result {
+        Some(result) => result,
+        None => tokio::fs::read_to_string(format!("{job_dir}/result.json")).await?,
+    };
+    if result.trim().is_empty() {
+        return Ok(to_raw_value(&serde_json::Value::Null));
+    }
+    serde_json::from_str::<Box<RawValue>>(&result)
+        .map_err(|e| Error::ExecutionErr(format!("result of wasm component is not json: {e}")))
+}
+
+/// Writes the precompiled component to the local cache of the worker, never to the object store
+async fn write_local_cwasm(cwasm_path: &str, cwasm: &[u8]) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(WASM_CACHE_DIR).await?;
+    let tmp_path = format!("{cwasm_path}.{}", Uuid::new_v4());
+    tokio::fs::write(&tmp_path, cwasm).await?;
+    tokio::fs::rename(&tmp_path, cwasm_path).await
+}
+
+#[cfg(feature = "rust")]
+async fn build_rust_component(
+    code: &str,
+    job: &MiniPulledJob,
+    conn: &Connection,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job_dir: &str,
+    worker_name: &str,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> error::Result<Vec<u8>> {
+    use crate::rust_executor::{gen_cargo_crate, CARGO_HOME, CARGO_PATH, RUSTUP_HOME};
+
+    check_executor_binary_exists("cargo", CARGO_PATH.as_str(), "wasm")?;
+    gen_cargo_crate(code, job_dir)?;
+
+    let mut build_cmd = Command::new(CARGO_PATH.as_str());
+    build_cmd
+        .current_dir(job_dir)
+        .env_clear()
+        .envs(PROXY_ENVS.clone())
+        .env("PATH", PATH_ENV.as_str())
+        .env("HOME", HOME_ENV.as_str())
+        .env("CARGO_HOME", CARGO_HOME.as_str())
+        .env("RUSTUP_HOME", RUSTUP_HOME.as_str())
+        .args(vec!["build", "--release", "--target", WASM_RUST_TARGET])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let build_process = start_child_process(build_cmd, CARGO_PATH.as_str(), &job.id, conn).await?;
+    handle_child(
+        &job.id,
+        conn,
+        mem_peak,
+        canceled_by,
+        build_process,
+        false,
+        worker_name,
+        &job.workspace_id,
+        "wasm rust build",
+        None,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+
+    Ok(tokio::fs::read(format!(
+        "{job_dir}/target/{WASM_RUST_TARGET}/release/main.wasm"
+    ))
+    .await?)
+}
+
+async fn build_go_component(
+    code: &str,
+    job: &MiniPulledJob,
+    conn: &Connection,
+    mem_peak: &mut i32,
+    canceled_by: &mut Option<CanceledBy>,
+    job_dir: &str,
+    worker_name: &str,
+    occupancy_metrics: &mut OccupancyMetrics,
+) -> error::Result<Vec<u8>> {
+    check_executor_binary_exists("tinygo", TINYGO_PATH.as_str(), "wasm")?;
+
+    //go does not like executing modules at temp root
+    let go_dir = &format!("{job_dir}/go");
+    tokio::fs::create_dir_all(go_dir).await?;
+
+    install_go_dependencies(
+        &job.id,
+        code,
+        mem_peak,
+        canceled_by,
+        go_dir,
+        conn,
+        true,
+        false,
+        false,
+        worker_name,
+        &job.workspace_id,
+        occupancy_metrics,
+    )
+    .await?;
+    gen_go_wrapper(code, go_dir)?;
+
+    let mut build_cmd = Command::new(TINYGO_PATH.as_str());
+    build_cmd
+        .current_dir(go_dir)
+        .env_clear()
+        .envs(PROXY_ENVS.clone())
+        .env("PATH", PATH_ENV.as_str())
+        .env("GOPATH", GO_CACHE_DIR)
+        .env("HOME", HOME_ENV.as_str())
+        .args(vec![
+            "build",
+            "-target=wasip2",
+            "-o",
+            "main.wasm",
+            "main.go",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let build_process = start_child_process(build_cmd, TINYGO_PATH.as_str(), &job.id, conn).await?;
+    handle_child(
+        &job.id,
+        conn,
+        mem_peak,
+        canceled_by,
+        build_process,
+        false,
+        worker_name,
+        &job.workspace_id,
+        "wasm go build",
+        None,
+        false,
+        &mut Some(occupancy_metrics),
+        None,
+    )
+    .await?;
+
+    Ok(tokio::fs::read(format!("{go_dir}/main.wasm")).await?)
+}
@@ -19,8 +19,8 @@ use windmill_common::{
     scripts::PREVIEW_IS_TAR_CODEBASE_HASH,
     utils::WarnAfterExt,
     worker::{
-        write_file, Connection, HttpClient, MAX_TIMEOUT, ROOT_CACHE_DIR, ROOT_CACHE_NOMOUNT_DIR,
-        TMP_DIR,
+        queue_notify_channel, write_file, Connection, HttpClient, MAX_TIMEOUT, QUEUE_LISTEN,
+        ROOT_CACHE_DIR, ROOT_CACHE_NOMOUNT_DIR, TMP_DIR,
     },
     KillpillSender,
 };
@@ -41,7 +41,7 @@ use windmill_common::METRICS_ENABLED;
 
 use reqwest::{Body, Response};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use sqlx::types::Json;
+use sqlx::{postgres::PgListener, types::Json};
 use std::{
     collections::HashMap,
     fs::DirBuilder,
@@ -62,7 +62,7 @@ use windmill_common::{
     jobs::JobKind,
     scripts::{get_full_hub_script_by_path, ScriptHash, ScriptLang, PREVIEW_IS_CODEBASE_HASH},
     utils::StripPath,
-    worker::{CLOUD_HOSTED, NO_LOGS, WORKER_CONFIG, WORKER_GROUP},
+    worker::{WasmSource, CLOUD_HOSTED, NO_LOGS, WORKER_CONFIG, WORKER_GROUP},
     DB, IS_READY,
 };
 
@@ -142,6 +142,21 @@ use crate::ansible_executor::handle_ansible_job;
 #[cfg(feature = "mysql")]
 use crate::mysql_executor::do_mysql;
 
+#[cfg(feature = "duckdb")]
+use crate::duckdb_executor::do_duckdb;
+
+#[cfg(feature = "wasm")]
+use crate::wasm_executor::handle_wasm_job;
+
+#[cfg(feature = "lua")]
+use crate::lua_executor::do_lua;
+
+#[cfg(feature = "ruby")]
+use crate::ruby_executor::handle_ruby_job;
+
+#[cfg(feature = "r")]
+use crate::r_executor::handle_r_job;
+
 #[cfg(feature = "oracledb")]
 use crate::oracledb_executor::do_oracledb;
 
@@ -173,6 +188,8 @@ pub const TAR_PY312_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "tar/python_312"
 pub const TAR_PY313_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "tar/python_313");
 
 pub const TAR_JAVA_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "tar/java");
+pub const TAR_RUBY_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "tar/ruby");
+pub const TAR_R_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "tar/r");
 
 pub const UV_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "uv");
 pub const PY_INSTALL_DIR: &str = concatcp!(ROOT_CACHE_DIR, "py_runtime");
@@ -185,6 +202,9 @@ pub const GO_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "go");
 pub const RUST_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "rust");
 pub const NU_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "nu");
 pub const CSHARP_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "csharp");
+pub const WASM_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "wasm");
+pub const RUBY_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "ruby");
+pub const R_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "r");
 
 // JAVA
 pub const JAVA_CACHE_DIR: &str = concatcp!(ROOT_CACHE_DIR, "java");
@@ -260,6 +280,15 @@ lazy_static::lazy_static! {
         });
 
 
+    /// with `QUEUE_LISTEN`, how long to wait for a notification before pulling anyway, for the jobs
+    /// becoming ready without a notification: scheduled for later (including the schedules and
+    /// the jobs delayed by a rate limit, a circuit breaker or a concurrency limit), resumed
+    /// flows, ...
+    pub static ref QUEUE_LISTEN_FALLBACK_POLL_MS: u64 = std::env::var("QUEUE_LISTEN_FALLBACK_POLL_MS")
+        .ok()
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(1000);
+
     pub static ref DISABLE_NUSER: bool = std::env::var("DISABLE_NUSER")
     .ok()
     .and_then(|x| x.parse::<bool>().ok())
@@ -374,6 +403,11 @@ pub const MAX_RESULT_SIZE: usize = 1024 * 1024 * 2; // 2MB
 
 pub const INIT_SCRIPT_TAG: &str = "init_script";
 
+#[derive(Deserialize)]
+struct DuckdbConnectionSettings {
+    connection_settings_str: String,
+}
+
 #[derive(Clone)]
 pub struct AuthedClient {
     pub base_internal_url: String,
@@ -522,6 +556,43 @@ impl AuthedClient {
         }
     }
 
+    /// Statements connecting DuckDB to the storage `s3_resource_path`, or to the workspace
+    /// storage if None
+    pub async fn get_duckdb_connection_settings(
+        &self,
+        s3_resource_path: Option<&str>,
+    ) -> anyhow::Result<String> {
+        let url = format!(
+            "{}/api/w/{}/job_helpers/v2/duckdb_connection_settings",
+            self.base_internal_url, self.workspace
+        );
+        let body = match s3_resource_path {
+            Some(path) => serde_json::json!({ "s3_resource_path": path }),
+            None => serde_json::json!({}),
+        };
+        let response = self
+            .force_client
+            .as_ref()
+            .unwrap_or(&HTTP_CLIENT)
+            .post(&url)
+            .json(&body)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                reqwest::header::HeaderValue::from_str(&format!("Bearer {}", self.token))?,
+            )
+            .send()
+            .await
+            .context("sending duckdb_connection_settings request")?;
+        match response.status().as_u16() {
+            200u16 => Ok(response
+                .json::<DuckdbConnectionSettings>()
+                .await
+                .context("decoding duckdb connection settings as json")?
+                .connection_settings_str),
+            _ => Err(anyhow::anyhow!(response.text().await.unwrap_or_default())),
+        }
+    }
+
     pub async fn upload_s3_file<S>(
         &self,
         workspace_id: &str,
@@ -795,6 +866,63 @@ fn add_outstanding_wait_time(
     }
 }
 
+/// Waits until a job is pushed on one of the tags of the worker, or for the fallback poll
+/// interval. Sleeps for `SLEEP_QUEUE` instead when the listener cannot be set up.
+async fn wait_for_queued_job(
+    db: &DB,
+    listener: &mut Option<(PgListener, Vec<String>)>,
+    worker_name: &str,
+) {
+    let tags = WORKER_CONFIG.read().await.worker_tags.clone();
+    if listener
+        .as_ref()
+        .is_some_and(|(_, listened)| listened != &tags)
+    {
+        tracing::info!(worker = %worker_name, "worker tags changed, listening to the new ones");
+        *listener = None;
+    }
+
+    if listener.is_none() {
+        let channels = tags
+            .iter()
+            .map(|t| queue_notify_channel(t))
+            .collect::<Vec<_>>();
+        match PgListener::connect_with(db).await {
+            Ok(mut l) => match l.listen_all(channels.iter().map(String::as_str)).await {
+                Ok(()) => *listener = Some((l, tags)),
+                Err(e) => {
+                    tracing::error!(worker = %worker_name, "could not listen to the queue notifications: {e:#}")
+                }
+            },
+            Err(e) => {
+                tracing::error!(worker = %worker_name, "could not connect to listen to the queue notifications: {e:#}")
+            }
+        }
+    }
+
+    let Some((l, _)) = listener.as_mut() else {
+        tokio::time::sleep(Duration::from_millis(*SLEEP_QUEUE)).await;
+        return;
+    };
+
+    match tokio::time::timeout(
+        Duration::from_millis(*QUEUE_LISTEN_FALLBACK_POLL_MS),
+        l.recv(),
+    )
+    .await
+    {
+        Ok(Ok(_)) => {
+            // the notifications received while running jobs are stale, a single pull covers them
+            while l.next_buffered().is_some() {}
+        }
+        Ok(Err(e)) => {
+            tracing::error!(worker = %worker_name, "error receiving the queue notifications: {e:#}");
+            *listener = None;
+        }
+        Err(_) => (),
+    }
+}
+
 pub async fn run_worker(
     conn: &Connection,
     hostname: &str,
@@ -1123,6 +1251,7 @@ pub async fn run_worker(
     };
 
     let mut last_executed_job: Option<Instant> = None;
+    let mut queue_listener: Option<(PgListener, Vec<String>)> = None;
 
     #[cfg(feature = "benchmark")]
     let mut started = false;
@@ -1849,7 +1978,12 @@ pub async fn run_worker(
                     None
                 };
 
-                tokio::time::sleep(Duration::from_millis(*SLEEP_QUEUE)).await;
+                match conn {
+                    Connection::Sql(db) if *QUEUE_LISTEN => {
+                        wait_for_queued_job(db, &mut queue_listener, &worker_name).await;
+                    }
+                    _ => tokio::time::sleep(Duration::from_millis(*SLEEP_QUEUE)).await,
+                }
 
                 #[cfg(feature = "benchmark")]
                 {
@@ -2674,6 +2808,25 @@ async fn handle_code_execution_job(
             occupancy_metrics,
         )
         .await;
+    } else if language == Some(ScriptLang::Duckdb) {
+        #[cfg(not(feature = "duckdb"))]
+        return Err(Error::internal_err(
+            "DuckDB requires the duckdb feature to be enabled".to_string(),
+        ));
+
+        #[cfg(feature = "duckdb")]
+        return do_duckdb(
+            job,
+            &client,
+            &code,
+            conn,
+            mem_peak,
+            canceled_by,
+            worker_name,
+            column_order,
+            occupancy_metrics,
+        )
+        .await;
     } else if language == Some(ScriptLang::Bigquery) {
         #[cfg(not(feature = "enterprise"))]
         {
@@ -2835,6 +2988,30 @@ async fn handle_code_execution_job(
         )
         .await?;
         return Ok(result);
+    } else if language == Some(ScriptLang::Lua) {
+        #[cfg(not(feature = "lua"))]
+        return Err(Error::internal_err(
+            "Lua requires the lua feature to be enabled".to_string(),
+        ));
+
+        #[cfg(feature = "lua")]
+        {
+            let reserved_variables =
+                get_reserved_variables(job, &client.token, conn, parent_runnable_path).await?;
+
+            return do_lua(
+                job,
+                &client,
+                &code,
+                conn,
+                mem_peak,
+                canceled_by,
+                worker_name,
+                reserved_variables,
+                occupancy_metrics,
+            )
+            .await;
+        }
     }
 
     let lang_str = job
@@ -3151,6 +3328,78 @@ mount {{
             })
             .await
         }
+        Some(ScriptLang::Wasm) => {
+            #[cfg(not(feature = "wasm"))]
+            return Err(Error::internal_err(
+                "Wasm requires the wasm feature to be enabled".to_string(),
+            ));
+
+            #[cfg(feature = "wasm")]
+            handle_wasm_job(
+                mem_peak,
+                canceled_by,
+                job,
+                conn,
+                client,
+                parent_runnable_path,
+                &code,
+                job_dir,
+                worker_name,
+                envs,
+                occupancy_metrics,
+            )
+            .await
+        }
+        Some(ScriptLang::Ruby) => {
+            #[cfg(not(feature = "ruby"))]
+            return Err(Error::internal_err(
+                "Ruby requires the ruby feature to be enabled".to_string(),
+            ));
+
+            #[cfg(feature = "ruby")]
+            handle_ruby_job(
+                lock.as_ref(),
+                mem_peak,
+                canceled_by,
+                job,
+                conn,
+                client,
+                parent_runnable_path,
+                job_dir,
+                &code,
+                base_internal_url,
+                worker_name,
+                envs,
+                &shared_mount,
+                occupancy_metrics,
+            )
+            .await
+        }
+        Some(ScriptLang::R) => {
+            #[cfg(not(feature = "r"))]
+            return Err(Error::internal_err(
+                "R requires the r feature to be enabled".to_string(),
+            ));
+
+            #[cfg(feature = "r")]
+            handle_r_job(
+                lock.as_ref(),
+                mem_peak,
+                canceled_by,
+                job,
+                conn,
+                client,
+                parent_runnable_path,
+                job_dir,
+                &code,
+                base_internal_url,
+                worker_name,
+                envs,
+                &shared_mount,
+                occupancy_metrics,
+            )
+            .await
+        }
         _ => panic!("unreachable, language is not supported: {language:#?}"),
     };
     tracing::info!(
@@ -3198,6 +3447,17 @@ fn parse_sig_of_lang(
             ScriptLang::Graphql => None,
             ScriptLang::Mssql => Some(windmill_parser_sql::parse_mssql_sig(code)?),
             ScriptLang::OracleDB => Some(windmill_parser_sql::parse_oracledb_sig(code)?),
+            ScriptLang::Duckdb => Some(windmill_parser_sql::parse_duckdb_sig(code)?),
+            #[cfg(feature = "lua")]
+            ScriptLang::Lua => Some(windmill_parser_lua::parse_lua_sig(code)?),
+            #[cfg(not(feature = "lua"))]
+            ScriptLang::Lua => None,
+            ScriptLang::Wasm => match WasmSource::of(code) {
+                WasmSource::Go => Some(windmill_parser_go::parse_go_sig(code)?),
+                #[cfg(feature = "rust")]
+                WasmSource::Rust => Some(windmill_parser_rust::parse_rust_signature(code)?),
+                _ => None,
+            },
             #[cfg(feature = "php")]
             ScriptLang::Php => Some(windmill_parser_php::parse_php_signature(
                 code,
@@ -3222,6 +3482,14 @@ fn parse_sig_of_lang(
             ScriptLang::Java => Some(windmill_parser_java::parse_java_signature(code)?),
             #[cfg(not(feature = "java"))]
             ScriptLang::Java => None,
+            #[cfg(feature = "ruby")]
+            ScriptLang::Ruby => Some(windmill_parser_ruby::parse_ruby_sig(code)?),
+            #[cfg(not(feature = "ruby"))]
+            ScriptLang::Ruby => None,
+            #[cfg(feature = "r")]
+            ScriptLang::R => Some(windmill_parser_r::parse_r_sig(code)?),
+            #[cfg(not(feature = "r"))]
+            ScriptLang::R => None,
             // for related places search: ADD_NEW_LANG
         }
     } else {
@@ -36,11 +36,14 @@ use windmill_common::db::Authed;
 use windmill_common::flow_status::{
     ApprovalConditions, FlowStatusModuleWParent, Iterator as FlowIterator, JobResult,
 };
-use windmill_common::flows::{add_virtual_items_if_necessary, Branch, FlowNodeId, StopAfterIf};
+use windmill_common::flows::{
+    add_virtual_items_if_necessary, retry_after_hint, Branch, FlowNodeId, StopAfterIf,
+};
 use windmill_common::jobs::{
     script_path_to_payload, JobKind, JobPayload, OnBehalfOf, RawCode, ENTRYPOINT_OVERRIDE,
 };
 use windmill_common::scripts::ScriptHash;
+use windmill_common::triggers::TriggerKind;
 use windmill_common::users::username_to_permissioned_as;
 use windmill_common::utils::WarnAfterExt;
 use windmill_common::worker::to_raw_value;
@@ -51,17 +54,20 @@ use windmill_common::{
 use windmill_common::{
     error::{self, to_anyhow, Error},
     flow_status::{
-        Approval, BranchAllStatus, BranchChosen, FlowStatus, FlowStatusModule, RetryStatus,
-        MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL,
+        Approval, BranchAllStatus, BranchChosen, CompensationStatus, CompensationStep, FlowStatus,
+        FlowStatusModule, RetryPredicate, RetryStatus, MAX_RETRY_ATTEMPTS, MAX_RETRY_INTERVAL,
+    },
+    flows::{
+        DeadlineAction, FlowModule, FlowModuleValue, FlowValue, InputTransform, Mock, Retry,
+        Suspend,
     },
-    flows::{FlowModule, FlowModuleValue, FlowValue, InputTransform, Retry, Suspend},
 };
 use windmill_queue::flow_status::Step;
 use windmill_queue::schedule::get_schedule_opt;
 use windmill_queue::{
-    add_completed_job, add_completed_job_error, append_logs, get_mini_pulled_job,
-    handle_maybe_scheduled_job, insert_concurrency_key, interpolate_args, CanceledBy,
-    MiniPulledJob, PushArgs, PushIsolationLevel, SameWorkerPayload, WrappedError,
+    add_completed_job, add_completed_job_error, append_logs, cancel_module_jobs,
+    get_mini_pulled_job, handle_maybe_scheduled_job, insert_concurrency_key, interpolate_args,
+    CanceledBy, MiniPulledJob, PushArgs, PushIsolationLevel, SameWorkerPayload, WrappedError,
 };
 
 type DB = sqlx::Pool<sqlx::Postgres>;
@@ -245,7 +251,7 @@ pub async fn update_flow_status_after_job_completion_internal(
         nresult,
         is_failure_step,
         _cleanup_module,
-    ) = {
+    ) = 'status: {
         // tracing::debug!("UPDATE FLOW STATUS: {flow:?} {success} {result:?} {w_id} {depth}");
 
         let (job_kind, script_hash, old_status, raw_flow) = sqlx::query!(
@@ -283,6 +289,36 @@ pub async fn update_flow_status_after_job_completion_internal(
             .await?;
         let flow_value = flow_data.value();
 
+        if let Some(compensation) = old_status
+            .compensation
+            .as_ref()
+            .filter(|c| c.job == *job_id_for_status)
+        {
+            let failed_result =
+                complete_compensation(db, w_id, flow, compensation, success).await?;
+
+            // the step failure was fully handled before the compensations were pushed, the flow
+            // now goes on with it: to the error handler if any, otherwise to its completion
+            success = false;
+            let flow_job = get_mini_pulled_job(db, &flow)
+                .await?
+                .ok_or_else(|| Error::internal_err(format!("requiring flow to be in the queue")))?;
+            let should_continue_flow = !flow_job.is_canceled()
+                && !skip_error_handler
+                && flow_value.failure_module.is_some();
+            break 'status (
+                should_continue_flow,
+                flow_job,
+                flow_data,
+                false,
+                None,
+                false,
+                Arc::new(failed_result),
+                false,
+                old_status.cleanup_module,
+            );
+        }
+
         let module_step = Step::from_i32_and_len(old_status.step, old_status.modules.len());
         let current_module = match module_step {
             Step::Step(i) => flow_value.modules.get(i),
@@ -328,6 +364,54 @@ pub async fn update_flow_status_after_job_completion_internal(
             FlowStatusModule::InProgress { branchall: Some(_), .. }
         );
 
+        let (parallelism, race) = if is_branch_all {
+            let value = current_module
+                .as_ref()
+                .and_then(|x| x.get_value_with_skip_failures().ok());
+            (
+                value.as_ref().and_then(|x| x.parallelism),
+                value.as_ref().and_then(|x| x.race).unwrap_or(false),
+            )
+        } else {
+            (parallelism, false)
+        };
+
+        let deadline_action = old_status
+            .deadline
+            .as_ref()
+            .filter(|d| d.is_expired(&module_status.id()))
+            .and(current_module)
+            .and_then(|m| m.get_deadline())
+            .map(|d| d.on_timeout);
+
+        let module_retry = match module_step {
+            Step::PreprocessorStep => flow_value
+                .preprocessor_module
+                .as_ref()
+                .and_then(|m| m.retry.clone()),
+            Step::Step(i) => flow_value.modules.get(i).and_then(|m| m.retry.clone()),
+            Step::FailureStep => flow_value
+                .failure_module
+                .as_ref()
+                .and_then(|m| m.retry.clone()),
+        }
+        .unwrap_or_default();
+        let retry_status = if success {
+            old_status.retry.clone()
+        } else {
+            eval_retry_predicate(
+                db,
+                w_id,
+                flow,
+                &module_retry,
+                &old_status.retry,
+                job_id_for_status,
+                &result,
+                client,
+            )
+            .await?
+        };
+
         // 0 length flows are not failure steps
         let is_failure_step =
             old_status.step >= old_status.modules.len() as i32 && old_status.modules.len() > 0;
@@ -432,11 +516,106 @@ pub async fn update_flow_status_after_job_completion_internal(
             _ => false,
         };
 
+        if old_status.race_losers.contains(job_id_for_status) {
+            tracing::info!(
+                "branch {job_id_for_status} of flow {flow} lost a race, ignoring its completion"
+            );
+            return Ok(UpdateFlowStatusAfterJobCompletion::NonLastParallelBranch);
+        }
+
         let mut tx = db.begin().await?;
 
+        if race {
+            // the winner of the race may have completed the module since the status was read
+            let in_progress = sqlx::query_scalar!(
+                "SELECT COALESCE(flow_status->'modules'->$1::int->>'type' = 'InProgress', false) AS \"in_progress!\"
+                 FROM v2_job_status WHERE id = $2 FOR UPDATE",
+                old_status.step,
+                flow
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| Error::internal_err(format!("error while locking race module: {e:#}")))?;
+            if !in_progress {
+                tx.commit().await?;
+                tracing::info!(
+                    "branch {job_id_for_status} of flow {flow} lost a race, ignoring its completion"
+                );
+                return Ok(UpdateFlowStatusAfterJobCompletion::NonLastParallelBranch);
+            }
+        }
+        let mut race_losers = vec![];
+
         add_time!(bench, "process module status START");
 
         let (inc_step_counter, new_status) = match module_status {
+            FlowStatusModule::InProgress {
+                branchall: Some(_),
+                parallel: true,
+                flow_jobs: Some(jobs),
+                flow_jobs_success,
+                ..
+            } if race && success && deadline_action != Some(DeadlineAction::Fail) => {
+                // the first branch to succeed completes the module, the others are canceled
+                let position = jobs.iter().position(|x| x == job_id_for_status);
+                let mut flow_jobs_success = flow_jobs_success.clone();
+                if let (Some(flow_job_success), Some(position)) =
+                    (flow_jobs_success.as_mut(), position)
+                {
+                    if position < flow_job_success.len() {
+                        flow_job_success[position] = Some(true);
+                    }
+                }
+                race_losers = jobs
+                    .iter()
+                    .filter(|x| *x != job_id_for_status)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                sqlx::query!(
+                    "UPDATE v2_job_status
+                     SET flow_status = JSONB_SET(
+                         flow_status,
+                         ARRAY['race_losers'],
+                         COALESCE(flow_status->'race_losers', '[]'::jsonb) || $1
+                     )
+                     WHERE id = $2",
+                    json!(race_losers),
+                    flow
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    Error::internal_err(format!("error while setting race losers: {e:#}"))
+                })?;
+                sqlx::query!(
+                    "DELETE FROM parallel_monitor_lock WHERE parent_flow_id = $1",
+                    flow
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| {
+                    Error::internal_err(format!(
+                        "error while deleting parallel_monitor_lock: {e:#}"
+                    ))
+                })?;
+                tracing::info!(
+                    "branch {job_id_for_status} of module {} of flow {flow} won the race",
+                    module_status.id()
+                );
+                (
+                    true,
+                    Some(FlowStatusModule::Success {
+                        id: module_status.id(),
+                        job: job_id_for_status.clone(),
+                        flow_jobs: None,
+                        flow_jobs_success,
+                        branch_chosen: position.map(|branch| BranchChosen::Branch { branch }),
+                        approvers: vec![],
+                        failed_retries: vec![],
+                        skipped: false,
+                    }),
+                )
+            }
             FlowStatusModule::InProgress {
                 iterator,
                 branchall,
@@ -561,7 +740,20 @@ pub async fn update_flow_status_after_job_completion_internal(
                         }
                     }
 
-                    let new_status = if skip_loop_failures
+                    let new_status = if race {
+                        // a succeeding branch completes the module right away, so all of them failed
+                        success = false;
+                        FlowStatusModule::Failure {
+                            id: module_status.id(),
+                            job: job_id_for_status.clone(),
+                            flow_jobs: Some(jobs.clone()),
+                            flow_jobs_success: flow_jobs_success.clone(),
+                            branch_chosen: None,
+                            failed_retries: vec![],
+                        }
+                    } else if deadline_action != Some(DeadlineAction::Fail)
+                         && (skip_loop_failures
+                         || deadline_action == Some(DeadlineAction::Continue)
                          || sqlx::query_scalar!(
                              "SELECT status = 'success' OR status = 'skipped' AS \"success!\" FROM v2_job_completed WHERE id = ANY($1)",
                              jobs.as_slice()
@@ -574,7 +766,7 @@ pub async fn update_flow_status_after_job_completion_internal(
                              ))
                          })?
                          .into_iter()
-                         .all(|x| x)
+                         .all(|x| x))
                      {
                          success = true;
                          FlowStatusModule::Success {
@@ -621,6 +813,23 @@ pub async fn update_flow_status_after_job_completion_internal(
                     add_time!(bench, "handle parallel flow start");
                     tx.commit().await?;
 
+                    if deadline_action.is_some() {
+                        if let Err(e) = cancel_module_jobs(
+                            db,
+                            w_id,
+                            jobs,
+                            "deadline",
+                            format!("deadline of module {} exceeded", module_status.id()),
+                        )
+                        .await
+                        {
+                            tracing::error!(
+                                "error while cancelling jobs of module {} of flow {flow} after its deadline: {e:#}",
+                                module_status.id()
+                            );
+                        }
+                    }
+
                     if parallelism.is_some() {
                         sqlx::query!(
                             "UPDATE v2_job_queue q SET suspend = 0
@@ -665,7 +874,8 @@ pub async fn update_flow_status_after_job_completion_internal(
                 ..
             } if (*while_loop
                 || (*index + 1 < itered.len()) && (success || skip_loop_failures))
-                && !stop_early =>
+                && !stop_early
+                && deadline_action.is_none() =>
             {
                 if let Some(jobs) = flow_jobs {
                     set_success_in_flow_job_success(
@@ -687,7 +897,10 @@ pub async fn update_flow_status_after_job_completion_internal(
                 flow_jobs_success,
                 flow_jobs,
                 ..
-            } if branch.to_owned() < len - 1 && (success || skip_seq_branch_failure) => {
+            } if branch.to_owned() < len - 1
+                && (success || skip_seq_branch_failure)
+                && deadline_action.is_none() =>
+            {
                 if let Some(jobs) = flow_jobs {
                     set_success_in_flow_job_success(
                         flow_jobs_success,
@@ -727,8 +940,12 @@ pub async fn update_flow_status_after_job_completion_internal(
                         }
                     }
                 }
-                if success
-                    || (flow_jobs.is_some() && (skip_loop_failures || skip_seq_branch_failure))
+                if deadline_action != Some(DeadlineAction::Fail)
+                    && (success
+                        || (flow_jobs.is_some()
+                            && (skip_loop_failures
+                                || skip_seq_branch_failure
+                                || deadline_action == Some(DeadlineAction::Continue))))
                 {
                     let is_skipped = if current_module.as_ref().is_some_and(|m| m.skip_if.is_some())
                     {
@@ -760,6 +977,7 @@ pub async fn update_flow_status_after_job_completion_internal(
                         }),
                     )
                 } else {
+                    success = false;
                     let inc = if continue_on_error {
                         let retry = current_module
                             .as_ref()
@@ -767,7 +985,8 @@ pub async fn update_flow_status_after_job_completion_internal(
                             .unwrap_or_default();
 
                         tracing::info!("update flow status on rety: {retry:#?} ");
-                        next_retry(&retry, &old_status.retry).is_none()
+                        next_retry_for_error(&retry, &retry_status, job_id_for_status, &result)
+                            .is_none()
                     } else {
                         false
                     };
@@ -887,6 +1106,22 @@ pub async fn update_flow_status_after_job_completion_internal(
                     Error::internal_err(format!("error while setting new flow status: {e:#}"))
                 })?;
 
+                if old_status
+                    .deadline
+                    .as_ref()
+                    .is_some_and(|d| d.id == new_status.id())
+                {
+                    sqlx::query!(
+                        "UPDATE v2_job_status
+                         SET flow_status = flow_status - 'deadline'
+                         WHERE id = $1",
+                        flow
+                    )
+                    .execute(&mut *tx)
+                    .await
+                    .context("remove flow status deadline")?;
+                }
+
                 if let Some(job_result) = new_status.job_result() {
                     sqlx::query!(
                          "UPDATE v2_job_status
@@ -908,6 +1143,13 @@ pub async fn update_flow_status_after_job_completion_internal(
 
         let mut nresult = if let Some(stop_early_err_msg) = stop_early_err_msg.as_ref() {
             Arc::new(to_raw_value(stop_early_err_msg))
+        } else if deadline_action == Some(DeadlineAction::Fail) {
+            Arc::new(to_raw_value(&Json(&WrappedError {
+                error: json!({
+                    "name": "DeadlineExceeded",
+                    "message": format!("module {} exceeded its deadline", module_status.id()),
+                }),
+            })))
         } else {
             match &new_status {
                 Some(FlowStatusModule::Success { flow_jobs: Some(jobs), .. })
@@ -981,6 +1223,26 @@ pub async fn update_flow_status_after_job_completion_internal(
             .ok_or_else(|| Error::internal_err(format!("requiring flow to be in the queue")))?;
         tx.commit().await?;
 
+        if !race_losers.is_empty() {
+            if let Err(e) = cancel_module_jobs(
+                db,
+                w_id,
+                &race_losers,
+                "race",
+                format!(
+                    "branch {job_id_for_status} of module {} won the race",
+                    module_status.id()
+                ),
+            )
+            .await
+            {
+                tracing::error!(
+                    "error while cancelling the remaining branches of module {} of flow {flow}: {e:#}",
+                    module_status.id()
+                );
+            }
+        }
+
         if matches!(module_step, Step::PreprocessorStep) {
             let tag_and_concurrency_key = get_tag_and_concurrency(&flow, db).await;
             let require_args = tag_and_concurrency_key.as_ref().is_some_and(|x| {
@@ -1109,43 +1371,21 @@ pub async fn update_flow_status_after_job_completion_internal(
             .unwrap_or_else(|| "none".to_string());
         tracing::info!(id = %flow_job.id, root_id = %job_root, "update flow status");
 
+        let skip_failure = skip_seq_branch_failure
+            || skip_parallel_branchall_failure
+            || skip_loop_failures
+            || continue_on_error;
+        let retry_left =
+            next_retry_for_error(&module_retry, &retry_status, job_id_for_status, &result)
+                .is_some();
+
         let should_continue_flow = match success {
             _ if stop_early => false,
             _ if flow_job.is_canceled() => false,
             true => !is_last_step,
             false if unrecoverable => false,
-            false
-                if skip_seq_branch_failure
-                    || skip_parallel_branchall_failure
-                    || skip_loop_failures
-                    || continue_on_error =>
-            {
-                !is_last_step
-            }
-            false
-                if next_retry(
-                    match module_step {
-                        Step::PreprocessorStep => flow_value
-                            .preprocessor_module
-                            .as_ref()
-                            .and_then(|m| m.retry.as_ref()),
-                        Step::Step(i) => flow_value
-                            .modules
-                            .get(i)
-                            .as_ref()
-                            .and_then(|m| m.retry.as_ref()),
-                        Step::FailureStep => flow_value
-                            .failure_module
-                            .as_ref()
-                            .and_then(|m| m.retry.as_ref()),
-                    }
-                    .unwrap_or(&Retry::default()),
-                    &old_status.retry,
-                )
-                .is_some() =>
-            {
-                true
-            }
+            false if skip_failure => !is_last_step,
+            false if retry_left => true,
             false
                 if !is_failure_step
                     && !skip_error_handler
@@ -1156,6 +1396,29 @@ pub async fn update_flow_status_after_job_completion_internal(
             false => false,
         };
 
+        if let Step::Step(failed_step) = module_step {
+            if !success
+                && !stop_early
+                && !flow_job.is_canceled()
+                && !unrecoverable
+                && !skip_failure
+                && !retry_left
+                && old_status.compensation.is_none()
+                && push_compensations(
+                    db,
+                    &flow_job,
+                    &flow_value.modules,
+                    &old_status,
+                    failed_step,
+                    job_id_for_status,
+                    &nresult,
+                )
+                .await?
+            {
+                return Ok(UpdateFlowStatusAfterJobCompletion::NotDone);
+            }
+        }
+
         tracing::debug!(id = %flow_job.id, root_id = %job_root, "flow status updated");
 
         (
@@ -1349,6 +1612,241 @@ fn find_flow_job_index(flow_jobs: &Vec<Uuid>, job_id_for_status: &Uuid) -> Optio
     flow_jobs.iter().position(|x| x == job_id_for_status)
 }
 
+/// Push a sub-flow running the compensations of the steps that succeeded before `failed_step`,
+/// latest first. Returns false when there is nothing to compensate.
+async fn push_compensations(
+    db: &DB,
+    flow_job: &MiniPulledJob,
+    modules: &[FlowModule],
+    status: &FlowStatus,
+    failed_step: usize,
+    failed_job: &Uuid,
+    failed_result: &Arc<Box<RawValue>>,
+) -> error::Result<bool> {
+    let mut compensations = vec![];
+    for (module, module_status) in modules
+        .iter()
+        .zip(status.modules.iter())
+        .take(failed_step)
+        .rev()
+    {
+        // only top-level script and flow steps can have a compensation, see check_compensations
+        let (
+            Some(compensation),
+            FlowStatusModule::Success { job, flow_jobs: None, skipped: false, .. },
+        ) = (module.compensation.as_ref(), module_status)
+        else {
+            continue;
+        };
+        let record = sqlx::query!(
+            "SELECT
+                 v2_job.args AS \"args: Json<Box<RawValue>>\",
+                 v2_job_completed.result AS \"result: Json<Box<RawValue>>\"
+             FROM v2_job LEFT JOIN v2_job_completed ON v2_job_completed.id = v2_job.id
+             WHERE v2_job.id = $1",
+            job
+        )
+        .fetch_one(db)
+        .await
+        .map_err(|e| {
+            Error::internal_err(format!(
+                "error while fetching input and result of step {}: {e:#}",
+                module.id
+            ))
+        })?;
+        compensations.push(compensation_module(
+            &module.id,
+            compensation,
+            record
+                .args
+                .map(|x| x.0)
+                .unwrap_or_else(|| to_raw_value(&Value::Null)),
+            record
+                .result
+                .map(|x| x.0)
+                .unwrap_or_else(|| to_raw_value(&Value::Null)),
+        )?);
+    }
+
+    if compensations.is_empty() {
+        return Ok(false);
+    }
+
+    let steps = compensations
+        .iter()
+        .map(|m| CompensationStep { id: m.id.clone(), job: None, success: None })
+        .collect::<Vec<_>>();
+    let ids = steps
+        .iter()
+        .map(|s| s.id.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let job_perms: Option<Authed> = sqlx::query_as!(
+        JobPerms,
+        "SELECT email, username, is_admin, is_operator, groups, folders FROM job_perms WHERE job_id = $1 AND workspace_id = $2",
+        flow_job.flow_innermost_root_job.unwrap_or(flow_job.id),
+        flow_job.workspace_id,
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|x| x.into());
+
+    let tx = db.begin().await?;
+    let (uuid, mut tx) = push(
+        db,
+        PushIsolationLevel::Transaction(tx),
+        &flow_job.workspace_id,
+        JobPayload::RawFlow {
+            value: FlowValue { modules: compensations, ..Default::default() },
+            path: Some(format!("{}/compensation", flow_job.runnable_path())),
+            restarted_from: None,
+        },
+        PushArgs::from(flow_job.args.as_ref().map(|x| &x.0).unwrap_or(&*EHM)),
+        &flow_job.created_by,
+        &flow_job.permissioned_as_email,
+        flow_job.permissioned_as.to_owned(),
+        None,
+        flow_job.schedule_path(),
+        Some(flow_job.id),
+        None,
+        None,
+        true,
+        false,
+        None,
+        flow_job.visible_to_owner,
+        Some(flow_job.tag.clone()),
+        None,
+        Some("compensation".to_string()),
+        flow_job.priority,
+        job_perms.as_ref(),
+        None,
+    )
+    .await?;
+
+    sqlx::query!(
+        "UPDATE v2_job_status
+         SET flow_status = JSONB_SET(flow_status, ARRAY['compensation'], $1)
+         WHERE id = $2",
+        json!(CompensationStatus {
+            job: uuid,
+            failed_step: status.modules[failed_step].id(),
+            failed_job: *failed_job,
+            failed_result: (**failed_result).clone(),
+            steps,
+            success: None,
+        }),
+        flow_job.id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| Error::internal_err(format!("error while setting compensation status: {e:#}")))?;
+    tx.commit().await?;
+
+    append_logs(
+        &flow_job.id,
+        &flow_job.workspace_id,
+        format!("Step {failed_step} failed, running compensations of: {ids}\n"),
+        &db.into(),
+    )
+    .await;
+    Ok(true)
+}
+
+/// The compensation of step `id`, fed with the `input` and `result` of that step.
+/// Compensations continue on error so that one failing does not prevent the others from running.
+fn compensation_module(
+    id: &str,
+    compensation: &FlowModule,
+    input: Box<RawValue>,
+    result: Box<RawValue>,
+) -> error::Result<FlowModule> {
+    let mut value = serde_json::from_str::<FlowModuleValue>(compensation.value.get())
+        .map_err(|e| Error::internal_err(format!("invalid compensation of step {id}: {e:#}")))?;
+    match &mut value {
+        FlowModuleValue::Script { input_transforms, .. }
+        | FlowModuleValue::Flow { input_transforms, .. }
+        | FlowModuleValue::RawScript { input_transforms, .. }
+        | FlowModuleValue::FlowScript { input_transforms, .. } => {
+            input_transforms.insert("input".to_string(), InputTransform::Static { value: input });
+            input_transforms.insert(
+                "result".to_string(),
+                InputTransform::Static { value: result },
+            );
+        }
+        _ => {}
+    }
+    Ok(FlowModule {
+        id: id.to_string(),
+        value: to_raw_value(&value),
+        continue_on_error: Some(true),
+        compensation: None,
+        ..(*compensation).clone()
+    })
+}
+
+/// Record the outcome of the compensations and return the result of the step failure that
+/// triggered them, with which the flow fails.
+async fn complete_compensation(
+    db: &DB,
+    w_id: &str,
+    flow: Uuid,
+    compensation: &CompensationStatus,
+    success: bool,
+) -> error::Result<Box<RawValue>> {
+    let compensation_status = sqlx::query_scalar!(
+        "SELECT flow_status AS \"flow_status: Json<Box<RawValue>>\" FROM v2_job_status WHERE id = $1",
+        compensation.job
+    )
+    .fetch_optional(db)
+    .await?
+    .flatten()
+    .and_then(|x| serde_json::from_str::<FlowStatus>(x.0.get()).ok());
+
+    let mut compensation = compensation.clone();
+    if let Some(compensation_status) = compensation_status {
+        for (step, module) in compensation
+            .steps
+            .iter_mut()
+            .zip(compensation_status.modules.iter())
+        {
+            step.job = module.job();
+            step.success = match module {
+                FlowStatusModule::Success { .. } => Some(true),
+                FlowStatusModule::Failure { .. } => Some(false),
+                _ => None,
+            };
+        }
+    }
+    let success = success && compensation.steps.iter().all(|s| s.success != Some(false));
+    compensation.success = Some(success);
+
+    sqlx::query!(
+        "UPDATE v2_job_status
+         SET flow_status = JSONB_SET(flow_status, ARRAY['compensation'], $1)
+         WHERE id = $2",
+        json!(compensation),
+        flow
+    )
+    .execute(db)
+    .await
+    .map_err(|e| Error::internal_err(format!("error while setting compensation status: {e:#}")))?;
+
+    append_logs(
+        &flow,
+        w_id,
+        if success {
+            "Compensations completed with success\n".to_string()
+        } else {
+            "Compensations completed with error\n".to_string()
+        },
+        &db.into(),
+    )
+    .await;
+
+    Ok(compensation.failed_result)
+}
+
 async fn set_success_in_flow_job_success<'c>(
     flow_jobs_success: &Option<Vec<Option<bool>>>,
     flow_jobs: &Vec<Uuid>,
@@ -1482,6 +1980,130 @@ fn next_retry(retry: &Retry, status: &RetryStatus) -> Option<(u32, Duration)> {
         .map(|d| (status.fail_count + 1, std::cmp::min(d, MAX_RETRY_INTERVAL)))
 }
 
+fn failed_job_error(result: &RawValue) -> serde_json::Value {
+    let mut error = serde_json::from_str::<serde_json::Value>(result.get()).unwrap_or_default();
+    if let Some(inner) = error.get_mut("error").map(serde_json::Value::take) {
+        error = inner;
+    }
+    error
+}
+
+/// Like [`next_retry`], but only retries if the error of the failed job matches the retry
+/// conditions, and waits for the `retry_after` hint of the error instead of the delay if set.
+/// The `retry_if` predicate must have been evaluated for this failure by [`eval_retry_predicate`].
+fn next_retry_for_error(
+    retry: &Retry,
+    status: &RetryStatus,
+    failed_job: &Uuid,
+    result: &RawValue,
+) -> Option<(u32, Duration)> {
+    let (fail_count, delay) = next_retry(retry, status)?;
+
+    let error = failed_job_error(result);
+    if let Some(retry_if) = retry.retry_if.as_ref() {
+        if !retry_if.matches_error(&error) {
+            tracing::info!("error does not match the retry conditions, not retrying");
+            return None;
+        }
+        if retry_if.expr.is_some()
+            && !status
+                .predicate
+                .as_ref()
+                .is_some_and(|p| p.job == *failed_job && p.retry)
+        {
+            tracing::info!("retry predicate did not return true, not retrying");
+            return None;
+        }
+    }
+
+    let delay = retry_after_hint(&error)
+        .map(|d| std::cmp::min(d, MAX_RETRY_INTERVAL))
+        .unwrap_or(delay);
+    Some((fail_count, delay))
+}
+
+/// Evaluate the `retry_if` predicate of the module once for its failed job, when the failure could
+/// otherwise be retried, and store its outcome in the retry status of the flow. An evaluation
+/// error is logged to the failed job and means no retry.
+async fn eval_retry_predicate(
+    db: &DB,
+    w_id: &str,
+    flow: Uuid,
+    retry: &Retry,
+    status: &RetryStatus,
+    failed_job: &Uuid,
+    result: &RawValue,
+    client: &AuthedClient,
+) -> error::Result<RetryStatus> {
+    let error = failed_job_error(result);
+    let Some(expr) = retry
+        .retry_if
+        .as_ref()
+        .filter(|retry_if| retry_if.matches_error(&error))
+        .and_then(|retry_if| retry_if.expr.as_ref())
+    else {
+        return Ok(status.clone());
+    };
+    if next_retry(retry, status).is_none()
+        || status
+            .predicate
+            .as_ref()
+            .is_some_and(|p| p.job == *failed_job)
+    {
+        return Ok(status.clone());
+    }
+
+    let context = HashMap::from([("error".to_string(), Arc::new(to_raw_value(&error)))]);
+    let retry = match eval_timeout(
+        format!("Boolean({expr})"),
+        context,
+        None,
+        Some(client),
+        None,
+        None,
+    )
+    .await
+    {
+        Ok(value) => match value.get() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            a @ _ => Err(format!(
+                "Expected a boolean value for the retry predicate, found: {a:?}"
+            )),
+        },
+        Err(e) => Err(format!("{e:#}")),
+    }
+    .unwrap_or_else(|e| {
+        tracing::warn!("error while evaluating the retry predicate of job {failed_job}: {e}");
+        false
+    });
+    if !retry {
+        append_logs(
+            failed_job,
+            w_id,
+            format!("\nretry predicate `{expr}` did not return true, not retrying\n"),
+            &db.into(),
+        )
+        .await;
+    }
+
+    let status = RetryStatus {
+        predicate: Some(RetryPredicate { job: *failed_job, retry }),
+        ..status.clone()
+    };
+    sqlx::query!(
+        "UPDATE v2_job_status
+         SET flow_status = JSONB_SET(flow_status, ARRAY['retry'], $1)
+         WHERE id = $2",
+        json!(status),
+        flow
+    )
+    .execute(db)
+    .await
+    .context("update flow retry predicate")?;
+    Ok(status)
+}
+
 async fn compute_bool_from_expr(
     expr: &str,
     flow_args: Marc<HashMap<String, Box<RawValue>>>,
@@ -1602,6 +2224,11 @@ pub async fn handle_flow(
         && schedule_path.is_some()
         && flow_job.runnable_path.is_some()
         && status.step == 0
+        // a first step that is a sleep waking up has already been through here
+        && !matches!(
+            status.modules.get(0),
+            Some(FlowStatusModule::WaitingForExecutor { .. })
+        )
     {
         let schedule_path = schedule_path.as_ref().unwrap();
 
@@ -1788,7 +2415,9 @@ async fn push_next_flow_job(
         })));
     }
 
-    if matches!(step, Step::Step(0)) {
+    if matches!(step, Step::Step(0))
+        && !matches!(status_module, FlowStatusModule::WaitingForExecutor { .. })
+    {
         if !flow_job.is_flow_step() && flow_job.schedule_path().is_some() {
             let schedule_path = flow_job.schedule_path();
             let no_flow_overlap = sqlx::query_scalar!(
@@ -1946,7 +2575,7 @@ async fn push_next_flow_job(
             }));
 
             // Persist approval user groups conditions, if any. Requires runnning the InputTransform
-            let required_events = suspend.required_events.unwrap() as u16;
+            let required_events = suspend.events_to_wait_for() as u16;
             let user_auth_required = suspend.user_auth_required.unwrap_or(false);
             if user_auth_required {
                 let self_approval_disabled = suspend.self_approval_disabled.unwrap_or(false);
@@ -2083,6 +2712,11 @@ async fn push_next_flow_job(
                 .warn_after_seconds(3)
                 .await?;
 
+                sqlx::query!("DELETE FROM flow_event_wait WHERE job = $1", last)
+                    .execute(&mut *tx)
+                    .warn_after_seconds(3)
+                    .await?;
+
                 /* continue on and run this job! */
                 tx.commit().warn_after_seconds(3).await?;
 
@@ -2117,6 +2751,34 @@ async fn push_next_flow_job(
                 .warn_after_seconds(3)
                 .await?;
 
+                /* register the flow as waiting on a correlated trigger message, the trigger
+                 * listeners route matching messages to it as resume messages */
+                if let Some(event) = suspend.event.clone() {
+                    let correlation_key = eval_result_transform(
+                        event.correlation_key,
+                        arc_last_job_result.clone(),
+                        arc_flow_job_args.clone(),
+                        "correlation key",
+                    )
+                    .await?;
+                    sqlx::query!(
+                        "INSERT INTO flow_event_wait
+                            (job, flow, workspace_id, trigger_kind, trigger_path, message_key, correlation_key)
+                         VALUES ($1, $2, $3, $4, $5, $6, $7)
+                         ON CONFLICT (job) DO UPDATE SET correlation_key = EXCLUDED.correlation_key",
+                        last,
+                        flow_job.id,
+                        flow_job.workspace_id,
+                        event.trigger_kind as TriggerKind,
+                        event.trigger_path,
+                        event.message_key,
+                        correlation_key,
+                    )
+                    .execute(&mut *tx)
+                    .warn_after_seconds(3)
+                    .await?;
+                }
+
                 sqlx::query!(
                     "UPDATE v2_job_runtime SET ping = NULL
                      WHERE id = $1",
@@ -2144,6 +2806,10 @@ async fn push_next_flow_job(
                      .warn_after_seconds(3)
                      .await?;
                 }
+                sqlx::query!("DELETE FROM flow_event_wait WHERE job = $1", last)
+                    .execute(&mut *tx)
+                    .warn_after_seconds(3)
+                    .await?;
                 tx.commit().warn_after_seconds(3).await?;
 
                 let (logs, error_name) = if let Some(disapprover) = is_disapproved {
@@ -2281,9 +2947,9 @@ async fn push_next_flow_job(
         }
     };
 
-    let retry = if matches!(&status_module, FlowStatusModule::Failure { .. },) {
+    let retry = if let FlowStatusModule::Failure { job, .. } = &status_module {
         let retry = &module.retry.clone().unwrap_or_default();
-        next_retry(retry, &status.retry)
+        next_retry_for_error(retry, &status.retry, job, &arc_last_job_result)
     } else {
         None
     };
@@ -2341,7 +3007,7 @@ async fn push_next_flow_job(
                         "UPDATE v2_job_status
                          SET flow_status = JSONB_SET(flow_status, ARRAY['retry'], $1)
                          WHERE id = $2",
-                        json!(RetryStatus { fail_count: 0, failed_jobs: vec![] }),
+                        json!(RetryStatus { fail_count: 0, failed_jobs: vec![], predicate: None }),
                         flow_job.id
                     )
                     .execute(db)
@@ -2355,6 +3021,41 @@ async fn push_next_flow_job(
         _ => None,
     };
 
+    if let Step::Step(i) = step {
+        if module.get_type().is_ok_and(|t| t == "sleep") {
+            match &status_module {
+                FlowStatusModule::WaitingForPriorSteps { .. } => {
+                    let FlowModuleValue::Sleep { seconds, until } = module.get_value()? else {
+                        unreachable!()
+                    };
+                    let wake_at = sleep_wake_time(
+                        seconds,
+                        until,
+                        arc_last_job_result.clone(),
+                        arc_flow_job_args.clone(),
+                    )
+                    .warn_after_seconds(3)
+                    .await?;
+                    if wake_at > chrono::Utc::now() {
+                        park_sleeping_flow(db, &flow_job, i, status_module.id(), wake_at).await?;
+                        return Ok(PushNextFlowJob::Done(None));
+                    }
+                }
+                FlowStatusModule::WaitingForExecutor { .. } => {
+                    // woke up, the step itself then passes the previous result through
+                    sqlx::query!(
+                        "UPDATE v2_job_queue SET suspend = 0 WHERE id = $1",
+                        flow_job.id
+                    )
+                    .execute(db)
+                    .warn_after_seconds(3)
+                    .await?;
+                }
+                _ => {}
+            }
+        }
+    }
+
     let mut transform_context: Option<IdContext> = None;
 
     let approvers = Arc::new(to_raw_value(&approvers));
@@ -2424,7 +3125,12 @@ async fn push_next_flow_job(
         } else {
             let value = module.get_value();
             match &value {
-                Ok(_) if matches!(value, Ok(FlowModuleValue::Identity)) || is_skipped => {
+                Ok(_)
+                    if matches!(
+                        value,
+                        Ok(FlowModuleValue::Identity | FlowModuleValue::Sleep { .. })
+                    ) || is_skipped =>
+                {
                     serde_json::from_str(
                         &serde_json::to_string(&PreviousResult {
                             previous_result: Some(&arc_last_job_result),
@@ -2467,6 +3173,51 @@ async fn push_next_flow_job(
                 }
             }
         };
+
+    /* replaying a completed flow: an unchanged step called with the same args as in the
+     * original run reuses its recorded result, as if it were mocked */
+    let replayed_module;
+    let args = match (args, status.replay.as_ref(), step) {
+        (Ok(args), Some(replay), Step::Step(_)) if !is_skipped => {
+            match replayed_step_result(
+                db,
+                &flow_job.workspace_id,
+                replay.flow_job_id,
+                module,
+                &args,
+            )
+            .warn_after_seconds(3)
+            .await?
+            {
+                Some(result) => {
+                    sqlx::query!(
+                        "UPDATE v2_job_status
+                         SET flow_status = JSONB_SET(
+                             flow_status,
+                             ARRAY['replay', 'reused'],
+                             COALESCE(flow_status->'replay'->'reused', '[]'::jsonb) || to_jsonb($1::text)
+                         )
+                         WHERE id = $2",
+                        module.id,
+                        flow_job.id
+                    )
+                    .execute(db)
+                    .warn_after_seconds(3)
+                    .await?;
+                    let mut hm = HashMap::new();
+                    hm.insert("previous_result".to_string(), to_raw_value(&result));
+                    replayed_module = FlowModule {
+                        mock: Some(Mock { enabled: true, return_value: Some(result) }),
+                        ..module.clone()
+                    };
+                    module = &replayed_module;
+                    Ok(Marc::new(hm))
+                }
+                None => Ok(args),
+            }
+        }
+        (args, _, _) => args,
+    };
     tracing::debug!(id = %flow_job.id, root_id = %job_root, "flow job args computed");
 
     let next_flow_transform = compute_next_flow_transform(
@@ -2783,6 +3534,7 @@ async fn push_next_flow_job(
             Some(module.id.clone()),
             new_job_priority_override,
             job_perms.as_ref(),
+            None,
         )
         .warn_after_seconds(2)
         .await?;
@@ -2800,9 +3552,12 @@ async fn push_next_flow_job(
 
         tracing::debug!(id = %flow_job.id, root_id = %job_root, "pushed next flow job: {uuid}");
 
-        if value_with_parallel.type_ == "forloopflow" {
+        if value_with_parallel.type_ == "forloopflow"
+            || (value_with_parallel.type_ == "branchall"
+                && value_with_parallel.parallel.is_some_and(|x| x))
+        {
             if let Some(p) = value_with_parallel.parallelism {
-                tracing::debug!(id = %flow_job.id, root_id = %job_root, "updating suspend for forloopflow job {uuid}");
+                tracing::debug!(id = %flow_job.id, root_id = %job_root, "updating suspend for {} job {uuid}", value_with_parallel.type_);
 
                 if i as u16 >= p {
                     sqlx::query!(
@@ -3011,6 +3766,27 @@ async fn push_next_flow_job(
             .execute(&mut *tx)
             .warn_after_seconds(3)
             .await?;
+
+            // the deadline of a loop or branch-all starts with its first iteration or branch
+            if !matches!(status_module, FlowStatusModule::InProgress { .. }) {
+                if let Some(deadline) = module.get_deadline() {
+                    sqlx::query!(
+                        "UPDATE v2_job_status SET
+                             flow_status = JSONB_SET(
+                                 flow_status,
+                                 ARRAY['deadline'],
+                                 JSONB_BUILD_OBJECT('id', $1::TEXT, 'at', now() + MAKE_INTERVAL(secs => $2))
+                             )
+                         WHERE id = $3",
+                        status_module.id(),
+                        deadline.seconds as f64,
+                        flow_job.id
+                    )
+                    .execute(&mut *tx)
+                    .warn_after_seconds(3)
+                    .await?;
+                }
+            }
         }
     };
 
@@ -3278,7 +4054,10 @@ async fn compute_next_flow_transform(
     }
 
     match module.get_value()? {
-        FlowModuleValue::Identity => trivial_next_job(JobPayload::Identity),
+        // reached once the flow woke up, see `park_sleeping_flow`
+        FlowModuleValue::Identity | FlowModuleValue::Sleep { .. } => {
+            trivial_next_job(JobPayload::Identity)
+        }
         FlowModuleValue::Flow { path, .. } => {
             let payload =
                 flow_to_payload(path, delete_after_use, &flow_job.workspace_id, db).await?;
@@ -4123,6 +4902,121 @@ async fn get_transform_context(
 //     }
 // }
 
+async fn eval_result_transform(
+    it: InputTransform,
+    last_result: Arc<Box<RawValue>>,
+    flow_args: Marc<HashMap<String, Box<RawValue>>>,
+    what: &str,
+) -> error::Result<Value> {
+    let value = match it {
+        InputTransform::Static { value } => value,
+        InputTransform::Javascript { expr } => {
+            let mut context = HashMap::with_capacity(2);
+            context.insert("result".to_string(), last_result.clone());
+            context.insert("previous_result".to_string(), last_result);
+            eval_timeout(expr.clone(), context, Some(flow_args), None, None, None)
+                .await
+                .map_err(|e| {
+                    Error::ExecutionErr(format!(
+                        "Error during isolated evaluation of expression `{expr}`:\n{e:#}"
+                    ))
+                })?
+        }
+    };
+    serde_json::from_str(value.get())
+        .map_err(|e| Error::ExecutionErr(format!("Invalid {what} value: {e:#}")))
+}
+
+/// When a sleep step wakes up: at the RFC 3339 timestamp `until` if set, otherwise after
+/// `seconds`.
+async fn sleep_wake_time(
+    seconds: Option<InputTransform>,
+    until: Option<InputTransform>,
+    last_result: Arc<Box<RawValue>>,
+    flow_args: Marc<HashMap<String, Box<RawValue>>>,
+) -> error::Result<chrono::DateTime<chrono::Utc>> {
+    if let Some(until) = until {
+        match eval_result_transform(until, last_result, flow_args, "sleep").await? {
+            Value::String(s) => chrono::DateTime::parse_from_rfc3339(&s)
+                .map(|d| d.with_timezone(&chrono::Utc))
+                .map_err(|e| {
+                    Error::ExecutionErr(format!(
+                        "Expected an RFC 3339 timestamp to sleep until, found `{s}`: {e}"
+                    ))
+                }),
+            x => Err(Error::ExecutionErr(format!(
+                "Expected an RFC 3339 timestamp to sleep until, found: {x:?}"
+            ))),
+        }
+    } else if let Some(seconds) = seconds {
+        match eval_result_transform(seconds, last_result, flow_args, "sleep").await? {
+            Value::Number(n) => n
+                .as_f64()
+                .filter(|x| *x >= 0.0)
+                .map(|x| from_now(Duration::from_millis((x * 1000.0) as u64)))
+                .ok_or_else(|| {
+                    Error::ExecutionErr(format!(
+                        "Expected a positive number of seconds to sleep, found: {n}"
+                    ))
+                }),
+            x => Err(Error::ExecutionErr(format!(
+                "Expected a number of seconds to sleep, found: {x:?}"
+            ))),
+        }
+    } else {
+        Ok(chrono::Utc::now())
+    }
+}
+
+/// Park the flow in the queue without holding a worker. The suspended pull query picks it up
+/// again once `suspend_until` is reached.
+async fn park_sleeping_flow(
+    db: &DB,
+    flow_job: &MiniPulledJob,
+    step: usize,
+    module_id: String,
+    wake_at: chrono::DateTime<chrono::Utc>,
+) -> error::Result<()> {
+    let mut tx = db.begin().await?;
+    sqlx::query!(
+        "WITH suspend AS (
+             UPDATE v2_job_queue SET suspend = 1, suspend_until = $2
+             WHERE id = $3
+             RETURNING id
+         ) UPDATE v2_job_status SET flow_status = JSONB_SET(
+             flow_status,
+             ARRAY['modules', $4::TEXT],
+             $1
+         ) WHERE id = (SELECT id FROM suspend)",
+        json!(FlowStatusModule::WaitingForExecutor { id: module_id, job: flow_job.id }),
+        wake_at,
+        flow_job.id,
+        step.to_string(),
+    )
+    .execute(&mut *tx)
+    .warn_after_seconds(3)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE v2_job_runtime SET ping = NULL
+         WHERE id = $1",
+        flow_job.id,
+    )
+    .execute(&mut *tx)
+    .warn_after_seconds(3)
+    .await?;
+    tx.commit().await?;
+
+    append_logs(
+        &flow_job.id,
+        &flow_job.workspace_id,
+        format!("Sleeping until {wake_at}\n"),
+        &db.into(),
+    )
+    .await;
+    Ok(())
+}
+
 fn from_now(duration: Duration) -> chrono::DateTime<chrono::Utc> {
     // "This function errors when original duration is larger than
     // the maximum value supported for this type."
@@ -4132,6 +5026,168 @@ fn from_now(duration: Duration) -> chrono::DateTime<chrono::Utc> {
         .unwrap_or(chrono::DateTime::<chrono::Utc>::MAX_UTC)
 }
 
+/// What a replay reuses from the completed flow it replays, which never changes once completed.
+struct ReplayedFlow {
+    /// definition of each top-level step, by id
+    modules: HashMap<String, Value>,
+    /// job of each step that ran successfully and was not skipped, by id
+    succeeded: HashMap<String, Uuid>,
+}
+
+lazy_static::lazy_static! {
+    static ref REPLAYED_FLOWS: cache::Cache<(String, Uuid), Arc<ReplayedFlow>> = cache::Cache::new(100);
+}
+
+/// Load the definition and status of the replayed flow, once per replay.
+async fn load_replayed_flow(
+    db: &DB,
+    w_id: &str,
+    replayed_flow: Uuid,
+) -> error::Result<Option<Arc<ReplayedFlow>>> {
+    let key = (w_id.to_string(), replayed_flow);
+    if let Some(replayed) = REPLAYED_FLOWS.get(&key) {
+        return Ok(Some(replayed));
+    }
+
+    let Some(record) = sqlx::query!(
+        "SELECT
+            v2_job.kind AS \"job_kind!: JobKind\",
+            v2_job.runnable_id AS \"script_hash: ScriptHash\",
+            v2_job_completed.flow_status AS \"flow_status!: Json<Box<RawValue>>\",
+            v2_job.raw_flow AS \"raw_flow: Json<Box<RawValue>>\"
+        FROM v2_job INNER JOIN v2_job_completed ON v2_job.id = v2_job_completed.id
+        WHERE v2_job.id = $1 AND v2_job.workspace_id = $2",
+        replayed_flow,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let flow_data = cache::job::fetch_flow(db, record.job_kind, record.script_hash)
+        .or_else(|_| cache::job::fetch_preview_flow(db, &replayed_flow, record.raw_flow))
+        .await?;
+    let modules = flow_data
+        .value()
+        .modules
+        .iter()
+        .filter_map(|m| Some((m.id.clone(), serde_json::from_str(m.value.get()).ok()?)))
+        .collect();
+    let status = serde_json::from_str::<FlowStatus>(record.flow_status.0.get())?;
+    let succeeded = status
+        .modules
+        .into_iter()
+        .filter_map(|m| match m {
+            FlowStatusModule::Success { id, job, skipped: false, .. } => Some((id, job)),
+            _ => None,
+        })
+        .collect();
+
+    let replayed = Arc::new(ReplayedFlow { modules, succeeded });
+    REPLAYED_FLOWS.insert(key, replayed.clone());
+    Ok(Some(replayed))
+}
+
+/// Result recorded for this step by the replayed flow, if the step ran successfully there with
+/// the same definition, the same version of its script or flow and the same args.
+async fn replayed_step_result(
+    db: &DB,
+    w_id: &str,
+    replayed_flow: Uuid,
+    module: &FlowModule,
+    args: &HashMap<String, Box<RawValue>>,
+) -> error::Result<Option<Value>> {
+    let value = module.get_value();
+    if module.mock.as_ref().is_some_and(|m| m.enabled)
+        || !matches!(
+            value,
+            Ok(FlowModuleValue::Script { .. }
+                | FlowModuleValue::RawScript { .. }
+                | FlowModuleValue::FlowScript { .. }
+                | FlowModuleValue::Flow { .. })
+        )
+    {
+        return Ok(None);
+    }
+
+    let Some(replayed) = load_replayed_flow(db, w_id, replayed_flow).await? else {
+        return Ok(None);
+    };
+    let as_value = |v: &RawValue| serde_json::from_str::<Value>(v.get()).ok();
+    let unchanged = replayed
+        .modules
+        .get(&module.id)
+        .is_some_and(|v| Some(v) == as_value(&module.value).as_ref());
+    let Some(job) = replayed.succeeded.get(&module.id).filter(|_| unchanged) else {
+        return Ok(None);
+    };
+
+    let recorded = sqlx::query!(
+        "SELECT
+            v2_job.args AS \"args: Json<HashMap<String, Box<RawValue>>>\",
+            v2_job.runnable_id AS \"script_hash: ScriptHash\",
+            v2_job_completed.result AS \"result: Json<Box<RawValue>>\"
+        FROM v2_job INNER JOIN v2_job_completed ON v2_job.id = v2_job_completed.id
+        WHERE v2_job.id = $1 AND v2_job.workspace_id = $2",
+        job,
+        w_id
+    )
+    .fetch_optional(db)
+    .await?;
+    let Some(recorded) = recorded else {
+        return Ok(None);
+    };
+
+    // a step on the latest version of a script or flow must not reuse the result of an older
+    // version. The versions of hub scripts are not recorded, their results are never reused.
+    match value {
+        Ok(FlowModuleValue::Script { path, .. }) if path.starts_with("hub/") => return Ok(None),
+        Ok(FlowModuleValue::Script { path, hash: None, .. }) => {
+            let latest_hash = sqlx::query_scalar!(
+                "SELECT hash FROM script
+                 WHERE path = $1 AND workspace_id = $2 AND deleted = false AND archived = false
+                 ORDER BY created_at DESC LIMIT 1",
+                path,
+                w_id
+            )
+            .fetch_optional(db)
+            .await?;
+            if latest_hash.map(ScriptHash) != recorded.script_hash {
+                return Ok(None);
+            }
+        }
+        Ok(FlowModuleValue::Flow { path, .. }) => {
+            let latest_version = get_latest_flow_version_info_for_path(db, w_id, &path, false)
+                .await
+                .ok()
+                .map(|info| ScriptHash(info.version));
+            if latest_version.is_none() || latest_version != recorded.script_hash {
+                return Ok(None);
+            }
+        }
+        _ => {}
+    }
+
+    let to_values = |args: &HashMap<String, Box<RawValue>>| {
+        args.iter()
+            .map(|(k, v)| (k.clone(), as_value(v)))
+            .collect::<HashMap<_, _>>()
+    };
+    let recorded_args = recorded.args.map(|a| a.0).unwrap_or_default();
+    if to_values(&recorded_args) != to_values(args) {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        recorded
+            .result
+            .and_then(|r| as_value(&r.0))
+            .unwrap_or(Value::Null),
+    ))
+}
+
 /// returns previous module non-zero suspend count and job, if relevant
 fn needs_resume(flow: &FlowValue, status: &FlowStatus) -> Option<(Suspend, Uuid)> {
     // for a restarted job, if the restarted step is just after a suspend, don't run the suspend
@@ -4148,7 +5204,7 @@ fn needs_resume(flow: &FlowValue, status: &FlowStatus) -> Option<(Suspend, Uuid)
     let suspend = flow.modules.get(prev)?.suspend.clone();
     if suspend
         .as_ref()
-        .and_then(|s| s.required_events)
+        .map(|s| s.events_to_wait_for())
         .unwrap_or(0)
         == 0
     {
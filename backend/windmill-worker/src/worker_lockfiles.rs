@@ -49,6 +49,10 @@ use crate::php_executor::{composer_install, parse_php_imports};
 use crate::python_executor::{
     create_dependencies_dir, handle_python_reqs, uv_pip_compile, PyVersion,
 };
+#[cfg(feature = "r")]
+use crate::r_executor::{parse_r_requirements, resolve_renv_lock};
+#[cfg(feature = "ruby")]
+use crate::ruby_executor::{bundle_lock, parse_ruby_requirements};
 #[cfg(feature = "rust")]
 use crate::rust_executor::generate_cargo_lockfile;
 use crate::{
@@ -622,6 +626,7 @@ async fn trigger_dependents_to_recompute_dependencies(
             None,
             None,
             None,
+            None,
         )
         .await?;
         tracing::info!(
@@ -938,6 +943,7 @@ async fn lock_modules<'c>(
                     skip_failures,
                     parallel,
                     parallelism,
+                    deadline,
                 } => {
                     let nmodules;
                     (nmodules, tx, modified_ids, errors) = Box::pin(lock_modules(
@@ -965,10 +971,11 @@ async fn lock_modules<'c>(
                         skip_failures,
                         parallel,
                         parallelism,
+                        deadline,
                     }
                     .into()
                 }
-                FlowModuleValue::BranchAll { branches, parallel } => {
+                FlowModuleValue::BranchAll { branches, parallel, parallelism, race, deadline } => {
                     let mut nbranches = vec![];
                     nmodified_ids = vec![];
                     for mut b in branches {
@@ -998,9 +1005,21 @@ async fn lock_modules<'c>(
                         b.modules = nmodules;
                         nbranches.push(b)
                     }
-                    e.value = FlowModuleValue::BranchAll { branches: nbranches, parallel }.into()
+                    e.value = FlowModuleValue::BranchAll {
+                        branches: nbranches,
+                        parallel,
+                        parallelism,
+                        race,
+                        deadline,
+                    }
+                    .into()
                 }
-                FlowModuleValue::WhileloopFlow { modules, modules_node, skip_failures } => {
+                FlowModuleValue::WhileloopFlow {
+                    modules,
+                    modules_node,
+                    skip_failures,
+                    deadline,
+                } => {
                     let nmodules;
                     (nmodules, tx, nmodified_ids, errors) = Box::pin(lock_modules(
                         modules,
@@ -1024,6 +1043,7 @@ async fn lock_modules<'c>(
                         modules: nmodules,
                         modules_node,
                         skip_failures,
+                        deadline,
                     }
                     .into()
                 }
@@ -2377,6 +2397,77 @@ async fn capture_dependency_job(
             )
             .await
         }
+        ScriptLang::Ruby => {
+            #[cfg(not(feature = "ruby"))]
+            return Err(Error::internal_err(
+                "Ruby requires the ruby feature to be enabled".to_string(),
+            ));
+
+            #[cfg(feature = "ruby")]
+            {
+                let gemfile = if raw_deps {
+                    if job_raw_code.is_empty() {
+                        return Ok("".to_string());
+                    }
+                    job_raw_code.to_string()
+                } else {
+                    match parse_ruby_requirements(job_raw_code) {
+                        Some(gemfile) => gemfile,
+                        None => {
+                            return Ok("".to_string());
+                        }
+                    }
+                };
+                bundle_lock(
+                    mem_peak,
+                    canceled_by,
+                    job_id,
+                    w_id,
+                    &Connection::Sql(db.clone()),
+                    job_dir,
+                    worker_name,
+                    gemfile,
+                    occupancy_metrics,
+                )
+                .await
+            }
+        }
+        ScriptLang::R => {
+            #[cfg(not(feature = "r"))]
+            return Err(Error::internal_err(
+                "R requires the r feature to be enabled".to_string(),
+            ));
+
+            #[cfg(feature = "r")]
+            {
+                // raw dependencies are the packages to lock, one per line
+                let packages = if raw_deps {
+                    job_raw_code
+                        .lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                } else {
+                    parse_r_requirements(job_raw_code)
+                };
+                if packages.is_empty() {
+                    return Ok("".to_string());
+                }
+                resolve_renv_lock(
+                    mem_peak,
+                    canceled_by,
+                    job_id,
+                    w_id,
+                    &Connection::Sql(db.clone()),
+                    job_dir,
+                    worker_name,
+                    packages,
+                    occupancy_metrics,
+                )
+                .await
+            }
+        }
         // for related places search: ADD_NEW_LANG
         _ => Ok("".to_owned()),
     }
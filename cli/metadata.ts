@@ -328,7 +328,9 @@ async function updateScriptLock(
       language == "deno" ||
       language == "php" ||
       language == "rust" ||
-      language == "ansible"
+      language == "ansible" ||
+      language == "ruby" ||
+      language == "r"
     )
   ) {
     return;
@@ -521,6 +523,11 @@ export async function inferSchema(
       { name: "database", typ: { resource: "ms_sql_server" } },
       ...inferedSchema.args,
     ];
+  } else if (language === "duckdb") {
+    const { parse_duckdb } = await import(
+      "./wasm/regex/windmill_parser_wasm.js"
+    );
+    inferedSchema = JSON.parse(parse_duckdb(content));
   } else if (language === "postgresql") {
     const { parse_sql } = await import("./wasm/regex/windmill_parser_wasm.js");
     inferedSchema = JSON.parse(parse_sql(content));
@@ -567,6 +574,27 @@ export async function inferSchema(
   } else if (language === "java") {
     const { parse_java } = await import("./wasm/java/windmill_parser_wasm.js");
     inferedSchema = JSON.parse(parse_java(content));
+  } else if (language === "wasm") {
+    if (content.trimStart().startsWith("data:application/wasm;base64,")) {
+      inferedSchema = { args: [] };
+    } else if (/^\s*package\s|^\/\/\s*go\s*$/m.test(content)) {
+      const { parse_go } = await import("./wasm/go/windmill_parser_wasm.js");
+      inferedSchema = JSON.parse(parse_go(content));
+    } else {
+      const { parse_rust } = await import(
+        "./wasm/rust/windmill_parser_wasm.js"
+      );
+      inferedSchema = JSON.parse(parse_rust(content));
+    }
+  } else if (language === "lua") {
+    const { parse_lua } = await import("./wasm/regex/windmill_parser_wasm.js");
+    inferedSchema = JSON.parse(parse_lua(content));
+  } else if (language === "ruby") {
+    const { parse_ruby } = await import("./wasm/regex/windmill_parser_wasm.js");
+    inferedSchema = JSON.parse(parse_ruby(content));
+  } else if (language === "r") {
+    const { parse_r } = await import("./wasm/regex/windmill_parser_wasm.js");
+    inferedSchema = JSON.parse(parse_r(content));
     // for related places search: ADD_NEW_LANG
   } else {
     throw new Error("Invalid language: " + language);
@@ -553,6 +553,16 @@ export function filePathExtensionFromContentType(
     return ".sf.sql";
   } else if (language === "mssql") {
     return ".ms.sql";
+  } else if (language === "duckdb") {
+    return ".duckdb.sql";
+  } else if (language === "wasm") {
+    return ".wasm.src";
+  } else if (language === "lua") {
+    return ".lua";
+  } else if (language === "ruby") {
+    return ".rb";
+  } else if (language === "r") {
+    return ".r";
   } else if (language === "postgresql") {
     return ".pg.sql";
   } else if (language === "graphql") {
@@ -593,6 +603,11 @@ export const exts = [
   ".odb.sql",
   ".sf.sql",
   ".ms.sql",
+  ".duckdb.sql",
+  ".wasm.src",
+  ".lua",
+  ".rb",
+  ".r",
   ".sql",
   ".gql",
   ".ps1",
@@ -18,7 +18,12 @@ export type ScriptLanguage =
   | "csharp"
   | "nu"
   | "ansible"
-  | "java";
+  | "java"
+  | "duckdb"
+  | "wasm"
+  | "lua"
+  | "ruby"
+  | "r";
 	// for related places search: ADD_NEW_LANG 
 
 export function inferContentTypeFromFilePath(
@@ -47,6 +52,16 @@ export function inferContentTypeFromFilePath(
     return "snowflake";
   } else if (contentPath.endsWith(".ms.sql")) {
     return "mssql";
+  } else if (contentPath.endsWith(".duckdb.sql")) {
+    return "duckdb";
+  } else if (contentPath.endsWith(".wasm.src")) {
+    return "wasm";
+  } else if (contentPath.endsWith(".lua")) {
+    return "lua";
+  } else if (contentPath.endsWith(".rb")) {
+    return "ruby";
+  } else if (contentPath.endsWith(".r")) {
+    return "r";
   } else if (contentPath.endsWith(".pg.sql")) {
     return "postgresql";
   } else if (contentPath.endsWith(".gql")) {
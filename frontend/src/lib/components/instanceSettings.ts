@@ -169,6 +169,15 @@ export const settings: Record<string, Setting[]> = {
 			storage: 'setting',
 			ee_only: ''
 		},
+		{
+			label: 'Archive jobs to s3',
+			description:
+				'Instead of deleting the jobs past the retention period, archive them as Parquet files to the instance object storage. Archived jobs can still be fetched and listed.',
+			key: 'job_archive_on_s3',
+			fieldType: 'boolean',
+			storage: 'setting',
+			ee_only: ''
+		},
 
 		{
 			label: 'Instance object storage',
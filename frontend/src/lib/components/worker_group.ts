@@ -53,7 +53,9 @@ export const defaultTags = [
     'ansible',
     'csharp',
     'nu',
-    'java'
+    'java',
+    'ruby',
+    'r'
     // KJQXZ 
 ]
 export const nativeTags = [
@@ -64,5 +66,7 @@ export const nativeTags = [
     'snowflake',
     'mssql',
     'bigquery',
-    'oracledb'
+    'oracledb',
+    'duckdb',
+    'lua'
 ]
\ No newline at end of file
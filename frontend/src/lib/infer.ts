@@ -13,6 +13,10 @@ import initRegexParsers, {
 	parse_snowflake,
 	parse_graphql,
 	parse_mssql,
+	parse_duckdb,
+	parse_lua,
+	parse_ruby,
+	parse_r,
 	parse_db_resource,
 	parse_bash,
 	parse_powershell
@@ -163,6 +167,9 @@ export async function inferArgs(
 					...inferedSchema.args
 				]
 			}
+		} else if (language == 'duckdb') {
+			await initWasmRegex()
+			inferedSchema = JSON.parse(parse_duckdb(code))
 		} else if (language == 'graphql') {
 			await initWasmRegex()
 			inferedSchema = JSON.parse(parse_graphql(code))
@@ -194,6 +201,25 @@ export async function inferArgs(
 		} else if (language == 'java') {
 			await initWasmJava()
 			inferedSchema = JSON.parse(parse_java(code))
+		} else if (language == 'wasm') {
+			if (code.trimStart().startsWith('data:application/wasm;base64,')) {
+				return null
+			} else if (/^\s*package\s|^\/\/\s*go\s*$/m.test(code)) {
+				await initWasmGo()
+				inferedSchema = JSON.parse(parse_go(code))
+			} else {
+				await initWasmRust()
+				inferedSchema = JSON.parse(parse_rust(code))
+			}
+		} else if (language == 'lua') {
+			await initWasmRegex()
+			inferedSchema = JSON.parse(parse_lua(code))
+		} else if (language == 'ruby') {
+			await initWasmRegex()
+			inferedSchema = JSON.parse(parse_ruby(code))
+		} else if (language == 'r') {
+			await initWasmRegex()
+			inferedSchema = JSON.parse(parse_r(code))
 			// for related places search: ADD_NEW_LANG 
 		} else {
 			return null
@@ -287,6 +287,15 @@ INSERT INTO demo VALUES (:name1, :name2);
 UPDATE demo SET col2 = :name3 WHERE col2 = :name2;
 `
 
+const DUCKDB_INIT_CODE = `-- to stream a large query result to your workspace storage use '-- s3'
+-- s3:/// reads the workspace storage, attach other databases with
+-- ATTACH '$res:f/your/path' AS db (TYPE postgres);
+-- $1 name1 = default arg
+-- $2 name2
+SELECT * FROM read_parquet('s3:///path/to/file.parquet')
+WHERE col1 = $1::VARCHAR AND col2 = $2::INTEGER;
+`
+
 const SNOWFLAKE_INIT_CODE = `-- to pin the database use '-- database f/your/path'
 -- to stream a large query result to your workspace storage use '-- s3'
 -- ? name1 (varchar) = default arg
@@ -371,6 +380,75 @@ fn main(who_to_greet: String, numbers: Vec<i8>) -> anyhow::Result<Ret> {
 }
 `
 
+const WASM_INIT_CODE = `//! Built to wasm32-wasip2 and run in-process as a WASI component.
+//! Go sources (starting with a \`package\` clause) are built with TinyGo and a
+//! precompiled component can be pasted as data:application/wasm;base64,<component>
+//!
+//! \`\`\`cargo
+//! [dependencies]
+//! anyhow = "1.0.86"
+//! \`\`\`
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+struct Ret {
+    msg: String,
+}
+
+fn main(who_to_greet: String) -> anyhow::Result<Ret> {
+    println!("Person to greet: {}", who_to_greet);
+    Ok(Ret {
+        msg: format!("Greetings {}!", who_to_greet),
+    })
+}
+`
+
+const LUA_INIT_CODE = `-- runs in-process, wmill.variable, wmill.resource and wmill.get_result
+-- give access to variables, resources and results of other jobs
+
+---@param name string
+---@param count? integer
+---@param tags string[]
+function main(name, count, tags)
+  count = count or 2
+  print("Hello " .. name)
+  local items = {}
+  for i = 1, count do
+    items[i] = { name = name, index = i, tags = tags }
+  end
+  return { items = items }
+end
+`
+
+const RUBY_INIT_CODE = `require 'net/http'
+require 'json'
+
+# remove the first # of the following lines to specify gems to install with bundler
+# # requirements:
+# # gem 'httparty', '~> 0.21'
+
+# @param name [String]
+# @param count [Integer]
+# @param db [Postgresql]
+def main(name, count = 2, db = nil, verbose: false)
+  puts "Hello #{name}" if verbose
+  { items: (1..count).map { |i| "#{name}#{i}" } }
+end
+`
+
+const R_INIT_CODE = `# packages loaded with library(), require() or pkg:: are locked in a renv.lock and installed
+# library(dplyr)
+
+# a c("fast", "slow") default is an enum, as with match.arg
+# data frames are returned as arrays of records
+main <- function(name, count = 2L, mode = c("fast", "slow"), verbose = FALSE) {
+  mode <- match.arg(mode)
+  if (verbose) print(paste("Hello", name))
+  data.frame(i = seq_len(count), label = paste0(name, seq_len(count)), mode = mode)
+}
+`
+
 const CSHARP_INIT_CODE = `#r "nuget: Humanizer, 2.14.1"
 
 using System;
@@ -1163,6 +1241,21 @@ export const INITIAL_CODE = {
 	},
 	java: {
 		script: JAVA_INIT_CODE
+	},
+	duckdb: {
+		script: DUCKDB_INIT_CODE
+	},
+	wasm: {
+		script: WASM_INIT_CODE
+	},
+	lua: {
+		script: LUA_INIT_CODE
+	},
+	ruby: {
+		script: RUBY_INIT_CODE
+	},
+	r: {
+		script: R_INIT_CODE
 	}
 	// for related places search: ADD_NEW_LANG
 }
@@ -1270,6 +1363,16 @@ export function initialCode(
 		return INITIAL_CODE.nu.script
 	} else if (language == 'java') {
 		return INITIAL_CODE.java.script
+	} else if (language == 'duckdb') {
+		return INITIAL_CODE.duckdb.script
+	} else if (language == 'wasm') {
+		return INITIAL_CODE.wasm.script
+	} else if (language == 'lua') {
+		return INITIAL_CODE.lua.script
+	} else if (language == 'ruby') {
+		return INITIAL_CODE.ruby.script
+	} else if (language == 'r') {
+		return INITIAL_CODE.r.script
 		// for related places search: ADD_NEW_LANG
 	} else if (language == 'bun' || language == 'bunnative') {
 		if (kind == 'trigger') {
@@ -38,6 +38,16 @@ export function scriptLangToEditorLang(
 		return 'sql'
 	} else if (lang == 'mssql') {
 		return 'sql'
+	} else if (lang == 'duckdb') {
+		return 'sql'
+	} else if (lang == 'wasm') {
+		return 'rust'
+	} else if (lang == 'lua') {
+		return 'lua'
+	} else if (lang == 'ruby') {
+		return 'ruby'
+	} else if (lang == 'r') {
+		return 'r'
 	} else if (lang == 'python3') {
 		return 'python'
 	} else if (lang == 'bash') {
@@ -132,6 +142,11 @@ const scriptLanguagesArray: [SupportedLanguage | 'docker' | 'bunnative', string]
 	['oracledb', 'Oracle Database'],
 	['snowflake', 'Snowflake'],
 	['mssql', 'MS SQL Server'],
+	['duckdb', 'DuckDB'],
+	['wasm', 'Wasm'],
+	['lua', 'Lua'],
+	['ruby', 'Ruby'],
+	['r', 'R'],
 	['graphql', 'GraphQL'],
 	['powershell', 'PowerShell'],
 	['php', 'PHP'],
@@ -150,7 +165,7 @@ export function processLangs(selected: string | undefined, langs: string[]): str
 		let ls = langs.filter((lang) => lang !== 'nativets')
 
 		//those languages are newer and may not be in the saved list
-		let nl = ['bunnative', 'rust', 'ansible', 'csharp', 'nu', 'java']
+		let nl = ['bunnative', 'rust', 'ansible', 'csharp', 'nu', 'java', 'duckdb', 'wasm', 'lua', 'ruby', 'r']
 		// for related places search: ADD_NEW_LANG
 		nl.forEach((lang) => {
 			if (!ls.includes(lang)) {
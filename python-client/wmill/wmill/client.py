@@ -419,6 +419,23 @@ class Windmill:
         else:
             return r.json()
 
+    def stream_result(self, value: Any, job_id: Optional[str] = None) -> None:
+        """
+        Append a partial result to the stream of the job, e.g. a token of an llm output.
+        Inside a flow, it is appended to the stream of the root flow by default.
+        """
+        workspace = get_workspace()
+        job_id = (
+            job_id
+            or os.environ.get("WM_ROOT_FLOW_JOB_ID")
+            or os.environ.get("WM_JOB_ID")
+        )
+
+        self.post(
+            f"/w/{workspace}/jobs/result_stream/append/{job_id}",
+            json=value,
+        )
+
     def set_flow_user_state(self, key: str, value: Any) -> None:
         """Set the user state of a flow at a given key"""
         flow_id = self.get_root_job_id()
@@ -1056,6 +1073,14 @@ def get_progress(job_id: Optional[str] = None) -> Any:
     return _client.get_progress(job_id)
 
 
+@init_global_client
+def stream_result(value: Any, job_id: Optional[str] = None) -> None:
+    """
+    Append a partial result to the stream of the job
+    """
+    return _client.stream_result(value, job_id)
+
+
 def set_shared_state_pickle(value: Any, path="state.pickle") -> None:
     """
     Set the state in the shared folder using pickle
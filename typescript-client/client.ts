@@ -455,6 +455,24 @@ export async function getProgress(jobId?: any): Promise<number | null> {
   });
 }
 
+/**
+ * Append a partial result to the stream of the job, e.g. a token of an llm output,
+ * sent to the clients of the run_wait_result_stream endpoints as soon as it is appended
+ * @param value any json value
+ * @param jobId? Job to stream to, defaults to the root flow when inside a flow, the current job otherwise
+ */
+export async function streamResult(value: any, jobId?: string): Promise<void> {
+  await JobService.appendResultStream({
+    id:
+      jobId ||
+      getEnv("WM_ROOT_FLOW_JOB_ID") ||
+      getEnv("WM_JOB_ID") ||
+      "NO_JOB_ID",
+    workspace: getWorkspace(),
+    requestBody: value,
+  });
+}
+
 /**
  * Set a flow user state
  * @param key key of the state